@@ -0,0 +1,7 @@
+DROP TABLE stock_reservations;
+ALTER TABLE product_variants DROP CONSTRAINT product_variants_stock_non_negative;
+ALTER TABLE products DROP CONSTRAINT products_stock_non_negative;
+ALTER TABLE products
+    DROP COLUMN stock_deactivated,
+    DROP COLUMN deactivate_when_out_of_stock,
+    DROP COLUMN low_stock_threshold;
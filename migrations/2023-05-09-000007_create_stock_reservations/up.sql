@@ -0,0 +1,22 @@
+ALTER TABLE products
+    ADD COLUMN low_stock_threshold INTEGER CHECK (low_stock_threshold >= 0),
+    ADD COLUMN deactivate_when_out_of_stock BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN stock_deactivated BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE stock_reservations (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL REFERENCES products(id),
+    variant_id INTEGER REFERENCES product_variants(id),
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    status VARCHAR(50) NOT NULL DEFAULT 'held',
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE products ADD CONSTRAINT products_stock_non_negative CHECK (stock >= 0);
+ALTER TABLE product_variants ADD CONSTRAINT product_variants_stock_non_negative CHECK (stock >= 0);
+
+CREATE INDEX idx_stock_reservations_order ON stock_reservations(order_id);
+CREATE INDEX idx_stock_reservations_held_expiry ON stock_reservations(expires_at) WHERE status = 'held';
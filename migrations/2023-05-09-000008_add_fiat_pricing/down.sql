@@ -0,0 +1,9 @@
+DROP TABLE order_quotes;
+ALTER TABLE order_items
+    DROP COLUMN fiat_currency,
+    DROP COLUMN price_fiat;
+ALTER TABLE product_variants DROP COLUMN price_fiat;
+ALTER TABLE products
+    DROP CONSTRAINT products_fiat_price_currency,
+    DROP COLUMN fiat_currency,
+    DROP COLUMN price_fiat;
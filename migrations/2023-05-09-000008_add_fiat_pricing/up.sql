@@ -0,0 +1,26 @@
+ALTER TABLE products
+    ADD COLUMN price_fiat DECIMAL(20, 2),
+    ADD COLUMN fiat_currency VARCHAR(3),
+    ADD CONSTRAINT products_fiat_price_currency CHECK (price_fiat IS NULL OR fiat_currency IS NOT NULL);
+
+ALTER TABLE product_variants ADD COLUMN price_fiat DECIMAL(20, 2);
+
+ALTER TABLE order_items
+    ADD COLUMN price_fiat DECIMAL(20, 2),
+    ADD COLUMN fiat_currency VARCHAR(3);
+
+CREATE TABLE order_quotes (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    fiat_currency VARCHAR(3) NOT NULL,
+    currency payment_currency NOT NULL,
+    rate DECIMAL(30, 12) NOT NULL,
+    source VARCHAR(255) NOT NULL,
+    quoted_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT unique_order_quote UNIQUE (order_id, fiat_currency)
+);
+
+CREATE INDEX idx_order_quotes_order ON order_quotes(order_id);
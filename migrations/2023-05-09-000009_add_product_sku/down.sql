@@ -0,0 +1,3 @@
+ALTER TABLE products
+    DROP CONSTRAINT unique_vendor_sku,
+    DROP COLUMN sku;
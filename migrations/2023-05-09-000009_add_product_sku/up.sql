@@ -0,0 +1,3 @@
+ALTER TABLE products
+    ADD COLUMN sku VARCHAR(100),
+    ADD CONSTRAINT unique_vendor_sku UNIQUE (vendor_id, sku);
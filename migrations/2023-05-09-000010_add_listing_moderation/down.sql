@@ -0,0 +1,11 @@
+DROP TABLE listing_flags;
+DROP TABLE listing_moderation_events;
+
+ALTER TABLE products
+    DROP COLUMN reviewed_at,
+    DROP COLUMN reviewed_by,
+    DROP COLUMN submitted_at,
+    DROP COLUMN moderation_reason,
+    DROP COLUMN listing_state;
+
+DROP TYPE listing_state;
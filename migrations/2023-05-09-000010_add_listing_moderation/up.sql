@@ -0,0 +1,49 @@
+CREATE TYPE listing_state AS ENUM (
+    'draft',
+    'pending_review',
+    'active',
+    'rejected',
+    'suspended'
+);
+
+ALTER TABLE products
+    ADD COLUMN listing_state listing_state NOT NULL DEFAULT 'draft',
+    ADD COLUMN moderation_reason TEXT,
+    ADD COLUMN submitted_at TIMESTAMP,
+    ADD COLUMN reviewed_by INTEGER REFERENCES users(id),
+    ADD COLUMN reviewed_at TIMESTAMP;
+
+-- Listings that were already on sale were published before review existed
+UPDATE products SET listing_state = 'active' WHERE is_active = TRUE;
+
+CREATE TABLE listing_moderation_events (
+    id SERIAL PRIMARY KEY,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    actor_id INTEGER REFERENCES users(id),
+    from_state listing_state NOT NULL,
+    to_state listing_state NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE listing_flags (
+    id SERIAL PRIMARY KEY,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    reporter_id INTEGER NOT NULL REFERENCES users(id),
+    reason VARCHAR(50) NOT NULL,
+    details TEXT,
+    status VARCHAR(50) NOT NULL DEFAULT 'open',
+    resolved_by INTEGER REFERENCES users(id),
+    resolved_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- A buyer can only have one open flag on a listing at a time
+CREATE UNIQUE INDEX idx_listing_flags_open_reporter
+    ON listing_flags(product_id, reporter_id)
+    WHERE status = 'open';
+
+CREATE INDEX idx_products_listing_state ON products(listing_state, submitted_at);
+CREATE INDEX idx_listing_flags_status ON listing_flags(status, created_at);
+CREATE INDEX idx_listing_moderation_events_product_id ON listing_moderation_events(product_id);
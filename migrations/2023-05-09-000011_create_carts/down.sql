@@ -0,0 +1,10 @@
+ALTER TABLE orders
+    DROP COLUMN shipping_fiat_currency,
+    DROP COLUMN shipping_fiat,
+    DROP COLUMN shipping_amount,
+    DROP COLUMN shipping_option_id,
+    DROP COLUMN checkout_id;
+
+DROP TABLE escrow_addresses;
+DROP TABLE cart_items;
+DROP TABLE shipping_options;
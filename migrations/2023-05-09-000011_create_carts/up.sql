@@ -0,0 +1,51 @@
+CREATE TABLE shipping_options (
+    id SERIAL PRIMARY KEY,
+    vendor_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    price_btc DECIMAL(20, 12),
+    price_xmr DECIMAL(20, 12),
+    price_fiat DECIMAL(20, 2),
+    fiat_currency VARCHAR(3),
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT shipping_options_fiat_price_currency
+        CHECK (price_fiat IS NULL OR fiat_currency IS NOT NULL)
+);
+
+CREATE TABLE cart_items (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    variant_id INTEGER REFERENCES product_variants(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- One line per product and variant in a cart
+CREATE UNIQUE INDEX idx_cart_items_unique_line
+    ON cart_items(user_id, product_id, COALESCE(variant_id, 0));
+
+-- Addresses generated offline (from an xpub or Monero subaddresses) and handed out one per order
+CREATE TABLE escrow_addresses (
+    id SERIAL PRIMARY KEY,
+    currency payment_currency NOT NULL,
+    address VARCHAR(255) NOT NULL UNIQUE,
+    order_id INTEGER UNIQUE REFERENCES orders(id),
+    assigned_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_escrow_addresses_unassigned ON escrow_addresses(currency, id) WHERE order_id IS NULL;
+
+ALTER TABLE orders
+    ADD COLUMN checkout_id VARCHAR(36),
+    ADD COLUMN shipping_option_id INTEGER REFERENCES shipping_options(id),
+    ADD COLUMN shipping_amount DECIMAL(20, 12) NOT NULL DEFAULT 0,
+    ADD COLUMN shipping_fiat DECIMAL(20, 2),
+    ADD COLUMN shipping_fiat_currency VARCHAR(3);
+
+CREATE INDEX idx_orders_checkout_id ON orders(checkout_id);
+CREATE INDEX idx_shipping_options_vendor_id ON shipping_options(vendor_id);
@@ -0,0 +1,5 @@
+DROP TABLE dispute_messages;
+DROP TABLE dispute_evidence;
+DROP TABLE disputes;
+
+-- PostgreSQL cannot drop a value from an enum; 'escrow_refund' stays on transaction_type
@@ -0,0 +1,44 @@
+ALTER TYPE transaction_type ADD VALUE IF NOT EXISTS 'escrow_refund';
+
+CREATE TABLE disputes (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL UNIQUE REFERENCES orders(id) ON DELETE CASCADE,
+    opened_by INTEGER NOT NULL REFERENCES users(id),
+    reason VARCHAR(50) NOT NULL,
+    description TEXT NOT NULL,
+    status VARCHAR(50) NOT NULL DEFAULT 'open',
+    assigned_to INTEGER REFERENCES users(id),
+    assigned_at TIMESTAMP,
+    -- When the next moderator action is due: assignment while open, a ruling once assigned
+    sla_due_at TIMESTAMP NOT NULL,
+    resolution VARCHAR(50),
+    buyer_percent INTEGER CHECK (buyer_percent BETWEEN 0 AND 100),
+    resolution_notes TEXT,
+    resolved_by INTEGER REFERENCES users(id),
+    resolved_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE dispute_evidence (
+    id SERIAL PRIMARY KEY,
+    dispute_id INTEGER NOT NULL REFERENCES disputes(id) ON DELETE CASCADE,
+    submitted_by INTEGER NOT NULL REFERENCES users(id),
+    kind VARCHAR(50) NOT NULL,
+    description TEXT NOT NULL,
+    encrypted_content TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE dispute_messages (
+    id SERIAL PRIMARY KEY,
+    dispute_id INTEGER NOT NULL REFERENCES disputes(id) ON DELETE CASCADE,
+    sender_id INTEGER NOT NULL REFERENCES users(id),
+    encrypted_content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_disputes_status_sla ON disputes(status, sla_due_at);
+CREATE INDEX idx_disputes_assigned_to ON disputes(assigned_to);
+CREATE INDEX idx_dispute_evidence_dispute ON dispute_evidence(dispute_id);
+CREATE INDEX idx_dispute_messages_dispute ON dispute_messages(dispute_id);
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS order_timers;
+
+ALTER TABLE order_status_history
+    DROP COLUMN IF EXISTS changed_by,
+    DROP COLUMN IF EXISTS actor;
@@ -0,0 +1,37 @@
+-- Who made a status change: a user (recorded in changed_by) or the scheduler
+ALTER TABLE order_status_history
+    ADD COLUMN actor VARCHAR(20) NOT NULL DEFAULT 'user',
+    ADD COLUMN changed_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+UPDATE order_status_history
+SET actor = 'system'
+WHERE notes LIKE 'Cancelled automatically:%';
+
+CREATE TABLE order_timers (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL,
+    due_at TIMESTAMP NOT NULL,
+    extended BOOLEAN NOT NULL DEFAULT FALSE,
+    fired_at TIMESTAMP,
+    cancelled_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT order_timers_kind CHECK (kind IN ('payment', 'acceptance', 'finalize'))
+);
+
+-- An order has at most one timer of each kind; rescheduling reuses the row
+CREATE UNIQUE INDEX idx_order_timers_order_kind ON order_timers(order_id, kind);
+
+-- The scheduler only ever looks at timers that are still pending
+CREATE INDEX idx_order_timers_due
+    ON order_timers(due_at)
+    WHERE fired_at IS NULL AND cancelled_at IS NULL;
+
+-- Unpaid orders already waiting on a stock reservation get a payment timer for its expiry
+INSERT INTO order_timers (order_id, kind, due_at)
+SELECT o.id, 'payment', MIN(r.expires_at)
+FROM orders o
+JOIN stock_reservations r ON r.order_id = o.id AND r.status = 'held'
+WHERE o.status = 'pending'
+GROUP BY o.id;
@@ -0,0 +1,28 @@
+CREATE TABLE jobs (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(100) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL CHECK (max_attempts > 0),
+    run_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    unique_key VARCHAR(255),
+    locked_by VARCHAR(100),
+    locked_at TIMESTAMP,
+    last_error TEXT,
+    finished_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT jobs_status CHECK (status IN ('queued', 'running', 'succeeded', 'dead'))
+);
+
+-- Workers claim the oldest runnable jobs first
+CREATE INDEX idx_jobs_runnable ON jobs(run_at) WHERE status = 'queued';
+
+-- A unique job can be enqueued again only once the previous one succeeded; a dead one
+-- blocks it until an admin retries or discards it
+CREATE UNIQUE INDEX idx_jobs_unique_key
+    ON jobs(unique_key)
+    WHERE unique_key IS NOT NULL AND status IN ('queued', 'running', 'dead');
+
+CREATE INDEX idx_jobs_status_kind ON jobs(status, kind);
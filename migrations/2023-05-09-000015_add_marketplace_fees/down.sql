@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS escrow_settlements;
+DROP TABLE IF EXISTS fee_rules;
@@ -0,0 +1,29 @@
+-- Marketplace fee rates in basis points (1/100 of a percent). A vendor rule applies to the
+-- vendor's whole order; otherwise each item uses the rule of its category or the nearest
+-- parent category that has one; otherwise the configured default.
+CREATE TABLE fee_rules (
+    id SERIAL PRIMARY KEY,
+    category_id INTEGER REFERENCES categories(id) ON DELETE CASCADE,
+    vendor_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    fee_bps INTEGER NOT NULL CHECK (fee_bps >= 0 AND fee_bps <= 10000),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fee_rules_one_scope CHECK ((category_id IS NULL) <> (vendor_id IS NULL))
+);
+
+CREATE UNIQUE INDEX idx_fee_rules_category ON fee_rules(category_id) WHERE category_id IS NOT NULL;
+CREATE UNIQUE INDEX idx_fee_rules_vendor ON fee_rules(vendor_id) WHERE vendor_id IS NOT NULL;
+
+-- One row per order whose escrow was paid out. The unique order_id is what makes a payout
+-- happen at most once, and the fee column is the marketplace's fee ledger.
+CREATE TABLE escrow_settlements (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL UNIQUE REFERENCES orders(id),
+    currency payment_currency NOT NULL,
+    refund DECIMAL(20, 12) NOT NULL CHECK (refund >= 0),
+    release DECIMAL(20, 12) NOT NULL CHECK (release >= 0),
+    fee DECIMAL(20, 12) NOT NULL CHECK (fee >= 0 AND fee <= release),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_escrow_settlements_created ON escrow_settlements(created_at);
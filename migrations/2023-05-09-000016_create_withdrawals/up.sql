@@ -0,0 +1,41 @@
+-- Addresses a user may withdraw to. An address is usable once the user signed its
+-- challenge with their PGP key and the cool-down after that has passed.
+CREATE TABLE withdrawal_addresses (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    currency payment_currency NOT NULL,
+    address VARCHAR(255) NOT NULL,
+    label VARCHAR(100),
+    challenge TEXT NOT NULL,
+    confirmed_at TIMESTAMP,
+    usable_at TIMESTAMP,
+    removed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_withdrawal_addresses_active
+    ON withdrawal_addresses(user_id, currency, address)
+    WHERE removed_at IS NULL;
+
+CREATE TABLE withdrawals (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    wallet_id INTEGER NOT NULL REFERENCES wallets(id),
+    address_id INTEGER NOT NULL REFERENCES withdrawal_addresses(id),
+    transaction_id INTEGER NOT NULL REFERENCES transactions(id),
+    currency payment_currency NOT NULL,
+    amount DECIMAL(20, 12) NOT NULL CHECK (amount > 0),
+    network_fee DECIMAL(20, 12) NOT NULL CHECK (network_fee >= 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',
+    tx_hash VARCHAR(255),
+    failure_reason TEXT,
+    processed_by INTEGER REFERENCES users(id),
+    processed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT withdrawals_status CHECK (status IN ('queued', 'processing', 'sent', 'failed', 'cancelled'))
+);
+
+CREATE INDEX idx_withdrawals_user ON withdrawals(user_id, created_at);
+CREATE INDEX idx_withdrawals_status ON withdrawals(status, created_at);
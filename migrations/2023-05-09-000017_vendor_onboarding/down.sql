@@ -0,0 +1,22 @@
+ALTER TABLE escrow_addresses
+    DROP CONSTRAINT IF EXISTS escrow_addresses_one_owner,
+    DROP COLUMN IF EXISTS vendor_bond_id;
+
+DROP INDEX IF EXISTS idx_vendor_bonds_status;
+DROP INDEX IF EXISTS idx_vendor_bonds_open;
+
+-- The old schema allowed one bond per vendor; keep each vendor's latest.
+DELETE FROM vendor_bonds b
+    USING vendor_bonds newer
+    WHERE newer.vendor_id = b.vendor_id AND newer.id > b.id;
+
+ALTER TABLE vendor_bonds ADD CONSTRAINT unique_vendor_bond UNIQUE (vendor_id);
+
+ALTER TABLE vendor_bonds
+    DROP COLUMN IF EXISTS close_reason,
+    DROP COLUMN IF EXISTS closed_at,
+    DROP COLUMN IF EXISTS reviewed_by,
+    DROP COLUMN IF EXISTS paid_at,
+    DROP COLUMN IF EXISTS note,
+    DROP COLUMN IF EXISTS tx_hash,
+    DROP COLUMN IF EXISTS payment_address;
@@ -0,0 +1,24 @@
+-- Vendor applications. A bond row is created when a buyer applies and moves through
+-- pending (awaiting payment) -> paid (awaiting review) -> active, and from there to
+-- refunded (voluntary exit) or forfeited (enforcement). Rejected and cancelled
+-- applications are kept, so a user may apply again once their last bond is closed.
+ALTER TABLE vendor_bonds
+    ADD COLUMN payment_address VARCHAR(255),
+    ADD COLUMN tx_hash VARCHAR(255),
+    ADD COLUMN note TEXT,
+    ADD COLUMN paid_at TIMESTAMP,
+    ADD COLUMN reviewed_by INTEGER REFERENCES users(id),
+    ADD COLUMN closed_at TIMESTAMP,
+    ADD COLUMN close_reason TEXT;
+
+ALTER TABLE vendor_bonds DROP CONSTRAINT unique_vendor_bond;
+
+CREATE UNIQUE INDEX idx_vendor_bonds_open ON vendor_bonds(vendor_id)
+    WHERE status IN ('pending', 'paid', 'active');
+CREATE INDEX idx_vendor_bonds_status ON vendor_bonds(status);
+
+-- Bond deposit addresses come from the escrow pool; an address is assigned to either an
+-- order or a bond, never both.
+ALTER TABLE escrow_addresses
+    ADD COLUMN vendor_bond_id INTEGER REFERENCES vendor_bonds(id),
+    ADD CONSTRAINT escrow_addresses_one_owner CHECK (order_id IS NULL OR vendor_bond_id IS NULL);
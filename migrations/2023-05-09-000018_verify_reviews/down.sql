@@ -0,0 +1,12 @@
+DROP INDEX IF EXISTS idx_reviews_reviewer_created;
+DROP INDEX IF EXISTS idx_reviews_open_flags;
+
+ALTER TABLE reviews
+    DROP COLUMN IF EXISTS moderated_at,
+    DROP COLUMN IF EXISTS moderated_by,
+    DROP COLUMN IF EXISTS flag_reason,
+    DROP COLUMN IF EXISTS flag_status,
+    DROP COLUMN IF EXISTS weight,
+    DROP COLUMN IF EXISTS vendor_replied_at,
+    DROP COLUMN IF EXISTS vendor_reply,
+    DROP COLUMN IF EXISTS edited_at;
@@ -0,0 +1,15 @@
+-- Reviews can be edited once and answered once by the vendor. Reviews that look like
+-- manipulation are weighted down when they are posted and flagged for moderators; an
+-- upheld flag takes the review out of the vendor's reputation entirely.
+ALTER TABLE reviews
+    ADD COLUMN edited_at TIMESTAMP,
+    ADD COLUMN vendor_reply TEXT,
+    ADD COLUMN vendor_replied_at TIMESTAMP,
+    ADD COLUMN weight DOUBLE PRECISION NOT NULL DEFAULT 1.0 CHECK (weight >= 0 AND weight <= 1),
+    ADD COLUMN flag_status VARCHAR(20) CHECK (flag_status IN ('open', 'upheld', 'dismissed')),
+    ADD COLUMN flag_reason TEXT,
+    ADD COLUMN moderated_by INTEGER REFERENCES users(id),
+    ADD COLUMN moderated_at TIMESTAMP;
+
+CREATE INDEX idx_reviews_open_flags ON reviews(created_at) WHERE flag_status = 'open';
+CREATE INDEX idx_reviews_reviewer_created ON reviews(reviewer_id, created_at);
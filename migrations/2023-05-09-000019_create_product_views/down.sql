@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_orders_vendor_completed;
+DROP TABLE IF EXISTS product_view_counts;
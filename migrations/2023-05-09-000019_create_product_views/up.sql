@@ -0,0 +1,10 @@
+-- Daily view counters per product, for vendor conversion stats. Only the count is kept;
+-- nothing about who viewed the listing.
+CREATE TABLE product_view_counts (
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    views INTEGER NOT NULL DEFAULT 0 CHECK (views >= 0),
+    PRIMARY KEY (product_id, day)
+);
+
+CREATE INDEX idx_orders_vendor_completed ON orders(vendor_id, completed_at) WHERE status = 'completed';
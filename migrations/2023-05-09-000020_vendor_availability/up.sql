@@ -0,0 +1,15 @@
+-- Whether a vendor is taking orders. Vendors without a row are active.
+CREATE TABLE vendor_availability (
+    vendor_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'vacation', 'closed')),
+    -- When a vendor on vacation expects to be back; shown to buyers only
+    return_date DATE,
+    -- Public note shown on the vendor's page and listings while away
+    message TEXT,
+    -- Take the vendor's listings out of view instead of only blocking checkout
+    hide_listings BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CHECK (return_date IS NULL OR status = 'vacation')
+);
+
+CREATE INDEX idx_vendor_availability_away ON vendor_availability(vendor_id) WHERE status <> 'active';
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_messages_unread;
+DROP INDEX IF EXISTS idx_messages_conversation_created;
+ALTER TABLE conversations DROP CONSTRAINT IF EXISTS ordered_participants;
@@ -0,0 +1,26 @@
+-- A conversation is stored once per pair of users, lower id first. Merge any pair stored in
+-- both orders into the copy that is already in order, then put the rest in order.
+UPDATE messages
+SET conversation_id = ordered.id
+FROM conversations reversed
+JOIN conversations ordered
+    ON ordered.user1_id = reversed.user2_id AND ordered.user2_id = reversed.user1_id
+WHERE messages.conversation_id = reversed.id
+  AND reversed.user1_id > reversed.user2_id;
+
+DELETE FROM conversations reversed
+USING conversations ordered
+WHERE ordered.user1_id = reversed.user2_id
+  AND ordered.user2_id = reversed.user1_id
+  AND reversed.user1_id > reversed.user2_id;
+
+UPDATE conversations
+SET user1_id = user2_id, user2_id = user1_id
+WHERE user1_id > user2_id;
+
+ALTER TABLE conversations
+    ADD CONSTRAINT ordered_participants CHECK (user1_id < user2_id);
+
+-- Newest-first message pages and unread counts
+CREATE INDEX idx_messages_conversation_created ON messages(conversation_id, created_at DESC);
+CREATE INDEX idx_messages_unread ON messages(conversation_id, sender_id) WHERE NOT is_read;
@@ -0,0 +1,3 @@
+-- Users who would rather have the server encrypt their messages to the recipient's key than do
+-- it themselves. The plaintext is only ever held in memory.
+ALTER TABLE users ADD COLUMN server_side_encryption BOOLEAN NOT NULL DEFAULT FALSE;
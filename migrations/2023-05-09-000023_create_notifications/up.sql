@@ -0,0 +1,25 @@
+-- What users are told about changes to their orders, messages, disputes and reviews. Entries
+-- only describe the change and link to it; message contents are never copied here.
+CREATE TABLE notifications (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(40) NOT NULL CHECK (kind IN (
+        'order_status_changed', 'message_received', 'dispute_updated', 'payment_confirmed', 'review_received'
+    )),
+    title VARCHAR(255) NOT NULL,
+    link VARCHAR(255),
+    read_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notifications_user_created ON notifications(user_id, created_at DESC);
+CREATE INDEX idx_notifications_unread ON notifications(user_id) WHERE read_at IS NULL;
+
+-- Kinds a user turned off; kinds without a row are on
+CREATE TABLE notification_preferences (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(40) NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, kind)
+);
@@ -0,0 +1,10 @@
+DELETE FROM conversations WHERE order_id IS NOT NULL;
+
+DROP INDEX idx_conversations_order;
+DROP INDEX idx_conversations_direct;
+
+ALTER TABLE conversations ADD CONSTRAINT unique_conversation UNIQUE (user1_id, user2_id);
+
+ALTER TABLE conversations
+    DROP COLUMN locked_at,
+    DROP COLUMN order_id;
@@ -0,0 +1,18 @@
+-- A conversation is either direct (one per pair of users) or scoped to one order between its
+-- buyer and vendor. Order conversations are locked once the order is closed.
+ALTER TABLE conversations
+    ADD COLUMN order_id INTEGER REFERENCES orders(id) ON DELETE CASCADE,
+    ADD COLUMN locked_at TIMESTAMP;
+
+ALTER TABLE conversations DROP CONSTRAINT unique_conversation;
+
+CREATE UNIQUE INDEX idx_conversations_direct ON conversations(user1_id, user2_id) WHERE order_id IS NULL;
+CREATE UNIQUE INDEX idx_conversations_order ON conversations(order_id) WHERE order_id IS NOT NULL;
+
+-- Give existing orders their conversation, already locked for closed orders
+INSERT INTO conversations (user1_id, user2_id, order_id, locked_at, created_at, updated_at)
+SELECT LEAST(buyer_id, vendor_id), GREATEST(buyer_id, vendor_id), id,
+       CASE WHEN status IN ('completed', 'cancelled') THEN COALESCE(completed_at, updated_at) END,
+       created_at, created_at
+FROM orders
+WHERE buyer_id <> vendor_id;
@@ -0,0 +1,3 @@
+DROP INDEX idx_messages_created;
+
+ALTER TABLE conversations DROP COLUMN retention_days;
@@ -0,0 +1,5 @@
+-- Days a conversation's messages are kept, set by its participants; NULL uses the default
+ALTER TABLE conversations ADD COLUMN retention_days INTEGER CHECK (retention_days > 0);
+
+-- Finding expired messages
+CREATE INDEX idx_messages_created ON messages(created_at);
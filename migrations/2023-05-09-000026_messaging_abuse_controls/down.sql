@@ -0,0 +1,7 @@
+DROP TABLE conversation_report_messages;
+DROP TABLE conversation_reports;
+
+DROP INDEX idx_conversations_started_by;
+ALTER TABLE conversations DROP COLUMN started_by;
+
+DROP TABLE user_blocks;
@@ -0,0 +1,49 @@
+-- Users someone will not hear from
+CREATE TABLE user_blocks (
+    blocker_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    blocked_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (blocker_id, blocked_id),
+    CONSTRAINT not_self CHECK (blocker_id <> blocked_id)
+);
+
+CREATE INDEX idx_user_blocks_blocked ON user_blocks(blocked_id);
+
+-- Who opened a direct conversation, for the daily quota; unknown for older conversations
+ALTER TABLE conversations ADD COLUMN started_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_conversations_started_by ON conversations(started_by, created_at) WHERE order_id IS NULL;
+
+-- A participant asking moderators to look at a conversation
+CREATE TABLE conversation_reports (
+    id SERIAL PRIMARY KEY,
+    conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    reporter_id INTEGER NOT NULL REFERENCES users(id),
+    reported_user_id INTEGER NOT NULL REFERENCES users(id),
+    reason VARCHAR(50) NOT NULL,
+    details TEXT,
+    status VARCHAR(50) NOT NULL DEFAULT 'open',
+    resolved_by INTEGER REFERENCES users(id),
+    resolved_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_conversation_reports_open_reporter
+    ON conversation_reports(conversation_id, reporter_id)
+    WHERE status = 'open';
+CREATE INDEX idx_conversation_reports_status ON conversation_reports(status, created_at);
+
+-- Copies of the messages the reporter chose to share, kept past the conversation's retention
+CREATE TABLE conversation_report_messages (
+    id SERIAL PRIMARY KEY,
+    report_id INTEGER NOT NULL REFERENCES conversation_reports(id) ON DELETE CASCADE,
+    message_id INTEGER REFERENCES messages(id) ON DELETE SET NULL,
+    sender_id INTEGER NOT NULL REFERENCES users(id),
+    encrypted_content TEXT NOT NULL,
+    -- The reporter's decryption of the message, shared with their consent
+    shared_content TEXT,
+    sent_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_conversation_report_messages_report ON conversation_report_messages(report_id);
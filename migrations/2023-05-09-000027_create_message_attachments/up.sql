@@ -0,0 +1,12 @@
+-- PGP-encrypted files sent with a message; deleted with it when it expires
+CREATE TABLE message_attachments (
+    id SERIAL PRIMARY KEY,
+    message_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    encrypted_data BYTEA NOT NULL,
+    size_bytes INTEGER NOT NULL CHECK (size_bytes > 0),
+    -- ASCII-armored rather than binary OpenPGP
+    armored BOOLEAN NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_message_attachments_message ON message_attachments(message_id);
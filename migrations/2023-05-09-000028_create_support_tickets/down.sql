@@ -0,0 +1,10 @@
+DELETE FROM notifications WHERE kind = 'support_ticket_updated';
+DELETE FROM notification_preferences WHERE kind = 'support_ticket_updated';
+
+ALTER TABLE notifications DROP CONSTRAINT notifications_kind_check;
+ALTER TABLE notifications ADD CONSTRAINT notifications_kind_check CHECK (kind IN (
+    'order_status_changed', 'message_received', 'dispute_updated', 'payment_confirmed', 'review_received'
+));
+
+DROP TABLE support_ticket_messages;
+DROP TABLE support_tickets;
@@ -0,0 +1,36 @@
+-- Requests from users to staff, handled by moderators and admins
+CREATE TABLE support_tickets (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    category VARCHAR(50) NOT NULL CHECK (category IN ('account', 'payment', 'vendor_application', 'report')),
+    priority VARCHAR(20) NOT NULL DEFAULT 'normal' CHECK (priority IN ('low', 'normal', 'high', 'urgent')),
+    status VARCHAR(20) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'awaiting_user', 'resolved')),
+    subject VARCHAR(255) NOT NULL,
+    assigned_to INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    assigned_at TIMESTAMP,
+    resolved_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_support_tickets_user ON support_tickets(user_id, created_at DESC);
+CREATE INDEX idx_support_tickets_queue ON support_tickets(status, created_at) WHERE status <> 'resolved';
+CREATE INDEX idx_support_tickets_assigned ON support_tickets(assigned_to) WHERE status <> 'resolved';
+
+-- The thread of a ticket; `encrypted` is false only when no key was available
+CREATE TABLE support_ticket_messages (
+    id SERIAL PRIMARY KEY,
+    ticket_id INTEGER NOT NULL REFERENCES support_tickets(id) ON DELETE CASCADE,
+    author_id INTEGER NOT NULL REFERENCES users(id),
+    content TEXT NOT NULL,
+    encrypted BOOLEAN NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_support_ticket_messages_ticket ON support_ticket_messages(ticket_id, created_at);
+
+ALTER TABLE notifications DROP CONSTRAINT notifications_kind_check;
+ALTER TABLE notifications ADD CONSTRAINT notifications_kind_check CHECK (kind IN (
+    'order_status_changed', 'message_received', 'dispute_updated', 'payment_confirmed', 'review_received',
+    'support_ticket_updated'
+));
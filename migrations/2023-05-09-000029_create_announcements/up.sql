@@ -0,0 +1,15 @@
+-- Site-wide notices from staff, cleartext-signed with the marketplace key
+CREATE TABLE announcements (
+    id SERIAL PRIMARY KEY,
+    author_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    severity VARCHAR(20) NOT NULL CHECK (severity IN ('info', 'warning', 'critical')),
+    title VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    -- The message exactly as signed, so users can verify it themselves
+    signed_message TEXT NOT NULL,
+    expires_at TIMESTAMP,
+    withdrawn_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_announcements_created ON announcements(created_at DESC) WHERE withdrawn_at IS NULL;
@@ -0,0 +1,13 @@
+DROP INDEX idx_jobs_unique_key;
+
+-- Keep the oldest of any jobs that now share a key
+DELETE FROM jobs newer
+USING jobs older
+WHERE newer.unique_key = older.unique_key
+  AND newer.id > older.id
+  AND newer.status IN ('queued', 'running', 'dead')
+  AND older.status IN ('queued', 'running', 'dead');
+
+CREATE UNIQUE INDEX idx_jobs_unique_key
+    ON jobs(unique_key)
+    WHERE unique_key IS NOT NULL AND status IN ('queued', 'running', 'dead');
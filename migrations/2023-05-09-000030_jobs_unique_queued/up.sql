@@ -0,0 +1,7 @@
+-- Only a queued job blocks another with the same key: a running job may have started
+-- before the event that queues the next one, and a dead job must not stop its key forever
+DROP INDEX idx_jobs_unique_key;
+
+CREATE UNIQUE INDEX idx_jobs_unique_key
+    ON jobs(unique_key)
+    WHERE unique_key IS NOT NULL AND status = 'queued';
@@ -0,0 +1,2 @@
+-- The backfilled rows can't be told apart from settlements recorded since; they are kept
+SELECT 1;
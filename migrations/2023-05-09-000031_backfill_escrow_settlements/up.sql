@@ -0,0 +1,14 @@
+-- Orders paid out before escrow_settlements existed only have their wallet postings. Record
+-- them, so settling one of them again is recognised as a repeat instead of paying twice.
+INSERT INTO escrow_settlements (order_id, currency, refund, release, fee, created_at)
+SELECT t.order_id,
+       o.currency,
+       COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'escrow_refund'), 0),
+       COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'escrow_release'), 0),
+       COALESCE(SUM(t.fee) FILTER (WHERE t.transaction_type = 'escrow_release'), 0),
+       MIN(t.created_at)
+FROM transactions t
+JOIN orders o ON o.id = t.order_id
+WHERE t.transaction_type IN ('escrow_refund', 'escrow_release')
+GROUP BY t.order_id, o.currency
+ON CONFLICT (order_id) DO NOTHING;
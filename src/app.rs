@@ -23,6 +23,7 @@ use crate::{
     models,
     routes,
     settings::SETTINGS,
+    tasks,
 };
 
 pub async fn create_app() -> Router {
@@ -45,6 +46,9 @@ pub async fn create_app() -> Router {
     // Register custom types for Diesel
     models::register_custom_types();
 
+    // Start background tasks
+    tasks::spawn_all();
+
     // Create the router with all routes
     let app = Router::new()
         .merge(routes::frontend::create_route())
@@ -54,10 +58,21 @@ pub async fn create_app() -> Router {
         // Use our DRY implementation for products as an alternative
         // .merge(routes::product_dry::create_route())
         .merge(routes::order::create_route())
+        .merge(routes::dispute::create_route())
+        .merge(routes::cart::create_route())
         .merge(routes::message::create_route())
         .merge(routes::payment::create_route())
         .merge(routes::vendor::create_route())
         .merge(routes::admin::create_route())
+        .merge(routes::moderation::create_route())
+        .merge(routes::job::create_route())
+        .merge(routes::fee::create_route())
+        .merge(routes::withdrawal::create_route())
+        .merge(routes::analytics::create_route())
+        .merge(routes::events::create_route())
+        .merge(routes::notification::create_route())
+        .merge(routes::support::create_route())
+        .merge(routes::announcement::create_route())
         // Serve static files
         .nest_service("/static", ServeDir::new(PathBuf::from("static")))
         // Add request ID middleware
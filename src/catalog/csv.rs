@@ -0,0 +1,187 @@
+//! CSV catalog files
+//!
+//! Each row holds a product and, optionally, one of its variants. A product with several
+//! variants takes several consecutive rows that repeat the product columns; rows are grouped
+//! by `sku`, or by `id` when there is no SKU. The product columns of the first row win.
+
+use bigdecimal::BigDecimal;
+use csv_async::{AsyncReaderBuilder, AsyncWriterBuilder, Trim};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncRead;
+use tokio_stream::StreamExt;
+
+use super::{LineError, ParsedRecord, ProductRecord, VariantRecord};
+use crate::constants::catalog::MAX_IMPORT_LINES;
+use crate::errors::Error;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Row {
+    id: Option<i32>,
+    sku: Option<String>,
+    title: String,
+    description: String,
+    category_id: Option<i32>,
+    price_btc: Option<BigDecimal>,
+    price_xmr: Option<BigDecimal>,
+    price_fiat: Option<BigDecimal>,
+    fiat_currency: Option<String>,
+    stock: i32,
+    is_active: Option<bool>,
+    variant_id: Option<i32>,
+    variant_title: Option<String>,
+    variant_description: Option<String>,
+    variant_price_btc: Option<BigDecimal>,
+    variant_price_xmr: Option<BigDecimal>,
+    variant_price_fiat: Option<BigDecimal>,
+    variant_stock: Option<i32>,
+}
+
+impl Row {
+    /// The key consecutive rows of the same product share
+    fn group_key(&self) -> Option<String> {
+        match (&self.sku, self.id) {
+            (Some(sku), _) if !sku.trim().is_empty() => Some(format!("sku:{}", sku.trim())),
+            (_, Some(id)) => Some(format!("id:{}", id)),
+            _ => None,
+        }
+    }
+
+    fn variant(&self) -> Option<VariantRecord> {
+        let title = self.variant_title.as_ref().filter(|title| !title.trim().is_empty())?;
+
+        Some(VariantRecord {
+            id: self.variant_id,
+            title: title.clone(),
+            description: self.variant_description.clone().filter(|text| !text.is_empty()),
+            price_btc: self.variant_price_btc.clone(),
+            price_xmr: self.variant_price_xmr.clone(),
+            price_fiat: self.variant_price_fiat.clone(),
+            stock: self.variant_stock.unwrap_or(0),
+        })
+    }
+
+    fn into_record(self) -> ProductRecord {
+        let variants = self.variant().into_iter().collect();
+
+        ProductRecord {
+            id: self.id,
+            sku: self.sku,
+            title: self.title,
+            description: self.description,
+            category_id: self.category_id,
+            price_btc: self.price_btc,
+            price_xmr: self.price_xmr,
+            price_fiat: self.price_fiat,
+            fiat_currency: self.fiat_currency,
+            stock: self.stock,
+            is_active: self.is_active.unwrap_or(true),
+            variants,
+        }
+    }
+}
+
+/// Read product records from CSV with a header row
+pub async fn read<R>(reader: R) -> Result<(Vec<ParsedRecord>, Vec<LineError>), Error>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut csv = AsyncReaderBuilder::new()
+        .trim(Trim::All)
+        .flexible(false)
+        .create_deserializer(reader);
+    let mut rows = csv.deserialize_with_pos::<Row>();
+
+    let mut records: Vec<ParsedRecord> = Vec::new();
+    let mut errors = Vec::new();
+    let mut last_key: Option<String> = None;
+    let mut count = 0;
+
+    while let Some((row, position)) = rows.next().await {
+        count += 1;
+        if count > MAX_IMPORT_LINES {
+            return Err(Error::validation_error(format!(
+                "Import files are limited to {} rows",
+                MAX_IMPORT_LINES
+            )));
+        }
+
+        let line = position.line();
+        let row = match row {
+            Ok(row) => row,
+            Err(err) => {
+                errors.push(LineError::new(line, err.to_string()));
+                last_key = None;
+                continue;
+            }
+        };
+        let key = row.group_key();
+
+        // A further variant of the product on the previous row
+        if key.is_some() && key == last_key {
+            if let (Some(previous), Some(variant)) = (records.last_mut(), row.variant()) {
+                previous.record.variants.push(variant);
+                continue;
+            }
+        }
+
+        last_key = key;
+        records.push(ParsedRecord {
+            line,
+            record: row.into_record(),
+        });
+    }
+
+    Ok((records, errors))
+}
+
+/// Write product records as CSV, with the header row first when `with_header` is set
+pub async fn write(records: &[ProductRecord], with_header: bool) -> Result<Vec<u8>, Error> {
+    let mut chunk = Vec::new();
+
+    for (index, record) in records.iter().enumerate() {
+        let mut writer = AsyncWriterBuilder::new()
+            .has_headers(with_header && index == 0)
+            .create_serializer(Vec::new());
+
+        let product_row = || Row {
+            id: record.id,
+            sku: record.sku.clone(),
+            title: record.title.clone(),
+            description: record.description.clone(),
+            category_id: record.category_id,
+            price_btc: record.price_btc.clone(),
+            price_xmr: record.price_xmr.clone(),
+            price_fiat: record.price_fiat.clone(),
+            fiat_currency: record.fiat_currency.clone(),
+            stock: record.stock,
+            is_active: Some(record.is_active),
+            ..Default::default()
+        };
+
+        if record.variants.is_empty() {
+            writer.serialize(product_row()).await.map_err(write_error)?;
+        }
+
+        for variant in &record.variants {
+            let row = Row {
+                variant_id: variant.id,
+                variant_title: Some(variant.title.clone()),
+                variant_description: variant.description.clone(),
+                variant_price_btc: variant.price_btc.clone(),
+                variant_price_xmr: variant.price_xmr.clone(),
+                variant_price_fiat: variant.price_fiat.clone(),
+                variant_stock: Some(variant.stock),
+                ..product_row()
+            };
+            writer.serialize(row).await.map_err(write_error)?;
+        }
+
+        chunk.extend(writer.into_inner().await.map_err(write_error)?);
+    }
+
+    Ok(chunk)
+}
+
+fn write_error(err: impl std::fmt::Display) -> Error {
+    Error::internal_error(format!("Failed to write CSV: {}", err), None, None)
+}
@@ -0,0 +1,85 @@
+//! JSON Lines catalog files
+//!
+//! Each non-blank line is one product as a JSON object, with its variants nested under
+//! `variants`.
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+use super::{LineError, ParsedRecord, ProductRecord};
+use crate::constants::catalog::{MAX_IMPORT_LINES, MAX_LINE_BYTES};
+use crate::errors::Error;
+
+/// Read product records from JSON Lines
+///
+/// A line longer than `MAX_LINE_BYTES` stops the import, since it is never held in memory
+/// whole.
+pub async fn read<R>(reader: R) -> Result<(Vec<ParsedRecord>, Vec<LineError>), Error>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+    let mut line_number = 0u64;
+
+    loop {
+        buffer.clear();
+        let read = (&mut reader)
+            .take(MAX_LINE_BYTES as u64 + 1)
+            .read_until(b'\n', &mut buffer)
+            .await
+            .map_err(|err| Error::validation_error(format!("Cannot read import file: {}", err)))?;
+        if read == 0 {
+            break;
+        }
+
+        line_number += 1;
+        if buffer.len() > MAX_LINE_BYTES {
+            return Err(Error::validation_error(format!(
+                "Line {} is longer than {} KiB",
+                line_number,
+                MAX_LINE_BYTES / 1024
+            )));
+        }
+        if line_number as usize > MAX_IMPORT_LINES {
+            return Err(Error::validation_error(format!(
+                "Import files are limited to {} lines",
+                MAX_IMPORT_LINES
+            )));
+        }
+
+        let line = match std::str::from_utf8(&buffer) {
+            Ok(line) => line,
+            Err(_) => {
+                errors.push(LineError::new(line_number, "Line is not valid UTF-8"));
+                continue;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<ProductRecord>(line) {
+            Ok(record) => records.push(ParsedRecord {
+                line: line_number,
+                record,
+            }),
+            Err(err) => errors.push(LineError::new(line_number, err.to_string())),
+        }
+    }
+
+    Ok((records, errors))
+}
+
+/// Write product records as JSON Lines
+pub fn write(records: &[ProductRecord]) -> Result<Vec<u8>, Error> {
+    let mut chunk = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut chunk, record).map_err(|err| {
+            Error::internal_error(format!("Failed to write JSON Lines: {}", err), None, None)
+        })?;
+        chunk.push(b'\n');
+    }
+    Ok(chunk)
+}
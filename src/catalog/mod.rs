@@ -0,0 +1,686 @@
+//! Bulk import and export of a vendor's catalog
+//!
+//! Catalogs are exchanged as CSV (one row per variant, product columns repeated) or JSON Lines
+//! (one product per line with its variants nested). Exports are written in the same shape the
+//! importer reads, so an exported file can be edited and imported back.
+
+pub mod csv;
+pub mod jsonl;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::Utc;
+use diesel::prelude::*;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::io::AsyncRead;
+use tracing::{debug, info};
+
+use crate::constants::catalog::{EXPORT_PAGE_SIZE, MAX_SKU_LENGTH, MAX_TITLE_LENGTH};
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::models::product::{ListingState, NewProduct, NewProductVariant, Product, ProductVariant};
+use crate::models::{inventory, moderation};
+use crate::pricing;
+use crate::schema::{categories, product_variants, products};
+
+/// A file format the catalog can be read from and written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "jsonl" | "ndjson" => Ok(Format::Jsonl),
+            other => Err(Error::validation_error(format!("Unknown catalog format: {}", other))),
+        }
+    }
+}
+
+/// A product as it appears in an import or export file
+///
+/// `id` or `sku` identify an existing product to update; a record with neither creates a
+/// new product.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductRecord {
+    pub id: Option<i32>,
+    pub sku: Option<String>,
+    pub title: String,
+    pub description: String,
+    pub category_id: Option<i32>,
+    pub price_btc: Option<BigDecimal>,
+    pub price_xmr: Option<BigDecimal>,
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
+    pub stock: i32,
+    #[serde(default = "default_is_active")]
+    pub is_active: bool,
+    #[serde(default)]
+    pub variants: Vec<VariantRecord>,
+}
+
+/// A product variant as it appears in an import or export file
+///
+/// `id` identifies an existing variant of the product; without it the variant is matched by
+/// title, and created if no variant has that title.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantRecord {
+    pub id: Option<i32>,
+    pub title: String,
+    pub description: Option<String>,
+    pub price_btc: Option<BigDecimal>,
+    pub price_xmr: Option<BigDecimal>,
+    pub price_fiat: Option<BigDecimal>,
+    pub stock: i32,
+}
+
+fn default_is_active() -> bool {
+    true
+}
+
+/// A parsed record together with the line of the file it started on
+#[derive(Debug, Clone)]
+pub struct ParsedRecord {
+    pub line: u64,
+    pub record: ProductRecord,
+}
+
+/// A problem with one line of an import file
+#[derive(Debug, Clone, Serialize)]
+pub struct LineError {
+    pub line: u64,
+    pub message: String,
+}
+
+impl LineError {
+    pub fn new(line: u64, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// What an import did, or would do in a dry run, to one product
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Update,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportLineResult {
+    pub line: u64,
+    pub action: ImportAction,
+    pub product_id: i32,
+    pub sku: Option<String>,
+    /// Names of the fields that changed on an existing product
+    pub changes: Vec<String>,
+}
+
+/// The outcome of an import
+///
+/// Imports are all-or-nothing: when any line has an error nothing is written, and every
+/// error is reported so the file can be fixed in one go.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub committed: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub results: Vec<ImportLineResult>,
+    pub errors: Vec<LineError>,
+}
+
+/// Read every product record from an import file
+///
+/// Syntax errors are reported per line and do not stop the rest of the file from being read.
+pub async fn read<R>(format: Format, reader: R) -> Result<(Vec<ParsedRecord>, Vec<LineError>), Error>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    match format {
+        Format::Csv => csv::read(reader).await,
+        Format::Jsonl => jsonl::read(reader).await,
+    }
+}
+
+/// Write product records in a catalog format; `with_header` starts a CSV file
+pub async fn write(format: Format, records: &[ProductRecord], with_header: bool) -> Result<Vec<u8>, Error> {
+    match format {
+        Format::Csv => csv::write(records, with_header).await,
+        Format::Jsonl => jsonl::write(records),
+    }
+}
+
+/// Stream a vendor's whole catalog in a catalog format, one page of products per chunk
+///
+/// Pages are loaded as the stream is polled, so only one is held in memory at a time.
+pub fn export_stream(vendor_id: i32, format: Format) -> impl Stream<Item = Result<Vec<u8>, Error>> {
+    futures::stream::try_unfold(Some(None), move |cursor: Option<Option<i32>>| async move {
+        let Some(after_id) = cursor else {
+            return Ok(None);
+        };
+
+        let page = tokio::task::spawn_blocking(move || {
+            let mut conn = get_connection()?;
+            export(&mut conn, vendor_id, after_id, EXPORT_PAGE_SIZE)
+        })
+        .await??;
+        if page.is_empty() {
+            return Ok(None);
+        }
+
+        let next = if (page.len() as i64) < EXPORT_PAGE_SIZE {
+            None
+        } else {
+            Some(page.last().and_then(|record| record.id))
+        };
+        let chunk = write(format, &page, after_id.is_none()).await?;
+        Ok(Some((chunk, next)))
+    })
+}
+
+/// Load one page of a vendor's catalog as records, by product id after `after_id`
+pub fn export(
+    conn: &mut PgConnection,
+    vendor_id: i32,
+    after_id: Option<i32>,
+    limit: i64,
+) -> Result<Vec<ProductRecord>, Error> {
+    let mut query = products::table
+        .filter(products::vendor_id.eq(vendor_id))
+        .into_boxed();
+    if let Some(after_id) = after_id {
+        query = query.filter(products::id.gt(after_id));
+    }
+    let catalog = query
+        .order(products::id.asc())
+        .limit(limit)
+        .load::<Product>(conn)?;
+
+    let variants = ProductVariant::belonging_to(&catalog)
+        .order(product_variants::id.asc())
+        .load::<ProductVariant>(conn)?
+        .grouped_by(&catalog);
+
+    let records = catalog
+        .into_iter()
+        .zip(variants)
+        .map(|(product, variants)| ProductRecord {
+            id: Some(product.id),
+            sku: product.sku,
+            title: product.title,
+            description: product.description,
+            category_id: product.category_id,
+            price_btc: product.price_btc,
+            price_xmr: product.price_xmr,
+            price_fiat: product.price_fiat,
+            fiat_currency: product.fiat_currency,
+            stock: product.stock,
+            is_active: product.is_active,
+            variants: variants
+                .into_iter()
+                .map(|variant| VariantRecord {
+                    id: Some(variant.id),
+                    title: variant.title,
+                    description: variant.description,
+                    price_btc: variant.price_btc,
+                    price_xmr: variant.price_xmr,
+                    price_fiat: variant.price_fiat,
+                    stock: variant.stock,
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    debug!(vendor_id = vendor_id, count = records.len(), "Catalog page exported");
+    Ok(records)
+}
+
+/// Apply parsed records to a vendor's catalog
+///
+/// Everything runs in one transaction. It is rolled back for a dry run, or when any line
+/// (including the syntax errors passed in as `parse_errors`) has an error. New products are
+/// submitted for review, and active listings whose title, description or category changed go
+/// back through review.
+pub fn import(
+    conn: &mut PgConnection,
+    vendor_id: i32,
+    records: Vec<ParsedRecord>,
+    parse_errors: Vec<LineError>,
+    dry_run: bool,
+) -> Result<ImportReport, Error> {
+    let outcome = conn.transaction::<ImportReport, ImportAbort, _>(|conn| {
+        let mut report = ImportReport {
+            dry_run,
+            errors: parse_errors,
+            ..Default::default()
+        };
+        let mut seen_products = HashSet::new();
+
+        for parsed in records {
+            let line = parsed.line;
+            let record = match validate(parsed.record) {
+                Ok(record) => record,
+                Err(message) => {
+                    report.errors.push(LineError::new(line, message));
+                    continue;
+                }
+            };
+
+            match apply(conn, vendor_id, &record)? {
+                Ok((product_id, action, changes)) => {
+                    if !seen_products.insert(product_id) {
+                        report.errors.push(LineError::new(
+                            line,
+                            format!("Product {} appears more than once in the file", product_id),
+                        ));
+                        continue;
+                    }
+
+                    match action {
+                        ImportAction::Create => report.created += 1,
+                        ImportAction::Update => report.updated += 1,
+                        ImportAction::Unchanged => report.unchanged += 1,
+                    }
+                    report.results.push(ImportLineResult {
+                        line,
+                        action,
+                        product_id,
+                        sku: record.sku.clone(),
+                        changes,
+                    });
+                }
+                Err(message) => report.errors.push(LineError::new(line, message)),
+            }
+        }
+
+        if dry_run || !report.errors.is_empty() {
+            return Err(ImportAbort::Rollback(report));
+        }
+
+        report.committed = true;
+        Ok(report)
+    });
+
+    let report = match outcome {
+        Ok(report) => report,
+        Err(ImportAbort::Rollback(report)) => report,
+        Err(ImportAbort::Failed(err)) => return Err(err),
+    };
+
+    info!(
+        vendor_id = vendor_id,
+        dry_run = dry_run,
+        committed = report.committed,
+        created = report.created,
+        updated = report.updated,
+        errors = report.errors.len(),
+        "Catalog import finished"
+    );
+
+    Ok(report)
+}
+
+/// Why an import transaction did not commit
+enum ImportAbort {
+    /// Rolled back on purpose; the report says what would have happened
+    Rollback(ImportReport),
+    Failed(Error),
+}
+
+impl From<diesel::result::Error> for ImportAbort {
+    fn from(err: diesel::result::Error) -> Self {
+        ImportAbort::Failed(Error::from(err))
+    }
+}
+
+impl From<Error> for ImportAbort {
+    fn from(err: Error) -> Self {
+        ImportAbort::Failed(err)
+    }
+}
+
+/// Check a record on its own and normalize its values
+fn validate(mut record: ProductRecord) -> Result<ProductRecord, String> {
+    record.title = record.title.trim().to_string();
+    if record.title.is_empty() || record.title.len() > MAX_TITLE_LENGTH {
+        return Err(format!("Title must be between 1 and {} characters", MAX_TITLE_LENGTH));
+    }
+
+    if record.description.trim().is_empty() {
+        return Err("Description is required".to_string());
+    }
+
+    record.sku = record
+        .sku
+        .map(|sku| sku.trim().to_string())
+        .filter(|sku| !sku.is_empty());
+    if record.sku.as_ref().map_or(false, |sku| sku.len() > MAX_SKU_LENGTH) {
+        return Err(format!("SKU must be at most {} characters", MAX_SKU_LENGTH));
+    }
+
+    if record.stock < 0 {
+        return Err("Stock cannot be negative".to_string());
+    }
+
+    check_prices(&record.price_btc, &record.price_xmr)?;
+
+    record.fiat_currency = record
+        .fiat_currency
+        .as_deref()
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(pricing::normalize_fiat_currency)
+        .transpose()
+        .map_err(error_message)?;
+    record.price_fiat = record
+        .price_fiat
+        .as_ref()
+        .map(pricing::normalize_fiat_price)
+        .transpose()
+        .map_err(error_message)?;
+
+    let has_fiat_variant = record.variants.iter().any(|variant| variant.price_fiat.is_some());
+    if (record.price_fiat.is_some() || has_fiat_variant) && record.fiat_currency.is_none() {
+        return Err("A fiat price needs a fiat currency".to_string());
+    }
+
+    let mut variant_titles = HashSet::new();
+    for variant in &mut record.variants {
+        variant.title = variant.title.trim().to_string();
+        if variant.title.is_empty() || variant.title.len() > MAX_TITLE_LENGTH {
+            return Err(format!(
+                "Variant title must be between 1 and {} characters",
+                MAX_TITLE_LENGTH
+            ));
+        }
+
+        if !variant_titles.insert(variant.title.to_lowercase()) {
+            return Err(format!("Variant \"{}\" appears more than once", variant.title));
+        }
+
+        if variant.stock < 0 {
+            return Err(format!("Stock of variant \"{}\" cannot be negative", variant.title));
+        }
+
+        check_prices(&variant.price_btc, &variant.price_xmr)?;
+        variant.price_fiat = variant
+            .price_fiat
+            .as_ref()
+            .map(pricing::normalize_fiat_price)
+            .transpose()
+            .map_err(error_message)?;
+    }
+
+    let product_priced =
+        record.price_btc.is_some() || record.price_xmr.is_some() || record.price_fiat.is_some();
+    let variants_priced = !record.variants.is_empty()
+        && record.variants.iter().all(|variant| {
+            variant.price_btc.is_some() || variant.price_xmr.is_some() || variant.price_fiat.is_some()
+        });
+    if !product_priced && !variants_priced {
+        return Err("At least one price is required".to_string());
+    }
+
+    Ok(record)
+}
+
+/// The message of an error without the "Validation error:" prefix
+fn error_message(err: Error) -> String {
+    match err {
+        Error::ValidationError(message) => message,
+        other => other.to_string(),
+    }
+}
+
+fn check_prices(price_btc: &Option<BigDecimal>, price_xmr: &Option<BigDecimal>) -> Result<(), String> {
+    for (name, price) in [("BTC", price_btc), ("XMR", price_xmr)] {
+        if price.as_ref().map_or(false, |price| price <= &BigDecimal::zero()) {
+            return Err(format!("{} price must be greater than zero", name));
+        }
+    }
+    Ok(())
+}
+
+type ApplyResult = Result<(i32, ImportAction, Vec<String>), String>;
+
+/// Create or update one product and its variants
+///
+/// Problems with the record itself come back as `Ok(Err(message))` so they can be reported
+/// against the line; only database failures abort the import.
+fn apply(conn: &mut PgConnection, vendor_id: i32, record: &ProductRecord) -> Result<ApplyResult, Error> {
+    if let Some(category_id) = record.category_id {
+        let exists = diesel::select(diesel::dsl::exists(categories::table.find(category_id)))
+            .get_result::<bool>(conn)?;
+        if !exists {
+            return Ok(Err(format!("Category {} does not exist", category_id)));
+        }
+    }
+
+    let existing = match (record.id, &record.sku) {
+        (Some(id), _) => {
+            let product = products::table
+                .find(id)
+                .filter(products::vendor_id.eq(vendor_id))
+                .for_update()
+                .first::<Product>(conn)
+                .optional()?;
+            match product {
+                Some(product) => Some(product),
+                None => return Ok(Err(format!("Product {} not found", id))),
+            }
+        }
+        (None, Some(sku)) => products::table
+            .filter(products::vendor_id.eq(vendor_id))
+            .filter(products::sku.eq(sku))
+            .for_update()
+            .first::<Product>(conn)
+            .optional()?,
+        (None, None) => None,
+    };
+
+    // Another product of this vendor may already use the SKU
+    if let (Some(sku), Some(product)) = (&record.sku, &existing) {
+        if product.sku.as_ref() != Some(sku) {
+            let taken = diesel::select(diesel::dsl::exists(
+                products::table
+                    .filter(products::vendor_id.eq(vendor_id))
+                    .filter(products::sku.eq(sku))
+                    .filter(products::id.ne(product.id)),
+            ))
+            .get_result::<bool>(conn)?;
+            if taken {
+                return Ok(Err(format!("SKU {} is already used by another product", sku)));
+            }
+        }
+    }
+
+    let (product_id, mut action, mut changes) = match existing {
+        Some(product) => {
+            let changes = product_changes(&product, record);
+            if !changes.is_empty() {
+                diesel::update(&product)
+                    .set((
+                        products::sku.eq(&record.sku),
+                        products::title.eq(&record.title),
+                        products::description.eq(&record.description),
+                        products::category_id.eq(record.category_id),
+                        products::price_btc.eq(&record.price_btc),
+                        products::price_xmr.eq(&record.price_xmr),
+                        products::price_fiat.eq(&record.price_fiat),
+                        products::fiat_currency.eq(&record.fiat_currency),
+                        products::stock.eq(record.stock),
+                        products::is_active.eq(record.is_active),
+                        products::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)?;
+                moderation::review_after_edit(conn, &product, vendor_id)?;
+            }
+            let action = if changes.is_empty() {
+                ImportAction::Unchanged
+            } else {
+                ImportAction::Update
+            };
+            (product.id, action, changes)
+        }
+        None => {
+            let product = diesel::insert_into(products::table)
+                .values(&NewProduct {
+                    vendor_id,
+                    title: record.title.clone(),
+                    description: record.description.clone(),
+                    category_id: record.category_id,
+                    price_btc: record.price_btc.clone(),
+                    price_xmr: record.price_xmr.clone(),
+                    stock: record.stock,
+                    is_active: record.is_active,
+                    low_stock_threshold: None,
+                    deactivate_when_out_of_stock: true,
+                    price_fiat: record.price_fiat.clone(),
+                    fiat_currency: record.fiat_currency.clone(),
+                    sku: record.sku.clone(),
+                })
+                .get_result::<Product>(conn)?;
+            moderation::transition(conn, &product, ListingState::PendingReview, Some(vendor_id), None)?;
+            (product.id, ImportAction::Create, Vec::new())
+        }
+    };
+
+    let existing_variants = product_variants::table
+        .filter(product_variants::product_id.eq(product_id))
+        .for_update()
+        .load::<ProductVariant>(conn)?;
+
+    for variant in &record.variants {
+        let matched = match variant.id {
+            Some(id) => match existing_variants.iter().find(|existing| existing.id == id) {
+                Some(existing) => Some(existing),
+                None => {
+                    return Ok(Err(format!(
+                        "Variant {} does not belong to product {}",
+                        id, product_id
+                    )))
+                }
+            },
+            None => existing_variants
+                .iter()
+                .find(|existing| existing.title.eq_ignore_ascii_case(&variant.title)),
+        };
+
+        match matched {
+            Some(existing) => {
+                let variant_changes = variant_changes(existing, variant);
+                if !variant_changes.is_empty() {
+                    diesel::update(existing)
+                        .set((
+                            product_variants::title.eq(&variant.title),
+                            product_variants::description.eq(&variant.description),
+                            product_variants::price_btc.eq(&variant.price_btc),
+                            product_variants::price_xmr.eq(&variant.price_xmr),
+                            product_variants::price_fiat.eq(&variant.price_fiat),
+                            product_variants::stock.eq(variant.stock),
+                            product_variants::updated_at.eq(Utc::now()),
+                        ))
+                        .execute(conn)?;
+                    changes.extend(
+                        variant_changes
+                            .into_iter()
+                            .map(|field| format!("variants[{}].{}", variant.title, field)),
+                    );
+                }
+            }
+            None => {
+                diesel::insert_into(product_variants::table)
+                    .values(&NewProductVariant {
+                        product_id,
+                        title: variant.title.clone(),
+                        description: variant.description.clone(),
+                        price_btc: variant.price_btc.clone(),
+                        price_xmr: variant.price_xmr.clone(),
+                        stock: variant.stock,
+                        price_fiat: variant.price_fiat.clone(),
+                    })
+                    .execute(conn)?;
+                if action != ImportAction::Create {
+                    changes.push(format!("variants[{}]", variant.title));
+                }
+            }
+        }
+    }
+
+    if action == ImportAction::Unchanged && !changes.is_empty() {
+        action = ImportAction::Update;
+    }
+
+    inventory::apply_stock_rules(conn, product_id)?;
+
+    Ok(Ok((product_id, action, changes)))
+}
+
+fn product_changes(product: &Product, record: &ProductRecord) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut check = |field: &str, changed: bool| {
+        if changed {
+            changes.push(field.to_string());
+        }
+    };
+
+    check("sku", product.sku != record.sku);
+    check("title", product.title != record.title);
+    check("description", product.description != record.description);
+    check("category_id", product.category_id != record.category_id);
+    check("price_btc", product.price_btc != record.price_btc);
+    check("price_xmr", product.price_xmr != record.price_xmr);
+    check("price_fiat", product.price_fiat != record.price_fiat);
+    check("fiat_currency", product.fiat_currency != record.fiat_currency);
+    check("stock", product.stock != record.stock);
+    check("is_active", product.is_active != record.is_active);
+
+    changes
+}
+
+fn variant_changes(variant: &ProductVariant, record: &VariantRecord) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut check = |field: &str, changed: bool| {
+        if changed {
+            changes.push(field.to_string());
+        }
+    };
+
+    check("title", variant.title != record.title);
+    check("description", variant.description != record.description);
+    check("price_btc", variant.price_btc != record.price_btc);
+    check("price_xmr", variant.price_xmr != record.price_xmr);
+    check("price_fiat", variant.price_fiat != record.price_fiat);
+    check("stock", variant.stock != record.stock);
+
+    changes
+}
@@ -0,0 +1,143 @@
+//! `catalog import` and `catalog export`
+//!
+//! ```text
+//! catalog import --vendor <id> --format csv|jsonl [--dry-run] <file>
+//! catalog export --vendor <id> --format csv|jsonl [<file>]
+//! ```
+//!
+//! Export writes to standard output when no file is given.
+
+use futures::TryStreamExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::catalog::{self, Format};
+use crate::database::get_connection;
+use crate::errors::Error;
+
+const USAGE: &str = "usage: catalog import --vendor <id> --format csv|jsonl [--dry-run] <file>\n       catalog export --vendor <id> --format csv|jsonl [<file>]";
+
+struct Options {
+    vendor_id: i32,
+    format: Format,
+    dry_run: bool,
+    path: Option<String>,
+}
+
+pub async fn run(args: &[String]) -> Result<(), Error> {
+    let (command, rest) = args
+        .split_first()
+        .ok_or_else(|| Error::validation_error(USAGE))?;
+    let options = parse_options(rest)?;
+
+    match command.as_str() {
+        "import" => import(options).await,
+        "export" => export(options).await,
+        _ => Err(Error::validation_error(USAGE)),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, Error> {
+    let mut vendor_id = None;
+    let mut format = None;
+    let mut dry_run = false;
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--vendor" => {
+                let value = args.next().ok_or_else(|| Error::validation_error(USAGE))?;
+                vendor_id = Some(
+                    value
+                        .parse::<i32>()
+                        .map_err(|_| Error::validation_error(format!("Invalid vendor id: {}", value)))?,
+                );
+            }
+            "--format" => {
+                let value = args.next().ok_or_else(|| Error::validation_error(USAGE))?;
+                format = Some(value.parse::<Format>()?);
+            }
+            "--dry-run" => dry_run = true,
+            value if value.starts_with("--") => {
+                return Err(Error::validation_error(format!("Unknown option: {}\n{}", value, USAGE)))
+            }
+            value => path = Some(value.to_string()),
+        }
+    }
+
+    Ok(Options {
+        vendor_id: vendor_id.ok_or_else(|| Error::validation_error(USAGE))?,
+        format: format.ok_or_else(|| Error::validation_error(USAGE))?,
+        dry_run,
+        path,
+    })
+}
+
+async fn import(options: Options) -> Result<(), Error> {
+    let path = options.path.ok_or_else(|| Error::validation_error(USAGE))?;
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|err| Error::validation_error(format!("Cannot open {}: {}", path, err)))?;
+
+    let (records, parse_errors) = catalog::read(options.format, file).await?;
+
+    let vendor_id = options.vendor_id;
+    let dry_run = options.dry_run;
+    let report = tokio::task::spawn_blocking(move || {
+        let mut conn = get_connection()?;
+        catalog::import(&mut conn, vendor_id, records, parse_errors, dry_run)
+    })
+    .await??;
+
+    for result in &report.results {
+        println!(
+            "line {}: {:?} product {}{}",
+            result.line,
+            result.action,
+            result.product_id,
+            if result.changes.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", result.changes.join(", "))
+            }
+        );
+    }
+    for error in &report.errors {
+        eprintln!("line {}: {}", error.line, error.message);
+    }
+
+    println!(
+        "{} created, {} updated, {} unchanged, {} errors{}",
+        report.created,
+        report.updated,
+        report.unchanged,
+        report.errors.len(),
+        if report.committed { "" } else { " (nothing written)" }
+    );
+
+    if report.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::validation_error("Import failed"))
+    }
+}
+
+async fn export(options: Options) -> Result<(), Error> {
+    let io_error = |err: std::io::Error| Error::internal_error(format!("Cannot write export: {}", err), None, None);
+
+    let mut output: Box<dyn AsyncWrite + Unpin> = match &options.path {
+        Some(path) => Box::new(tokio::fs::File::create(path).await.map_err(io_error)?),
+        None => Box::new(tokio::io::stdout()),
+    };
+
+    let mut chunks = std::pin::pin!(catalog::export_stream(options.vendor_id, options.format));
+    while let Some(chunk) = chunks.try_next().await? {
+        output.write_all(&chunk).await.map_err(io_error)?;
+    }
+    output.flush().await.map_err(io_error)?;
+
+    if let Some(path) = options.path {
+        eprintln!("Exported the catalog to {}", path);
+    }
+    Ok(())
+}
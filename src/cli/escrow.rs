@@ -0,0 +1,64 @@
+//! `escrow load`
+//!
+//! ```text
+//! escrow load --currency btc|xmr <file>
+//! ```
+//!
+//! Adds the addresses in a file (one per line) to the escrow address pool. Addresses that are
+//! already in the pool are skipped.
+
+use diesel::prelude::*;
+
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::models::escrow::NewEscrowAddress;
+use crate::models::payment::PaymentCurrency;
+use crate::schema::escrow_addresses;
+
+const USAGE: &str = "usage: escrow load --currency btc|xmr <file>";
+
+pub async fn run(args: &[String]) -> Result<(), Error> {
+    match args {
+        [command, flag, currency, path] if command == "load" && flag == "--currency" => {
+            let currency = match currency.to_ascii_lowercase().as_str() {
+                "btc" => PaymentCurrency::BTC,
+                "xmr" => PaymentCurrency::XMR,
+                other => return Err(Error::validation_error(format!("Unknown currency: {}", other))),
+            };
+            load(currency, path).await
+        }
+        _ => Err(Error::validation_error(USAGE)),
+    }
+}
+
+async fn load(currency: PaymentCurrency, path: &str) -> Result<(), Error> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|err| Error::validation_error(format!("Cannot read {}: {}", path, err)))?;
+
+    let addresses: Vec<NewEscrowAddress> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|address| NewEscrowAddress {
+            currency,
+            address: address.to_string(),
+        })
+        .collect();
+
+    let total = addresses.len();
+    let added = tokio::task::spawn_blocking(move || {
+        let mut conn = get_connection()?;
+        Ok::<_, Error>(
+            diesel::insert_into(escrow_addresses::table)
+                .values(&addresses)
+                .on_conflict(escrow_addresses::address)
+                .do_nothing()
+                .execute(&mut conn)?,
+        )
+    })
+    .await??;
+
+    println!("{} of {} {:?} addresses added to the escrow pool", added, total, currency);
+    Ok(())
+}
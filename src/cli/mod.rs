@@ -0,0 +1,37 @@
+//! Command line tools for operators
+//!
+//! `tor_marketplace <command> [args]` runs a command instead of starting the server. The
+//! server's `main` hands its arguments to `run` before anything else, and exits with the
+//! command's result when there was one:
+//!
+//! ```ignore
+//! let args: Vec<String> = std::env::args().skip(1).collect();
+//! if let Some(result) = cli::run(&args).await {
+//!     if let Err(err) = result {
+//!         eprintln!("{}", err);
+//!         std::process::exit(1);
+//!     }
+//!     return;
+//! }
+//! ```
+
+pub mod catalog;
+pub mod escrow;
+pub mod worker;
+
+use crate::errors::Error;
+
+/// Run a command if one was given on the command line
+///
+/// # Returns
+/// * `Option<Result<(), Error>>` - `None` when no command was given and the server should start
+pub async fn run(args: &[String]) -> Option<Result<(), Error>> {
+    let (command, rest) = args.split_first()?;
+
+    Some(match command.as_str() {
+        "catalog" => catalog::run(rest).await,
+        "escrow" => escrow::run(rest).await,
+        "worker" => worker::run(rest).await,
+        other => Err(Error::validation_error(format!("Unknown command: {}", other))),
+    })
+}
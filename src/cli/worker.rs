@@ -0,0 +1,29 @@
+//! `worker`
+//!
+//! ```text
+//! worker
+//! ```
+//!
+//! Runs a job worker without the HTTP server until interrupted; the `worker` binary runs it
+//! directly. Start as many as needed; set `jobs.in_process = false` to keep the server itself
+//! from running jobs.
+
+use tracing::info;
+
+use crate::errors::Error;
+use crate::jobs::{self, worker::Worker};
+
+const USAGE: &str = "usage: worker";
+
+pub async fn run(args: &[String]) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::validation_error(USAGE));
+    }
+
+    tokio::select! {
+        _ = Worker::new(jobs::registry()).run() => {}
+        _ = tokio::signal::ctrl_c() => info!("Worker shutting down"),
+    }
+
+    Ok(())
+}
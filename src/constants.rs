@@ -71,6 +71,337 @@ pub mod tor {
     pub const DEFAULT_CONTROL_PORT: u16 = 9051;
 }
 
+/// Inventory constants
+pub mod inventory {
+    /// How long stock stays reserved for an unpaid order, in minutes
+    pub const DEFAULT_RESERVATION_TTL_MINUTES: i64 = 60;
+}
+
+/// Order timer constants
+pub mod order_timers {
+    /// How often the scheduler looks for due timers, in seconds
+    pub const DEFAULT_INTERVAL_SECONDS: u64 = 60;
+
+    /// Hours a vendor has to accept a paid order before it is cancelled and refunded
+    pub const DEFAULT_ACCEPTANCE_TIMEOUT_HOURS: i64 = 72;
+
+    /// Days after shipping before an order is finalized for the buyer
+    pub const DEFAULT_FINALIZE_AFTER_DAYS: i64 = 14;
+
+    /// Days a buyer can add, once, to the finalize window
+    pub const DEFAULT_EXTENSION_DAYS: i64 = 7;
+
+    /// Maximum number of due timers handled in one scheduler run
+    pub const BATCH_SIZE: i64 = 100;
+}
+
+/// Background job queue constants
+pub mod jobs {
+    /// How many jobs a worker runs at once
+    pub const DEFAULT_CONCURRENCY: i64 = 4;
+
+    /// How often an idle worker polls for new jobs, in milliseconds
+    pub const DEFAULT_POLL_INTERVAL_MS: u64 = 1_000;
+
+    /// Running jobs older than this belong to a crashed worker, in seconds
+    pub const DEFAULT_STALE_AFTER_SECONDS: i64 = 600;
+
+    /// Delay before the first retry, doubled with every attempt, in seconds
+    pub const DEFAULT_BACKOFF_BASE_SECONDS: i64 = 10;
+
+    /// Longest delay between two attempts, in seconds
+    pub const DEFAULT_BACKOFF_MAX_SECONDS: i64 = 3_600;
+
+    /// Attempts a job gets before it is dead-lettered, unless it says otherwise
+    pub const DEFAULT_MAX_ATTEMPTS: i32 = 5;
+}
+
+/// Pricing constants
+pub mod pricing {
+    /// How long fetched exchange rates are reused before asking the provider again, in seconds
+    pub const DEFAULT_CACHE_TTL_SECONDS: u64 = 60;
+
+    /// Rates older than this are never used to price an order, in seconds
+    pub const DEFAULT_MAX_STALENESS_SECONDS: u64 = 900;
+
+    /// How long an order's converted price is locked, in minutes
+    pub const DEFAULT_QUOTE_LOCK_MINUTES: i64 = 30;
+
+    /// Decimal places kept for fiat amounts
+    pub const FIAT_SCALE: i64 = 2;
+
+    /// Decimal places kept for BTC amounts
+    pub const BTC_SCALE: i64 = 8;
+
+    /// Decimal places kept for XMR amounts
+    pub const XMR_SCALE: i64 = 12;
+}
+
+/// Catalog import and export constants
+pub mod catalog {
+    /// Maximum number of lines accepted in a single import
+    pub const MAX_IMPORT_LINES: usize = 10_000;
+
+    /// Maximum size of an import file, in bytes
+    pub const MAX_IMPORT_BYTES: usize = 8 * 1024 * 1024;
+
+    /// Maximum length of one JSON Lines record, in bytes
+    pub const MAX_LINE_BYTES: usize = 64 * 1024;
+
+    /// Products loaded and written per chunk of an export
+    pub const EXPORT_PAGE_SIZE: i64 = 100;
+
+    /// Maximum length of a vendor's SKU
+    pub const MAX_SKU_LENGTH: usize = 100;
+
+    /// Maximum length of a product or variant title
+    pub const MAX_TITLE_LENGTH: usize = 255;
+}
+
+/// Shopping cart constants
+pub mod cart {
+    /// Maximum number of distinct lines in a cart
+    pub const MAX_CART_LINES: i64 = 100;
+
+    /// Maximum quantity of a single cart line
+    pub const MAX_LINE_QUANTITY: i32 = 1_000;
+}
+
+/// Marketplace fee constants
+pub mod fees {
+    /// Default fee on released escrow, in basis points (5%)
+    pub const DEFAULT_FEE_BPS: i32 = 500;
+
+    /// Basis points in a whole; no fee can be higher
+    pub const MAX_FEE_BPS: i32 = 10_000;
+}
+
+/// Vendor analytics constants
+pub mod analytics {
+    /// Days covered when no range is given
+    pub const DEFAULT_RANGE_DAYS: i64 = 30;
+
+    /// Longest range a report can cover, in days
+    pub const MAX_RANGE_DAYS: i64 = 365;
+}
+
+/// Review constants
+pub mod reviews {
+    /// Days after an order completes during which its buyer can review or edit the review
+    pub const REVIEW_WINDOW_DAYS: i64 = 60;
+
+    /// Maximum length of a review comment or vendor reply, in characters
+    pub const MAX_TEXT_LENGTH: usize = 2_000;
+
+    /// Orders below this share of the vendor's median order count as tiny
+    pub const TINY_ORDER_RATIO: f64 = 0.25;
+
+    /// Tiny completed orders from one buyer to one vendor before their reviews look farmed
+    pub const TINY_ORDER_THRESHOLD: i64 = 3;
+
+    /// Accounts younger than this are watched for review bursts, in days
+    pub const NEW_ACCOUNT_DAYS: i64 = 14;
+
+    /// Reviews by a new account within `BURST_WINDOW_HOURS` that count as a burst
+    pub const BURST_REVIEWS: i64 = 3;
+
+    /// The window for counting a review burst, in hours
+    pub const BURST_WINDOW_HOURS: i64 = 24;
+
+    /// Weight of a review that tripped a manipulation check
+    pub const SUSPICIOUS_WEIGHT: f64 = 0.25;
+}
+
+/// Reputation constants
+pub mod reputation {
+    /// A review loses half its weight every this many days
+    pub const REVIEW_HALF_LIFE_DAYS: f64 = 180.0;
+
+    /// Reviews on orders this many times the vendor's median order weigh the most (and at
+    /// most this many times as much as a median order); small orders weigh at least the inverse
+    pub const MAX_VALUE_WEIGHT: f64 = 2.0;
+
+    /// Weight of the neutral rating every vendor starts with, in median-sized fresh reviews
+    pub const PRIOR_REVIEW_WEIGHT: f64 = 2.0;
+
+    /// The neutral starting rating, on the 1-5 scale
+    pub const PRIOR_RATING: f64 = 3.5;
+
+    /// A dispute rate at or above this scores zero
+    pub const MAX_DISPUTE_RATE: f64 = 0.2;
+
+    /// Orders shipped within this many hours of payment count as on time
+    pub const SHIPPING_DEADLINE_HOURS: i64 = 72;
+
+    /// Accounts reach the full age score at this age
+    pub const FULL_AGE_DAYS: f64 = 365.0;
+
+    /// Component weights; they add up to 1
+    pub const RATING_WEIGHT: f64 = 0.5;
+    pub const DISPUTE_WEIGHT: f64 = 0.2;
+    pub const SHIPPING_WEIGHT: f64 = 0.2;
+    pub const AGE_WEIGHT: f64 = 0.1;
+}
+
+/// Vendor bond constants
+pub mod vendor_bonds {
+    /// Bond a buyer pays to become a vendor, in BTC
+    pub const DEFAULT_AMOUNT_BTC: &str = "0.01";
+}
+
+/// Messaging constants
+pub mod messages {
+    /// Maximum size of a message body, in bytes
+    pub const MAX_CONTENT_BYTES: usize = 64 * 1024;
+
+    /// Whether messages must also be encrypted to the sender's own key by default
+    pub const DEFAULT_REQUIRE_SENDER_COPY: bool = false;
+
+    /// Days messages are kept unless a conversation sets its own retention
+    pub const DEFAULT_RETENTION_DAYS: i32 = 180;
+
+    /// Shortest retention a conversation can set
+    pub const MIN_RETENTION_DAYS: i32 = 1;
+
+    /// Days added to the finalize window and its extension for the shortest retention of an
+    /// order conversation
+    pub const ORDER_RETENTION_MARGIN_DAYS: i32 = 7;
+
+    /// Longest retention a conversation can set
+    pub const MAX_RETENTION_DAYS: i32 = 365;
+
+    /// Accounts younger than this many days get the new account conversation quota
+    pub const NEW_ACCOUNT_DAYS: i64 = 7;
+
+    /// Reputation at or above which a user gets the trusted conversation quota
+    pub const TRUSTED_REPUTATION: f64 = 70.0;
+
+    /// Direct conversations a new account can start per day by default
+    pub const DEFAULT_NEW_ACCOUNT_DAILY_CONVERSATIONS: i64 = 3;
+
+    /// Direct conversations an established account can start per day by default
+    pub const DEFAULT_DAILY_CONVERSATIONS: i64 = 10;
+
+    /// Direct conversations a trusted account can start per day by default
+    pub const DEFAULT_TRUSTED_DAILY_CONVERSATIONS: i64 = 30;
+
+    /// Most messages one report can share
+    pub const MAX_REPORTED_MESSAGES: usize = 50;
+
+    /// Largest encrypted attachment, in bytes
+    pub const MAX_ATTACHMENT_BYTES: usize = 5 * 1024 * 1024;
+
+    /// Most attachments one message can carry
+    pub const MAX_ATTACHMENTS_PER_MESSAGE: i64 = 5;
+}
+
+/// Live notification constants
+pub mod events {
+    /// Events held for slow streams before they start missing some
+    pub const BUS_CAPACITY: usize = 1_024;
+
+    /// Seconds between keep-alive comments on an idle stream
+    pub const KEEP_ALIVE_SECONDS: u64 = 15;
+
+    /// Seconds a stream ticket stays valid; reconnects within that time reuse it, and it is
+    /// never renewed
+    pub const TICKET_TTL_SECONDS: i64 = 600;
+
+    /// Seconds between reloads of the no-JavaScript notifications page
+    pub const FALLBACK_REFRESH_SECONDS: u32 = 30;
+
+    /// Cookie that signs the no-JavaScript notifications page in; it carries the session's
+    /// token and is only sent to `SESSION_COOKIE_PATH`
+    pub const SESSION_COOKIE: &str = "notifications_session";
+
+    /// Path the session cookie is scoped to
+    pub const SESSION_COOKIE_PATH: &str = "/notifications";
+
+    /// Postgres channel committed events are sent on
+    pub const NOTIFY_CHANNEL: &str = "marketplace_events";
+
+    /// Milliseconds between checks for new events on the listening connection
+    pub const LISTEN_POLL_MS: u64 = 100;
+
+    /// Seconds to wait before reconnecting a listener that lost its connection
+    pub const LISTEN_RETRY_SECONDS: u64 = 5;
+}
+
+/// Notification constants
+pub mod notifications {
+    /// Days notifications are kept, read or not
+    pub const DEFAULT_RETENTION_DAYS: i64 = 90;
+
+    /// Most notifications kept per user; the oldest go first
+    pub const DEFAULT_MAX_PER_USER: i64 = 500;
+
+    /// Notifications shown on the notification center page
+    pub const CENTER_PAGE_SIZE: i64 = 50;
+}
+
+/// Housekeeping constants
+pub mod housekeeping {
+    /// Seconds between housekeeping runs
+    pub const DEFAULT_INTERVAL_SECONDS: u64 = 3_600;
+}
+
+/// Support ticket constants
+pub mod support {
+    /// Maximum length of a ticket subject, in characters
+    pub const MAX_SUBJECT_LENGTH: usize = 255;
+
+    /// Maximum size of a ticket message, in bytes
+    pub const MAX_CONTENT_BYTES: usize = 64 * 1024;
+}
+
+/// Announcement constants
+pub mod announcements {
+    /// Default number of announcements shown in the banner
+    pub const DEFAULT_BANNER_LIMIT: i64 = 3;
+
+    /// Maximum length of an announcement title, in characters
+    pub const MAX_TITLE_LENGTH: usize = 255;
+
+    /// Maximum size of a signed announcement, in bytes
+    pub const MAX_SIGNED_BYTES: usize = 16 * 1024;
+}
+
+/// Vendor availability constants
+pub mod availability {
+    /// Maximum length of a vendor's away message, in characters
+    pub const MAX_MESSAGE_LENGTH: usize = 500;
+
+    /// Furthest a vacation's return date can be set, in days from today
+    pub const MAX_AWAY_DAYS: i64 = 365;
+}
+
+/// Withdrawal constants
+pub mod withdrawals {
+    /// Hours after confirming a new address before it can be withdrawn to
+    pub const DEFAULT_ADDRESS_COOLDOWN_HOURS: i64 = 48;
+
+    /// Network fee charged on a BTC withdrawal when no estimate is configured
+    pub const DEFAULT_BTC_NETWORK_FEE: &str = "0.00005";
+
+    /// Network fee charged on an XMR withdrawal when no estimate is configured
+    pub const DEFAULT_XMR_NETWORK_FEE: &str = "0.0002";
+
+    /// Maximum number of saved addresses per user and currency
+    pub const MAX_ADDRESSES_PER_CURRENCY: i64 = 10;
+
+    /// Maximum length of an address label, in characters
+    pub const MAX_LABEL_LENGTH: usize = 100;
+}
+
+/// Dispute constants
+pub mod disputes {
+    /// Default hours a moderator has to pick up a new dispute
+    pub const DEFAULT_ASSIGNMENT_SLA_HOURS: i64 = 24;
+
+    /// Default hours an assigned moderator has to rule on a dispute
+    pub const DEFAULT_RESOLUTION_SLA_HOURS: i64 = 72;
+}
+
 /// Cryptocurrency constants
 pub mod crypto {
     /// Bitcoin confirmation threshold
@@ -64,6 +64,9 @@ pub enum Error {
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    #[error("Exchange rate unavailable: {0}")]
+    ExchangeRateUnavailable(String),
+
     #[error("Rate limit exceeded")]
     RateLimitExceeded,
 
@@ -106,6 +109,7 @@ impl Error {
             Error::CryptoError { .. } => (StatusCode::INTERNAL_SERVER_ERROR, 50008),
             Error::TorError { .. } => (StatusCode::INTERNAL_SERVER_ERROR, 50009),
             Error::InternalServerError { .. } => (StatusCode::INTERNAL_SERVER_ERROR, 50010),
+            Error::ExchangeRateUnavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, 50011),
         }
     }
 
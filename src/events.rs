@@ -0,0 +1,134 @@
+//! In-process event bus for live notifications
+//!
+//! `models::notification` stores a notification and queues an event for each user who
+//! should hear about a change, and every open `/events` stream picks out its own user's
+//! events. Events only carry ids: clients refetch what changed through the usual
+//! authenticated routes.
+//!
+//! Events are queued with Postgres `NOTIFY`, which is only delivered once the surrounding
+//! transaction commits and never when it rolls back, so a client refetching on an event
+//! always sees the change. `tasks::events` listens on the channel and publishes what it
+//! receives to the in-process bus, which also carries events queued by a separate worker.
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::constants::events::{BUS_CAPACITY, NOTIFY_CHANNEL, TICKET_TTL_SECONDS};
+use crate::errors::Error;
+use crate::models::order::OrderStatus;
+
+/// Something a user should be told about
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    MessageReceived {
+        conversation_id: i32,
+        message_id: i32,
+        sender_id: i32,
+    },
+    OrderStatusChanged {
+        order_id: i32,
+        status: OrderStatus,
+    },
+    DisputeUpdated {
+        dispute_id: i32,
+        order_id: i32,
+        status: String,
+    },
+    PaymentConfirmed {
+        order_id: i32,
+    },
+    ReviewReceived {
+        review_id: i32,
+        order_id: i32,
+        rating: i32,
+    },
+    SupportTicketUpdated {
+        ticket_id: i32,
+        status: String,
+    },
+}
+
+impl Event {
+    /// The SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::MessageReceived { .. } => "message_received",
+            Event::OrderStatusChanged { .. } => "order_status_changed",
+            Event::DisputeUpdated { .. } => "dispute_updated",
+            Event::PaymentConfirmed { .. } => "payment_confirmed",
+            Event::ReviewReceived { .. } => "review_received",
+            Event::SupportTicketUpdated { .. } => "support_ticket_updated",
+        }
+    }
+}
+
+/// An event and the user it is for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub user_id: i32,
+    pub event: Event,
+}
+
+static BUS: Lazy<broadcast::Sender<Envelope>> = Lazy::new(|| broadcast::channel(BUS_CAPACITY).0);
+
+/// Tell a user about something once the connection's open transaction commits
+///
+/// Outside a transaction the event goes out right away.
+pub fn publish_after_commit(conn: &mut PgConnection, user_id: i32, event: Event) -> Result<(), Error> {
+    let payload = serde_json::to_string(&Envelope { user_id, event })
+        .map_err(|err| Error::internal_error(format!("Cannot serialize event: {}", err), None, None))?;
+
+    diesel::sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(NOTIFY_CHANNEL)
+        .bind::<Text, _>(payload)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Hand a committed event to the open streams; nothing happens when nobody is listening
+pub fn publish(envelope: Envelope) {
+    let _ = BUS.send(envelope);
+}
+
+/// Listen to every event; streams filter out other users' events themselves
+pub fn subscribe() -> broadcast::Receiver<Envelope> {
+    BUS.subscribe()
+}
+
+/// Short-lived tickets that open an event stream
+///
+/// `EventSource` cannot send an `Authorization` header, so a client trades its token for a
+/// ticket and passes that in the stream URL instead. A ticket can only open event streams
+/// and is never renewed; the client trades its token again once it expires.
+static TICKETS: Lazy<Mutex<HashMap<String, (i32, DateTime<Utc>)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Issue a stream ticket for a user
+pub fn issue_ticket(user_id: i32) -> (String, DateTime<Utc>) {
+    let ticket = Uuid::new_v4().simple().to_string();
+    let now = Utc::now();
+    let expires_at = now + Duration::seconds(TICKET_TTL_SECONDS);
+
+    let mut tickets = TICKETS.lock().expect("ticket store poisoned");
+    tickets.retain(|_, (_, expires_at)| *expires_at > now);
+    tickets.insert(ticket.clone(), (user_id, expires_at));
+
+    (ticket, expires_at)
+}
+
+/// The user a ticket was issued to, while it is valid
+pub fn redeem_ticket(ticket: &str) -> Option<i32> {
+    let tickets = TICKETS.lock().expect("ticket store poisoned");
+    tickets
+        .get(ticket)
+        .filter(|(_, expires_at)| *expires_at > Utc::now())
+        .map(|(user_id, _)| *user_id)
+}
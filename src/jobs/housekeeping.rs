@@ -0,0 +1,40 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::jobs::Job;
+use crate::models::{message, notification};
+
+/// Delete notifications past their retention limits
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PruneNotifications;
+
+impl Job for PruneNotifications {
+    const KIND: &'static str = "prune_notifications";
+
+    fn unique_key(&self) -> Option<String> {
+        Some(Self::KIND.to_string())
+    }
+
+    fn run(self, conn: &mut PgConnection) -> Result<(), Error> {
+        notification::prune(conn)?;
+        Ok(())
+    }
+}
+
+/// Hard-delete messages past their conversation's retention
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurgeExpiredMessages;
+
+impl Job for PurgeExpiredMessages {
+    const KIND: &'static str = "purge_expired_messages";
+
+    fn unique_key(&self) -> Option<String> {
+        Some(Self::KIND.to_string())
+    }
+
+    fn run(self, conn: &mut PgConnection) -> Result<(), Error> {
+        message::purge_expired(conn)?;
+        Ok(())
+    }
+}
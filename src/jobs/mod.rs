@@ -0,0 +1,124 @@
+//! Background jobs backed by the `jobs` table
+//!
+//! A job is a typed payload stored as JSON. Workers claim jobs with `SKIP LOCKED`, run them
+//! on the blocking pool, retry failures with exponential backoff and dead-letter jobs that
+//! fail on every attempt. Workers run inside the server (`jobs.in_process`) or on their own
+//! with `tor_marketplace worker`.
+
+pub mod housekeeping;
+pub mod order_timers;
+pub mod reputation;
+pub mod worker;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use tracing::debug;
+
+use crate::constants::jobs::DEFAULT_MAX_ATTEMPTS;
+use crate::errors::Error;
+use crate::models::job::{self, JobRecord, NewJobRecord};
+
+/// A kind of background work
+pub trait Job: Serialize + DeserializeOwned + Send + 'static {
+    /// Stored with every job and used to find its handler; never change it once jobs are queued
+    const KIND: &'static str;
+
+    /// Attempts before the job is dead-lettered
+    const MAX_ATTEMPTS: i32 = DEFAULT_MAX_ATTEMPTS;
+
+    /// Jobs with the same key are not queued twice
+    fn unique_key(&self) -> Option<String> {
+        None
+    }
+
+    /// Do the work; an error schedules a retry
+    ///
+    /// A job can run more than once (after a crash or a failed attempt), so it must be safe to
+    /// repeat.
+    fn run(self, conn: &mut PgConnection) -> Result<(), Error>;
+}
+
+/// Queue a job to run as soon as a worker is free
+///
+/// # Returns
+/// * `Result<Option<JobRecord>, Error>` - The queued job, `None` if an identical unique job
+///   is already queued, or an error
+pub fn enqueue<J: Job>(conn: &mut PgConnection, job: &J) -> Result<Option<JobRecord>, Error> {
+    enqueue_at(conn, job, Utc::now())
+}
+
+/// Queue a job to run no earlier than `run_at`
+pub fn enqueue_at<J: Job>(
+    conn: &mut PgConnection,
+    job: &J,
+    run_at: DateTime<Utc>,
+) -> Result<Option<JobRecord>, Error> {
+    let payload = serde_json::to_value(job).map_err(|err| {
+        Error::internal_error(format!("Cannot serialize {} job: {}", J::KIND, err), None, None)
+    })?;
+
+    let record = job::insert(
+        conn,
+        &NewJobRecord {
+            kind: J::KIND.to_string(),
+            payload,
+            max_attempts: J::MAX_ATTEMPTS,
+            run_at,
+            unique_key: job.unique_key(),
+        },
+    )?;
+
+    match &record {
+        Some(record) => debug!(job_id = record.id, kind = J::KIND, "Job queued"),
+        None => debug!(kind = J::KIND, unique_key = ?job.unique_key(), "Duplicate job skipped"),
+    }
+
+    Ok(record)
+}
+
+type Handler = fn(serde_json::Value, &mut PgConnection) -> Result<(), Error>;
+
+/// The job kinds a worker knows how to run
+#[derive(Default, Clone)]
+pub struct Registry {
+    handlers: HashMap<&'static str, Handler>,
+}
+
+impl Registry {
+    pub fn register<J: Job>(mut self) -> Self {
+        self.handlers.insert(J::KIND, handle::<J>);
+        self
+    }
+
+    pub fn kinds(&self) -> Vec<&'static str> {
+        self.handlers.keys().copied().collect()
+    }
+
+    /// Run a claimed job with the handler for its kind
+    pub fn run(&self, record: &JobRecord, conn: &mut PgConnection) -> Result<(), Error> {
+        let handler = self.handlers.get(record.kind.as_str()).ok_or_else(|| {
+            Error::internal_error(format!("No handler for {} jobs", record.kind), None, None)
+        })?;
+
+        handler(record.payload.clone(), conn)
+    }
+}
+
+fn handle<J: Job>(payload: serde_json::Value, conn: &mut PgConnection) -> Result<(), Error> {
+    let job = serde_json::from_value::<J>(payload).map_err(|err| {
+        Error::internal_error(format!("Invalid {} job payload: {}", J::KIND, err), None, None)
+    })?;
+
+    job.run(conn)
+}
+
+/// Every job kind in the application
+pub fn registry() -> Registry {
+    Registry::default()
+        .register::<order_timers::FireOrderTimer>()
+        .register::<reputation::RecomputeReputation>()
+        .register::<housekeeping::PruneNotifications>()
+        .register::<housekeeping::PurgeExpiredMessages>()
+}
@@ -0,0 +1,25 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::jobs::Job;
+use crate::models::order_timer;
+
+/// Apply a due order timer: cancel an unpaid or unaccepted order, or finalize a shipped one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FireOrderTimer {
+    pub timer_id: i32,
+}
+
+impl Job for FireOrderTimer {
+    const KIND: &'static str = "fire_order_timer";
+
+    fn unique_key(&self) -> Option<String> {
+        Some(format!("{}{}", order_timer::JOB_KEY_PREFIX, self.timer_id))
+    }
+
+    fn run(self, conn: &mut PgConnection) -> Result<(), Error> {
+        order_timer::run(conn, self.timer_id)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::jobs::{self, Job};
+use crate::models::reputation;
+
+/// Recompute a vendor's reputation after a payment, review, shipment, dispute or finished order
+///
+/// The score is always rebuilt from scratch, so one queued job covers every event that
+/// happened before it runs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecomputeReputation {
+    pub vendor_id: i32,
+}
+
+impl Job for RecomputeReputation {
+    const KIND: &'static str = "recompute_reputation";
+
+    fn unique_key(&self) -> Option<String> {
+        Some(format!("reputation:{}", self.vendor_id))
+    }
+
+    fn run(self, conn: &mut PgConnection) -> Result<(), Error> {
+        reputation::recompute(conn, self.vendor_id)?;
+        Ok(())
+    }
+}
+
+/// Queue a reputation update for a vendor
+///
+/// Returns `false` when an update for the vendor was already queued.
+pub fn schedule(conn: &mut PgConnection, vendor_id: i32) -> Result<bool, Error> {
+    Ok(jobs::enqueue(conn, &RecomputeReputation { vendor_id })?.is_some())
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{debug, error, info};
+
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::jobs::Registry;
+use crate::models::job::{self, JobRecord};
+use crate::settings::SETTINGS;
+
+/// Polls the queue and runs the jobs it claims
+pub struct Worker {
+    id: String,
+    registry: Arc<Registry>,
+}
+
+impl Worker {
+    pub fn new(registry: Registry) -> Self {
+        Self {
+            id: format!("{}-{}", std::process::id(), uuid::Uuid::new_v4()),
+            registry: Arc::new(registry),
+        }
+    }
+
+    /// Run the worker in the background of the server
+    pub fn spawn(self) {
+        tokio::spawn(self.run());
+    }
+
+    /// Poll for jobs until the process stops
+    ///
+    /// Claims up to `jobs.concurrency` jobs at a time and runs them side by side. When the
+    /// queue is empty the worker sleeps for `jobs.poll_interval_ms`; while there is work it
+    /// polls again right away.
+    pub async fn run(self) {
+        let idle = Duration::from_millis(SETTINGS.jobs.poll_interval_ms);
+
+        info!(worker_id = %self.id, kinds = ?self.registry.kinds(), "Job worker started");
+
+        loop {
+            match self.run_batch().await {
+                Ok(0) => sleep(idle).await,
+                Ok(count) => debug!(worker_id = %self.id, count = count, "Job batch finished"),
+                Err(err) => {
+                    error!(worker_id = %self.id, error = %err, "Failed to poll the job queue");
+                    sleep(idle).await;
+                }
+            }
+        }
+    }
+
+    async fn run_batch(&self) -> Result<usize, Error> {
+        let worker_id = self.id.clone();
+        let kinds = self.registry.kinds();
+
+        let claimed = tokio::task::spawn_blocking(move || {
+            let mut conn = get_connection()?;
+            job::requeue_stale(&mut conn)?;
+            job::claim(&mut conn, &worker_id, &kinds, SETTINGS.jobs.concurrency)
+        })
+        .await??;
+
+        let count = claimed.len();
+        let handles: Vec<_> = claimed
+            .into_iter()
+            .map(|record| {
+                let registry = Arc::clone(&self.registry);
+                tokio::task::spawn_blocking(move || execute(&registry, record))
+            })
+            .collect();
+
+        for handle in handles {
+            if let Err(err) = handle.await {
+                error!(error = %err, "Job task panicked");
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+/// Run one claimed job and record how it went
+fn execute(registry: &Registry, record: JobRecord) {
+    let result = get_connection().and_then(|mut conn| {
+        let outcome = registry.run(&record, &mut conn);
+
+        match &outcome {
+            Ok(()) => job::complete(&mut conn, record.id)?,
+            Err(err) => job::fail(&mut conn, &record, &err.to_string())?,
+        }
+
+        if outcome.is_ok() {
+            debug!(job_id = record.id, kind = %record.kind, "Job succeeded");
+        }
+        Ok(())
+    });
+
+    // The job stays `running` and is picked up again once it goes stale
+    if let Err(err) = result {
+        error!(job_id = record.id, kind = %record.kind, error = %err, "Failed to record job outcome");
+    }
+}
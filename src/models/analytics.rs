@@ -0,0 +1,533 @@
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::constants::analytics::MAX_RANGE_DAYS;
+use crate::errors::Error;
+use crate::models::order::OrderStatus;
+use crate::models::order_timer::kinds;
+use crate::models::payment::PaymentCurrency;
+use crate::models::product::ListingState;
+use crate::schema::{disputes, order_items, order_timers, orders, product_view_counts, products, reviews};
+use crate::templates::VendorStatsContext;
+
+/// The stretch of time a report covers: the last `days` days up to now
+///
+/// Orders are bounded by their timestamps; views are only counted per day, so they cover the
+/// days from `from`'s up to, but not including, `to`'s. A period never shares a day of views
+/// with the one before it.
+#[derive(Debug, Clone, Copy)]
+pub struct Period {
+    pub days: i64,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl Period {
+    pub fn last_days(days: i64) -> Result<Self, Error> {
+        if !(1..=MAX_RANGE_DAYS).contains(&days) {
+            return Err(Error::validation_error(format!(
+                "The range must be between 1 and {} days",
+                MAX_RANGE_DAYS
+            )));
+        }
+
+        let to = Utc::now();
+        Ok(Self {
+            days,
+            from: to - Duration::days(days),
+            to,
+        })
+    }
+
+    /// The period of the same length just before this one
+    pub fn previous(&self) -> Self {
+        Self {
+            days: self.days,
+            from: self.from - Duration::days(self.days),
+            to: self.from,
+        }
+    }
+}
+
+/// Count a view of a product's listing
+pub fn record_view(conn: &mut PgConnection, product_id: i32) -> Result<(), Error> {
+    diesel::insert_into(product_view_counts::table)
+        .values((
+            product_view_counts::product_id.eq(product_id),
+            product_view_counts::day.eq(Utc::now().date_naive()),
+            product_view_counts::views.eq(1),
+        ))
+        .on_conflict((product_view_counts::product_id, product_view_counts::day))
+        .do_update()
+        .set(product_view_counts::views.eq(product_view_counts::views + 1))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// The headline numbers of a vendor's dashboard
+///
+/// Sales and revenue count orders completed in the period; revenue is what buyers paid
+/// (items and shipping) before marketplace fees. The rating and product counts are current.
+#[derive(Debug, Serialize)]
+pub struct VendorSummary {
+    pub days: i64,
+    pub total_sales: i64,
+    /// Change in completed orders against the previous period, in percent
+    pub sales_change: f64,
+    pub revenue_btc: BigDecimal,
+    pub revenue_xmr: BigDecimal,
+    pub rating: Option<f64>,
+    pub review_count: i64,
+    pub active_products: i64,
+    pub total_products: i64,
+    pub views: i64,
+    /// Orders placed in the period, whatever became of them
+    pub orders_placed: i64,
+    /// Orders placed per listing view
+    pub conversion_rate: Option<f64>,
+}
+
+impl From<VendorSummary> for VendorStatsContext {
+    fn from(summary: VendorSummary) -> Self {
+        Self {
+            total_sales: summary.total_sales as i32,
+            sales_change: summary.sales_change,
+            revenue_btc: summary.revenue_btc.to_string(),
+            revenue_xmr: summary.revenue_xmr.to_string(),
+            rating: summary.rating.unwrap_or(0.0),
+            review_count: summary.review_count as i32,
+            active_products: summary.active_products as i32,
+            total_products: summary.total_products as i32,
+        }
+    }
+}
+
+pub fn summary(conn: &mut PgConnection, vendor_id: i32, period: Period) -> Result<VendorSummary, Error> {
+    let completed = completed_totals(conn, vendor_id, period)?;
+    let previous_sales = completed_totals(conn, vendor_id, period.previous())?.len() as i64;
+    let total_sales = completed.len() as i64;
+
+    let mut revenue_btc = BigDecimal::zero();
+    let mut revenue_xmr = BigDecimal::zero();
+    for (currency, total) in completed {
+        match currency {
+            PaymentCurrency::BTC => revenue_btc += total,
+            PaymentCurrency::XMR => revenue_xmr += total,
+        }
+    }
+
+    let ratings = reviews::table
+        .filter(reviews::vendor_id.eq(vendor_id))
+        .filter(reviews::weight.gt(0.0))
+        .select(reviews::rating)
+        .load::<i32>(conn)?;
+    let rating = (!ratings.is_empty())
+        .then(|| round2(ratings.iter().sum::<i32>() as f64 / ratings.len() as f64));
+
+    let listing_states = products::table
+        .filter(products::vendor_id.eq(vendor_id))
+        .select((products::is_active, products::listing_state))
+        .load::<(bool, ListingState)>(conn)?;
+    let active_products = listing_states
+        .iter()
+        .filter(|(is_active, state)| *is_active && *state == ListingState::Active)
+        .count() as i64;
+
+    let views = view_counts(conn, vendor_id, None, period)?.values().sum::<i64>();
+
+    let orders_placed = orders::table
+        .filter(orders::vendor_id.eq(vendor_id))
+        .filter(orders::created_at.ge(period.from))
+        .filter(orders::created_at.lt(period.to))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok(VendorSummary {
+        days: period.days,
+        total_sales,
+        sales_change: percent_change(previous_sales, total_sales),
+        revenue_btc,
+        revenue_xmr,
+        rating,
+        review_count: ratings.len() as i64,
+        active_products,
+        total_products: listing_states.len() as i64,
+        views,
+        orders_placed,
+        conversion_rate: conversion(orders_placed, views),
+    })
+}
+
+/// How one product did over a period
+#[derive(Debug, Serialize)]
+pub struct ProductStats {
+    pub product_id: i32,
+    pub title: String,
+    pub views: i64,
+    pub orders_placed: i64,
+    pub conversion_rate: Option<f64>,
+    /// Units in completed orders
+    pub units_sold: i64,
+    pub revenue_btc: BigDecimal,
+    pub revenue_xmr: BigDecimal,
+}
+
+/// Every product of a vendor with its views, orders and sales, best sellers first
+pub fn product_stats(conn: &mut PgConnection, vendor_id: i32, period: Period) -> Result<Vec<ProductStats>, Error> {
+    let titles = products::table
+        .filter(products::vendor_id.eq(vendor_id))
+        .select((products::id, products::title))
+        .load::<(i32, String)>(conn)?;
+
+    let views = view_counts(conn, vendor_id, None, period)?;
+    let placed = placed_orders(conn, vendor_id, None, period)?;
+    let sold = sold_items(conn, vendor_id, None, period)?;
+
+    let mut stats: Vec<ProductStats> = titles
+        .into_iter()
+        .map(|(product_id, title)| {
+            let views = views.get(&product_id).copied().unwrap_or(0);
+            let orders_placed = placed.iter().filter(|(id, _)| *id == product_id).count() as i64;
+
+            let mut units_sold = 0;
+            let mut revenue_btc = BigDecimal::zero();
+            let mut revenue_xmr = BigDecimal::zero();
+            for item in sold.iter().filter(|item| item.product_id == product_id) {
+                units_sold += item.quantity as i64;
+                match item.currency {
+                    PaymentCurrency::BTC => revenue_btc += item.amount(),
+                    PaymentCurrency::XMR => revenue_xmr += item.amount(),
+                }
+            }
+
+            ProductStats {
+                product_id,
+                title,
+                views,
+                orders_placed,
+                conversion_rate: conversion(orders_placed, views),
+                units_sold,
+                revenue_btc,
+                revenue_xmr,
+            }
+        })
+        .collect();
+
+    stats.sort_by(|a, b| b.units_sold.cmp(&a.units_sold).then(b.views.cmp(&a.views)));
+    Ok(stats)
+}
+
+/// The width of a point in a sales series
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    #[default]
+    Day,
+    /// Weeks start on Monday
+    Week,
+}
+
+/// One day or week of a product's sales
+#[derive(Debug, Serialize)]
+pub struct SeriesPoint {
+    /// The first day of the bucket
+    pub date: NaiveDate,
+    pub views: i64,
+    pub orders_placed: i64,
+    pub units_sold: i64,
+    pub revenue_btc: BigDecimal,
+    pub revenue_xmr: BigDecimal,
+}
+
+/// A product's views and sales over a period, one point per bucket, empty buckets included
+pub fn product_series(
+    conn: &mut PgConnection,
+    vendor_id: i32,
+    product_id: i32,
+    period: Period,
+    bucket: Bucket,
+) -> Result<Vec<SeriesPoint>, Error> {
+    let owned = diesel::select(diesel::dsl::exists(
+        products::table
+            .find(product_id)
+            .filter(products::vendor_id.eq(vendor_id)),
+    ))
+    .get_result::<bool>(conn)?;
+    if !owned {
+        return Err(Error::not_found());
+    }
+
+    let bucket_of = |date: NaiveDate| match bucket {
+        Bucket::Day => date,
+        Bucket::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+    };
+
+    let mut points: BTreeMap<NaiveDate, SeriesPoint> = BTreeMap::new();
+    let mut day = period.from.date_naive();
+    while day <= period.to.date_naive() {
+        let start = bucket_of(day);
+        points.entry(start).or_insert_with(|| SeriesPoint {
+            date: start,
+            views: 0,
+            orders_placed: 0,
+            units_sold: 0,
+            revenue_btc: BigDecimal::zero(),
+            revenue_xmr: BigDecimal::zero(),
+        });
+        day += Duration::days(1);
+    }
+
+    let daily_views = product_view_counts::table
+        .filter(product_view_counts::product_id.eq(product_id))
+        .filter(product_view_counts::day.ge(period.from.date_naive()))
+        .filter(product_view_counts::day.lt(period.to.date_naive()))
+        .select((product_view_counts::day, product_view_counts::views))
+        .load::<(NaiveDate, i32)>(conn)?;
+    for (day, views) in daily_views {
+        if let Some(point) = points.get_mut(&bucket_of(day)) {
+            point.views += views as i64;
+        }
+    }
+
+    for (_, created_at) in placed_orders(conn, vendor_id, Some(product_id), period)? {
+        if let Some(point) = points.get_mut(&bucket_of(created_at.date_naive())) {
+            point.orders_placed += 1;
+        }
+    }
+
+    for item in sold_items(conn, vendor_id, Some(product_id), period)? {
+        if let Some(point) = points.get_mut(&bucket_of(item.completed_at.date_naive())) {
+            point.units_sold += item.quantity as i64;
+            match item.currency {
+                PaymentCurrency::BTC => point.revenue_btc += item.amount(),
+                PaymentCurrency::XMR => point.revenue_xmr += item.amount(),
+            }
+        }
+    }
+
+    Ok(points.into_values().collect())
+}
+
+/// An order waiting on the vendor
+#[derive(Debug, Serialize)]
+pub struct ActionItem {
+    pub order_id: i32,
+    pub status: OrderStatus,
+    /// What the vendor has to do: `accept`, `ship` or `answer_dispute`
+    pub action: &'static str,
+    pub currency: PaymentCurrency,
+    pub total_amount: BigDecimal,
+    pub created_at: DateTime<Utc>,
+    /// When the order is cancelled if the vendor does nothing
+    pub due_at: Option<DateTime<Utc>>,
+    pub dispute_id: Option<i32>,
+}
+
+/// Orders that need the vendor: paid ones to accept, accepted ones to ship and disputed ones
+/// to answer; most urgent first
+pub fn action_queue(conn: &mut PgConnection, vendor_id: i32) -> Result<Vec<ActionItem>, Error> {
+    let waiting = orders::table
+        .filter(orders::vendor_id.eq(vendor_id))
+        .filter(orders::status.eq_any([OrderStatus::Paid, OrderStatus::Processing, OrderStatus::Disputed]))
+        .select((
+            orders::id,
+            orders::status,
+            orders::currency,
+            orders::total_amount,
+            orders::created_at,
+        ))
+        .load::<(i32, OrderStatus, PaymentCurrency, BigDecimal, DateTime<Utc>)>(conn)?;
+
+    let ids: Vec<i32> = waiting.iter().map(|(id, ..)| *id).collect();
+
+    let deadlines: HashMap<i32, DateTime<Utc>> = order_timers::table
+        .filter(order_timers::order_id.eq_any(&ids))
+        .filter(order_timers::kind.eq(kinds::ACCEPTANCE))
+        .filter(order_timers::fired_at.is_null())
+        .filter(order_timers::cancelled_at.is_null())
+        .select((order_timers::order_id, order_timers::due_at))
+        .load::<(i32, DateTime<Utc>)>(conn)?
+        .into_iter()
+        .collect();
+
+    let open_disputes: HashMap<i32, i32> = disputes::table
+        .filter(disputes::order_id.eq_any(&ids))
+        .filter(disputes::resolved_at.is_null())
+        .select((disputes::order_id, disputes::id))
+        .load::<(i32, i32)>(conn)?
+        .into_iter()
+        .collect();
+
+    let mut queue: Vec<ActionItem> = waiting
+        .into_iter()
+        .map(|(order_id, status, currency, total_amount, created_at)| ActionItem {
+            order_id,
+            status,
+            action: match status {
+                OrderStatus::Paid => "accept",
+                OrderStatus::Processing => "ship",
+                _ => "answer_dispute",
+            },
+            currency,
+            total_amount,
+            created_at,
+            due_at: deadlines.get(&order_id).copied(),
+            dispute_id: open_disputes.get(&order_id).copied(),
+        })
+        .collect();
+
+    // Deadlines first, soonest at the top; then oldest orders
+    queue.sort_by(|a, b| match (a.due_at, b.due_at) {
+        (Some(a_due), Some(b_due)) => a_due.cmp(&b_due),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.created_at.cmp(&b.created_at),
+    });
+
+    Ok(queue)
+}
+
+/// Currency and total of each of the vendor's orders completed in the period
+fn completed_totals(
+    conn: &mut PgConnection,
+    vendor_id: i32,
+    period: Period,
+) -> Result<Vec<(PaymentCurrency, BigDecimal)>, Error> {
+    let totals = orders::table
+        .filter(orders::vendor_id.eq(vendor_id))
+        .filter(orders::status.eq(OrderStatus::Completed))
+        .filter(orders::completed_at.ge(period.from))
+        .filter(orders::completed_at.lt(period.to))
+        .select((orders::currency, orders::total_amount))
+        .load::<(PaymentCurrency, BigDecimal)>(conn)?;
+
+    Ok(totals)
+}
+
+/// Listing views per product in the period
+fn view_counts(
+    conn: &mut PgConnection,
+    vendor_id: i32,
+    product_id: Option<i32>,
+    period: Period,
+) -> Result<HashMap<i32, i64>, Error> {
+    let mut query = product_view_counts::table
+        .inner_join(products::table)
+        .filter(products::vendor_id.eq(vendor_id))
+        .filter(product_view_counts::day.ge(period.from.date_naive()))
+        .filter(product_view_counts::day.lt(period.to.date_naive()))
+        .select((product_view_counts::product_id, product_view_counts::views))
+        .into_boxed();
+    if let Some(product_id) = product_id {
+        query = query.filter(product_view_counts::product_id.eq(product_id));
+    }
+
+    let mut counts = HashMap::new();
+    for (product_id, views) in query.load::<(i32, i32)>(conn)? {
+        *counts.entry(product_id).or_insert(0) += views as i64;
+    }
+
+    Ok(counts)
+}
+
+/// (product, order placed at) for every order placed in the period, once per product
+fn placed_orders(
+    conn: &mut PgConnection,
+    vendor_id: i32,
+    product_id: Option<i32>,
+    period: Period,
+) -> Result<Vec<(i32, DateTime<Utc>)>, Error> {
+    let mut query = order_items::table
+        .inner_join(orders::table)
+        .filter(orders::vendor_id.eq(vendor_id))
+        .filter(orders::created_at.ge(period.from))
+        .filter(orders::created_at.lt(period.to))
+        .select((order_items::product_id, orders::id, orders::created_at))
+        .distinct()
+        .into_boxed();
+    if let Some(product_id) = product_id {
+        query = query.filter(order_items::product_id.eq(product_id));
+    }
+
+    let placed = query
+        .load::<(i32, i32, DateTime<Utc>)>(conn)?
+        .into_iter()
+        .map(|(product_id, _, created_at)| (product_id, created_at))
+        .collect();
+
+    Ok(placed)
+}
+
+struct SoldItem {
+    product_id: i32,
+    quantity: i32,
+    price_per_unit: BigDecimal,
+    currency: PaymentCurrency,
+    completed_at: DateTime<Utc>,
+}
+
+impl SoldItem {
+    fn amount(&self) -> BigDecimal {
+        &self.price_per_unit * BigDecimal::from(self.quantity)
+    }
+}
+
+/// Items of the vendor's orders completed in the period
+fn sold_items(
+    conn: &mut PgConnection,
+    vendor_id: i32,
+    product_id: Option<i32>,
+    period: Period,
+) -> Result<Vec<SoldItem>, Error> {
+    let mut query = order_items::table
+        .inner_join(orders::table)
+        .filter(orders::vendor_id.eq(vendor_id))
+        .filter(orders::status.eq(OrderStatus::Completed))
+        .filter(orders::completed_at.ge(period.from))
+        .filter(orders::completed_at.lt(period.to))
+        .select((
+            order_items::product_id,
+            order_items::quantity,
+            order_items::price_per_unit,
+            orders::currency,
+            orders::completed_at.assume_not_null(),
+        ))
+        .into_boxed();
+    if let Some(product_id) = product_id {
+        query = query.filter(order_items::product_id.eq(product_id));
+    }
+
+    let items = query
+        .load::<(i32, i32, BigDecimal, PaymentCurrency, DateTime<Utc>)>(conn)?
+        .into_iter()
+        .map(|(product_id, quantity, price_per_unit, currency, completed_at)| SoldItem {
+            product_id,
+            quantity,
+            price_per_unit,
+            currency,
+            completed_at,
+        })
+        .collect();
+
+    Ok(items)
+}
+
+fn percent_change(previous: i64, current: i64) -> f64 {
+    match previous {
+        0 if current == 0 => 0.0,
+        0 => 100.0,
+        _ => round2((current - previous) as f64 / previous as f64 * 100.0),
+    }
+}
+
+fn conversion(orders: i64, views: i64) -> Option<f64> {
+    (views > 0).then(|| (orders as f64 / views as f64 * 10_000.0).round() / 10_000.0)
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
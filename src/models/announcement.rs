@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::constants::announcements::*;
+use crate::errors::Error;
+use crate::pgp;
+use crate::schema::announcements;
+use crate::settings::SETTINGS;
+
+/// Valid announcement severities, least severe first
+pub mod severities {
+    /// General news
+    pub const INFO: &str = "info";
+    /// Planned maintenance, policy changes
+    pub const WARNING: &str = "warning";
+    /// Key rotations, outages, anything users must act on
+    pub const CRITICAL: &str = "critical";
+
+    pub const ALL: [&str; 3] = [INFO, WARNING, CRITICAL];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = announcements)]
+pub struct Announcement {
+    pub id: i32,
+    pub author_id: Option<i32>,
+    pub severity: String,
+    /// The first line of the signed text
+    pub title: String,
+    /// The rest of the signed text
+    pub body: String,
+    /// The announcement exactly as signed, for `gpg --verify`
+    pub signed_message: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub withdrawn_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Announcement {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = announcements)]
+struct NewAnnouncement {
+    author_id: i32,
+    severity: String,
+    title: String,
+    body: String,
+    signed_message: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Publish an announcement signed offline with the marketplace key
+///
+/// The signed text's first line becomes the title and the rest the body, so everything
+/// users read is covered by the signature.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `author_id` - The admin publishing it
+/// * `severity` - One of `severities`
+/// * `signed_message` - The output of `gpg --clearsign` with the key in `announcements.pgp_public_key`
+/// * `expires_at` - When it stops showing in the banner, if ever
+///
+/// # Returns
+/// * `Result<Announcement, Error>` - The published announcement or an error
+pub fn publish(
+    conn: &mut PgConnection,
+    author_id: i32,
+    severity: &str,
+    signed_message: &str,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<Announcement, Error> {
+    if !severities::ALL.contains(&severity) {
+        return Err(Error::validation_error(format!("Invalid severity: {}", severity)));
+    }
+    if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(Error::validation_error("The expiry must be in the future"));
+    }
+    if signed_message.len() > MAX_SIGNED_BYTES {
+        return Err(Error::validation_error(format!(
+            "Announcements are limited to {} KiB",
+            MAX_SIGNED_BYTES / 1024
+        )));
+    }
+
+    let key = SETTINGS
+        .announcements
+        .pgp_public_key
+        .as_deref()
+        .ok_or_else(|| Error::validation_error("No marketplace key is configured for announcements"))?;
+
+    let signed_text = pgp::verify_cleartext(key, signed_message).map_err(|err| match err {
+        Error::ValidationError(_) => {
+            Error::validation_error("The announcement is not signed with the marketplace key")
+        }
+        err => err,
+    })?;
+
+    let signed_text = signed_text.trim();
+    let (title, body) = signed_text.split_once('\n').unwrap_or((signed_text, ""));
+    let title = title.trim();
+    if title.is_empty() {
+        return Err(Error::validation_error("The first line of the announcement must be its title"));
+    }
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(Error::validation_error(format!(
+            "Titles are limited to {} characters",
+            MAX_TITLE_LENGTH
+        )));
+    }
+
+    let announcement = diesel::insert_into(announcements::table)
+        .values(&NewAnnouncement {
+            author_id,
+            severity: severity.to_string(),
+            title: title.to_string(),
+            body: body.trim().to_string(),
+            signed_message: signed_message.to_string(),
+            expires_at,
+        })
+        .get_result::<Announcement>(conn)?;
+
+    info!(announcement_id = announcement.id, author_id = author_id, severity = %severity, "Announcement published");
+    Ok(announcement)
+}
+
+/// The announcements the banner shows: not expired or withdrawn, most severe and then newest first
+pub fn active(conn: &mut PgConnection, limit: i64) -> Result<Vec<Announcement>, Error> {
+    Ok(announcements::table
+        .filter(announcements::withdrawn_at.is_null())
+        .filter(
+            announcements::expires_at
+                .is_null()
+                .or(announcements::expires_at.gt(Utc::now())),
+        )
+        .order((
+            sql::<Integer>("CASE severity WHEN 'critical' THEN 0 WHEN 'warning' THEN 1 ELSE 2 END").asc(),
+            announcements::created_at.desc(),
+        ))
+        .limit(limit)
+        .load::<Announcement>(conn)?)
+}
+
+/// One page of announcements that were not withdrawn, newest first
+pub fn list(
+    conn: &mut PgConnection,
+    include_expired: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Announcement>, i64), Error> {
+    let mut count_query = announcements::table
+        .filter(announcements::withdrawn_at.is_null())
+        .into_boxed();
+    let mut query = announcements::table
+        .filter(announcements::withdrawn_at.is_null())
+        .into_boxed();
+
+    if !include_expired {
+        let now = Utc::now();
+        count_query = count_query.filter(announcements::expires_at.is_null().or(announcements::expires_at.gt(now)));
+        query = query.filter(announcements::expires_at.is_null().or(announcements::expires_at.gt(now)));
+    }
+
+    let total = count_query.count().get_result::<i64>(conn)?;
+
+    let announcements = query
+        .order((announcements::created_at.desc(), announcements::id.desc()))
+        .limit(limit)
+        .offset(offset)
+        .load::<Announcement>(conn)?;
+
+    Ok((announcements, total))
+}
+
+/// Take an announcement down; the row is kept so its signature can still be audited
+pub fn withdraw(conn: &mut PgConnection, id: i32) -> Result<Announcement, Error> {
+    let announcement = diesel::update(
+        announcements::table
+            .find(id)
+            .filter(announcements::withdrawn_at.is_null()),
+    )
+    .set(announcements::withdrawn_at.eq(Utc::now()))
+    .get_result::<Announcement>(conn)
+    .optional()?
+    .ok_or_else(Error::not_found)?;
+
+    info!(announcement_id = id, "Announcement withdrawn");
+    Ok(announcement)
+}
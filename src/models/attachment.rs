@@ -0,0 +1,186 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use tracing::info;
+
+use crate::constants::messages::{MAX_ATTACHMENTS_PER_MESSAGE, MAX_ATTACHMENT_BYTES};
+use crate::errors::Error;
+use crate::models::block;
+use crate::models::message::{self, Message};
+use crate::models::user::User;
+use crate::pgp;
+use crate::schema::{conversations, message_attachments, messages, users};
+use crate::settings::SETTINGS;
+
+/// A PGP-encrypted file sent with a message, without its contents
+///
+/// Attachments are deleted with their message, so they expire under the conversation's
+/// retention and are kept under the same legal hold.
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Attachment {
+    pub id: i32,
+    pub message_id: i32,
+    pub size_bytes: i32,
+    /// ASCII-armored rather than binary OpenPGP
+    pub armored: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+type AttachmentColumns = (
+    message_attachments::id,
+    message_attachments::message_id,
+    message_attachments::size_bytes,
+    message_attachments::armored,
+    message_attachments::created_at,
+);
+
+const ATTACHMENT_COLUMNS: AttachmentColumns = (
+    message_attachments::id,
+    message_attachments::message_id,
+    message_attachments::size_bytes,
+    message_attachments::armored,
+    message_attachments::created_at,
+);
+
+/// Attach an encrypted file to a message the sender wrote
+///
+/// The file must be an OpenPGP message encrypted to the recipient's current key, and to the
+/// sender's too when `messaging.require_sender_copy` is set. The server never encrypts
+/// attachments itself, whatever the sender's message settings.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `sender_id` - The author of the message
+/// * `message_id` - The message
+/// * `data` - The encrypted file
+///
+/// # Returns
+/// * `Result<Attachment, Error>` - The stored attachment or an error
+pub fn attach(conn: &mut PgConnection, sender_id: i32, message_id: i32, data: &[u8]) -> Result<Attachment, Error> {
+    if data.is_empty() {
+        return Err(Error::validation_error("The attachment is empty"));
+    }
+    if data.len() > MAX_ATTACHMENT_BYTES {
+        return Err(Error::validation_error(format!(
+            "Attachments are limited to {} MiB",
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        )));
+    }
+
+    let (recipients, armored) = pgp::file_recipients(data)?;
+
+    conn.transaction::<Attachment, Error, _>(|conn| {
+        let message = messages::table
+            .find(message_id)
+            .filter(messages::sender_id.eq(sender_id))
+            .for_update()
+            .first::<Message>(conn)
+            .optional()?
+            .ok_or_else(Error::not_found)?;
+
+        let conversation = message::find_for_participant(conn, message.conversation_id, sender_id)?;
+        if conversation.is_locked() {
+            return Err(Error::validation_error(
+                "The order is closed, so its conversation is read-only",
+            ));
+        }
+        let recipient_id = conversation.other_user_id(sender_id);
+        if conversation.order_id.is_none() && block::between(conn, sender_id, recipient_id)? {
+            return Err(Error::validation_error("You cannot message this user"));
+        }
+
+        let attached = message_attachments::table
+            .filter(message_attachments::message_id.eq(message.id))
+            .count()
+            .get_result::<i64>(conn)?;
+        if attached >= MAX_ATTACHMENTS_PER_MESSAGE {
+            return Err(Error::validation_error(format!(
+                "A message can carry at most {} attachments",
+                MAX_ATTACHMENTS_PER_MESSAGE
+            )));
+        }
+
+        let recipient = users::table.find(recipient_id).first::<User>(conn)?;
+        let recipient_key = recipient.pgp_public_key.as_deref().ok_or_else(|| {
+            Error::validation_error(format!(
+                "{} has no PGP key, so files to them cannot be encrypted",
+                recipient.username
+            ))
+        })?;
+        if !pgp::encrypted_to(&recipients, recipient_key)? {
+            return Err(Error::validation_error(
+                "The attachment is not encrypted to the recipient's current PGP key",
+            ));
+        }
+
+        if SETTINGS.messaging.require_sender_copy {
+            let sender_key = users::table
+                .find(sender_id)
+                .select(users::pgp_public_key)
+                .first::<Option<String>>(conn)?
+                .ok_or_else(|| Error::validation_error("Add a PGP key to your account to send messages"))?;
+            if !pgp::encrypted_to(&recipients, &sender_key)? {
+                return Err(Error::validation_error("The attachment must also be encrypted to your own PGP key"));
+            }
+        }
+
+        let attachment = diesel::insert_into(message_attachments::table)
+            .values((
+                message_attachments::message_id.eq(message.id),
+                message_attachments::encrypted_data.eq(data),
+                message_attachments::size_bytes.eq(data.len() as i32),
+                message_attachments::armored.eq(armored),
+            ))
+            .returning(ATTACHMENT_COLUMNS)
+            .get_result::<Attachment>(conn)?;
+
+        info!(
+            attachment_id = attachment.id,
+            message_id = message.id,
+            size_bytes = attachment.size_bytes,
+            "Attachment added"
+        );
+        Ok(attachment)
+    })
+}
+
+/// The attachments of some messages, oldest first
+pub fn for_messages(conn: &mut PgConnection, message_ids: &[i32]) -> Result<Vec<Attachment>, Error> {
+    Ok(message_attachments::table
+        .filter(message_attachments::message_id.eq_any(message_ids))
+        .order(message_attachments::id.asc())
+        .select(ATTACHMENT_COLUMNS)
+        .load::<Attachment>(conn)?)
+}
+
+/// An attachment and its encrypted contents, for a participant of its conversation
+///
+/// Anyone else, moderators included, gets not found.
+pub fn download(conn: &mut PgConnection, attachment_id: i32, user_id: i32) -> Result<(Attachment, Vec<u8>), Error> {
+    let (id, message_id, size_bytes, armored, created_at, data) = message_attachments::table
+        .inner_join(messages::table.inner_join(conversations::table))
+        .filter(message_attachments::id.eq(attachment_id))
+        .filter(conversations::user1_id.eq(user_id).or(conversations::user2_id.eq(user_id)))
+        .select((
+            message_attachments::id,
+            message_attachments::message_id,
+            message_attachments::size_bytes,
+            message_attachments::armored,
+            message_attachments::created_at,
+            message_attachments::encrypted_data,
+        ))
+        .first::<(i32, i32, i32, bool, DateTime<Utc>, Vec<u8>)>(conn)
+        .optional()?
+        .ok_or_else(Error::not_found)?;
+
+    Ok((
+        Attachment {
+            id,
+            message_id,
+            size_bytes,
+            armored,
+            created_at,
+        },
+        data,
+    ))
+}
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::constants::availability::*;
+use crate::errors::Error;
+use crate::models::user::{roles, User};
+use crate::schema::{users, vendor_availability};
+
+/// Valid vendor availability statuses
+pub mod statuses {
+    /// Taking orders
+    pub const ACTIVE: &str = "active";
+    /// Away for a while; listings stay up (unless hidden) but cannot be bought
+    pub const VACATION: &str = "vacation";
+    /// Not selling until further notice; listings are hidden
+    pub const CLOSED: &str = "closed";
+
+    pub const ALL: [&str; 3] = [ACTIVE, VACATION, CLOSED];
+}
+
+/// Whether a vendor is taking orders, and what buyers are told when they are not
+///
+/// Vendors who never set their availability have no row and are active.
+#[derive(Debug, Clone, Serialize, Queryable, Identifiable)]
+#[diesel(table_name = vendor_availability, primary_key(vendor_id))]
+pub struct Availability {
+    pub vendor_id: i32,
+    pub status: String,
+    pub return_date: Option<NaiveDate>,
+    pub message: Option<String>,
+    pub hide_listings: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Availability {
+    /// The availability of a vendor who never set one
+    pub fn active(vendor_id: i32) -> Self {
+        Self {
+            vendor_id,
+            status: statuses::ACTIVE.to_string(),
+            return_date: None,
+            message: None,
+            hide_listings: false,
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status == statuses::ACTIVE
+    }
+
+    /// Whether buyers should not see the vendor's listings at all
+    pub fn hides_listings(&self) -> bool {
+        self.status == statuses::CLOSED || (self.status == statuses::VACATION && self.hide_listings)
+    }
+
+    /// What a buyer is told when trying to order from the vendor
+    fn unavailable_reason(&self) -> String {
+        let mut reason = match (self.status.as_str(), self.return_date) {
+            (statuses::VACATION, Some(return_date)) => {
+                format!("This vendor is on vacation until {}", return_date)
+            }
+            (statuses::VACATION, None) => "This vendor is on vacation".to_string(),
+            _ => "This vendor is not taking orders".to_string(),
+        };
+        if let Some(message) = &self.message {
+            reason.push_str(": ");
+            reason.push_str(message);
+        }
+        reason
+    }
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = vendor_availability, treat_none_as_null = true)]
+struct NewAvailability {
+    vendor_id: i32,
+    status: String,
+    return_date: Option<NaiveDate>,
+    message: Option<String>,
+    hide_listings: bool,
+    updated_at: DateTime<Utc>,
+}
+
+/// What a vendor sets
+#[derive(Debug, Deserialize)]
+pub struct AvailabilityInput {
+    pub status: String,
+    pub return_date: Option<NaiveDate>,
+    pub message: Option<String>,
+    #[serde(default)]
+    pub hide_listings: bool,
+}
+
+/// A vendor's availability, active when never set
+pub fn get(conn: &mut PgConnection, vendor_id: i32) -> Result<Availability, Error> {
+    Ok(vendor_availability::table
+        .find(vendor_id)
+        .first::<Availability>(conn)
+        .optional()?
+        .unwrap_or_else(|| Availability::active(vendor_id)))
+}
+
+/// The availability of several vendors, keyed by vendor id
+pub fn load(conn: &mut PgConnection, vendor_ids: &[i32]) -> Result<HashMap<i32, Availability>, Error> {
+    let mut found: HashMap<i32, Availability> = vendor_availability::table
+        .filter(vendor_availability::vendor_id.eq_any(vendor_ids))
+        .load::<Availability>(conn)?
+        .into_iter()
+        .map(|availability| (availability.vendor_id, availability))
+        .collect();
+
+    for vendor_id in vendor_ids {
+        found
+            .entry(*vendor_id)
+            .or_insert_with(|| Availability::active(*vendor_id));
+    }
+    Ok(found)
+}
+
+/// Fail unless the vendor is taking orders
+///
+/// Orders already placed are not affected by a vendor going away; they keep their timers.
+pub fn ensure_open(conn: &mut PgConnection, vendor_id: i32) -> Result<(), Error> {
+    let availability = get(conn, vendor_id)?;
+    if availability.is_open() {
+        Ok(())
+    } else {
+        Err(Error::validation_error(availability.unavailable_reason()))
+    }
+}
+
+/// Change a vendor's availability
+///
+/// Going back to active clears the return date and message. The return date is only
+/// informative; the vendor stays on vacation until they switch back themselves.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `vendor_id` - The vendor
+/// * `input` - The new status, return date, message and whether to hide listings
+///
+/// # Returns
+/// * `Result<Availability, Error>` - The saved availability or an error
+pub fn set(conn: &mut PgConnection, vendor_id: i32, input: AvailabilityInput) -> Result<Availability, Error> {
+    if !statuses::ALL.contains(&input.status.as_str()) {
+        return Err(Error::validation_error(format!(
+            "Invalid availability status: {}",
+            input.status
+        )));
+    }
+
+    let vendor = users::table.find(vendor_id).first::<User>(conn)?;
+    if vendor.role != roles::VENDOR {
+        return Err(Error::validation_error("Only vendors can set their availability"));
+    }
+
+    let away = input.status != statuses::ACTIVE;
+
+    let message = input
+        .message
+        .map(|message| message.trim().to_string())
+        .filter(|message| away && !message.is_empty());
+    if message
+        .as_ref()
+        .is_some_and(|message| message.chars().count() > MAX_MESSAGE_LENGTH)
+    {
+        return Err(Error::validation_error(format!(
+            "Away messages are limited to {} characters",
+            MAX_MESSAGE_LENGTH
+        )));
+    }
+
+    let return_date = match input.return_date {
+        Some(_) if input.status != statuses::VACATION => {
+            return Err(Error::validation_error("Only a vacation can have a return date"));
+        }
+        Some(return_date) => {
+            let today = Utc::now().date_naive();
+            if return_date <= today {
+                return Err(Error::validation_error("The return date must be in the future"));
+            }
+            if return_date > today + Duration::days(MAX_AWAY_DAYS) {
+                return Err(Error::validation_error(format!(
+                    "The return date can be at most {} days away",
+                    MAX_AWAY_DAYS
+                )));
+            }
+            Some(return_date)
+        }
+        None => None,
+    };
+
+    let row = NewAvailability {
+        vendor_id,
+        hide_listings: input.status == statuses::VACATION && input.hide_listings,
+        status: input.status,
+        return_date,
+        message,
+        updated_at: Utc::now(),
+    };
+
+    let availability = diesel::insert_into(vendor_availability::table)
+        .values(&row)
+        .on_conflict(vendor_availability::vendor_id)
+        .do_update()
+        .set(&row)
+        .get_result::<Availability>(conn)?;
+
+    info!(
+        vendor_id = vendor_id,
+        status = %availability.status,
+        hide_listings = availability.hide_listings,
+        "Vendor availability changed"
+    );
+    Ok(availability)
+}
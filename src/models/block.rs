@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use tracing::info;
+
+use crate::errors::Error;
+use crate::schema::{user_blocks, users};
+
+/// A user someone has blocked
+///
+/// Blocked users cannot start direct conversations with the blocker or message them in one.
+/// Conversations about an order stay open, since the order cannot finish without them.
+#[derive(Debug, Serialize, Queryable)]
+pub struct BlockedUser {
+    pub user_id: i32,
+    pub username: String,
+    pub blocked_at: DateTime<Utc>,
+}
+
+/// Block a user; blocking someone already blocked changes nothing
+pub fn block(conn: &mut PgConnection, blocker_id: i32, blocked_id: i32) -> Result<(), Error> {
+    if blocker_id == blocked_id {
+        return Err(Error::validation_error("You cannot block yourself"));
+    }
+
+    let exists = diesel::select(diesel::dsl::exists(users::table.find(blocked_id))).get_result::<bool>(conn)?;
+    if !exists {
+        return Err(Error::not_found());
+    }
+
+    let inserted = diesel::insert_into(user_blocks::table)
+        .values((
+            user_blocks::blocker_id.eq(blocker_id),
+            user_blocks::blocked_id.eq(blocked_id),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    if inserted > 0 {
+        info!(blocker_id = blocker_id, blocked_id = blocked_id, "User blocked");
+    }
+    Ok(())
+}
+
+pub fn unblock(conn: &mut PgConnection, blocker_id: i32, blocked_id: i32) -> Result<(), Error> {
+    diesel::delete(user_blocks::table.find((blocker_id, blocked_id))).execute(conn)?;
+    Ok(())
+}
+
+/// The users someone has blocked, most recent first
+pub fn list(conn: &mut PgConnection, blocker_id: i32) -> Result<Vec<BlockedUser>, Error> {
+    Ok(user_blocks::table
+        .inner_join(users::table.on(users::id.eq(user_blocks::blocked_id)))
+        .filter(user_blocks::blocker_id.eq(blocker_id))
+        .order(user_blocks::created_at.desc())
+        .select((users::id, users::username, user_blocks::created_at))
+        .load::<BlockedUser>(conn)?)
+}
+
+/// Whether either user has blocked the other
+pub fn between(conn: &mut PgConnection, user_id: i32, other_id: i32) -> Result<bool, Error> {
+    Ok(diesel::select(diesel::dsl::exists(
+        user_blocks::table.filter(
+            user_blocks::blocker_id
+                .eq(user_id)
+                .and(user_blocks::blocked_id.eq(other_id))
+                .or(user_blocks::blocker_id.eq(other_id).and(user_blocks::blocked_id.eq(user_id))),
+        ),
+    ))
+    .get_result::<bool>(conn)?)
+}
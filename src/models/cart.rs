@@ -0,0 +1,63 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::product::Product;
+use crate::schema::{cart_items, shipping_options};
+
+/// A line of a buyer's cart
+///
+/// Prices are not stored: they are worked out when the cart is shown and again at checkout,
+/// so the cart always reflects the current listing.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = cart_items)]
+#[diesel(belongs_to(Product))]
+pub struct CartItem {
+    pub id: i32,
+    pub user_id: i32,
+    pub product_id: i32,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = cart_items)]
+pub struct NewCartItem {
+    pub user_id: i32,
+    pub product_id: i32,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+}
+
+/// A way a vendor ships orders, chosen by the buyer at checkout
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = shipping_options)]
+pub struct ShippingOption {
+    pub id: i32,
+    pub vendor_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub price_btc: Option<BigDecimal>,
+    pub price_xmr: Option<BigDecimal>,
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = shipping_options)]
+pub struct NewShippingOption {
+    #[serde(skip)]
+    pub vendor_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub price_btc: Option<BigDecimal>,
+    pub price_xmr: Option<BigDecimal>,
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
+}
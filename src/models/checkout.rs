@@ -0,0 +1,275 @@
+use bigdecimal::BigDecimal;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::Error;
+use crate::models::availability;
+use crate::models::cart::ShippingOption;
+use crate::models::escrow;
+use crate::models::inventory::{self, ReservationRequest};
+use crate::models::message;
+use crate::models::order::{NewOrder, NewOrderItem, NewOrderQuote, NewOrderStatusHistory, Order, OrderStatus};
+use crate::models::order_timer;
+use crate::models::payment::PaymentCurrency;
+use crate::models::product::{ListingState, Product, ProductVariant};
+use crate::pricing::{self, ExchangeRates};
+use crate::schema::{
+    order_items, order_quotes, order_status_history, orders, product_variants, products,
+    shipping_options,
+};
+use crate::settings::SETTINGS;
+
+/// A product and quantity to order
+#[derive(Debug, Clone, Copy)]
+pub struct OrderLine {
+    pub product_id: i32,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+}
+
+/// Everything needed to place one order with one vendor
+#[derive(Debug, Clone)]
+pub struct PlaceOrder {
+    pub buyer_id: i32,
+    pub currency: PaymentCurrency,
+    pub encrypted_shipping_address: String,
+    pub shipping_option_id: Option<i32>,
+    /// Groups the orders created by one cart checkout
+    pub checkout_id: Option<String>,
+    pub lines: Vec<OrderLine>,
+}
+
+/// The fiat currencies needed to price some products and shipping options
+///
+/// Used to fetch exchange rates before opening the transaction that places the orders.
+pub fn fiat_currencies(
+    conn: &mut PgConnection,
+    product_ids: &[i32],
+    shipping_option_ids: &[i32],
+) -> Result<HashSet<String>, Error> {
+    let mut currencies = products::table
+        .filter(products::id.eq_any(product_ids))
+        .select(products::fiat_currency)
+        .distinct()
+        .load::<Option<String>>(conn)?;
+
+    currencies.extend(
+        shipping_options::table
+            .filter(shipping_options::id.eq_any(shipping_option_ids))
+            .select(shipping_options::fiat_currency)
+            .distinct()
+            .load::<Option<String>>(conn)?,
+    );
+
+    Ok(currencies.into_iter().flatten().collect())
+}
+
+/// Place an order with a single vendor and reserve its stock
+///
+/// Prices every line (and the shipping option) in the order's currency, assigns the order its
+/// own escrow address, locks the exchange rates used and reserves the stock until the payment
+/// deadline. Fails while the vendor is on vacation or closed. Must run inside a transaction; any error rolls the order back.
+///
+/// # Arguments
+/// * `conn` - A connection with an open transaction
+/// * `request` - The order to place
+/// * `rates` - Exchange rates for every fiat currency the lines are priced in
+///
+/// # Returns
+/// * `Result<Order, Error>` - The placed order or an error
+pub fn place_order(
+    conn: &mut PgConnection,
+    request: &PlaceOrder,
+    rates: &HashMap<String, ExchangeRates>,
+) -> Result<Order, Error> {
+    if request.lines.is_empty() {
+        return Err(Error::validation_error("An order needs at least one item"));
+    }
+
+    if request.encrypted_shipping_address.trim().is_empty() {
+        return Err(Error::validation_error("Shipping address is required"));
+    }
+
+    let product_ids: Vec<i32> = request.lines.iter().map(|line| line.product_id).collect();
+    let listed = products::table
+        .filter(products::id.eq_any(&product_ids))
+        .filter(products::is_active.eq(true))
+        .filter(products::listing_state.eq(ListingState::Active))
+        .load::<Product>(conn)?;
+
+    let mut vendor_id = None;
+    let mut reservations = Vec::with_capacity(request.lines.len());
+    let mut priced_lines = Vec::with_capacity(request.lines.len());
+    let mut total_amount = BigDecimal::from(0);
+
+    for line in &request.lines {
+        if line.quantity <= 0 {
+            return Err(Error::validation_error("Quantity must be greater than zero"));
+        }
+
+        let product = listed
+            .iter()
+            .find(|product| product.id == line.product_id)
+            .ok_or_else(|| Error::validation_error(format!("Product {} is not available", line.product_id)))?;
+
+        // Orders have a single vendor, so every line must come from the same one
+        if *vendor_id.get_or_insert(product.vendor_id) != product.vendor_id {
+            return Err(Error::validation_error(
+                "All items of an order must come from the same vendor",
+            ));
+        }
+
+        if product.vendor_id == request.buyer_id {
+            return Err(Error::validation_error("You cannot buy your own products"));
+        }
+
+        let variant = match line.variant_id {
+            Some(variant_id) => Some(
+                product_variants::table
+                    .find(variant_id)
+                    .filter(product_variants::product_id.eq(product.id))
+                    .first::<ProductVariant>(conn)
+                    .optional()?
+                    .ok_or_else(|| Error::validation_error(format!("Variant {} is not available", variant_id)))?,
+            ),
+            None => None,
+        };
+
+        let price = pricing::unit_price(product, variant.as_ref(), request.currency, rates)?;
+
+        total_amount += &price.amount * BigDecimal::from(line.quantity);
+        priced_lines.push((line, price));
+        reservations.push(ReservationRequest {
+            product_id: line.product_id,
+            variant_id: line.variant_id,
+            quantity: line.quantity,
+        });
+    }
+
+    let vendor_id = vendor_id.expect("order has at least one line");
+    availability::ensure_open(conn, vendor_id)?;
+
+    let shipping = match request.shipping_option_id {
+        Some(option_id) => {
+            let option = shipping_options::table
+                .find(option_id)
+                .filter(shipping_options::vendor_id.eq(vendor_id))
+                .filter(shipping_options::is_active.eq(true))
+                .first::<ShippingOption>(conn)
+                .optional()?
+                .ok_or_else(|| {
+                    Error::validation_error(format!("Shipping option {} is not available", option_id))
+                })?;
+            Some(pricing::shipping_price(&option, request.currency, rates)?)
+        }
+        None => None,
+    };
+
+    let shipping_amount = shipping
+        .as_ref()
+        .map_or_else(|| BigDecimal::from(0), |price| price.amount.clone());
+    total_amount += &shipping_amount;
+
+    let order = diesel::insert_into(orders::table)
+        .values(&NewOrder {
+            buyer_id: request.buyer_id,
+            vendor_id,
+            status: OrderStatus::Pending,
+            currency: request.currency,
+            total_amount,
+            escrow_address: None,
+            encrypted_shipping_address: request.encrypted_shipping_address.clone(),
+            checkout_id: request.checkout_id.clone(),
+            shipping_option_id: request.shipping_option_id,
+            shipping_amount,
+            shipping_fiat: shipping.as_ref().and_then(|price| price.price_fiat.clone()),
+            shipping_fiat_currency: shipping.as_ref().and_then(|price| price.fiat_currency.clone()),
+        })
+        .get_result::<Order>(conn)?;
+
+    let mut quoted_currencies: HashSet<String> = priced_lines
+        .iter()
+        .filter_map(|(_, price)| price.fiat_currency.clone())
+        .collect();
+    quoted_currencies.extend(shipping.and_then(|price| price.fiat_currency));
+
+    let new_items: Vec<NewOrderItem> = priced_lines
+        .into_iter()
+        .map(|(line, price)| NewOrderItem {
+            order_id: order.id,
+            product_id: line.product_id,
+            variant_id: line.variant_id,
+            quantity: line.quantity,
+            price_per_unit: price.amount,
+            price_fiat: price.price_fiat,
+            fiat_currency: price.fiat_currency,
+        })
+        .collect();
+
+    diesel::insert_into(order_items::table)
+        .values(&new_items)
+        .execute(conn)?;
+
+    save_quotes(conn, order.id, request.currency, quoted_currencies, rates)?;
+    message::open_for_order(conn, &order)?;
+
+    let escrow_address = escrow::assign_address(conn, order.id, request.currency)?;
+
+    let expires_at = Utc::now() + Duration::minutes(SETTINGS.inventory.reservation_ttl_minutes);
+    inventory::reserve_stock(conn, order.id, &reservations, expires_at)?;
+    order_timer::schedule(conn, order.id, order_timer::kinds::PAYMENT, expires_at)?;
+
+    diesel::insert_into(order_status_history::table)
+        .values(&NewOrderStatusHistory::by_user(
+            order.id,
+            OrderStatus::Pending,
+            request.buyer_id,
+            Some(format!("Stock reserved until {}", expires_at.to_rfc3339())),
+        ))
+        .execute(conn)?;
+
+    Ok(Order {
+        escrow_address: Some(escrow_address),
+        ..order
+    })
+}
+
+/// Record the rates an order was priced at and lock them for the quote window
+pub fn save_quotes<I>(
+    conn: &mut PgConnection,
+    order_id: i32,
+    currency: PaymentCurrency,
+    fiat_currencies: I,
+    rates: &HashMap<String, ExchangeRates>,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    let expires_at = Utc::now() + Duration::minutes(SETTINGS.pricing.quote_lock_minutes);
+
+    for fiat_currency in fiat_currencies {
+        let rates = rates.get(&fiat_currency).ok_or_else(|| {
+            Error::ExchangeRateUnavailable(format!("No {} rates to quote", fiat_currency))
+        })?;
+
+        let quote = NewOrderQuote {
+            order_id,
+            fiat_currency,
+            currency,
+            rate: rates.rate(currency).clone(),
+            source: rates.source.clone(),
+            quoted_at: rates.as_of,
+            expires_at,
+        };
+
+        diesel::insert_into(order_quotes::table)
+            .values(&quote)
+            .on_conflict((order_quotes::order_id, order_quotes::fiat_currency))
+            .do_update()
+            .set((&quote, order_quotes::updated_at.eq(Utc::now())))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,465 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::errors::Error;
+use crate::events::Event;
+use crate::models::escrow::{self, Settlement};
+use crate::models::order::{self, NewOrderStatusHistory, Order, OrderStatus};
+use crate::models::notification;
+use crate::models::order_timer;
+use crate::models::payment::Transaction;
+use crate::models::user::{roles, User};
+use crate::schema::{dispute_evidence, dispute_messages, disputes, orders, users};
+use crate::settings::SETTINGS;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = disputes)]
+#[diesel(belongs_to(Order))]
+pub struct Dispute {
+    pub id: i32,
+    pub order_id: i32,
+    pub opened_by: i32,
+    pub reason: String,
+    pub description: String,
+    pub status: String,
+    pub assigned_to: Option<i32>,
+    pub assigned_at: Option<DateTime<Utc>>,
+    /// When the next moderator action is due
+    pub sla_due_at: DateTime<Utc>,
+    pub resolution: Option<String>,
+    pub buyer_percent: Option<i32>,
+    pub resolution_notes: Option<String>,
+    pub resolved_by: Option<i32>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Dispute {
+    /// The notification sent to both parties when the dispute changes
+    pub fn event(&self) -> Event {
+        Event::DisputeUpdated {
+            dispute_id: self.id,
+            order_id: self.order_id,
+            status: self.status.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = disputes)]
+pub struct NewDispute {
+    pub order_id: i32,
+    pub opened_by: i32,
+    pub reason: String,
+    pub description: String,
+    pub sla_due_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = dispute_evidence)]
+#[diesel(belongs_to(Dispute))]
+pub struct DisputeEvidence {
+    pub id: i32,
+    pub dispute_id: i32,
+    pub submitted_by: i32,
+    pub kind: String,
+    pub description: String,
+    /// Encrypted to the moderators' key by the submitter
+    pub encrypted_content: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = dispute_evidence)]
+pub struct NewDisputeEvidence {
+    pub dispute_id: i32,
+    pub submitted_by: i32,
+    pub kind: String,
+    pub description: String,
+    pub encrypted_content: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = dispute_messages)]
+#[diesel(belongs_to(Dispute))]
+pub struct DisputeMessage {
+    pub id: i32,
+    pub dispute_id: i32,
+    pub sender_id: i32,
+    pub encrypted_content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = dispute_messages)]
+pub struct NewDisputeMessage {
+    pub dispute_id: i32,
+    pub sender_id: i32,
+    pub encrypted_content: String,
+}
+
+/// Valid dispute statuses
+pub mod statuses {
+    /// Waiting for a moderator to pick it up
+    pub const OPEN: &str = "open";
+    /// A moderator is looking at it
+    pub const ASSIGNED: &str = "assigned";
+    /// Ruled on and the escrow paid out
+    pub const RESOLVED: &str = "resolved";
+}
+
+/// Valid reasons for opening a dispute
+pub mod reasons {
+    pub const NOT_RECEIVED: &str = "not_received";
+    pub const NOT_AS_DESCRIBED: &str = "not_as_described";
+    pub const DAMAGED: &str = "damaged";
+    pub const WRONG_ITEM: &str = "wrong_item";
+    pub const OTHER: &str = "other";
+
+    pub const ALL: [&str; 5] = [NOT_RECEIVED, NOT_AS_DESCRIBED, DAMAGED, WRONG_ITEM, OTHER];
+}
+
+/// Valid kinds of evidence
+pub mod evidence_kinds {
+    pub const TRACKING: &str = "tracking";
+    pub const PHOTO: &str = "photo";
+    pub const CORRESPONDENCE: &str = "correspondence";
+    pub const OTHER: &str = "other";
+
+    pub const ALL: [&str; 4] = [TRACKING, PHOTO, CORRESPONDENCE, OTHER];
+}
+
+/// A moderator's ruling on a dispute
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Resolution {
+    /// Everything back to the buyer
+    Refund,
+    /// Everything to the vendor
+    Release,
+    /// The buyer gets `buyer_percent` of the total and the vendor the rest
+    Split { buyer_percent: u8 },
+}
+
+impl Resolution {
+    fn name(&self) -> &'static str {
+        match self {
+            Resolution::Refund => "refund",
+            Resolution::Release => "release",
+            Resolution::Split { .. } => "split",
+        }
+    }
+
+    fn buyer_percent(&self) -> u8 {
+        match self {
+            Resolution::Refund => 100,
+            Resolution::Release => 0,
+            Resolution::Split { buyer_percent } => *buyer_percent,
+        }
+    }
+
+    fn settlement(&self, order: &Order) -> Settlement {
+        match self {
+            Resolution::Refund => Settlement::refund(order),
+            Resolution::Release => Settlement::release(order),
+            Resolution::Split { buyer_percent } => Settlement::split(order, *buyer_percent),
+        }
+    }
+
+    /// Where the order ends up: cancelled if the buyer got everything back, completed otherwise
+    fn order_status(&self) -> OrderStatus {
+        match self {
+            Resolution::Refund => OrderStatus::Cancelled,
+            _ => OrderStatus::Completed,
+        }
+    }
+}
+
+/// Orders in these statuses have been paid and can be disputed
+const DISPUTABLE: [OrderStatus; 4] = [
+    OrderStatus::Paid,
+    OrderStatus::Processing,
+    OrderStatus::Shipped,
+    OrderStatus::Delivered,
+];
+
+/// Open a dispute on a paid order
+///
+/// The order moves to `Disputed`, which freezes it until a moderator rules.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `order_id` - The disputed order
+/// * `buyer_id` - The buyer opening the dispute
+/// * `reason` - One of `reasons`
+/// * `description` - The buyer's account of the problem
+///
+/// # Returns
+/// * `Result<Dispute, Error>` - The new dispute or an error
+pub fn open(
+    conn: &mut PgConnection,
+    order_id: i32,
+    buyer_id: i32,
+    reason: &str,
+    description: String,
+) -> Result<Dispute, Error> {
+    if !reasons::ALL.contains(&reason) {
+        return Err(Error::validation_error(format!("Invalid dispute reason: {}", reason)));
+    }
+
+    if description.trim().is_empty() {
+        return Err(Error::validation_error("Describe the problem"));
+    }
+
+    conn.transaction::<Dispute, Error, _>(|conn| {
+        let order = orders::table
+            .find(order_id)
+            .filter(orders::buyer_id.eq(buyer_id))
+            .for_update()
+            .first::<Order>(conn)?;
+
+        if !DISPUTABLE.contains(&order.status) {
+            return Err(Error::validation_error(format!(
+                "A {:?} order cannot be disputed",
+                order.status
+            )));
+        }
+
+        let dispute = diesel::insert_into(disputes::table)
+            .values(&NewDispute {
+                order_id,
+                opened_by: buyer_id,
+                reason: reason.to_string(),
+                description,
+                sla_due_at: Utc::now() + Duration::hours(SETTINGS.disputes.assignment_sla_hours),
+            })
+            .get_result::<Dispute>(conn)?;
+
+        order::set_status(
+            conn,
+            &order,
+            NewOrderStatusHistory::by_user(order.id, OrderStatus::Disputed, buyer_id, Some(format!("Dispute opened: {}", reason))),
+        )?;
+        order_timer::on_status_change(conn, order.id, OrderStatus::Disputed)?;
+        notification::notify_parties(conn, &order, dispute.event())?;
+
+        info!(dispute_id = dispute.id, order_id = order_id, reason = %reason, "Dispute opened");
+        Ok(dispute)
+    })
+}
+
+/// Load a dispute and its order if `user_id` is the buyer or vendor of the order
+///
+/// Anyone else gets a not found error, so outsiders cannot tell that a dispute exists.
+pub fn find_for_party(conn: &mut PgConnection, dispute_id: i32, user_id: i32) -> Result<(Dispute, Order), Error> {
+    let (dispute, order) = disputes::table
+        .inner_join(orders::table)
+        .filter(disputes::id.eq(dispute_id))
+        .select((disputes::all_columns, orders::all_columns))
+        .first::<(Dispute, Order)>(conn)?;
+
+    if order.buyer_id != user_id && order.vendor_id != user_id {
+        return Err(Error::not_found());
+    }
+
+    Ok((dispute, order))
+}
+
+/// Give a dispute to a moderator and restart its SLA clock for the ruling
+///
+/// Only moderators and admins who are not a party to the order can take a dispute.
+pub fn assign(conn: &mut PgConnection, dispute_id: i32, moderator_id: i32) -> Result<Dispute, Error> {
+    conn.transaction::<Dispute, Error, _>(|conn| {
+        let moderator = users::table
+            .find(moderator_id)
+            .first::<User>(conn)
+            .optional()?
+            .ok_or_else(|| Error::validation_error("No such moderator"))?;
+        if moderator.role != roles::MODERATOR && moderator.role != roles::ADMIN {
+            return Err(Error::validation_error("Disputes can only be assigned to moderators and admins"));
+        }
+
+        let dispute = disputes::table.find(dispute_id).for_update().first::<Dispute>(conn)?;
+
+        if dispute.status == statuses::RESOLVED {
+            return Err(Error::validation_error("This dispute is already resolved"));
+        }
+
+        let order = orders::table.find(dispute.order_id).first::<Order>(conn)?;
+        if is_party(&order, moderator_id) {
+            return Err(Error::validation_error("A dispute cannot be assigned to a party to the order"));
+        }
+
+        let now = Utc::now();
+        let dispute = diesel::update(&dispute)
+            .set((
+                disputes::status.eq(statuses::ASSIGNED),
+                disputes::assigned_to.eq(moderator_id),
+                disputes::assigned_at.eq(now),
+                disputes::sla_due_at.eq(now + Duration::hours(SETTINGS.disputes.resolution_sla_hours)),
+                disputes::updated_at.eq(now),
+            ))
+            .get_result::<Dispute>(conn)?;
+
+        notification::notify_parties(conn, &order, dispute.event())?;
+
+        info!(dispute_id = dispute_id, moderator_id = moderator_id, "Dispute assigned");
+        Ok(dispute)
+    })
+}
+
+/// Whether the user is the buyer or vendor of the order, and so can't handle its dispute
+fn is_party(order: &Order, user_id: i32) -> bool {
+    order.buyer_id == user_id || order.vendor_id == user_id
+}
+
+/// Rule on a dispute and pay out the escrow accordingly
+///
+/// Only the assigned moderator (or an admin) can rule, and never the order's buyer or
+/// vendor. The ruling, the escrow postings and the order's final status are written in one
+/// transaction.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `dispute_id` - The dispute
+/// * `moderator_id` - The moderator ruling
+/// * `is_admin` - Whether the moderator is an admin, who can rule on any dispute
+/// * `resolution` - The ruling
+/// * `notes` - The reasoning, shown to both parties
+///
+/// # Returns
+/// * `Result<(Dispute, Vec<Transaction>), Error>` - The resolved dispute and the postings made
+pub fn resolve(
+    conn: &mut PgConnection,
+    dispute_id: i32,
+    moderator_id: i32,
+    is_admin: bool,
+    resolution: Resolution,
+    notes: String,
+) -> Result<(Dispute, Vec<Transaction>), Error> {
+    if resolution.buyer_percent() > 100 {
+        return Err(Error::validation_error("The buyer's share must be between 0 and 100 percent"));
+    }
+
+    if notes.trim().is_empty() {
+        return Err(Error::validation_error("Explain the ruling"));
+    }
+
+    conn.transaction::<(Dispute, Vec<Transaction>), Error, _>(|conn| {
+        let dispute = disputes::table.find(dispute_id).for_update().first::<Dispute>(conn)?;
+
+        if dispute.status == statuses::RESOLVED {
+            return Err(Error::validation_error("This dispute is already resolved"));
+        }
+
+        if dispute.assigned_to != Some(moderator_id) && !is_admin {
+            return Err(Error::validation_error("Only the assigned moderator can rule on this dispute"));
+        }
+
+        let order = orders::table.find(dispute.order_id).for_update().first::<Order>(conn)?;
+        if is_party(&order, moderator_id) {
+            return Err(Error::validation_error("A party to the order cannot rule on its dispute"));
+        }
+
+        let postings = escrow::settle(conn, &order, &resolution.settlement(&order))?;
+
+        let now = Utc::now();
+        let dispute = diesel::update(&dispute)
+            .set((
+                disputes::status.eq(statuses::RESOLVED),
+                disputes::resolution.eq(resolution.name()),
+                disputes::buyer_percent.eq(resolution.buyer_percent() as i32),
+                disputes::resolution_notes.eq(&notes),
+                disputes::resolved_by.eq(moderator_id),
+                disputes::resolved_at.eq(now),
+                disputes::updated_at.eq(now),
+            ))
+            .get_result::<Dispute>(conn)?;
+
+        order::set_status(
+            conn,
+            &order,
+            NewOrderStatusHistory::by_user(
+                order.id,
+                resolution.order_status(),
+                moderator_id,
+                Some(format!("Dispute resolved ({}): {}", resolution.name(), notes)),
+            ),
+        )?;
+        order_timer::on_status_change(conn, order.id, resolution.order_status())?;
+        notification::notify_parties(conn, &order, dispute.event())?;
+
+        info!(
+            dispute_id = dispute_id,
+            order_id = order.id,
+            moderator_id = moderator_id,
+            resolution = %resolution.name(),
+            buyer_percent = resolution.buyer_percent(),
+            "Dispute resolved"
+        );
+
+        Ok((dispute, postings))
+    })
+}
+
+/// A dispute in the moderators' queue
+#[derive(Debug, Serialize)]
+pub struct QueueEntry {
+    #[serde(flatten)]
+    pub dispute: Dispute,
+    pub order: Order,
+    /// Whether `sla_due_at` has passed
+    pub overdue: bool,
+}
+
+/// Unresolved disputes, the most urgent first
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `assigned_to` - Only disputes assigned to this moderator, if given
+/// * `limit` - Maximum number of entries
+/// * `offset` - Number of entries to skip
+///
+/// # Returns
+/// * `Result<(Vec<QueueEntry>, i64), Error>` - One page of the queue and its total size
+pub fn queue(
+    conn: &mut PgConnection,
+    assigned_to: Option<i32>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<QueueEntry>, i64), Error> {
+    let mut count_query = disputes::table
+        .filter(disputes::status.ne(statuses::RESOLVED))
+        .into_boxed();
+    let mut query = disputes::table
+        .inner_join(orders::table)
+        .filter(disputes::status.ne(statuses::RESOLVED))
+        .into_boxed();
+
+    if let Some(moderator_id) = assigned_to {
+        count_query = count_query.filter(disputes::assigned_to.eq(moderator_id));
+        query = query.filter(disputes::assigned_to.eq(moderator_id));
+    }
+
+    let total = count_query.count().get_result::<i64>(conn)?;
+
+    let now = Utc::now();
+    let entries = query
+        .order((disputes::sla_due_at.asc(), disputes::id.asc()))
+        .limit(limit)
+        .offset(offset)
+        .select((disputes::all_columns, orders::all_columns))
+        .load::<(Dispute, Order)>(conn)?
+        .into_iter()
+        .map(|(dispute, order)| QueueEntry {
+            overdue: dispute.sla_due_at < now,
+            dispute,
+            order,
+        })
+        .collect();
+
+    Ok((entries, total))
+}
@@ -0,0 +1,287 @@
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+
+use crate::errors::Error;
+use crate::models::fee;
+use crate::models::order::Order;
+use crate::models::payment::{self, NewTransaction, PaymentCurrency, Transaction, TransactionType, Wallet};
+use crate::pricing;
+use crate::schema::{escrow_addresses, escrow_settlements, orders, transactions, wallets};
+
+/// A deposit address from the escrow pool
+///
+/// Addresses are derived offline (from an xpub for BTC, as subaddresses for XMR) and loaded
+/// into the pool, so the server never holds the keys that can spend escrow funds.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = escrow_addresses)]
+pub struct EscrowAddress {
+    pub id: i32,
+    pub currency: PaymentCurrency,
+    pub address: String,
+    pub order_id: Option<i32>,
+    pub assigned_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Set instead of `order_id` when the address takes a vendor bond
+    pub vendor_bond_id: Option<i32>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = escrow_addresses)]
+pub struct NewEscrowAddress {
+    pub currency: PaymentCurrency,
+    pub address: String,
+}
+
+/// Give an order its own escrow address from the pool
+///
+/// The address is taken with `SKIP LOCKED`, so concurrent checkouts never get the same one.
+/// Must run inside the transaction that creates the order.
+///
+/// # Arguments
+/// * `conn` - A connection with an open transaction
+/// * `order_id` - The order the address is for
+/// * `currency` - The order's payment currency
+///
+/// # Returns
+/// * `Result<String, Error>` - The assigned address or an error if the pool is empty
+pub fn assign_address(conn: &mut PgConnection, order_id: i32, currency: PaymentCurrency) -> Result<String, Error> {
+    let address = take_address(conn, currency)?;
+
+    diesel::update(&address)
+        .set((
+            escrow_addresses::order_id.eq(order_id),
+            escrow_addresses::assigned_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+
+    diesel::update(orders::table.find(order_id))
+        .set(orders::escrow_address.eq(&address.address))
+        .execute(conn)?;
+
+    debug!(order_id = order_id, currency = ?currency, "Escrow address assigned");
+    Ok(address.address)
+}
+
+/// Give a vendor bond its own BTC deposit address from the pool
+///
+/// Like [`assign_address`], but the address is tied to the bond instead of an order. Must
+/// run inside the transaction that creates the bond.
+pub fn assign_bond_address(conn: &mut PgConnection, vendor_bond_id: i32) -> Result<String, Error> {
+    let address = take_address(conn, PaymentCurrency::BTC)?;
+
+    diesel::update(&address)
+        .set((
+            escrow_addresses::vendor_bond_id.eq(vendor_bond_id),
+            escrow_addresses::assigned_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+
+    debug!(vendor_bond_id = vendor_bond_id, "Bond address assigned");
+    Ok(address.address)
+}
+
+/// Lock the oldest unassigned address in a currency
+fn take_address(conn: &mut PgConnection, currency: PaymentCurrency) -> Result<EscrowAddress, Error> {
+    let address = escrow_addresses::table
+        .filter(escrow_addresses::currency.eq(currency))
+        .filter(escrow_addresses::order_id.is_null())
+        .filter(escrow_addresses::vendor_bond_id.is_null())
+        .order(escrow_addresses::id.asc())
+        .for_update()
+        .skip_locked()
+        .first::<EscrowAddress>(conn)
+        .optional()?;
+
+    address.ok_or_else(|| {
+        error!(currency = ?currency, "Escrow address pool is empty");
+        Error::CryptoError {
+            message: format!("No {:?} escrow addresses available", currency),
+            source: None,
+        }
+    })
+}
+
+/// How the escrowed total of an order is paid out
+#[derive(Debug, Clone, Serialize)]
+pub struct Settlement {
+    /// Paid back to the buyer
+    pub refund: BigDecimal,
+    /// Paid to the vendor
+    pub release: BigDecimal,
+}
+
+impl Settlement {
+    /// Everything goes back to the buyer
+    pub fn refund(order: &Order) -> Self {
+        Self::split(order, 100)
+    }
+
+    /// Everything goes to the vendor
+    pub fn release(order: &Order) -> Self {
+        Self::split(order, 0)
+    }
+
+    /// The buyer gets `buyer_percent` of the total, rounded down to the currency's precision,
+    /// and the vendor gets the rest, so nothing is lost to rounding
+    pub fn split(order: &Order, buyer_percent: u8) -> Self {
+        let scale = pricing::crypto_scale(order.currency);
+        let refund = (&order.total_amount * BigDecimal::from(buyer_percent.min(100)) / BigDecimal::from(100))
+            .with_scale_round(scale, RoundingMode::Down);
+        let release = &order.total_amount - &refund;
+
+        Self { refund, release }
+    }
+}
+
+/// A paid-out escrow: how an order's total was divided and the fee the marketplace kept
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = escrow_settlements)]
+pub struct EscrowSettlement {
+    pub id: i32,
+    pub order_id: i32,
+    pub currency: PaymentCurrency,
+    pub refund: BigDecimal,
+    pub release: BigDecimal,
+    /// Taken out of `release`; the vendor received `release - fee`
+    pub fee: BigDecimal,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = escrow_settlements)]
+pub struct NewEscrowSettlement {
+    pub order_id: i32,
+    pub currency: PaymentCurrency,
+    pub refund: BigDecimal,
+    pub release: BigDecimal,
+    pub fee: BigDecimal,
+}
+
+/// Pay out an order's escrow and post the movements to the parties' wallets
+///
+/// The refund is credited to the buyer as an `EscrowRefund`. The release is credited to the
+/// vendor as an `EscrowRelease` for the full amount, with the marketplace fee in its `fee`
+/// column; the wallet receives the release less the fee. A party with no wallet in the
+/// order's currency gets one opened as part of the settlement.
+///
+/// Settling is idempotent: the first call records an `escrow_settlements` row (one per
+/// order), and calling again with the same division returns the original postings without
+/// paying anything. A different division of an already settled order is an error. Must run
+/// inside a transaction with the order row locked.
+///
+/// # Arguments
+/// * `conn` - A connection with an open transaction
+/// * `order` - The locked order
+/// * `settlement` - How the total is divided
+///
+/// # Returns
+/// * `Result<Vec<Transaction>, Error>` - The posted transactions or an error
+pub fn settle(conn: &mut PgConnection, order: &Order, settlement: &Settlement) -> Result<Vec<Transaction>, Error> {
+    if &settlement.refund + &settlement.release != order.total_amount
+        || settlement.refund < BigDecimal::zero()
+        || settlement.release < BigDecimal::zero()
+    {
+        return Err(Error::validation_error("A settlement must pay out exactly the order total"));
+    }
+
+    let existing = escrow_settlements::table
+        .filter(escrow_settlements::order_id.eq(order.id))
+        .first::<EscrowSettlement>(conn)
+        .optional()?;
+
+    if let Some(existing) = existing {
+        if existing.refund != settlement.refund || existing.release != settlement.release {
+            return Err(Error::validation_error(format!("The escrow of order {} was already paid out", order.id)));
+        }
+
+        debug!(order_id = order.id, "Escrow already settled, returning the original postings");
+        return Ok(transactions::table
+            .filter(transactions::order_id.eq(order.id))
+            .filter(transactions::transaction_type.eq_any(SETTLEMENT_TYPES))
+            .order(transactions::id.asc())
+            .load::<Transaction>(conn)?);
+    }
+
+    let fee = fee::fee_for_release(conn, order, &settlement.release)?;
+
+    // The unique order_id stops a second payout even if the caller forgot to lock the order
+    diesel::insert_into(escrow_settlements::table)
+        .values(&NewEscrowSettlement {
+            order_id: order.id,
+            currency: order.currency,
+            refund: settlement.refund.clone(),
+            release: settlement.release.clone(),
+            fee: fee.clone(),
+        })
+        .execute(conn)?;
+
+    let mut posted = Vec::with_capacity(2);
+
+    if !settlement.refund.is_zero() {
+        let wallet = payout_wallet(conn, order.buyer_id, order)?;
+        credit(conn, &wallet, &settlement.refund)?;
+        posted.push(post(conn, &wallet, order, TransactionType::EscrowRefund, &settlement.refund, BigDecimal::zero())?);
+    }
+
+    if !settlement.release.is_zero() {
+        let wallet = payout_wallet(conn, order.vendor_id, order)?;
+        credit(conn, &wallet, &(&settlement.release - &fee))?;
+        posted.push(post(conn, &wallet, order, TransactionType::EscrowRelease, &settlement.release, fee.clone())?);
+    }
+
+    info!(
+        order_id = order.id,
+        refund = %settlement.refund,
+        release = %settlement.release,
+        fee = %fee,
+        "Escrow settled"
+    );
+
+    Ok(posted)
+}
+
+/// The transaction types a settlement posts
+const SETTLEMENT_TYPES: [TransactionType; 2] = [TransactionType::EscrowRefund, TransactionType::EscrowRelease];
+
+/// The wallet a party to the order is paid into, locked and opened if they have none
+fn payout_wallet(conn: &mut PgConnection, user_id: i32, order: &Order) -> Result<Wallet, Error> {
+    payment::lock_or_open_wallet(conn, user_id, payment::wallet_type(order.currency))
+}
+
+fn credit(conn: &mut PgConnection, wallet: &Wallet, amount: &BigDecimal) -> Result<(), Error> {
+    diesel::update(wallet)
+        .set((
+            wallets::balance.eq(wallets::balance + amount),
+            wallets::updated_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn post(
+    conn: &mut PgConnection,
+    wallet: &Wallet,
+    order: &Order,
+    transaction_type: TransactionType,
+    amount: &BigDecimal,
+    fee: BigDecimal,
+) -> Result<Transaction, Error> {
+    let transaction = diesel::insert_into(transactions::table)
+        .values(&NewTransaction {
+            wallet_id: wallet.id,
+            transaction_type,
+            amount: amount.clone(),
+            fee,
+            tx_hash: None,
+            order_id: Some(order.id),
+            status: "completed".to_string(),
+            completed_at: Some(Utc::now()),
+        })
+        .get_result::<Transaction>(conn)?;
+
+    Ok(transaction)
+}
@@ -0,0 +1,190 @@
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::constants::fees::MAX_FEE_BPS;
+use crate::errors::Error;
+use crate::models::order::Order;
+use crate::models::payment::PaymentCurrency;
+use crate::pricing;
+use crate::schema::{categories, fee_rules, order_items, products};
+use crate::settings::SETTINGS;
+
+/// A marketplace fee rate for a category or a single vendor, in basis points
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = fee_rules)]
+pub struct FeeRule {
+    pub id: i32,
+    pub category_id: Option<i32>,
+    pub vendor_id: Option<i32>,
+    pub fee_bps: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = fee_rules)]
+pub struct NewFeeRule {
+    pub category_id: Option<i32>,
+    pub vendor_id: Option<i32>,
+    pub fee_bps: i32,
+}
+
+/// Set the fee rate of a category or a vendor, replacing any rate it had
+pub fn set_rule(conn: &mut PgConnection, rule: NewFeeRule) -> Result<FeeRule, Error> {
+    if !(0..=MAX_FEE_BPS).contains(&rule.fee_bps) {
+        return Err(Error::validation_error(format!(
+            "The fee must be between 0 and {} basis points",
+            MAX_FEE_BPS
+        )));
+    }
+
+    conn.transaction::<FeeRule, Error, _>(|conn| {
+        let existing = match (rule.category_id, rule.vendor_id) {
+            (Some(category_id), None) => fee_rules::table
+                .filter(fee_rules::category_id.eq(category_id))
+                .for_update()
+                .first::<FeeRule>(conn)
+                .optional()?,
+            (None, Some(vendor_id)) => fee_rules::table
+                .filter(fee_rules::vendor_id.eq(vendor_id))
+                .for_update()
+                .first::<FeeRule>(conn)
+                .optional()?,
+            _ => return Err(Error::validation_error("A fee rule is for either a category or a vendor")),
+        };
+
+        let saved = match existing {
+            Some(existing) => diesel::update(&existing)
+                .set((
+                    fee_rules::fee_bps.eq(rule.fee_bps),
+                    fee_rules::updated_at.eq(Utc::now()),
+                ))
+                .get_result::<FeeRule>(conn)?,
+            None => diesel::insert_into(fee_rules::table)
+                .values(&rule)
+                .get_result::<FeeRule>(conn)?,
+        };
+
+        Ok(saved)
+    })
+}
+
+/// The marketplace fee on an order if its whole total were released to the vendor
+///
+/// A vendor rule covers the whole order. Otherwise each item is charged at the rate of its
+/// category, or of the nearest parent category with a rule, or the default rate; shipping is
+/// charged at the default rate. The result is rounded down to the currency's precision.
+pub fn full_fee(conn: &mut PgConnection, order: &Order) -> Result<BigDecimal, Error> {
+    let scale = pricing::crypto_scale(order.currency);
+    let default_bps = SETTINGS.fees.default_bps;
+
+    let vendor_rule = fee_rules::table
+        .filter(fee_rules::vendor_id.eq(order.vendor_id))
+        .first::<FeeRule>(conn)
+        .optional()?;
+
+    if let Some(rule) = vendor_rule {
+        return Ok(apply_bps(&order.total_amount, rule.fee_bps).with_scale_round(scale, RoundingMode::Down));
+    }
+
+    let items = order_items::table
+        .inner_join(products::table)
+        .filter(order_items::order_id.eq(order.id))
+        .select((order_items::quantity, order_items::price_per_unit, products::category_id))
+        .load::<(i32, BigDecimal, Option<i32>)>(conn)?;
+
+    let rates = CategoryRates::load(conn)?;
+
+    let mut fee = apply_bps(&order.shipping_amount, default_bps);
+    for (quantity, price_per_unit, category_id) in items {
+        let bps = category_id
+            .and_then(|category_id| rates.resolve(category_id))
+            .unwrap_or(default_bps);
+        fee += apply_bps(&(price_per_unit * BigDecimal::from(quantity)), bps);
+    }
+
+    Ok(fee.with_scale_round(scale, RoundingMode::Down))
+}
+
+/// The fee on the part of an order's total that is released to the vendor
+///
+/// The fee scales with the share released: a vendor who gets 60% of the order pays 60% of
+/// its fee. Rounded down, so the vendor never pays more than the full fee's share.
+pub fn fee_for_release(conn: &mut PgConnection, order: &Order, release: &BigDecimal) -> Result<BigDecimal, Error> {
+    if release.is_zero() || order.total_amount.is_zero() {
+        return Ok(BigDecimal::zero());
+    }
+
+    let full = full_fee(conn, order)?;
+    Ok(share_of_fee(&full, &order.total_amount, release, order.currency))
+}
+
+/// The part of `full_fee` owed on `release` out of `total`, rounded down and never more than
+/// `release` itself
+pub fn share_of_fee(
+    full_fee: &BigDecimal,
+    total: &BigDecimal,
+    release: &BigDecimal,
+    currency: PaymentCurrency,
+) -> BigDecimal {
+    if release.is_zero() || total.is_zero() {
+        return BigDecimal::zero();
+    }
+
+    let fee = (full_fee * release / total).with_scale_round(pricing::crypto_scale(currency), RoundingMode::Down);
+    fee.min(release.clone())
+}
+
+/// `bps` basis points of `amount`, unrounded
+pub fn apply_bps(amount: &BigDecimal, bps: i32) -> BigDecimal {
+    amount * BigDecimal::from(bps) / BigDecimal::from(MAX_FEE_BPS)
+}
+
+/// Category fee rules with the category tree, to find the rule that applies to a category
+pub struct CategoryRates {
+    parents: HashMap<i32, Option<i32>>,
+    rates: HashMap<i32, i32>,
+}
+
+impl CategoryRates {
+    /// Rates keyed by category, with each category's parent
+    pub fn new(parents: HashMap<i32, Option<i32>>, rates: HashMap<i32, i32>) -> Self {
+        Self { parents, rates }
+    }
+
+    fn load(conn: &mut PgConnection) -> Result<Self, Error> {
+        let parents = categories::table
+            .select((categories::id, categories::parent_id))
+            .load::<(i32, Option<i32>)>(conn)?
+            .into_iter()
+            .collect();
+
+        let rates = fee_rules::table
+            .filter(fee_rules::category_id.is_not_null())
+            .select((fee_rules::category_id.assume_not_null(), fee_rules::fee_bps))
+            .load::<(i32, i32)>(conn)?
+            .into_iter()
+            .collect();
+
+        Ok(Self::new(parents, rates))
+    }
+
+    /// The rate of the category or its nearest ancestor with a rule
+    pub fn resolve(&self, category_id: i32) -> Option<i32> {
+        let mut current = Some(category_id);
+
+        // Bounded by the number of categories, in case the tree has a cycle
+        for _ in 0..=self.parents.len() {
+            let id = current?;
+            if let Some(bps) = self.rates.get(&id) {
+                return Some(*bps);
+            }
+            current = self.parents.get(&id).copied().flatten();
+        }
+
+        None
+    }
+}
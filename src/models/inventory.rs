@@ -0,0 +1,301 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::errors::Error;
+use crate::models::product::{ListingState, Product};
+use crate::schema::{product_variants, products, stock_reservations};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = stock_reservations)]
+pub struct StockReservation {
+    pub id: i32,
+    pub order_id: i32,
+    pub product_id: i32,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = stock_reservations)]
+pub struct NewStockReservation {
+    pub order_id: i32,
+    pub product_id: i32,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Valid reservation statuses
+pub mod statuses {
+    /// Stock has been taken off the shelf but the order is not paid yet
+    pub const HELD: &str = "held";
+    /// The order was paid, the stock is sold
+    pub const COMMITTED: &str = "committed";
+    /// The stock was put back on the shelf
+    pub const RELEASED: &str = "released";
+}
+
+/// A single order line that needs stock set aside
+#[derive(Debug, Clone, Copy)]
+pub struct ReservationRequest {
+    pub product_id: i32,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+}
+
+/// Reserve stock for every line of an order
+///
+/// Stock is taken with conditional updates (`stock >= quantity`), so two buyers racing for the
+/// last unit cannot both succeed. This must run inside the transaction that creates the order;
+/// if any line cannot be reserved the error rolls the whole order back.
+///
+/// # Arguments
+/// * `conn` - A connection with an open transaction
+/// * `order_id` - The order the stock is reserved for
+/// * `lines` - The order lines to reserve
+/// * `expires_at` - When the reservation is released if the order is still unpaid
+///
+/// # Returns
+/// * `Result<Vec<StockReservation>, Error>` - The created reservations or an error
+pub fn reserve_stock(
+    conn: &mut PgConnection,
+    order_id: i32,
+    lines: &[ReservationRequest],
+    expires_at: DateTime<Utc>,
+) -> Result<Vec<StockReservation>, Error> {
+    let mut reservations = Vec::with_capacity(lines.len());
+
+    for line in lines {
+        if line.quantity <= 0 {
+            return Err(Error::validation_error("Quantity must be greater than zero"));
+        }
+
+        let rows_affected = match line.variant_id {
+            Some(variant_id) => diesel::update(
+                product_variants::table
+                    .filter(product_variants::id.eq(variant_id))
+                    .filter(
+                        product_variants::product_id.eq_any(
+                            products::table
+                                .filter(products::id.eq(line.product_id))
+                                .filter(products::is_active.eq(true))
+                                .filter(products::listing_state.eq(ListingState::Active))
+                                .select(products::id),
+                        ),
+                    )
+                    .filter(product_variants::stock.ge(line.quantity)),
+            )
+            .set((
+                product_variants::stock.eq(product_variants::stock - line.quantity),
+                product_variants::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?,
+            None => diesel::update(
+                products::table
+                    .filter(products::id.eq(line.product_id))
+                    .filter(products::is_active.eq(true))
+                    .filter(products::listing_state.eq(ListingState::Active))
+                    .filter(products::stock.ge(line.quantity)),
+            )
+            .set((
+                products::stock.eq(products::stock - line.quantity),
+                products::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?,
+        };
+
+        if rows_affected == 0 {
+            debug!(
+                order_id = order_id,
+                product_id = line.product_id,
+                variant_id = ?line.variant_id,
+                quantity = line.quantity,
+                "Not enough stock to reserve"
+            );
+            return Err(Error::validation_error(format!(
+                "Insufficient stock for product {}",
+                line.product_id
+            )));
+        }
+
+        let reservation = diesel::insert_into(stock_reservations::table)
+            .values(&NewStockReservation {
+                order_id,
+                product_id: line.product_id,
+                variant_id: line.variant_id,
+                quantity: line.quantity,
+                status: statuses::HELD.to_string(),
+                expires_at,
+            })
+            .get_result::<StockReservation>(conn)?;
+
+        reservations.push(reservation);
+    }
+
+    // Only once every line is reserved: a rule that switches a listing off must not fail a
+    // later line of the same product
+    let mut product_ids: Vec<i32> = lines.iter().map(|line| line.product_id).collect();
+    product_ids.sort_unstable();
+    product_ids.dedup();
+    for product_id in product_ids {
+        apply_stock_rules(conn, product_id)?;
+    }
+
+    debug!(order_id = order_id, count = reservations.len(), "Stock reserved for order");
+    Ok(reservations)
+}
+
+/// Mark the held reservations of a paid order as sold
+///
+/// The stock was already taken when the reservation was made, so this only stops the
+/// reservation from being released when the order is cancelled.
+pub fn commit_reservations(conn: &mut PgConnection, order_id: i32) -> Result<usize, Error> {
+    let committed = diesel::update(
+        stock_reservations::table
+            .filter(stock_reservations::order_id.eq(order_id))
+            .filter(stock_reservations::status.eq(statuses::HELD)),
+    )
+    .set((
+        stock_reservations::status.eq(statuses::COMMITTED),
+        stock_reservations::updated_at.eq(Utc::now()),
+    ))
+    .execute(conn)?;
+
+    debug!(order_id = order_id, count = committed, "Stock reservations committed");
+    Ok(committed)
+}
+
+/// Put the held stock of an order back on the shelf
+///
+/// Committed reservations are left alone: once an order is paid, its stock is sold.
+pub fn release_reservations(conn: &mut PgConnection, order_id: i32) -> Result<usize, Error> {
+    let released = put_back(conn, order_id, statuses::HELD)?;
+
+    debug!(order_id = order_id, count = released, "Stock reservations released");
+    Ok(released)
+}
+
+/// Put the sold stock of a paid order back on the shelf
+///
+/// Used when a paid order is cancelled before the vendor shipped anything.
+pub fn restock_committed(conn: &mut PgConnection, order_id: i32) -> Result<usize, Error> {
+    let restocked = put_back(conn, order_id, statuses::COMMITTED)?;
+
+    debug!(order_id = order_id, count = restocked, "Committed stock returned");
+    Ok(restocked)
+}
+
+/// Release an order's reservations in the given status and add their stock back
+fn put_back(conn: &mut PgConnection, order_id: i32, status: &str) -> Result<usize, Error> {
+    let reservations = stock_reservations::table
+        .filter(stock_reservations::order_id.eq(order_id))
+        .filter(stock_reservations::status.eq(status))
+        .for_update()
+        .load::<StockReservation>(conn)?;
+
+    for reservation in &reservations {
+        match reservation.variant_id {
+            Some(variant_id) => {
+                diesel::update(product_variants::table.find(variant_id))
+                    .set((
+                        product_variants::stock.eq(product_variants::stock + reservation.quantity),
+                        product_variants::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)?;
+            }
+            None => {
+                diesel::update(products::table.find(reservation.product_id))
+                    .set((
+                        products::stock.eq(products::stock + reservation.quantity),
+                        products::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+
+        diesel::update(reservation)
+            .set((
+                stock_reservations::status.eq(statuses::RELEASED),
+                stock_reservations::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+
+        apply_stock_rules(conn, reservation.product_id)?;
+    }
+
+    Ok(reservations.len())
+}
+
+/// Apply the vendor's stock rules to a product after its stock changed
+///
+/// A listing is deactivated when it runs out (if `deactivate_when_out_of_stock` is set) or
+/// falls to its `low_stock_threshold`. Listings deactivated this way are flagged with
+/// `stock_deactivated` and reactivated once stock is back above the limits. Setting
+/// `is_active` by hand clears the flag, so listings switched off by the vendor, or taken
+/// down with a demoted vendor, are never reactivated here.
+pub fn apply_stock_rules(conn: &mut PgConnection, product_id: i32) -> Result<(), Error> {
+    let product = products::table
+        .find(product_id)
+        .for_update()
+        .first::<Product>(conn)?;
+
+    let variant_stock = product_variants::table
+        .filter(product_variants::product_id.eq(product_id))
+        .select(diesel::dsl::sum(product_variants::stock))
+        .first::<Option<i64>>(conn)?;
+
+    // Products with variants sell from the variants' stock, not their own
+    let available = variant_stock.unwrap_or(product.stock as i64);
+
+    if should_deactivate(&product, available) {
+        if product.is_active {
+            diesel::update(&product)
+                .set((
+                    products::is_active.eq(false),
+                    products::stock_deactivated.eq(true),
+                    products::updated_at.eq(Utc::now()),
+                ))
+                .execute(conn)?;
+
+            warn!(
+                product_id = product_id,
+                vendor_id = product.vendor_id,
+                available = available,
+                "Listing deactivated by stock rules"
+            );
+        }
+    } else if product.stock_deactivated {
+        diesel::update(&product)
+            .set((
+                products::is_active.eq(true),
+                products::stock_deactivated.eq(false),
+                products::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+
+        info!(
+            product_id = product_id,
+            vendor_id = product.vendor_id,
+            available = available,
+            "Listing reactivated after restock"
+        );
+    }
+
+    Ok(())
+}
+
+fn should_deactivate(product: &Product, available: i64) -> bool {
+    let out_of_stock = product.deactivate_when_out_of_stock && available <= 0;
+    let low_stock = product
+        .low_stock_threshold
+        .map_or(false, |threshold| available <= threshold as i64);
+
+    out_of_stock || low_stock
+}
@@ -0,0 +1,326 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Text, Timestamp};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::errors::Error;
+use crate::schema::jobs;
+use crate::settings::SETTINGS;
+
+/// A unit of background work stored in the queue
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = jobs)]
+pub struct JobRecord {
+    pub id: i64,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// The job is not picked up before this time
+    pub run_at: DateTime<Utc>,
+    pub unique_key: Option<String>,
+    /// The worker running the job
+    pub locked_by: Option<String>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = jobs)]
+pub struct NewJobRecord {
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub unique_key: Option<String>,
+}
+
+/// Valid job statuses
+pub mod statuses {
+    /// Waiting for `run_at` and a free worker
+    pub const QUEUED: &str = "queued";
+    /// Claimed by a worker
+    pub const RUNNING: &str = "running";
+    /// Finished without error
+    pub const SUCCEEDED: &str = "succeeded";
+    /// Failed on every attempt; waits for an admin to retry it
+    pub const DEAD: &str = "dead";
+
+    pub const ALL: [&str; 4] = [QUEUED, RUNNING, SUCCEEDED, DEAD];
+}
+
+/// Add a job to the queue
+///
+/// A job with a `unique_key` is skipped if one with the same key is already queued. Running
+/// and dead jobs don't count, so an event after a job started still gets a run of its own.
+///
+/// # Returns
+/// * `Result<Option<JobRecord>, Error>` - The queued job, `None` if it was a duplicate, or an error
+pub fn insert(conn: &mut PgConnection, job: &NewJobRecord) -> Result<Option<JobRecord>, Error> {
+    let record = diesel::insert_into(jobs::table)
+        .values(job)
+        .on_conflict_do_nothing()
+        .get_result::<JobRecord>(conn)
+        .optional()?;
+
+    Ok(record)
+}
+
+/// Claim up to `limit` runnable jobs of the given kinds for a worker
+///
+/// Jobs are locked with `SKIP LOCKED`, so any number of workers can poll at once without
+/// claiming the same job twice.
+pub fn claim(
+    conn: &mut PgConnection,
+    worker_id: &str,
+    kinds: &[&str],
+    limit: i64,
+) -> Result<Vec<JobRecord>, Error> {
+    conn.transaction::<Vec<JobRecord>, Error, _>(|conn| {
+        let now = Utc::now();
+
+        let ids = jobs::table
+            .filter(jobs::status.eq(statuses::QUEUED))
+            .filter(jobs::run_at.le(now))
+            .filter(jobs::kind.eq_any(kinds))
+            .order(jobs::run_at.asc())
+            .select(jobs::id)
+            .limit(limit)
+            .for_update()
+            .skip_locked()
+            .load::<i64>(conn)?;
+
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let claimed = diesel::update(jobs::table.filter(jobs::id.eq_any(&ids)))
+            .set((
+                jobs::status.eq(statuses::RUNNING),
+                jobs::attempts.eq(jobs::attempts + 1),
+                jobs::locked_by.eq(worker_id),
+                jobs::locked_at.eq(now),
+                jobs::updated_at.eq(now),
+            ))
+            .get_results::<JobRecord>(conn)?;
+
+        Ok(claimed)
+    })
+}
+
+/// Mark a running job as done
+pub fn complete(conn: &mut PgConnection, id: i64) -> Result<(), Error> {
+    let now = Utc::now();
+
+    diesel::update(jobs::table.find(id))
+        .set((
+            jobs::status.eq(statuses::SUCCEEDED),
+            jobs::locked_by.eq(None::<String>),
+            jobs::locked_at.eq(None::<DateTime<Utc>>),
+            jobs::last_error.eq(None::<String>),
+            jobs::finished_at.eq(now),
+            jobs::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Record a failed attempt
+///
+/// The job is queued again after an exponential backoff, or dead-lettered once it has used
+/// all of its attempts.
+pub fn fail(conn: &mut PgConnection, job: &JobRecord, error: &str) -> Result<(), Error> {
+    let now = Utc::now();
+    let dead = job.attempts >= job.max_attempts;
+
+    if !dead && has_queued_twin(conn, job)? {
+        diesel::delete(jobs::table.find(job.id)).execute(conn)?;
+        warn!(job_id = job.id, kind = %job.kind, error = %error, "Job failed, an identical job is already queued");
+        return Ok(());
+    }
+
+    let (status, run_at, finished_at) = if dead {
+        (statuses::DEAD, job.run_at, Some(now))
+    } else {
+        (statuses::QUEUED, now + backoff(job.attempts), None)
+    };
+
+    diesel::update(jobs::table.find(job.id))
+        .set((
+            jobs::status.eq(status),
+            jobs::run_at.eq(run_at),
+            jobs::locked_by.eq(None::<String>),
+            jobs::locked_at.eq(None::<DateTime<Utc>>),
+            jobs::last_error.eq(error),
+            jobs::finished_at.eq(finished_at),
+            jobs::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    if dead {
+        warn!(job_id = job.id, kind = %job.kind, attempts = job.attempts, error = %error, "Job dead-lettered");
+    } else {
+        warn!(job_id = job.id, kind = %job.kind, attempts = job.attempts, run_at = %run_at, error = %error, "Job failed, will retry");
+    }
+
+    Ok(())
+}
+
+/// How long to wait before the next attempt: doubles with every attempt, up to a ceiling
+pub fn backoff(attempts: i32) -> Duration {
+    let exponent = (attempts.max(1) - 1).min(30) as u32;
+    let seconds = SETTINGS.jobs.backoff_base_seconds.saturating_mul(2_i64.pow(exponent));
+    Duration::seconds(seconds.min(SETTINGS.jobs.backoff_max_seconds))
+}
+
+/// Queue jobs again whose worker stopped without finishing them
+///
+/// A job that has been running for longer than `jobs.stale_after_seconds` is assumed to
+/// belong to a worker that crashed. The attempt it was on still counts. Stale jobs whose key
+/// is already queued again, or shared with a newer stale job, are dropped instead.
+pub fn requeue_stale(conn: &mut PgConnection) -> Result<usize, Error> {
+    let now = Utc::now();
+    let cutoff = now - Duration::seconds(SETTINGS.jobs.stale_after_seconds);
+
+    let dropped = diesel::sql_query(
+        "DELETE FROM jobs stale \
+         WHERE stale.status = $1 AND stale.locked_at < $2 AND stale.unique_key IS NOT NULL \
+           AND EXISTS ( \
+               SELECT 1 FROM jobs twin \
+               WHERE twin.unique_key = stale.unique_key AND twin.id <> stale.id \
+                 AND (twin.status = $3 OR (twin.status = $1 AND twin.locked_at < $2 AND twin.id > stale.id)) \
+           )",
+    )
+    .bind::<Text, _>(statuses::RUNNING)
+    .bind::<Timestamp, _>(cutoff.naive_utc())
+    .bind::<Text, _>(statuses::QUEUED)
+    .execute(conn)?;
+
+    if dropped > 0 {
+        warn!(count = dropped, "Stale jobs dropped, identical jobs are already queued");
+    }
+
+    let requeued = diesel::update(
+        jobs::table
+            .filter(jobs::status.eq(statuses::RUNNING))
+            .filter(jobs::locked_at.lt(cutoff)),
+    )
+    .set((
+        jobs::status.eq(statuses::QUEUED),
+        jobs::run_at.eq(now),
+        jobs::locked_by.eq(None::<String>),
+        jobs::locked_at.eq(None::<DateTime<Utc>>),
+        jobs::last_error.eq("Worker stopped before the job finished"),
+        jobs::updated_at.eq(now),
+    ))
+    .execute(conn)?;
+
+    if requeued > 0 {
+        warn!(count = requeued, "Stale jobs queued again");
+    }
+
+    Ok(requeued)
+}
+
+/// Queue a dead job again with a fresh set of attempts
+pub fn retry(conn: &mut PgConnection, id: i64) -> Result<JobRecord, Error> {
+    conn.transaction::<JobRecord, Error, _>(|conn| {
+        let job = jobs::table.find(id).for_update().first::<JobRecord>(conn)?;
+
+        if job.status != statuses::DEAD {
+            return Err(Error::validation_error("Only dead jobs can be retried"));
+        }
+        if has_queued_twin(conn, &job)? {
+            return Err(Error::validation_error(
+                "An identical job is already queued; discard this one instead",
+            ));
+        }
+
+        let now = Utc::now();
+        let job = diesel::update(&job)
+            .set((
+                jobs::status.eq(statuses::QUEUED),
+                jobs::attempts.eq(0),
+                jobs::run_at.eq(now),
+                jobs::finished_at.eq(None::<DateTime<Utc>>),
+                jobs::updated_at.eq(now),
+            ))
+            .get_result::<JobRecord>(conn)?;
+
+        info!(job_id = id, kind = %job.kind, "Dead job queued again");
+        Ok(job)
+    })
+}
+
+/// Whether another job with the same unique key is waiting to run
+fn has_queued_twin(conn: &mut PgConnection, job: &JobRecord) -> Result<bool, Error> {
+    let Some(unique_key) = &job.unique_key else {
+        return Ok(false);
+    };
+
+    let twin = jobs::table
+        .filter(jobs::unique_key.eq(unique_key))
+        .filter(jobs::status.eq(statuses::QUEUED))
+        .filter(jobs::id.ne(job.id))
+        .select(jobs::id)
+        .first::<i64>(conn)
+        .optional()?;
+
+    Ok(twin.is_some())
+}
+
+/// Delete a dead job without running it again
+///
+/// Work that is still pending, such as a due order timer, is queued afresh by its scheduler.
+pub fn discard(conn: &mut PgConnection, id: i64) -> Result<(), Error> {
+    let deleted = diesel::delete(
+        jobs::table
+            .find(id)
+            .filter(jobs::status.eq(statuses::DEAD)),
+    )
+    .execute(conn)?;
+
+    if deleted == 0 {
+        return Err(Error::validation_error("Only dead jobs can be discarded"));
+    }
+
+    info!(job_id = id, "Dead job discarded");
+    Ok(())
+}
+
+/// One page of jobs, newest first, optionally filtered by status and kind
+pub fn list(
+    conn: &mut PgConnection,
+    status: Option<&str>,
+    kind: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<JobRecord>, i64), Error> {
+    let mut query = jobs::table.into_boxed();
+    let mut count_query = jobs::table.into_boxed();
+
+    if let Some(status) = status {
+        query = query.filter(jobs::status.eq(status.to_string()));
+        count_query = count_query.filter(jobs::status.eq(status.to_string()));
+    }
+
+    if let Some(kind) = kind {
+        query = query.filter(jobs::kind.eq(kind.to_string()));
+        count_query = count_query.filter(jobs::kind.eq(kind.to_string()));
+    }
+
+    let total = count_query.count().get_result::<i64>(conn)?;
+    let records = query
+        .order(jobs::id.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<JobRecord>(conn)?;
+
+    Ok((records, total))
+}
@@ -1,8 +1,22 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Integer, Nullable, Text, Timestamp};
 use serde::{Deserialize, Serialize};
+use tracing::info;
 
-use crate::schema::{conversations, messages};
+use crate::constants::messages::*;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::models::attachment::Attachment;
+use crate::models::{block, dispute, notification};
+use crate::models::order::Order;
+use crate::models::user::User;
+use crate::pgp;
+use crate::schema::{
+    conversation_report_messages, conversation_reports, conversations, messages, orders, users,
+};
+use crate::settings::SETTINGS;
+use crate::templates::{ConversationContext, RelatedOrderContext};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
 #[diesel(table_name = conversations)]
@@ -12,6 +26,14 @@ pub struct Conversation {
     pub user2_id: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// The order the conversation is about; `None` for direct conversations
+    pub order_id: Option<i32>,
+    /// When the order was closed; nothing more can be sent after that
+    pub locked_at: Option<DateTime<Utc>>,
+    /// Days messages are kept, set by a participant; `None` uses `messaging.retention_days`
+    pub retention_days: Option<i32>,
+    /// Who opened a direct conversation, counted against their daily quota
+    pub started_by: Option<i32>,
 }
 
 #[derive(Debug, Insertable)]
@@ -19,6 +41,8 @@ pub struct Conversation {
 pub struct NewConversation {
     pub user1_id: i32,
     pub user2_id: i32,
+    pub order_id: Option<i32>,
+    pub started_by: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
@@ -48,5 +72,811 @@ pub struct ConversationWithMessages {
     #[serde(flatten)]
     pub conversation: Conversation,
     pub messages: Vec<Message>,
+    /// Attachments of the messages on the page
+    pub attachments: Vec<Attachment>,
     pub other_user_id: i32,
 }
+
+impl Conversation {
+    pub fn is_locked(&self) -> bool {
+        self.locked_at.is_some()
+    }
+
+    /// Days the conversation's messages are kept
+    pub fn effective_retention_days(&self) -> i32 {
+        self.retention_days.unwrap_or(SETTINGS.messaging.retention_days)
+    }
+
+    /// Shortest retention the conversation can set
+    ///
+    /// Order conversations keep at least the finalize window, its extension and a margin,
+    /// so neither party can delete what the other may need as dispute evidence.
+    pub fn min_retention_days(&self) -> i32 {
+        if self.order_id.is_none() {
+            return MIN_RETENTION_DAYS;
+        }
+        let timers = &SETTINGS.order_timers;
+        (timers.finalize_after_days + timers.extension_days) as i32 + ORDER_RETENTION_MARGIN_DAYS
+    }
+
+    /// The order shown alongside the conversation, for order conversations
+    pub fn related_order(&self) -> Option<RelatedOrderContext> {
+        self.order_id.map(|id| RelatedOrderContext { id })
+    }
+
+    /// The participant who is not `user_id`
+    pub fn other_user_id(&self, user_id: i32) -> i32 {
+        if self.user1_id == user_id {
+            self.user2_id
+        } else {
+            self.user1_id
+        }
+    }
+}
+
+/// A conversation in a user's inbox, with its latest message and what they have not read
+#[derive(Debug, Serialize, QueryableByName)]
+pub struct ConversationSummary {
+    #[diesel(sql_type = Integer)]
+    pub id: i32,
+    #[diesel(sql_type = Integer)]
+    pub other_user_id: i32,
+    #[diesel(sql_type = Text)]
+    pub other_username: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub last_message: Option<String>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub last_message_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = BigInt)]
+    pub unread_count: i64,
+    #[diesel(sql_type = Timestamp)]
+    pub updated_at: DateTime<Utc>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    pub order_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub locked_at: Option<DateTime<Utc>>,
+    /// Days messages are kept, the conversation's own or the default
+    #[diesel(sql_type = Integer)]
+    pub retention_days: i32,
+    /// An open order, or an unresolved dispute on it, keeps every message
+    #[diesel(sql_type = Bool)]
+    pub on_legal_hold: bool,
+    /// When the oldest message will be deleted, unless on legal hold
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<ConversationSummary> for ConversationContext {
+    fn from(summary: ConversationSummary) -> Self {
+        Self {
+            id: summary.id,
+            other_username: summary.other_username,
+            last_message: summary.last_message.unwrap_or_default(),
+            last_message_time: summary
+                .last_message_at
+                .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default(),
+            unread_count: summary.unread_count as i32,
+            order_id: summary.order_id,
+            retention_days: summary.retention_days,
+            on_legal_hold: summary.on_legal_hold,
+            expires_at: summary
+                .expires_at
+                .filter(|_| !summary.on_legal_hold)
+                .map(|at| at.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+/// Open the direct conversation between two users, or find the one they already have
+///
+/// Participants are stored lower id first, so either user starting it finds the same row.
+/// Users who blocked each other cannot open one, and a new conversation counts against the
+/// starter's daily quota. Conversations about an order are opened with `open_for_order`
+/// instead.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `user_id` - The user starting the conversation
+/// * `other_id` - The user they want to talk to
+///
+/// # Returns
+/// * `Result<Conversation, Error>` - The conversation or an error
+pub fn open(conn: &mut PgConnection, user_id: i32, other_id: i32) -> Result<Conversation, Error> {
+    if user_id == other_id {
+        return Err(Error::validation_error("You cannot start a conversation with yourself"));
+    }
+
+    let exists = diesel::select(diesel::dsl::exists(users::table.find(other_id))).get_result::<bool>(conn)?;
+    if !exists {
+        return Err(Error::not_found());
+    }
+
+    if block::between(conn, user_id, other_id)? {
+        return Err(Error::validation_error("You cannot message this user"));
+    }
+
+    let (user1_id, user2_id) = (user_id.min(other_id), user_id.max(other_id));
+    let find_direct = |conn: &mut PgConnection| {
+        conversations::table
+            .filter(conversations::user1_id.eq(user1_id))
+            .filter(conversations::user2_id.eq(user2_id))
+            .filter(conversations::order_id.is_null())
+            .first::<Conversation>(conn)
+            .optional()
+    };
+
+    if let Some(conversation) = find_direct(conn)? {
+        return Ok(conversation);
+    }
+
+    let starter = users::table.find(user_id).first::<User>(conn)?;
+    let quota = daily_conversation_quota(&starter);
+    let started_today = conversations::table
+        .filter(conversations::started_by.eq(user_id))
+        .filter(conversations::order_id.is_null())
+        .filter(conversations::created_at.gt(Utc::now() - Duration::days(1)))
+        .count()
+        .get_result::<i64>(conn)?;
+    if started_today >= quota {
+        return Err(Error::validation_error(format!(
+            "You can start {} new conversations a day; try again later",
+            quota
+        )));
+    }
+
+    diesel::insert_into(conversations::table)
+        .values(&NewConversation {
+            user1_id,
+            user2_id,
+            order_id: None,
+            started_by: Some(user_id),
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    find_direct(conn)?.ok_or_else(Error::not_found)
+}
+
+/// How many direct conversations a user can start per day
+///
+/// Trusted reputation lifts the limit above an established account's, and new accounts get
+/// the lowest regardless of reputation.
+pub fn daily_conversation_quota(user: &User) -> i64 {
+    let settings = &SETTINGS.messaging;
+
+    if user.created_at > Utc::now() - Duration::days(NEW_ACCOUNT_DAYS) {
+        settings.new_account_daily_conversations
+    } else if user.reputation.is_some_and(|reputation| reputation >= TRUSTED_REPUTATION) {
+        settings.trusted_daily_conversations
+    } else {
+        settings.daily_conversations
+    }
+}
+
+/// Open the conversation between an order's buyer and vendor, or find the one it already has
+///
+/// Every order has at most one, opened at checkout.
+pub fn open_for_order(conn: &mut PgConnection, order: &Order) -> Result<Conversation, Error> {
+    let (user1_id, user2_id) = (
+        order.buyer_id.min(order.vendor_id),
+        order.buyer_id.max(order.vendor_id),
+    );
+
+    diesel::insert_into(conversations::table)
+        .values(&NewConversation {
+            user1_id,
+            user2_id,
+            order_id: Some(order.id),
+            started_by: None,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    for_order(conn, order.id)?.ok_or_else(Error::not_found)
+}
+
+/// The conversation about an order, for its buyer or vendor; anyone else gets not found
+pub fn find_for_order_party(conn: &mut PgConnection, order_id: i32, user_id: i32) -> Result<Conversation, Error> {
+    let order = orders::table
+        .find(order_id)
+        .filter(orders::buyer_id.eq(user_id).or(orders::vendor_id.eq(user_id)))
+        .first::<Order>(conn)
+        .optional()?
+        .ok_or_else(Error::not_found)?;
+
+    open_for_order(conn, &order)
+}
+
+/// The conversation about an order, if it has one
+pub fn for_order(conn: &mut PgConnection, order_id: i32) -> Result<Option<Conversation>, Error> {
+    Ok(conversations::table
+        .filter(conversations::order_id.eq(order_id))
+        .first::<Conversation>(conn)
+        .optional()?)
+}
+
+/// Make an order's conversation read-only once the order is closed
+pub fn lock_for_order(conn: &mut PgConnection, order_id: i32) -> Result<(), Error> {
+    diesel::update(
+        conversations::table
+            .filter(conversations::order_id.eq(order_id))
+            .filter(conversations::locked_at.is_null()),
+    )
+    .set(conversations::locked_at.eq(Utc::now()))
+    .execute(conn)?;
+    Ok(())
+}
+
+/// A conversation the user takes part in; anyone else gets not found
+pub fn find_for_participant(conn: &mut PgConnection, conversation_id: i32, user_id: i32) -> Result<Conversation, Error> {
+    conversations::table
+        .find(conversation_id)
+        .filter(conversations::user1_id.eq(user_id).or(conversations::user2_id.eq(user_id)))
+        .first::<Conversation>(conn)
+        .optional()?
+        .ok_or_else(Error::not_found)
+}
+
+/// One page of a user's conversations, most recently active first
+///
+/// The latest message and unread count come from the same query as the page.
+pub fn summaries(
+    conn: &mut PgConnection,
+    user_id: i32,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<ConversationSummary>, i64), Error> {
+    let total = conversations::table
+        .filter(conversations::user1_id.eq(user_id).or(conversations::user2_id.eq(user_id)))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let page = diesel::sql_query(
+        "SELECT c.id, other.id AS other_user_id, other.username AS other_username, \
+                last.encrypted_content AS last_message, last.created_at AS last_message_at, \
+                (SELECT COUNT(*) FROM messages unread \
+                 WHERE unread.conversation_id = c.id AND unread.sender_id <> $1 AND NOT unread.is_read) \
+                    AS unread_count, \
+                c.updated_at, c.order_id, c.locked_at, \
+                COALESCE(c.retention_days, $4) AS retention_days, \
+                (c.order_id IS NOT NULL AND c.locked_at IS NULL) \
+                    OR EXISTS (SELECT 1 FROM disputes d WHERE d.order_id = c.order_id AND d.status <> $5) \
+                    AS on_legal_hold, \
+                (SELECT MIN(oldest.created_at) FROM messages oldest WHERE oldest.conversation_id = c.id) \
+                    + make_interval(days => COALESCE(c.retention_days, $4)) AS expires_at \
+         FROM conversations c \
+         JOIN users other ON other.id = CASE WHEN c.user1_id = $1 THEN c.user2_id ELSE c.user1_id END \
+         LEFT JOIN LATERAL ( \
+             SELECT m.encrypted_content, m.created_at FROM messages m \
+             WHERE m.conversation_id = c.id ORDER BY m.created_at DESC, m.id DESC LIMIT 1 \
+         ) last ON TRUE \
+         WHERE c.user1_id = $1 OR c.user2_id = $1 \
+         ORDER BY COALESCE(last.created_at, c.created_at) DESC, c.id DESC \
+         LIMIT $2 OFFSET $3",
+    )
+    .bind::<Integer, _>(user_id)
+    .bind::<BigInt, _>(limit)
+    .bind::<BigInt, _>(offset)
+    .bind::<Integer, _>(SETTINGS.messaging.retention_days)
+    .bind::<Text, _>(dispute::statuses::RESOLVED)
+    .load::<ConversationSummary>(conn)?;
+
+    Ok((page, total))
+}
+
+/// One page of a conversation's messages, newest first
+///
+/// When a participant reads, messages on the page sent by the other participant are marked
+/// read. Moderators read with no `reader_id` and mark nothing.
+pub fn page(
+    conn: &mut PgConnection,
+    conversation: &Conversation,
+    reader_id: Option<i32>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Message>, i64), Error> {
+    let total = messages::table
+        .filter(messages::conversation_id.eq(conversation.id))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let mut page = messages::table
+        .filter(messages::conversation_id.eq(conversation.id))
+        .order((messages::created_at.desc(), messages::id.desc()))
+        .limit(limit)
+        .offset(offset)
+        .load::<Message>(conn)?;
+
+    let unread: Vec<i32> = page
+        .iter()
+        .filter(|message| {
+            reader_id.is_some_and(|reader_id| !message.is_read && message.sender_id != reader_id)
+        })
+        .map(|message| message.id)
+        .collect();
+
+    if !unread.is_empty() {
+        diesel::update(messages::table.filter(messages::id.eq_any(&unread)))
+            .set(messages::is_read.eq(true))
+            .execute(conn)?;
+
+        for message in page.iter_mut().filter(|message| unread.contains(&message.id)) {
+            message.is_read = true;
+        }
+    }
+
+    Ok((page, total))
+}
+
+/// Post a message to a conversation the sender takes part in
+///
+/// The body must be an ASCII-armored PGP message encrypted to the recipient's current key,
+/// and to the sender's too when `messaging.require_sender_copy` is set. Senders who opted in
+/// to server-side encryption may send plaintext instead; it is encrypted to the recipient (and
+/// to the sender when they have a key) before anything is stored.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `sender_id` - The author
+/// * `conversation_id` - The conversation
+/// * `content` - The message body
+///
+/// # Returns
+/// * `Result<Message, Error>` - The stored message or an error
+pub fn send(conn: &mut PgConnection, sender_id: i32, conversation_id: i32, content: &str) -> Result<Message, Error> {
+    if content.trim().is_empty() {
+        return Err(Error::validation_error("The message cannot be empty"));
+    }
+    if content.len() > MAX_CONTENT_BYTES {
+        return Err(Error::validation_error(format!(
+            "Messages are limited to {} KiB",
+            MAX_CONTENT_BYTES / 1024
+        )));
+    }
+
+    conn.transaction::<Message, Error, _>(|conn| {
+        let conversation = find_for_participant(conn, conversation_id, sender_id)?;
+        if conversation.is_locked() {
+            return Err(Error::validation_error(
+                "The order is closed, so its conversation is read-only",
+            ));
+        }
+        if conversation.order_id.is_none()
+            && block::between(conn, sender_id, conversation.other_user_id(sender_id))?
+        {
+            return Err(Error::validation_error("You cannot message this user"));
+        }
+
+        let sender = users::table.find(sender_id).first::<User>(conn)?;
+        let recipient = users::table
+            .find(conversation.other_user_id(sender_id))
+            .first::<User>(conn)?;
+
+        let encrypted_content = seal(&sender, &recipient, content)?;
+
+        let message = diesel::insert_into(messages::table)
+            .values(&NewMessage {
+                conversation_id: conversation.id,
+                sender_id,
+                encrypted_content,
+                is_read: false,
+            })
+            .get_result::<Message>(conn)?;
+
+        diesel::update(&conversation)
+            .set(conversations::updated_at.eq(Utc::now()))
+            .execute(conn)?;
+
+        notification::notify(
+            conn,
+            recipient.id,
+            Event::MessageReceived {
+                conversation_id: message.conversation_id,
+                message_id: message.id,
+                sender_id,
+            },
+        )?;
+
+        Ok(message)
+    })
+}
+
+/// The ciphertext to store for a message, checked or produced here
+fn seal(sender: &User, recipient: &User, content: &str) -> Result<String, Error> {
+    let recipient_key = recipient.pgp_public_key.as_deref().ok_or_else(|| {
+        Error::validation_error(format!(
+            "{} has no PGP key, so messages to them cannot be encrypted",
+            recipient.username
+        ))
+    })?;
+    let require_sender_copy = SETTINGS.messaging.require_sender_copy;
+
+    if !pgp::is_armored_message(content) {
+        if !sender.server_side_encryption {
+            return Err(Error::validation_error(
+                "Messages must be encrypted to the recipient's PGP key before sending",
+            ));
+        }
+
+        let mut keys = vec![recipient_key];
+        match sender.pgp_public_key.as_deref() {
+            Some(sender_key) => keys.push(sender_key),
+            None if require_sender_copy => {
+                return Err(Error::validation_error("Add a PGP key to your account to send messages"));
+            }
+            None => {}
+        }
+        return pgp::encrypt(content, &keys);
+    }
+
+    let recipients = pgp::recipients(content)?;
+    if !pgp::encrypted_to(&recipients, recipient_key)? {
+        return Err(Error::validation_error(
+            "The message is not encrypted to the recipient's current PGP key",
+        ));
+    }
+
+    if require_sender_copy {
+        let sender_key = sender
+            .pgp_public_key
+            .as_deref()
+            .ok_or_else(|| Error::validation_error("Add a PGP key to your account to send messages"))?;
+        if !pgp::encrypted_to(&recipients, sender_key)? {
+            return Err(Error::validation_error("The message must also be encrypted to your own PGP key"));
+        }
+    }
+
+    Ok(content.to_string())
+}
+
+/// Set how many days a conversation's messages are kept, or `None` for the default
+///
+/// Either participant can change it, and the change applies to messages already sent.
+/// Order conversations can't go below `Conversation::min_retention_days`.
+pub fn set_retention(
+    conn: &mut PgConnection,
+    conversation_id: i32,
+    user_id: i32,
+    retention_days: Option<i32>,
+) -> Result<Conversation, Error> {
+    let conversation = find_for_participant(conn, conversation_id, user_id)?;
+
+    let min_days = conversation.min_retention_days();
+    if retention_days.is_some_and(|days| !(min_days..=MAX_RETENTION_DAYS).contains(&days)) {
+        return Err(Error::validation_error(format!(
+            "Retention must be between {} and {} days",
+            min_days, MAX_RETENTION_DAYS
+        )));
+    }
+
+    let conversation = diesel::update(&conversation)
+        .set(conversations::retention_days.eq(retention_days))
+        .get_result::<Conversation>(conn)?;
+
+    info!(
+        conversation_id = conversation.id,
+        user_id = user_id,
+        retention_days = ?retention_days,
+        "Conversation retention changed"
+    );
+    Ok(conversation)
+}
+
+/// Hard-delete messages older than their conversation's retention
+///
+/// Conversations about an order are on legal hold while the order is open, since it can
+/// still be disputed, and while a dispute on it is unresolved.
+///
+/// # Returns
+/// * `Result<usize, Error>` - How many messages were deleted
+pub fn purge_expired(conn: &mut PgConnection) -> Result<usize, Error> {
+    let deleted = diesel::sql_query(
+        "DELETE FROM messages m USING conversations c \
+         WHERE m.conversation_id = c.id \
+           AND m.created_at < $1 - make_interval(days => COALESCE(c.retention_days, $2)) \
+           AND (c.order_id IS NULL OR c.locked_at IS NOT NULL) \
+           AND NOT EXISTS ( \
+               SELECT 1 FROM disputes d WHERE d.order_id = c.order_id AND d.status <> $3 \
+           )",
+    )
+    .bind::<Timestamp, _>(Utc::now().naive_utc())
+    .bind::<Integer, _>(SETTINGS.messaging.retention_days)
+    .bind::<Text, _>(dispute::statuses::RESOLVED)
+    .execute(conn)?;
+
+    if deleted > 0 {
+        info!(deleted = deleted, "Expired messages deleted");
+    }
+    Ok(deleted)
+}
+
+/// Turn server-side encryption of the user's outgoing messages on or off
+pub fn set_server_side_encryption(conn: &mut PgConnection, user_id: i32, enabled: bool) -> Result<(), Error> {
+    diesel::update(users::table.find(user_id))
+        .set((
+            users::server_side_encryption.eq(enabled),
+            users::updated_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+
+    info!(user_id = user_id, enabled = enabled, "Server-side message encryption changed");
+    Ok(())
+}
+
+/// Valid conversation report statuses
+pub mod report_statuses {
+    /// Waiting for a moderator
+    pub const OPEN: &str = "open";
+    /// A moderator agreed the conversation was abusive
+    pub const UPHELD: &str = "upheld";
+    /// A moderator found nothing to act on
+    pub const DISMISSED: &str = "dismissed";
+}
+
+/// Valid reasons for reporting a conversation
+pub mod report_reasons {
+    pub const SPAM: &str = "spam";
+    pub const HARASSMENT: &str = "harassment";
+    pub const SCAM: &str = "scam";
+    pub const OFF_PLATFORM_PAYMENT: &str = "off_platform_payment";
+    pub const OTHER: &str = "other";
+
+    pub const ALL: [&str; 5] = [SPAM, HARASSMENT, SCAM, OFF_PLATFORM_PAYMENT, OTHER];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = conversation_reports)]
+pub struct ConversationReport {
+    pub id: i32,
+    pub conversation_id: i32,
+    pub reporter_id: i32,
+    /// The other participant
+    pub reported_user_id: i32,
+    pub reason: String,
+    pub details: Option<String>,
+    pub status: String,
+    pub resolved_by: Option<i32>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = conversation_reports)]
+struct NewConversationReport {
+    conversation_id: i32,
+    reporter_id: i32,
+    reported_user_id: i32,
+    reason: String,
+    details: Option<String>,
+}
+
+/// A copy of a message shared with moderators through a report
+///
+/// The copy outlives the message, so retention cannot erase what was reported.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = conversation_report_messages)]
+#[diesel(belongs_to(ConversationReport, foreign_key = report_id))]
+pub struct ReportedMessage {
+    pub id: i32,
+    pub report_id: i32,
+    /// The original, until it expires
+    pub message_id: Option<i32>,
+    pub sender_id: i32,
+    pub encrypted_content: String,
+    /// What the reporter says the message reads, shared with their consent
+    pub shared_content: Option<String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = conversation_report_messages)]
+struct NewReportedMessage {
+    report_id: i32,
+    message_id: Option<i32>,
+    sender_id: i32,
+    encrypted_content: String,
+    shared_content: Option<String>,
+    sent_at: DateTime<Utc>,
+}
+
+/// A message the reporter picked to share
+#[derive(Debug, Deserialize)]
+pub struct SharedMessage {
+    pub message_id: i32,
+    /// The reporter's decryption, since moderators cannot read the ciphertext
+    pub content: Option<String>,
+}
+
+/// A report with the messages it shares, for moderators
+#[derive(Debug, Serialize)]
+pub struct ReportDetails {
+    #[serde(flatten)]
+    pub report: ConversationReport,
+    pub messages: Vec<ReportedMessage>,
+}
+
+/// Report a conversation to moderators, sharing the messages the reporter picked
+///
+/// Nothing in a conversation is visible to moderators unless a participant shares it here, so
+/// the reporter has to consent to sharing. Each shared message is copied with its ciphertext and,
+/// optionally, the reporter's decryption of it.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `reporter_id` - The participant reporting
+/// * `conversation_id` - The conversation
+/// * `reason` - One of `report_reasons`
+/// * `details` - Free text from the reporter
+/// * `shared` - The messages to share
+/// * `consent` - Whether the reporter agreed to share them
+///
+/// # Returns
+/// * `Result<ConversationReport, Error>` - The new report or an error
+pub fn report(
+    conn: &mut PgConnection,
+    reporter_id: i32,
+    conversation_id: i32,
+    reason: &str,
+    details: Option<String>,
+    shared: Vec<SharedMessage>,
+    consent: bool,
+) -> Result<ConversationReport, Error> {
+    if !report_reasons::ALL.contains(&reason) {
+        return Err(Error::validation_error(format!("Invalid report reason: {}", reason)));
+    }
+    if !consent {
+        return Err(Error::validation_error(
+            "Agree to share the selected messages with moderators to report the conversation",
+        ));
+    }
+    if shared.is_empty() {
+        return Err(Error::validation_error("Select the messages to share with moderators"));
+    }
+    if shared.len() > MAX_REPORTED_MESSAGES {
+        return Err(Error::validation_error(format!(
+            "A report can share at most {} messages",
+            MAX_REPORTED_MESSAGES
+        )));
+    }
+    if shared
+        .iter()
+        .filter_map(|message| message.content.as_ref())
+        .any(|content| content.len() > MAX_CONTENT_BYTES)
+    {
+        return Err(Error::validation_error(format!(
+            "Shared messages are limited to {} KiB",
+            MAX_CONTENT_BYTES / 1024
+        )));
+    }
+
+    conn.transaction::<ConversationReport, Error, _>(|conn| {
+        let conversation = find_for_participant(conn, conversation_id, reporter_id)?;
+
+        let already_open = diesel::select(diesel::dsl::exists(
+            conversation_reports::table
+                .filter(conversation_reports::conversation_id.eq(conversation.id))
+                .filter(conversation_reports::reporter_id.eq(reporter_id))
+                .filter(conversation_reports::status.eq(report_statuses::OPEN)),
+        ))
+        .get_result::<bool>(conn)?;
+        if already_open {
+            return Err(Error::validation_error("You have already reported this conversation"));
+        }
+
+        let ids: Vec<i32> = shared.iter().map(|message| message.message_id).collect();
+        let originals = messages::table
+            .filter(messages::conversation_id.eq(conversation.id))
+            .filter(messages::id.eq_any(&ids))
+            .load::<Message>(conn)?;
+        if originals.len() != ids.len() {
+            return Err(Error::validation_error("Only messages from this conversation can be shared"));
+        }
+
+        let report = diesel::insert_into(conversation_reports::table)
+            .values(&NewConversationReport {
+                conversation_id: conversation.id,
+                reporter_id,
+                reported_user_id: conversation.other_user_id(reporter_id),
+                reason: reason.to_string(),
+                details,
+            })
+            .get_result::<ConversationReport>(conn)?;
+
+        let copies: Vec<NewReportedMessage> = shared
+            .into_iter()
+            .filter_map(|message| {
+                let original = originals.iter().find(|original| original.id == message.message_id)?;
+                Some(NewReportedMessage {
+                    report_id: report.id,
+                    message_id: Some(original.id),
+                    sender_id: original.sender_id,
+                    encrypted_content: original.encrypted_content.clone(),
+                    shared_content: message.content,
+                    sent_at: original.created_at,
+                })
+            })
+            .collect();
+
+        diesel::insert_into(conversation_report_messages::table)
+            .values(&copies)
+            .execute(conn)?;
+
+        info!(
+            report_id = report.id,
+            conversation_id = conversation.id,
+            reporter_id = reporter_id,
+            reason = %reason,
+            shared = copies.len(),
+            "Conversation reported"
+        );
+        Ok(report)
+    })
+}
+
+/// One page of open conversation reports, oldest first
+pub fn open_reports(
+    conn: &mut PgConnection,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<ConversationReport>, i64), Error> {
+    let total = conversation_reports::table
+        .filter(conversation_reports::status.eq(report_statuses::OPEN))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let reports = conversation_reports::table
+        .filter(conversation_reports::status.eq(report_statuses::OPEN))
+        .order(conversation_reports::created_at.asc())
+        .limit(limit)
+        .offset(offset)
+        .load::<ConversationReport>(conn)?;
+
+    Ok((reports, total))
+}
+
+/// A report and the messages it shares, oldest message first
+pub fn report_details(conn: &mut PgConnection, report_id: i32) -> Result<ReportDetails, Error> {
+    let report = conversation_reports::table
+        .find(report_id)
+        .first::<ConversationReport>(conn)?;
+
+    let messages = ReportedMessage::belonging_to(&report)
+        .order((conversation_report_messages::sent_at.asc(), conversation_report_messages::id.asc()))
+        .load::<ReportedMessage>(conn)?;
+
+    Ok(ReportDetails { report, messages })
+}
+
+/// Close an open report as upheld or dismissed
+pub fn resolve_report(
+    conn: &mut PgConnection,
+    report_id: i32,
+    moderator_id: i32,
+    status: &str,
+) -> Result<ConversationReport, Error> {
+    let now = Utc::now();
+    let report = diesel::update(
+        conversation_reports::table
+            .find(report_id)
+            .filter(conversation_reports::status.eq(report_statuses::OPEN)),
+    )
+    .set((
+        conversation_reports::status.eq(status),
+        conversation_reports::resolved_by.eq(moderator_id),
+        conversation_reports::resolved_at.eq(now),
+        conversation_reports::updated_at.eq(now),
+    ))
+    .get_result::<ConversationReport>(conn)
+    .optional()?
+    .ok_or_else(|| Error::validation_error("The report is not open"))?;
+
+    info!(
+        report_id = report_id,
+        moderator_id = moderator_id,
+        status = %status,
+        "Conversation report resolved"
+    );
+    Ok(report)
+}
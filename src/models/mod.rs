@@ -4,6 +4,25 @@ pub mod order;
 pub mod message;
 pub mod payment;
 pub mod vendor;
+pub mod inventory;
+pub mod moderation;
+pub mod cart;
+pub mod escrow;
+pub mod checkout;
+pub mod dispute;
+pub mod order_timer;
+pub mod job;
+pub mod fee;
+pub mod withdrawal;
+pub mod reputation;
+pub mod review;
+pub mod analytics;
+pub mod availability;
+pub mod notification;
+pub mod block;
+pub mod attachment;
+pub mod support;
+pub mod announcement;
 
 // Temporary placeholder for the register_custom_types function
 // This will be implemented when we have the actual database models
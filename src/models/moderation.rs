@@ -0,0 +1,354 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::errors::Error;
+use crate::models::product::{ListingState, Product};
+use crate::schema::{listing_flags, listing_moderation_events, products};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = listing_moderation_events)]
+#[diesel(belongs_to(Product))]
+pub struct ListingModerationEvent {
+    pub id: i32,
+    pub product_id: i32,
+    /// The moderator or vendor who made the change; `None` for automatic changes
+    pub actor_id: Option<i32>,
+    pub from_state: ListingState,
+    pub to_state: ListingState,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = listing_moderation_events)]
+pub struct NewListingModerationEvent {
+    pub product_id: i32,
+    pub actor_id: Option<i32>,
+    pub from_state: ListingState,
+    pub to_state: ListingState,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = listing_flags)]
+#[diesel(belongs_to(Product))]
+pub struct ListingFlag {
+    pub id: i32,
+    pub product_id: i32,
+    pub reporter_id: i32,
+    pub reason: String,
+    pub details: Option<String>,
+    pub status: String,
+    pub resolved_by: Option<i32>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = listing_flags)]
+pub struct NewListingFlag {
+    pub product_id: i32,
+    pub reporter_id: i32,
+    pub reason: String,
+    pub details: Option<String>,
+}
+
+/// Valid flag statuses
+pub mod flag_statuses {
+    /// Waiting for a moderator
+    pub const OPEN: &str = "open";
+    /// A moderator agreed and suspended or rejected the listing
+    pub const UPHELD: &str = "upheld";
+    /// A moderator looked at the listing and left it up
+    pub const DISMISSED: &str = "dismissed";
+}
+
+/// Valid reasons a buyer can give when flagging a listing
+pub mod flag_reasons {
+    pub const PROHIBITED: &str = "prohibited";
+    pub const SCAM: &str = "scam";
+    pub const MISLEADING: &str = "misleading";
+    pub const WRONG_CATEGORY: &str = "wrong_category";
+    pub const OTHER: &str = "other";
+
+    pub const ALL: [&str; 5] = [PROHIBITED, SCAM, MISLEADING, WRONG_CATEGORY, OTHER];
+}
+
+/// A listing waiting for a moderator, either because it was submitted or because it was flagged
+#[derive(Debug, Serialize)]
+pub struct QueueEntry {
+    pub product: Product,
+    pub open_flags: Vec<ListingFlag>,
+}
+
+/// Whether a listing may move from one state to another
+///
+/// Vendors move their own listings between `Draft`, `PendingReview` and back; everything
+/// else is a moderator's decision.
+pub fn can_transition(from: ListingState, to: ListingState) -> bool {
+    use ListingState::*;
+
+    matches!(
+        (from, to),
+        (Draft, PendingReview)
+            | (Rejected, PendingReview)
+            | (PendingReview, Draft)
+            | (PendingReview, Active)
+            | (PendingReview, Rejected)
+            | (Active, PendingReview)
+            | (Active, Suspended)
+            | (Active, Rejected)
+            | (Suspended, Active)
+            | (Suspended, Rejected)
+    )
+}
+
+/// Move a listing to a new state and record who did it and why
+///
+/// Must be called with the product row locked. Moderator decisions (anything other than a
+/// submission) also record the reviewer.
+///
+/// # Arguments
+/// * `conn` - A connection with an open transaction
+/// * `product` - The locked product
+/// * `to` - The new state
+/// * `actor_id` - The user making the change, or `None` for automatic changes
+/// * `reason` - Why, shown to the vendor for rejections and suspensions
+///
+/// # Returns
+/// * `Result<Product, Error>` - The updated product or an error
+pub fn transition(
+    conn: &mut PgConnection,
+    product: &Product,
+    to: ListingState,
+    actor_id: Option<i32>,
+    reason: Option<String>,
+) -> Result<Product, Error> {
+    let from = product.listing_state;
+    if !can_transition(from, to) {
+        return Err(Error::validation_error(format!(
+            "A {:?} listing cannot be moved to {:?}",
+            from, to
+        )));
+    }
+
+    if matches!(to, ListingState::Rejected | ListingState::Suspended) && reason.is_none() {
+        return Err(Error::validation_error("A reason is required"));
+    }
+
+    let now = Utc::now();
+    let updated = match to {
+        ListingState::PendingReview => diesel::update(product)
+            .set((
+                products::listing_state.eq(to),
+                products::moderation_reason.eq(&reason),
+                products::submitted_at.eq(now),
+                products::updated_at.eq(now),
+            ))
+            .get_result::<Product>(conn)?,
+        ListingState::Draft => diesel::update(product)
+            .set((
+                products::listing_state.eq(to),
+                products::submitted_at.eq(None::<DateTime<Utc>>),
+                products::updated_at.eq(now),
+            ))
+            .get_result::<Product>(conn)?,
+        _ => diesel::update(product)
+            .set((
+                products::listing_state.eq(to),
+                products::moderation_reason.eq(&reason),
+                products::reviewed_by.eq(actor_id),
+                products::reviewed_at.eq(now),
+                products::updated_at.eq(now),
+            ))
+            .get_result::<Product>(conn)?,
+    };
+
+    diesel::insert_into(listing_moderation_events::table)
+        .values(&NewListingModerationEvent {
+            product_id: product.id,
+            actor_id,
+            from_state: from,
+            to_state: to,
+            reason,
+        })
+        .execute(conn)?;
+
+    info!(
+        product_id = product.id,
+        vendor_id = product.vendor_id,
+        actor_id = ?actor_id,
+        from = ?from,
+        to = ?to,
+        "Listing state changed"
+    );
+
+    Ok(updated)
+}
+
+/// Send an active listing back to review if its title, description or category changed
+///
+/// # Arguments
+/// * `conn` - A connection with an open transaction
+/// * `before` - The product as it was before the edit
+/// * `actor_id` - The vendor who made the edit
+///
+/// # Returns
+/// * `Result<Product, Error>` - The product as it is now
+pub fn review_after_edit(conn: &mut PgConnection, before: &Product, actor_id: i32) -> Result<Product, Error> {
+    let after = products::table.find(before.id).for_update().first::<Product>(conn)?;
+
+    let content_changed = before.title != after.title
+        || before.description != after.description
+        || before.category_id != after.category_id;
+
+    if after.listing_state == ListingState::Active && content_changed {
+        return transition(
+            conn,
+            &after,
+            ListingState::PendingReview,
+            Some(actor_id),
+            Some("Listing edited".to_string()),
+        );
+    }
+
+    Ok(after)
+}
+
+/// Flag a listing for a moderator to look at
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `product_id` - The flagged listing
+/// * `reporter_id` - The buyer flagging it
+/// * `reason` - One of `flag_reasons`
+/// * `details` - Free text from the buyer
+///
+/// # Returns
+/// * `Result<ListingFlag, Error>` - The new flag or an error
+pub fn flag_listing(
+    conn: &mut PgConnection,
+    product_id: i32,
+    reporter_id: i32,
+    reason: &str,
+    details: Option<String>,
+) -> Result<ListingFlag, Error> {
+    if !flag_reasons::ALL.contains(&reason) {
+        return Err(Error::validation_error(format!("Invalid flag reason: {}", reason)));
+    }
+
+    conn.transaction::<ListingFlag, Error, _>(|conn| {
+        let product = products::table
+            .find(product_id)
+            .filter(products::listing_state.eq(ListingState::Active))
+            .first::<Product>(conn)?;
+
+        if product.vendor_id == reporter_id {
+            return Err(Error::validation_error("You cannot flag your own listing"));
+        }
+
+        let already_open = diesel::select(diesel::dsl::exists(
+            listing_flags::table
+                .filter(listing_flags::product_id.eq(product_id))
+                .filter(listing_flags::reporter_id.eq(reporter_id))
+                .filter(listing_flags::status.eq(flag_statuses::OPEN)),
+        ))
+        .get_result::<bool>(conn)?;
+        if already_open {
+            return Err(Error::validation_error("You have already flagged this listing"));
+        }
+
+        let flag = diesel::insert_into(listing_flags::table)
+            .values(&NewListingFlag {
+                product_id,
+                reporter_id,
+                reason: reason.to_string(),
+                details,
+            })
+            .get_result::<ListingFlag>(conn)?;
+
+        info!(
+            product_id = product_id,
+            reporter_id = reporter_id,
+            reason = %reason,
+            "Listing flagged"
+        );
+
+        Ok(flag)
+    })
+}
+
+/// Close the open flags of a listing
+pub fn resolve_flags(
+    conn: &mut PgConnection,
+    product_id: i32,
+    status: &str,
+    moderator_id: i32,
+) -> Result<usize, Error> {
+    let now = Utc::now();
+    let resolved = diesel::update(
+        listing_flags::table
+            .filter(listing_flags::product_id.eq(product_id))
+            .filter(listing_flags::status.eq(flag_statuses::OPEN)),
+    )
+    .set((
+        listing_flags::status.eq(status),
+        listing_flags::resolved_by.eq(moderator_id),
+        listing_flags::resolved_at.eq(now),
+        listing_flags::updated_at.eq(now),
+    ))
+    .execute(conn)?;
+
+    debug!(product_id = product_id, count = resolved, status = %status, "Listing flags resolved");
+    Ok(resolved)
+}
+
+/// The moderation queue, oldest first
+///
+/// Holds listings waiting for review and listings with open flags, each with its open flags.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `limit` - Maximum number of entries
+/// * `offset` - Number of entries to skip
+///
+/// # Returns
+/// * `Result<(Vec<QueueEntry>, i64), Error>` - One page of the queue and its total size
+pub fn queue(conn: &mut PgConnection, limit: i64, offset: i64) -> Result<(Vec<QueueEntry>, i64), Error> {
+    let flagged_ids = listing_flags::table
+        .filter(listing_flags::status.eq(flag_statuses::OPEN))
+        .select(listing_flags::product_id);
+
+    let in_queue = products::listing_state
+        .eq(ListingState::PendingReview)
+        .or(products::id.eq_any(flagged_ids));
+
+    let total = products::table
+        .filter(in_queue.clone())
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let listings = products::table
+        .filter(in_queue)
+        .order((products::submitted_at.asc().nulls_last(), products::id.asc()))
+        .limit(limit)
+        .offset(offset)
+        .load::<Product>(conn)?;
+
+    let flags = ListingFlag::belonging_to(&listings)
+        .filter(listing_flags::status.eq(flag_statuses::OPEN))
+        .order(listing_flags::created_at.asc())
+        .load::<ListingFlag>(conn)?
+        .grouped_by(&listings);
+
+    let entries = listings
+        .into_iter()
+        .zip(flags)
+        .map(|(product, open_flags)| QueueEntry { product, open_flags })
+        .collect();
+
+    Ok((entries, total))
+}
@@ -0,0 +1,291 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::models::order::Order;
+use crate::schema::{notification_preferences, notifications};
+use crate::settings::SETTINGS;
+
+/// Valid notification kinds
+pub mod kinds {
+    pub const ORDER_STATUS_CHANGED: &str = "order_status_changed";
+    pub const MESSAGE_RECEIVED: &str = "message_received";
+    pub const DISPUTE_UPDATED: &str = "dispute_updated";
+    pub const PAYMENT_CONFIRMED: &str = "payment_confirmed";
+    pub const REVIEW_RECEIVED: &str = "review_received";
+    pub const SUPPORT_TICKET_UPDATED: &str = "support_ticket_updated";
+
+    pub const ALL: [&str; 6] = [
+        ORDER_STATUS_CHANGED,
+        MESSAGE_RECEIVED,
+        DISPUTE_UPDATED,
+        PAYMENT_CONFIRMED,
+        REVIEW_RECEIVED,
+        SUPPORT_TICKET_UPDATED,
+    ];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = notifications)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub kind: String,
+    pub title: String,
+    /// Where the change can be seen
+    pub link: Option<String>,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = notifications)]
+struct NewNotification {
+    user_id: i32,
+    kind: String,
+    title: String,
+    link: Option<String>,
+}
+
+/// Whether a user gets notifications of one kind
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Preference {
+    pub kind: String,
+    pub enabled: bool,
+}
+
+/// The kind, title and link stored for an event
+fn describe(event: &Event) -> (&'static str, String, Option<String>) {
+    match event {
+        Event::MessageReceived { conversation_id, .. } => (
+            kinds::MESSAGE_RECEIVED,
+            "New message".to_string(),
+            Some(format!("/messages/{}", conversation_id)),
+        ),
+        Event::OrderStatusChanged { order_id, status } => {
+            let status = format!("{:?}", status).to_lowercase();
+            (
+                kinds::ORDER_STATUS_CHANGED,
+                format!("Order #{} is now {}", order_id, status),
+                Some(format!("/orders/{}", order_id)),
+            )
+        }
+        Event::DisputeUpdated { order_id, status, .. } => (
+            kinds::DISPUTE_UPDATED,
+            format!("The dispute on order #{} is {}", order_id, status),
+            Some(format!("/orders/{}", order_id)),
+        ),
+        Event::PaymentConfirmed { order_id } => (
+            kinds::PAYMENT_CONFIRMED,
+            format!("Payment for order #{} confirmed", order_id),
+            Some(format!("/orders/{}", order_id)),
+        ),
+        Event::ReviewReceived { order_id, rating, .. } => (
+            kinds::REVIEW_RECEIVED,
+            format!("New {}-star review on order #{}", rating, order_id),
+            Some(format!("/orders/{}", order_id)),
+        ),
+        Event::SupportTicketUpdated { ticket_id, status } => (
+            kinds::SUPPORT_TICKET_UPDATED,
+            format!("Support ticket #{} is {}", ticket_id, status.replace('_', " ")),
+            Some(format!("/support/tickets/{}", ticket_id)),
+        ),
+    }
+}
+
+/// Store a notification for a user and push it to their live streams
+///
+/// Kinds the user turned off are neither stored nor pushed. Inside a transaction, the push
+/// waits for the commit.
+pub fn notify(conn: &mut PgConnection, user_id: i32, event: Event) -> Result<(), Error> {
+    let (kind, title, link) = describe(&event);
+
+    let enabled = notification_preferences::table
+        .find((user_id, kind))
+        .select(notification_preferences::enabled)
+        .first::<bool>(conn)
+        .optional()?
+        .unwrap_or(true);
+    if !enabled {
+        return Ok(());
+    }
+
+    diesel::insert_into(notifications::table)
+        .values(&NewNotification {
+            user_id,
+            kind: kind.to_string(),
+            title,
+            link,
+        })
+        .execute(conn)?;
+
+    events::publish_after_commit(conn, user_id, event)
+}
+
+/// Notify both the buyer and the vendor of an order
+pub fn notify_parties(conn: &mut PgConnection, order: &Order, event: Event) -> Result<(), Error> {
+    notify(conn, order.buyer_id, event.clone())?;
+    notify(conn, order.vendor_id, event)
+}
+
+/// One page of a user's notifications, newest first
+pub fn list(
+    conn: &mut PgConnection,
+    user_id: i32,
+    unread_only: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Notification>, i64), Error> {
+    let mut total = notifications::table
+        .filter(notifications::user_id.eq(user_id))
+        .into_boxed();
+    let mut page = notifications::table
+        .filter(notifications::user_id.eq(user_id))
+        .into_boxed();
+    if unread_only {
+        total = total.filter(notifications::read_at.is_null());
+        page = page.filter(notifications::read_at.is_null());
+    }
+
+    let total = total.count().get_result::<i64>(conn)?;
+    let page = page
+        .order((notifications::created_at.desc(), notifications::id.desc()))
+        .limit(limit)
+        .offset(offset)
+        .load::<Notification>(conn)?;
+
+    Ok((page, total))
+}
+
+/// How many notifications the user has not read
+pub fn unread_count(conn: &mut PgConnection, user_id: i32) -> Result<i64, Error> {
+    Ok(notifications::table
+        .filter(notifications::user_id.eq(user_id))
+        .filter(notifications::read_at.is_null())
+        .count()
+        .get_result::<i64>(conn)?)
+}
+
+/// Mark some of the user's notifications read, or all of them when `ids` is `None`
+///
+/// # Returns
+/// * `Result<usize, Error>` - How many were unread until now
+pub fn mark_read(conn: &mut PgConnection, user_id: i32, ids: Option<&[i32]>) -> Result<usize, Error> {
+    let mut unread = diesel::update(notifications::table)
+        .filter(notifications::user_id.eq(user_id))
+        .filter(notifications::read_at.is_null())
+        .into_boxed();
+    if let Some(ids) = ids {
+        unread = unread.filter(notifications::id.eq_any(ids));
+    }
+
+    Ok(unread.set(notifications::read_at.eq(Utc::now())).execute(conn)?)
+}
+
+/// Mark one of the user's notifications read
+///
+/// # Returns
+/// * `Result<Notification, Error>` - The notification, or not found when it belongs to someone else
+pub fn mark_one_read(conn: &mut PgConnection, user_id: i32, id: i32) -> Result<Notification, Error> {
+    let notification = notifications::table
+        .filter(notifications::id.eq(id))
+        .filter(notifications::user_id.eq(user_id))
+        .first::<Notification>(conn)
+        .optional()?
+        .ok_or_else(Error::not_found)?;
+    if notification.read_at.is_some() {
+        return Ok(notification);
+    }
+
+    Ok(diesel::update(&notification)
+        .set(notifications::read_at.eq(Utc::now()))
+        .get_result::<Notification>(conn)?)
+}
+
+/// Every kind with whether the user gets it
+pub fn preferences(conn: &mut PgConnection, user_id: i32) -> Result<Vec<Preference>, Error> {
+    let disabled: Vec<String> = notification_preferences::table
+        .filter(notification_preferences::user_id.eq(user_id))
+        .filter(notification_preferences::enabled.eq(false))
+        .select(notification_preferences::kind)
+        .load::<String>(conn)?;
+
+    Ok(kinds::ALL
+        .iter()
+        .map(|kind| Preference {
+            kind: kind.to_string(),
+            enabled: !disabled.iter().any(|disabled| disabled == kind),
+        })
+        .collect())
+}
+
+/// Turn kinds of notification on or off for a user
+pub fn set_preferences(
+    conn: &mut PgConnection,
+    user_id: i32,
+    changes: &[Preference],
+) -> Result<Vec<Preference>, Error> {
+    if let Some(unknown) = changes.iter().find(|change| !kinds::ALL.contains(&change.kind.as_str())) {
+        return Err(Error::validation_error(format!(
+            "Invalid notification kind: {}",
+            unknown.kind
+        )));
+    }
+
+    conn.transaction::<_, Error, _>(|conn| {
+        let now = Utc::now();
+        for change in changes {
+            diesel::insert_into(notification_preferences::table)
+                .values((
+                    notification_preferences::user_id.eq(user_id),
+                    notification_preferences::kind.eq(&change.kind),
+                    notification_preferences::enabled.eq(change.enabled),
+                    notification_preferences::updated_at.eq(now),
+                ))
+                .on_conflict((notification_preferences::user_id, notification_preferences::kind))
+                .do_update()
+                .set((
+                    notification_preferences::enabled.eq(change.enabled),
+                    notification_preferences::updated_at.eq(now),
+                ))
+                .execute(conn)?;
+        }
+
+        preferences(conn, user_id)
+    })
+}
+
+/// Delete notifications past `notifications.retention_days`, and each user's oldest beyond
+/// `notifications.max_per_user`
+///
+/// # Returns
+/// * `Result<usize, Error>` - How many were deleted
+pub fn prune(conn: &mut PgConnection) -> Result<usize, Error> {
+    let settings = &SETTINGS.notifications;
+
+    let expired = diesel::delete(
+        notifications::table
+            .filter(notifications::created_at.lt(Utc::now() - Duration::days(settings.retention_days))),
+    )
+    .execute(conn)?;
+
+    let overflow = diesel::sql_query(
+        "DELETE FROM notifications WHERE id IN ( \
+             SELECT id FROM ( \
+                 SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY created_at DESC, id DESC) AS position \
+                 FROM notifications \
+             ) ranked WHERE position > $1 \
+         )",
+    )
+    .bind::<BigInt, _>(settings.max_per_user)
+    .execute(conn)?;
+
+    if expired + overflow > 0 {
+        info!(expired = expired, overflow = overflow, "Notifications pruned");
+    }
+    Ok(expired + overflow)
+}
@@ -3,7 +3,11 @@ use diesel::prelude::*;
 use diesel::sql_types::Text;
 use serde::{Deserialize, Serialize};
 
-use crate::schema::{order_items, order_status_history, orders};
+use crate::errors::Error;
+use crate::events::Event;
+use crate::jobs;
+use crate::models::{message, notification};
+use crate::schema::{order_items, order_quotes, order_status_history, orders};
 use bigdecimal::BigDecimal;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, DbEnum)]
@@ -43,6 +47,14 @@ pub struct Order {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
+    /// Shared by the orders created from one cart checkout
+    pub checkout_id: Option<String>,
+    pub shipping_option_id: Option<i32>,
+    /// Shipping cost in the order's currency, included in `total_amount`
+    pub shipping_amount: BigDecimal,
+    /// The fiat shipping price `shipping_amount` was converted from, if any
+    pub shipping_fiat: Option<BigDecimal>,
+    pub shipping_fiat_currency: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -55,6 +67,11 @@ pub struct NewOrder {
     pub total_amount: BigDecimal,
     pub escrow_address: Option<String>,
     pub encrypted_shipping_address: String,
+    pub checkout_id: Option<String>,
+    pub shipping_option_id: Option<i32>,
+    pub shipping_amount: BigDecimal,
+    pub shipping_fiat: Option<BigDecimal>,
+    pub shipping_fiat_currency: Option<String>,
 }
 
 #[derive(Debug, AsChangeset)]
@@ -77,6 +94,8 @@ pub struct OrderItem {
     pub quantity: i32,
     pub price_per_unit: BigDecimal,
     pub created_at: DateTime<Utc>,
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -87,6 +106,8 @@ pub struct NewOrderItem {
     pub variant_id: Option<i32>,
     pub quantity: i32,
     pub price_per_unit: BigDecimal,
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
@@ -98,6 +119,10 @@ pub struct OrderStatusHistory {
     pub status: OrderStatus,
     pub notes: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// One of `history_actors`
+    pub actor: String,
+    /// The user who made the change, if it was not automatic
+    pub changed_by: Option<i32>,
 }
 
 #[derive(Debug, Insertable)]
@@ -106,6 +131,130 @@ pub struct NewOrderStatusHistory {
     pub order_id: i32,
     pub status: OrderStatus,
     pub notes: Option<String>,
+    pub actor: String,
+    pub changed_by: Option<i32>,
+}
+
+impl NewOrderStatusHistory {
+    /// A status change made by a user
+    pub fn by_user(order_id: i32, status: OrderStatus, user_id: i32, notes: Option<String>) -> Self {
+        Self {
+            order_id,
+            status,
+            notes,
+            actor: history_actors::USER.to_string(),
+            changed_by: Some(user_id),
+        }
+    }
+
+    /// A status change made by the order timer scheduler
+    pub fn by_system(order_id: i32, status: OrderStatus, notes: String) -> Self {
+        Self {
+            order_id,
+            status,
+            notes: Some(notes),
+            actor: history_actors::SYSTEM.to_string(),
+            changed_by: None,
+        }
+    }
+}
+
+/// Move an order to a new status and record the change in its history
+///
+/// Statuses that feed the vendor's reputation (shipped, disputed, completed, cancelled) also
+/// queue a reputation update, and closing the order locks its conversation. Buyer and vendor
+/// are notified, and notified again when the order is paid.
+///
+/// # Arguments
+/// * `conn` - A database connection, normally with an open transaction
+/// * `order` - The order, locked by the caller
+/// * `entry` - The history entry; its status is the one the order moves to
+///
+/// # Returns
+/// * `Result<Order, Error>` - The updated order or an error
+pub fn set_status(conn: &mut PgConnection, order: &Order, entry: NewOrderStatusHistory) -> Result<Order, Error> {
+    let now = Utc::now();
+    let completed_at = (entry.status == OrderStatus::Completed).then_some(now);
+
+    let order = diesel::update(order)
+        .set((
+            orders::status.eq(entry.status),
+            orders::updated_at.eq(now),
+            orders::completed_at.eq(completed_at.or(order.completed_at)),
+        ))
+        .get_result::<Order>(conn)?;
+
+    diesel::insert_into(order_status_history::table)
+        .values(&entry)
+        .execute(conn)?;
+
+    if matches!(
+        entry.status,
+        OrderStatus::Paid
+            | OrderStatus::Shipped
+            | OrderStatus::Disputed
+            | OrderStatus::Completed
+            | OrderStatus::Cancelled
+    ) {
+        jobs::reputation::schedule(conn, order.vendor_id)?;
+    }
+
+    if matches!(entry.status, OrderStatus::Completed | OrderStatus::Cancelled) {
+        message::lock_for_order(conn, order.id)?;
+    }
+
+    notification::notify_parties(
+        conn,
+        &order,
+        Event::OrderStatusChanged {
+            order_id: order.id,
+            status: order.status,
+        },
+    )?;
+    if order.status == OrderStatus::Paid {
+        notification::notify_parties(conn, &order, Event::PaymentConfirmed { order_id: order.id })?;
+    }
+
+    Ok(order)
+}
+
+/// Who made an order status change
+pub mod history_actors {
+    /// A buyer, vendor, moderator or admin
+    pub const USER: &str = "user";
+    /// The order timer scheduler
+    pub const SYSTEM: &str = "system";
+}
+
+/// The exchange rate an order's fiat-priced items were converted at
+///
+/// A quote is locked until `expires_at`; after that an unpaid order has to be re-quoted.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = order_quotes)]
+#[diesel(belongs_to(Order))]
+pub struct OrderQuote {
+    pub id: i32,
+    pub order_id: i32,
+    pub fiat_currency: String,
+    pub currency: crate::models::payment::PaymentCurrency,
+    pub rate: BigDecimal,
+    pub source: String,
+    pub quoted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = order_quotes)]
+pub struct NewOrderQuote {
+    pub order_id: i32,
+    pub fiat_currency: String,
+    pub currency: crate::models::payment::PaymentCurrency,
+    pub rate: BigDecimal,
+    pub source: String,
+    pub quoted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
 }
 
 // For API responses
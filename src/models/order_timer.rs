@@ -0,0 +1,332 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::constants::order_timers::BATCH_SIZE;
+use crate::errors::Error;
+use crate::models::dispute;
+use crate::models::escrow::{self, Settlement};
+use crate::models::inventory;
+use crate::models::job;
+use crate::models::order::{self, NewOrderStatusHistory, Order, OrderStatus};
+use crate::schema::{disputes, order_timers, orders};
+use crate::settings::SETTINGS;
+
+/// A deadline after which the scheduler acts on an order
+///
+/// An order has at most one timer of each kind. A timer is pending until it either fires
+/// (`fired_at`) or is cancelled because the order moved on in time (`cancelled_at`).
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = order_timers)]
+#[diesel(belongs_to(Order))]
+pub struct OrderTimer {
+    pub id: i32,
+    pub order_id: i32,
+    pub kind: String,
+    pub due_at: DateTime<Utc>,
+    /// Whether the buyer already used their one extension
+    pub extended: bool,
+    pub fired_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = order_timers)]
+pub struct NewOrderTimer {
+    pub order_id: i32,
+    pub kind: String,
+    pub due_at: DateTime<Utc>,
+}
+
+/// Valid timer kinds
+pub mod kinds {
+    /// Cancel a pending order that was not paid in time
+    pub const PAYMENT: &str = "payment";
+    /// Cancel and refund a paid order the vendor never accepted
+    pub const ACCEPTANCE: &str = "acceptance";
+    /// Complete a shipped order and release its escrow to the vendor
+    pub const FINALIZE: &str = "finalize";
+}
+
+/// Start a timer for an order, or restart it if the order already had one of this kind
+pub fn schedule(
+    conn: &mut PgConnection,
+    order_id: i32,
+    kind: &str,
+    due_at: DateTime<Utc>,
+) -> Result<OrderTimer, Error> {
+    let timer = diesel::insert_into(order_timers::table)
+        .values(&NewOrderTimer {
+            order_id,
+            kind: kind.to_string(),
+            due_at,
+        })
+        .on_conflict((order_timers::order_id, order_timers::kind))
+        .do_update()
+        .set((
+            order_timers::due_at.eq(due_at),
+            order_timers::extended.eq(false),
+            order_timers::fired_at.eq(None::<DateTime<Utc>>),
+            order_timers::cancelled_at.eq(None::<DateTime<Utc>>),
+            order_timers::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<OrderTimer>(conn)?;
+
+    debug!(order_id = order_id, kind = kind, due_at = %due_at, "Order timer scheduled");
+    Ok(timer)
+}
+
+/// Stop an order's pending timer of the given kind, if it has one
+pub fn cancel(conn: &mut PgConnection, order_id: i32, kind: &str) -> Result<usize, Error> {
+    let now = Utc::now();
+
+    let cancelled = diesel::update(
+        order_timers::table
+            .filter(order_timers::order_id.eq(order_id))
+            .filter(order_timers::kind.eq(kind))
+            .filter(order_timers::fired_at.is_null())
+            .filter(order_timers::cancelled_at.is_null()),
+    )
+    .set((
+        order_timers::cancelled_at.eq(now),
+        order_timers::updated_at.eq(now),
+    ))
+    .execute(conn)?;
+
+    Ok(cancelled)
+}
+
+/// Schedule the timers an order needs now that it moved to `status`
+///
+/// Called in the same transaction as every user-made status change. Timers for the status
+/// the order left are cancelled; the scheduler would skip them anyway, but cancelling keeps
+/// the table honest about what is still pending.
+pub fn on_status_change(conn: &mut PgConnection, order_id: i32, status: OrderStatus) -> Result<(), Error> {
+    let now = Utc::now();
+
+    match status {
+        OrderStatus::Paid => {
+            cancel(conn, order_id, kinds::PAYMENT)?;
+            schedule(
+                conn,
+                order_id,
+                kinds::ACCEPTANCE,
+                now + Duration::hours(SETTINGS.order_timers.acceptance_timeout_hours),
+            )?;
+        }
+        OrderStatus::Processing => {
+            cancel(conn, order_id, kinds::ACCEPTANCE)?;
+        }
+        OrderStatus::Shipped => {
+            schedule(
+                conn,
+                order_id,
+                kinds::FINALIZE,
+                now + Duration::days(SETTINGS.order_timers.finalize_after_days),
+            )?;
+        }
+        OrderStatus::Cancelled | OrderStatus::Completed | OrderStatus::Disputed => {
+            for kind in [kinds::PAYMENT, kinds::ACCEPTANCE, kinds::FINALIZE] {
+                cancel(conn, order_id, kind)?;
+            }
+        }
+        OrderStatus::Pending | OrderStatus::Delivered => {}
+    }
+
+    Ok(())
+}
+
+/// Push back the auto-finalize deadline of a shipped order
+///
+/// A buyer who has not received their goods yet can do this once per order, adding
+/// `order_timers.extension_days` to the deadline.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `order_id` - The order
+/// * `buyer_id` - The buyer asking for more time
+///
+/// # Returns
+/// * `Result<OrderTimer, Error>` - The extended timer or an error
+pub fn extend_finalize(conn: &mut PgConnection, order_id: i32, buyer_id: i32) -> Result<OrderTimer, Error> {
+    conn.transaction::<OrderTimer, Error, _>(|conn| {
+        let order = orders::table.find(order_id).for_update().first::<Order>(conn)?;
+
+        if order.buyer_id != buyer_id {
+            return Err(Error::not_found());
+        }
+
+        let timer = order_timers::table
+            .filter(order_timers::order_id.eq(order_id))
+            .filter(order_timers::kind.eq(kinds::FINALIZE))
+            .filter(order_timers::fired_at.is_null())
+            .filter(order_timers::cancelled_at.is_null())
+            .for_update()
+            .first::<OrderTimer>(conn)
+            .optional()?
+            .ok_or_else(|| Error::validation_error("This order is not waiting to be finalized"))?;
+
+        if timer.extended {
+            return Err(Error::validation_error("The finalize window can only be extended once"));
+        }
+
+        let due_at = timer.due_at + Duration::days(SETTINGS.order_timers.extension_days);
+        let timer = diesel::update(&timer)
+            .set((
+                order_timers::due_at.eq(due_at),
+                order_timers::extended.eq(true),
+                order_timers::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<OrderTimer>(conn)?;
+
+        info!(order_id = order_id, due_at = %due_at, "Finalize window extended");
+        Ok(timer)
+    })
+}
+
+/// Prefix of the unique key of the job that fires a timer, followed by the timer id
+pub const JOB_KEY_PREFIX: &str = "order_timer:";
+
+/// Pending timers that are due and have no unfinished job, the most overdue first
+///
+/// A timer whose job is queued or running is already being handled. One whose job was
+/// dead-lettered stays out of the scheduler until an admin retries the job, or discards it
+/// to hand the timer back.
+pub fn due(conn: &mut PgConnection) -> Result<Vec<i32>, Error> {
+    let has_unfinished_job = format!(
+        "EXISTS (SELECT 1 FROM jobs j WHERE j.unique_key = '{}' || order_timers.id \
+         AND j.status IN ('{}', '{}', '{}'))",
+        JOB_KEY_PREFIX,
+        job::statuses::QUEUED,
+        job::statuses::RUNNING,
+        job::statuses::DEAD
+    );
+
+    let ids = order_timers::table
+        .filter(order_timers::due_at.le(Utc::now()))
+        .filter(order_timers::fired_at.is_null())
+        .filter(order_timers::cancelled_at.is_null())
+        .filter(diesel::dsl::not(diesel::dsl::sql::<Bool>(&has_unfinished_job)))
+        .order(order_timers::due_at.asc())
+        .select(order_timers::id)
+        .limit(BATCH_SIZE)
+        .load::<i32>(conn)?;
+
+    Ok(ids)
+}
+
+/// Claim a due timer and apply it to its order
+///
+/// The timer is locked with `SKIP LOCKED` and handled in one transaction, so running it
+/// twice at once is harmless. A timer whose order already moved on is cancelled instead of
+/// fired.
+///
+/// # Returns
+/// * `Result<bool, Error>` - Whether the order was changed, or an error
+pub fn run(conn: &mut PgConnection, timer_id: i32) -> Result<bool, Error> {
+    conn.transaction::<bool, Error, _>(|conn| {
+        // Another worker may have taken or finished it since it was found due
+        let timer = order_timers::table
+            .find(timer_id)
+            .filter(order_timers::fired_at.is_null())
+            .filter(order_timers::cancelled_at.is_null())
+            .for_update()
+            .skip_locked()
+            .first::<OrderTimer>(conn)
+            .optional()?;
+
+        let Some(timer) = timer else {
+            return Ok(false);
+        };
+
+        let order = orders::table.find(timer.order_id).for_update().first::<Order>(conn)?;
+        let fired = fire(conn, &timer, &order)?;
+
+        let now = Utc::now();
+        let (fired_at, cancelled_at) = if fired { (Some(now), None) } else { (None, Some(now)) };
+        diesel::update(&timer)
+            .set((
+                order_timers::fired_at.eq(fired_at),
+                order_timers::cancelled_at.eq(cancelled_at),
+                order_timers::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        Ok(fired)
+    })
+}
+
+/// Apply a due timer to its order, returning false if the order no longer needs it
+fn fire(conn: &mut PgConnection, timer: &OrderTimer, order: &Order) -> Result<bool, Error> {
+    match (timer.kind.as_str(), order.status) {
+        (kinds::PAYMENT, OrderStatus::Pending) => {
+            inventory::release_reservations(conn, order.id)?;
+            order::set_status(
+                conn,
+                order,
+                NewOrderStatusHistory::by_system(
+                    order.id,
+                    OrderStatus::Cancelled,
+                    "Cancelled automatically: payment not received in time".to_string(),
+                ),
+            )?;
+
+            info!(order_id = order.id, "Unpaid order expired, stock released");
+        }
+        (kinds::ACCEPTANCE, OrderStatus::Paid) => {
+            escrow::settle(conn, order, &Settlement::refund(order))?;
+            inventory::restock_committed(conn, order.id)?;
+            order::set_status(
+                conn,
+                order,
+                NewOrderStatusHistory::by_system(
+                    order.id,
+                    OrderStatus::Cancelled,
+                    "Cancelled automatically: the vendor did not accept the order in time, payment refunded"
+                        .to_string(),
+                ),
+            )?;
+
+            info!(order_id = order.id, vendor_id = order.vendor_id, "Unaccepted order refunded");
+        }
+        (kinds::FINALIZE, OrderStatus::Shipped | OrderStatus::Delivered) => {
+            if has_open_dispute(conn, order.id)? {
+                return Ok(false);
+            }
+
+            escrow::settle(conn, order, &Settlement::release(order))?;
+            order::set_status(
+                conn,
+                order,
+                NewOrderStatusHistory::by_system(
+                    order.id,
+                    OrderStatus::Completed,
+                    "Finalized automatically: the finalize window ended, escrow released".to_string(),
+                ),
+            )?;
+
+            info!(order_id = order.id, vendor_id = order.vendor_id, "Order finalized automatically");
+        }
+        (kind, status) => {
+            debug!(order_id = order.id, kind = kind, status = ?status, "Stale order timer skipped");
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn has_open_dispute(conn: &mut PgConnection, order_id: i32) -> Result<bool, Error> {
+    let open = diesel::select(diesel::dsl::exists(
+        disputes::table
+            .filter(disputes::order_id.eq(order_id))
+            .filter(disputes::status.ne(dispute::statuses::RESOLVED)),
+    ))
+    .get_result::<bool>(conn)?;
+
+    Ok(open)
+}
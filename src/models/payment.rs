@@ -3,10 +3,11 @@ use diesel::prelude::*;
 use diesel::sql_types::Text;
 use serde::{Deserialize, Serialize};
 
+use crate::errors::Error;
 use crate::schema::{transactions, wallets};
 use bigdecimal::BigDecimal;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, DbEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, DbEnum)]
 #[ExistingTypePath = "crate::models::payment::PaymentCurrencyMapping"]
 pub enum PaymentCurrency {
     BTC,
@@ -35,6 +36,7 @@ pub enum TransactionType {
     Withdrawal,
     EscrowLock,
     EscrowRelease,
+    EscrowRefund,
     Fee,
 }
 
@@ -70,6 +72,47 @@ pub struct NewWallet {
     pub balance: BigDecimal,
 }
 
+/// The kind of wallet that holds a currency
+pub fn wallet_type(currency: PaymentCurrency) -> WalletType {
+    match currency {
+        PaymentCurrency::BTC => WalletType::BTC,
+        PaymentCurrency::XMR => WalletType::XMR,
+    }
+}
+
+/// Lock a user's wallet of a kind for the rest of the transaction, if they have one
+pub fn lock_wallet(conn: &mut PgConnection, user_id: i32, wallet_type: WalletType) -> Result<Option<Wallet>, Error> {
+    let wallet = wallets::table
+        .filter(wallets::user_id.eq(user_id))
+        .filter(wallets::wallet_type.eq(wallet_type))
+        .for_update()
+        .first::<Wallet>(conn)
+        .optional()?;
+
+    Ok(wallet)
+}
+
+/// Lock a user's wallet of a kind, opening an empty one first if they have none
+///
+/// A wallet opened here only holds a ledger balance, so it has no key or address yet.
+pub fn lock_or_open_wallet(conn: &mut PgConnection, user_id: i32, wallet_type: WalletType) -> Result<Wallet, Error> {
+    diesel::insert_into(wallets::table)
+        .values(&NewWallet {
+            user_id,
+            wallet_type,
+            encrypted_private_key: String::new(),
+            public_address: String::new(),
+            balance: BigDecimal::from(0),
+        })
+        .on_conflict((wallets::user_id, wallets::wallet_type))
+        .do_nothing()
+        .execute(conn)?;
+
+    lock_wallet(conn, user_id, wallet_type)?.ok_or_else(|| {
+        Error::internal_error(format!("Wallet of user {} vanished after opening it", user_id), None, None)
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
 #[diesel(table_name = transactions)]
 #[diesel(belongs_to(Wallet))]
@@ -97,6 +140,7 @@ pub struct NewTransaction {
     pub tx_hash: Option<String>,
     pub order_id: Option<i32>,
     pub status: String,
+    pub completed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, AsChangeset)]
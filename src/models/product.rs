@@ -4,6 +4,25 @@ use serde::{Deserialize, Serialize};
 
 use crate::schema::{categories, product_images, product_variants, products};
 
+/// Where a listing is in moderation
+///
+/// Only `Active` listings are shown to buyers and can be ordered, and then only while
+/// `is_active` (the vendor's own switch, also used by the stock rules) is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, DbEnum)]
+#[ExistingTypePath = "crate::models::product::ListingStateMapping"]
+#[serde(rename_all = "snake_case")]
+pub enum ListingState {
+    Draft,
+    PendingReview,
+    Active,
+    Rejected,
+    Suspended,
+}
+
+#[derive(Debug, Clone, Copy, QueryId, SqlType)]
+#[diesel(postgres_type(name = "listing_state"))]
+pub struct ListingStateMapping;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
 #[diesel(table_name = categories)]
 pub struct Category {
@@ -38,6 +57,17 @@ pub struct Product {
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub low_stock_threshold: Option<i32>,
+    pub deactivate_when_out_of_stock: bool,
+    pub stock_deactivated: bool,
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
+    pub sku: Option<String>,
+    pub listing_state: ListingState,
+    pub moderation_reason: Option<String>,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Insertable)]
@@ -51,6 +81,11 @@ pub struct NewProduct {
     pub price_xmr: Option<BigDecimal>,
     pub stock: i32,
     pub is_active: bool,
+    pub low_stock_threshold: Option<i32>,
+    pub deactivate_when_out_of_stock: bool,
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
+    pub sku: Option<String>,
 }
 
 #[derive(Debug, AsChangeset)]
@@ -63,6 +98,13 @@ pub struct UpdateProduct {
     pub price_xmr: Option<BigDecimal>,
     pub stock: Option<i32>,
     pub is_active: Option<bool>,
+    /// Cleared whenever `is_active` is set by hand, so the stock rules leave it alone
+    pub stock_deactivated: Option<bool>,
+    pub low_stock_threshold: Option<Option<i32>>,
+    pub deactivate_when_out_of_stock: Option<bool>,
+    pub price_fiat: Option<Option<BigDecimal>>,
+    pub fiat_currency: Option<Option<String>>,
+    pub sku: Option<Option<String>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
 
@@ -79,6 +121,7 @@ pub struct ProductVariant {
     pub stock: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub price_fiat: Option<BigDecimal>,
 }
 
 #[derive(Debug, Insertable)]
@@ -90,6 +133,7 @@ pub struct NewProductVariant {
     pub price_btc: Option<BigDecimal>,
     pub price_xmr: Option<BigDecimal>,
     pub stock: i32,
+    pub price_fiat: Option<BigDecimal>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
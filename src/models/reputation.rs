@@ -0,0 +1,250 @@
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl::min;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::debug;
+
+use crate::constants::reputation::*;
+use crate::errors::Error;
+use crate::models::order::OrderStatus;
+use crate::models::payment::PaymentCurrency;
+use crate::schema::{disputes, order_status_history, orders, reviews, users};
+
+/// How a vendor's reputation was reached
+///
+/// The score runs from 0 to 100 and is the weighted sum of four components, each scored
+/// from 0 to 1.
+#[derive(Debug, Clone, Serialize)]
+pub struct Breakdown {
+    pub vendor_id: i32,
+    pub score: f64,
+    pub components: Vec<Component>,
+    /// Plain average of the reviews that count, for display next to the score
+    pub average_rating: Option<f64>,
+    pub review_count: i64,
+    /// Completed orders
+    pub total_sales: i64,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// One part of a reputation score
+#[derive(Debug, Clone, Serialize)]
+pub struct Component {
+    pub name: &'static str,
+    /// From 0 to 1
+    pub score: f64,
+    pub weight: f64,
+    /// Points added to the 0-100 score
+    pub points: f64,
+    /// What went into the component, in words
+    pub detail: String,
+}
+
+impl Component {
+    fn new(name: &'static str, score: f64, weight: f64, detail: String) -> Self {
+        let score = score.clamp(0.0, 1.0);
+        Self {
+            name,
+            score,
+            weight,
+            points: score * weight * 100.0,
+            detail,
+        }
+    }
+}
+
+/// Work out a vendor's reputation from their reviews and order history
+///
+/// * Rating: the average review, each weighted by its age (halving every
+///   `REVIEW_HALF_LIFE_DAYS`) and by its order's value against the vendor's median order in
+///   the same currency. A neutral prior keeps a single review from deciding the score.
+/// * Disputes: disputes per paid order, scoring zero at `MAX_DISPUTE_RATE`.
+/// * Shipping: the share of shipped orders shipped within `SHIPPING_DEADLINE_HOURS` of payment.
+/// * Account age: grows linearly to full at `FULL_AGE_DAYS`.
+///
+/// Vendors with no orders yet get full marks for disputes and shipping; the rating prior and
+/// account age keep new vendors below established ones.
+pub fn compute(conn: &mut PgConnection, vendor_id: i32) -> Result<Breakdown, Error> {
+    let now = Utc::now();
+
+    let created_at = users::table
+        .find(vendor_id)
+        .select(users::created_at)
+        .first::<DateTime<Utc>>(conn)?;
+
+    // Reviews removed by a moderator have no weight left and do not count at all
+    let reviewed = reviews::table
+        .inner_join(orders::table)
+        .filter(reviews::vendor_id.eq(vendor_id))
+        .filter(reviews::weight.gt(0.0))
+        .select((
+            reviews::rating,
+            reviews::created_at,
+            reviews::weight,
+            orders::total_amount,
+            orders::currency,
+        ))
+        .load::<ReviewedOrder>(conn)?;
+
+    // First time each order reached Paid and Shipped
+    let milestones = order_status_history::table
+        .inner_join(orders::table)
+        .filter(orders::vendor_id.eq(vendor_id))
+        .filter(order_status_history::status.eq_any([OrderStatus::Paid, OrderStatus::Shipped]))
+        .group_by((order_status_history::order_id, order_status_history::status))
+        .select((
+            order_status_history::order_id,
+            order_status_history::status,
+            min(order_status_history::created_at),
+        ))
+        .load::<(i32, OrderStatus, Option<DateTime<Utc>>)>(conn)?;
+
+    let dispute_count = disputes::table
+        .inner_join(orders::table)
+        .filter(orders::vendor_id.eq(vendor_id))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let total_sales = orders::table
+        .filter(orders::vendor_id.eq(vendor_id))
+        .filter(orders::status.eq(OrderStatus::Completed))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let rating = rating_component(&reviewed, now);
+
+    let mut paid_at = HashMap::new();
+    let mut shipped_at = HashMap::new();
+    for (order_id, status, at) in milestones {
+        let Some(at) = at else { continue };
+        match status {
+            OrderStatus::Paid => paid_at.insert(order_id, at),
+            _ => shipped_at.insert(order_id, at),
+        };
+    }
+
+    let paid_orders = paid_at.len() as i64;
+    let dispute_rate = if paid_orders == 0 {
+        0.0
+    } else {
+        dispute_count as f64 / paid_orders as f64
+    };
+    let disputes = Component::new(
+        "disputes",
+        1.0 - dispute_rate / MAX_DISPUTE_RATE,
+        DISPUTE_WEIGHT,
+        format!("{} disputes on {} paid orders ({:.1}%)", dispute_count, paid_orders, dispute_rate * 100.0),
+    );
+
+    let deadline = Duration::hours(SHIPPING_DEADLINE_HOURS);
+    let timed: Vec<bool> = shipped_at
+        .iter()
+        .filter_map(|(order_id, shipped)| paid_at.get(order_id).map(|paid| *shipped - *paid <= deadline))
+        .collect();
+    let on_time = timed.iter().filter(|on_time| **on_time).count();
+    let shipping = Component::new(
+        "shipping",
+        if timed.is_empty() { 1.0 } else { on_time as f64 / timed.len() as f64 },
+        SHIPPING_WEIGHT,
+        format!(
+            "{} of {} orders shipped within {} hours of payment",
+            on_time,
+            timed.len(),
+            SHIPPING_DEADLINE_HOURS
+        ),
+    );
+
+    let age_days = (now - created_at).num_days().max(0);
+    let age = Component::new(
+        "account_age",
+        age_days as f64 / FULL_AGE_DAYS,
+        AGE_WEIGHT,
+        format!("Account is {} days old", age_days),
+    );
+
+    let average_rating = if reviewed.is_empty() {
+        None
+    } else {
+        Some(reviewed.iter().map(|(rating, ..)| *rating as f64).sum::<f64>() / reviewed.len() as f64)
+    };
+
+    let components = vec![rating, disputes, shipping, age];
+    let score = components.iter().map(|component| component.points).sum::<f64>();
+
+    Ok(Breakdown {
+        vendor_id,
+        score: (score * 100.0).round() / 100.0,
+        components,
+        average_rating,
+        review_count: reviewed.len() as i64,
+        total_sales,
+        computed_at: now,
+    })
+}
+
+/// Recompute a vendor's reputation and store it in `users.reputation`
+pub fn recompute(conn: &mut PgConnection, vendor_id: i32) -> Result<Breakdown, Error> {
+    let breakdown = compute(conn, vendor_id)?;
+
+    diesel::update(users::table.find(vendor_id))
+        .set(users::reputation.eq(breakdown.score))
+        .execute(conn)?;
+
+    debug!(vendor_id = vendor_id, score = breakdown.score, "Reputation recomputed");
+    Ok(breakdown)
+}
+
+/// A review's rating, date and moderation weight with its order's total and currency
+type ReviewedOrder = (i32, DateTime<Utc>, f64, BigDecimal, PaymentCurrency);
+
+fn rating_component(reviewed: &[ReviewedOrder], now: DateTime<Utc>) -> Component {
+    let medians = median_totals(reviewed);
+
+    let mut weighted_sum = PRIOR_RATING * PRIOR_REVIEW_WEIGHT;
+    let mut total_weight = PRIOR_REVIEW_WEIGHT;
+    for (rating, created_at, weight, total, currency) in reviewed {
+        let age_days = (now - *created_at).num_hours().max(0) as f64 / 24.0;
+        let recency = 0.5_f64.powf(age_days / REVIEW_HALF_LIFE_DAYS);
+
+        let value = match (total.to_f64(), medians.get(currency)) {
+            (Some(total), Some(median)) if *median > 0.0 => {
+                (total / median).sqrt().clamp(1.0 / MAX_VALUE_WEIGHT, MAX_VALUE_WEIGHT)
+            }
+            _ => 1.0,
+        };
+
+        weighted_sum += *rating as f64 * recency * value * weight;
+        total_weight += recency * value * weight;
+    }
+
+    let weighted_rating = weighted_sum / total_weight;
+    Component::new(
+        "rating",
+        (weighted_rating - 1.0) / 4.0,
+        RATING_WEIGHT,
+        format!(
+            "Weighted rating {:.2} of 5 from {} reviews, weighted by age, order value and moderation",
+            weighted_rating,
+            reviewed.len()
+        ),
+    )
+}
+
+fn median_totals(reviewed: &[ReviewedOrder]) -> HashMap<PaymentCurrency, f64> {
+    let mut totals: HashMap<PaymentCurrency, Vec<f64>> = HashMap::new();
+    for (_, _, _, total, currency) in reviewed {
+        if let Some(total) = total.to_f64() {
+            totals.entry(*currency).or_default().push(total);
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(currency, mut values)| {
+            values.sort_by(|a, b| a.total_cmp(b));
+            (currency, values[values.len() / 2])
+        })
+        .collect()
+}
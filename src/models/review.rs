@@ -0,0 +1,383 @@
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::constants::reviews::*;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::jobs;
+use crate::models::notification;
+use crate::models::order::{Order, OrderStatus};
+use crate::models::vendor::{NewReview, Review};
+use crate::schema::{order_items, orders, reviews, users};
+
+/// Valid review flag statuses
+pub mod flag_statuses {
+    /// Tripped a manipulation check; waiting for a moderator
+    pub const OPEN: &str = "open";
+    /// A moderator agreed; the review no longer counts
+    pub const UPHELD: &str = "upheld";
+    /// A moderator found nothing wrong; the review counts in full
+    pub const DISMISSED: &str = "dismissed";
+}
+
+/// What a buyer writes about an order
+#[derive(Debug)]
+pub struct ReviewInput {
+    pub rating: i32,
+    pub comment: Option<String>,
+}
+
+impl ReviewInput {
+    fn validate(self) -> Result<Self, Error> {
+        if !(1..=5).contains(&self.rating) {
+            return Err(Error::validation_error("The rating must be between 1 and 5"));
+        }
+
+        let comment = self.comment.map(|comment| comment.trim().to_string()).filter(|c| !c.is_empty());
+        if comment.as_ref().is_some_and(|comment| comment.chars().count() > MAX_TEXT_LENGTH) {
+            return Err(Error::validation_error(format!(
+                "Comments are limited to {} characters",
+                MAX_TEXT_LENGTH
+            )));
+        }
+
+        Ok(Self {
+            rating: self.rating,
+            comment,
+        })
+    }
+}
+
+/// Review a completed order
+///
+/// Only the buyer can review, once per order, within `REVIEW_WINDOW_DAYS` of completion.
+/// Reviews that trip a manipulation check are saved with a reduced weight and flagged for
+/// moderators; the buyer is not told.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `buyer_id` - The reviewer
+/// * `order_id` - The completed order
+/// * `product_id` - The product reviewed; defaults to the order's first item
+/// * `input` - The rating and comment
+///
+/// # Returns
+/// * `Result<Review, Error>` - The new review or an error
+pub fn create(
+    conn: &mut PgConnection,
+    buyer_id: i32,
+    order_id: i32,
+    product_id: Option<i32>,
+    input: ReviewInput,
+) -> Result<Review, Error> {
+    let input = input.validate()?;
+
+    conn.transaction::<Review, Error, _>(|conn| {
+        let order = orders::table
+            .find(order_id)
+            .filter(orders::buyer_id.eq(buyer_id))
+            .first::<Order>(conn)
+            .optional()?
+            .ok_or_else(Error::not_found)?;
+
+        check_window(&order)?;
+
+        let items = order_items::table
+            .filter(order_items::order_id.eq(order.id))
+            .order(order_items::id.asc())
+            .select(order_items::product_id)
+            .load::<i32>(conn)?;
+        let product_id = match product_id {
+            Some(product_id) if items.contains(&product_id) => product_id,
+            Some(_) => return Err(Error::validation_error("That product is not part of this order")),
+            None => *items
+                .first()
+                .ok_or_else(|| Error::validation_error("This order has no items to review"))?,
+        };
+
+        let suspicion = suspicion(conn, buyer_id, &order)?;
+        let (weight, flag_status) = match &suspicion {
+            Some(_) => (SUSPICIOUS_WEIGHT, Some(flag_statuses::OPEN.to_string())),
+            None => (1.0, None),
+        };
+
+        let review = diesel::insert_into(reviews::table)
+            .values(&NewReview {
+                order_id: order.id,
+                reviewer_id: buyer_id,
+                vendor_id: order.vendor_id,
+                product_id,
+                rating: input.rating,
+                comment: input.comment,
+                weight,
+                flag_status,
+                flag_reason: suspicion.clone(),
+            })
+            .on_conflict_do_nothing()
+            .get_result::<Review>(conn)
+            .optional()?
+            .ok_or_else(|| Error::validation_error("You have already reviewed this order"))?;
+
+        if let Some(reason) = &suspicion {
+            warn!(review_id = review.id, reviewer_id = buyer_id, reason = %reason, "Review flagged");
+        }
+
+        jobs::reputation::schedule(conn, order.vendor_id)?;
+        notification::notify(
+            conn,
+            order.vendor_id,
+            Event::ReviewReceived {
+                review_id: review.id,
+                order_id: order.id,
+                rating: review.rating,
+            },
+        )?;
+
+        info!(review_id = review.id, order_id = order.id, vendor_id = order.vendor_id, "Review posted");
+        Ok(review)
+    })
+}
+
+/// Change a review; each review can be edited once, within the review window
+pub fn edit(conn: &mut PgConnection, buyer_id: i32, review_id: i32, input: ReviewInput) -> Result<Review, Error> {
+    let input = input.validate()?;
+
+    conn.transaction::<Review, Error, _>(|conn| {
+        let review = reviews::table
+            .find(review_id)
+            .filter(reviews::reviewer_id.eq(buyer_id))
+            .for_update()
+            .first::<Review>(conn)
+            .optional()?
+            .ok_or_else(Error::not_found)?;
+
+        if review.edited_at.is_some() {
+            return Err(Error::validation_error("A review can only be edited once"));
+        }
+
+        let order = orders::table.find(review.order_id).first::<Order>(conn)?;
+        check_window(&order)?;
+
+        let now = Utc::now();
+        let review = diesel::update(&review)
+            .set((
+                reviews::rating.eq(input.rating),
+                reviews::comment.eq(input.comment),
+                reviews::edited_at.eq(now),
+                reviews::updated_at.eq(now),
+            ))
+            .get_result::<Review>(conn)?;
+
+        jobs::reputation::schedule(conn, review.vendor_id)?;
+
+        Ok(review)
+    })
+}
+
+/// Post the vendor's public answer to a review; one per review, and final
+pub fn reply(conn: &mut PgConnection, vendor_id: i32, review_id: i32, text: &str) -> Result<Review, Error> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(Error::validation_error("The reply cannot be empty"));
+    }
+    if text.chars().count() > MAX_TEXT_LENGTH {
+        return Err(Error::validation_error(format!(
+            "Replies are limited to {} characters",
+            MAX_TEXT_LENGTH
+        )));
+    }
+
+    let now = Utc::now();
+    let review = diesel::update(
+        reviews::table
+            .find(review_id)
+            .filter(reviews::vendor_id.eq(vendor_id))
+            .filter(reviews::vendor_reply.is_null()),
+    )
+    .set((
+        reviews::vendor_reply.eq(text),
+        reviews::vendor_replied_at.eq(now),
+        reviews::updated_at.eq(now),
+    ))
+    .get_result::<Review>(conn)
+    .optional()?;
+
+    match review {
+        Some(review) => Ok(review),
+        None => {
+            let exists = diesel::select(diesel::dsl::exists(
+                reviews::table.find(review_id).filter(reviews::vendor_id.eq(vendor_id)),
+            ))
+            .get_result::<bool>(conn)?;
+
+            if exists {
+                Err(Error::validation_error("You have already replied to this review"))
+            } else {
+                Err(Error::not_found())
+            }
+        }
+    }
+}
+
+/// Settle a flagged review
+///
+/// Upholding the flag drops the review out of the vendor's reputation and off public
+/// listings; dismissing it restores its full weight.
+pub fn moderate(conn: &mut PgConnection, moderator_id: i32, review_id: i32, uphold: bool) -> Result<Review, Error> {
+    conn.transaction::<Review, Error, _>(|conn| {
+        let review = reviews::table.find(review_id).for_update().first::<Review>(conn)?;
+
+        if review.flag_status.as_deref() != Some(flag_statuses::OPEN) {
+            return Err(Error::validation_error("This review is not waiting for a moderator"));
+        }
+
+        let (status, weight) = if uphold {
+            (flag_statuses::UPHELD, 0.0)
+        } else {
+            (flag_statuses::DISMISSED, 1.0)
+        };
+
+        let now = Utc::now();
+        let review = diesel::update(&review)
+            .set((
+                reviews::flag_status.eq(status),
+                reviews::weight.eq(weight),
+                reviews::moderated_by.eq(moderator_id),
+                reviews::moderated_at.eq(now),
+                reviews::updated_at.eq(now),
+            ))
+            .get_result::<Review>(conn)?;
+
+        jobs::reputation::schedule(conn, review.vendor_id)?;
+
+        info!(review_id = review.id, moderator_id = moderator_id, status = %status, "Review flag settled");
+        Ok(review)
+    })
+}
+
+fn check_window(order: &Order) -> Result<(), Error> {
+    if order.status != OrderStatus::Completed {
+        return Err(Error::validation_error("Only completed orders can be reviewed"));
+    }
+
+    let completed_at = order.completed_at.unwrap_or(order.updated_at);
+    if Utc::now() > completed_at + Duration::days(REVIEW_WINDOW_DAYS) {
+        return Err(Error::validation_error(format!(
+            "Reviews close {} days after an order completes",
+            REVIEW_WINDOW_DAYS
+        )));
+    }
+
+    Ok(())
+}
+
+/// Why a new review looks like manipulation, if it does
+///
+/// * Many tiny orders: the buyer has at least `TINY_ORDER_THRESHOLD` completed orders with
+///   the vendor worth less than `TINY_ORDER_RATIO` of the vendor's median order.
+/// * A burst from a new account: an account younger than `NEW_ACCOUNT_DAYS` posting its
+///   `BURST_REVIEWS`th review within `BURST_WINDOW_HOURS`.
+fn suspicion(conn: &mut PgConnection, buyer_id: i32, order: &Order) -> Result<Option<String>, Error> {
+    let mut reasons = Vec::new();
+
+    let completed = orders::table
+        .filter(orders::vendor_id.eq(order.vendor_id))
+        .filter(orders::currency.eq(order.currency))
+        .filter(orders::status.eq(OrderStatus::Completed))
+        .select((orders::buyer_id, orders::total_amount))
+        .load::<(i32, BigDecimal)>(conn)?;
+
+    if let Some(median) = median(completed.iter().filter_map(|(_, total)| total.to_f64())) {
+        let tiny = completed
+            .iter()
+            .filter(|(buyer, total)| {
+                *buyer == buyer_id && total.to_f64().is_some_and(|total| total < median * TINY_ORDER_RATIO)
+            })
+            .count() as i64;
+
+        if tiny >= TINY_ORDER_THRESHOLD {
+            reasons.push(format!(
+                "{} tiny {:?} orders to this vendor (under {:.0}% of their median order)",
+                tiny,
+                order.currency,
+                TINY_ORDER_RATIO * 100.0
+            ));
+        }
+    }
+
+    let created_at = users::table
+        .find(buyer_id)
+        .select(users::created_at)
+        .first::<DateTime<Utc>>(conn)?;
+    let now = Utc::now();
+
+    if now - created_at < Duration::days(NEW_ACCOUNT_DAYS) {
+        let recent = reviews::table
+            .filter(reviews::reviewer_id.eq(buyer_id))
+            .filter(reviews::created_at.gt(now - Duration::hours(BURST_WINDOW_HOURS)))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        // Counting the review being posted
+        if recent + 1 >= BURST_REVIEWS {
+            reasons.push(format!(
+                "{} reviews in {} hours from a {}-day-old account",
+                recent + 1,
+                BURST_WINDOW_HOURS,
+                (now - created_at).num_days()
+            ));
+        }
+    }
+
+    Ok((!reasons.is_empty()).then(|| reasons.join("; ")))
+}
+
+fn median(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let mut values: Vec<f64> = values.collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    Some(values[values.len() / 2])
+}
+
+/// A flagged review with what tripped the check, for moderators
+#[derive(Debug, Serialize)]
+pub struct FlaggedReview {
+    #[serde(flatten)]
+    pub review: Review,
+    pub weight: f64,
+    pub flag_reason: Option<String>,
+    pub reviewer: String,
+}
+
+/// One page of reviews waiting for a moderator, oldest first
+pub fn flagged(conn: &mut PgConnection, limit: i64, offset: i64) -> Result<(Vec<FlaggedReview>, i64), Error> {
+    let total = reviews::table
+        .filter(reviews::flag_status.eq(flag_statuses::OPEN))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let entries = reviews::table
+        .inner_join(users::table.on(users::id.eq(reviews::reviewer_id)))
+        .filter(reviews::flag_status.eq(flag_statuses::OPEN))
+        .order(reviews::created_at.asc())
+        .limit(limit)
+        .offset(offset)
+        .select((reviews::all_columns, users::username))
+        .load::<(Review, String)>(conn)?
+        .into_iter()
+        .map(|(review, reviewer)| FlaggedReview {
+            weight: review.weight,
+            flag_reason: review.flag_reason.clone(),
+            review,
+            reviewer,
+        })
+        .collect();
+
+    Ok((entries, total))
+}
@@ -0,0 +1,494 @@
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::constants::support::*;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::models::notification;
+use crate::models::user::{roles, User};
+use crate::pgp;
+use crate::schema::{support_ticket_messages, support_tickets, users};
+use crate::settings::SETTINGS;
+
+/// Valid ticket statuses
+pub mod statuses {
+    /// Waiting for staff
+    pub const OPEN: &str = "open";
+    /// Staff replied and are waiting for the user
+    pub const AWAITING_USER: &str = "awaiting_user";
+    /// Closed by staff or the user; a reply from the user opens it again
+    pub const RESOLVED: &str = "resolved";
+
+    pub const ALL: [&str; 3] = [OPEN, AWAITING_USER, RESOLVED];
+}
+
+/// Valid ticket categories
+pub mod categories {
+    pub const ACCOUNT: &str = "account";
+    pub const PAYMENT: &str = "payment";
+    pub const VENDOR_APPLICATION: &str = "vendor_application";
+    pub const REPORT: &str = "report";
+
+    pub const ALL: [&str; 4] = [ACCOUNT, PAYMENT, VENDOR_APPLICATION, REPORT];
+}
+
+/// Valid ticket priorities, lowest first
+pub mod priorities {
+    pub const LOW: &str = "low";
+    pub const NORMAL: &str = "normal";
+    pub const HIGH: &str = "high";
+    pub const URGENT: &str = "urgent";
+
+    pub const ALL: [&str; 4] = [LOW, NORMAL, HIGH, URGENT];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = support_tickets)]
+pub struct SupportTicket {
+    pub id: i32,
+    pub user_id: i32,
+    pub category: String,
+    pub priority: String,
+    pub status: String,
+    pub subject: String,
+    /// The moderator or admin handling the ticket
+    pub assigned_to: Option<i32>,
+    pub assigned_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SupportTicket {
+    fn event(&self) -> Event {
+        Event::SupportTicketUpdated {
+            ticket_id: self.id,
+            status: self.status.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = support_tickets)]
+struct NewSupportTicket {
+    user_id: i32,
+    category: String,
+    priority: String,
+    subject: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(table_name = support_ticket_messages)]
+#[diesel(belongs_to(SupportTicket, foreign_key = ticket_id))]
+pub struct TicketMessage {
+    pub id: i32,
+    pub ticket_id: i32,
+    pub author_id: i32,
+    pub content: String,
+    /// False only when neither the user nor the staff had a key
+    pub encrypted: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = support_ticket_messages)]
+struct NewTicketMessage {
+    ticket_id: i32,
+    author_id: i32,
+    content: String,
+    encrypted: bool,
+}
+
+/// A ticket with its whole thread, oldest message first
+#[derive(Debug, Serialize)]
+pub struct TicketWithMessages {
+    #[serde(flatten)]
+    pub ticket: SupportTicket,
+    pub messages: Vec<TicketMessage>,
+}
+
+/// What staff can change on a ticket
+#[derive(Debug, Deserialize)]
+pub struct TicketUpdate {
+    pub status: Option<String>,
+    pub priority: Option<String>,
+}
+
+/// Open a ticket with its first message
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `user_id` - The user asking for help
+/// * `category` - One of `categories`
+/// * `priority` - One of `priorities`, normal when not given
+/// * `subject` - A short summary, stored unencrypted
+/// * `content` - The first message
+///
+/// # Returns
+/// * `Result<TicketWithMessages, Error>` - The new ticket or an error
+pub fn open(
+    conn: &mut PgConnection,
+    user_id: i32,
+    category: &str,
+    priority: Option<&str>,
+    subject: &str,
+    content: &str,
+) -> Result<TicketWithMessages, Error> {
+    if !categories::ALL.contains(&category) {
+        return Err(Error::validation_error(format!("Invalid ticket category: {}", category)));
+    }
+    let priority = priority.unwrap_or(priorities::NORMAL);
+    if !priorities::ALL.contains(&priority) {
+        return Err(Error::validation_error(format!("Invalid ticket priority: {}", priority)));
+    }
+
+    let subject = subject.trim();
+    if subject.is_empty() {
+        return Err(Error::validation_error("The ticket needs a subject"));
+    }
+    if subject.chars().count() > MAX_SUBJECT_LENGTH {
+        return Err(Error::validation_error(format!(
+            "Subjects are limited to {} characters",
+            MAX_SUBJECT_LENGTH
+        )));
+    }
+    check_content(content)?;
+
+    conn.transaction::<TicketWithMessages, Error, _>(|conn| {
+        let owner = users::table.find(user_id).first::<User>(conn)?;
+
+        let ticket = diesel::insert_into(support_tickets::table)
+            .values(&NewSupportTicket {
+                user_id,
+                category: category.to_string(),
+                priority: priority.to_string(),
+                subject: subject.to_string(),
+            })
+            .get_result::<SupportTicket>(conn)?;
+
+        let message = insert_message(conn, &ticket, &owner, user_id, content)?;
+
+        info!(ticket_id = ticket.id, user_id = user_id, category = %category, "Support ticket opened");
+        Ok(TicketWithMessages {
+            ticket,
+            messages: vec![message],
+        })
+    })
+}
+
+/// A ticket with its thread if `user_id` opened it; anyone else gets not found
+pub fn find_for_owner(conn: &mut PgConnection, ticket_id: i32, user_id: i32) -> Result<TicketWithMessages, Error> {
+    let ticket = support_tickets::table
+        .find(ticket_id)
+        .filter(support_tickets::user_id.eq(user_id))
+        .first::<SupportTicket>(conn)
+        .optional()?
+        .ok_or_else(Error::not_found)?;
+
+    with_messages(conn, ticket)
+}
+
+/// A ticket with its thread, for staff
+pub fn find(conn: &mut PgConnection, ticket_id: i32) -> Result<TicketWithMessages, Error> {
+    let ticket = support_tickets::table.find(ticket_id).first::<SupportTicket>(conn)?;
+    with_messages(conn, ticket)
+}
+
+fn with_messages(conn: &mut PgConnection, ticket: SupportTicket) -> Result<TicketWithMessages, Error> {
+    let messages = TicketMessage::belonging_to(&ticket)
+        .order((support_ticket_messages::created_at.asc(), support_ticket_messages::id.asc()))
+        .load::<TicketMessage>(conn)?;
+
+    Ok(TicketWithMessages { ticket, messages })
+}
+
+/// One page of a user's tickets, most recently updated first
+pub fn list_for_user(
+    conn: &mut PgConnection,
+    user_id: i32,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<SupportTicket>, i64), Error> {
+    let total = support_tickets::table
+        .filter(support_tickets::user_id.eq(user_id))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let tickets = support_tickets::table
+        .filter(support_tickets::user_id.eq(user_id))
+        .order((support_tickets::updated_at.desc(), support_tickets::id.desc()))
+        .limit(limit)
+        .offset(offset)
+        .load::<SupportTicket>(conn)?;
+
+    Ok((tickets, total))
+}
+
+/// One page of the staff queue, most urgent first and then oldest first
+///
+/// Without a status, the queue holds every ticket that is not resolved.
+pub fn queue(
+    conn: &mut PgConnection,
+    status: Option<&str>,
+    category: Option<&str>,
+    assigned_to: Option<i32>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<SupportTicket>, i64), Error> {
+    if status.is_some_and(|status| !statuses::ALL.contains(&status)) {
+        return Err(Error::validation_error("Invalid ticket status"));
+    }
+
+    let mut count_query = support_tickets::table.into_boxed();
+    let mut query = support_tickets::table.into_boxed();
+
+    match status {
+        Some(status) => {
+            count_query = count_query.filter(support_tickets::status.eq(status));
+            query = query.filter(support_tickets::status.eq(status));
+        }
+        None => {
+            count_query = count_query.filter(support_tickets::status.ne(statuses::RESOLVED));
+            query = query.filter(support_tickets::status.ne(statuses::RESOLVED));
+        }
+    }
+    if let Some(category) = category {
+        count_query = count_query.filter(support_tickets::category.eq(category));
+        query = query.filter(support_tickets::category.eq(category));
+    }
+    if let Some(staff_id) = assigned_to {
+        count_query = count_query.filter(support_tickets::assigned_to.eq(staff_id));
+        query = query.filter(support_tickets::assigned_to.eq(staff_id));
+    }
+
+    let total = count_query.count().get_result::<i64>(conn)?;
+
+    let tickets = query
+        .order((
+            sql::<Integer>(
+                "CASE priority WHEN 'urgent' THEN 0 WHEN 'high' THEN 1 WHEN 'normal' THEN 2 ELSE 3 END",
+            )
+            .asc(),
+            support_tickets::created_at.asc(),
+        ))
+        .limit(limit)
+        .offset(offset)
+        .load::<SupportTicket>(conn)?;
+
+    Ok((tickets, total))
+}
+
+/// Add a message to a ticket's thread
+///
+/// A reply from the user puts the ticket back in the staff queue, reopening it if it was
+/// resolved; a reply from staff leaves it waiting for the user. The other side is notified.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `ticket_id` - The ticket
+/// * `author_id` - The user who opened it, or a moderator or admin
+/// * `as_staff` - Whether the author is replying as staff
+/// * `content` - The message
+///
+/// # Returns
+/// * `Result<TicketMessage, Error>` - The stored message or an error
+pub fn reply(
+    conn: &mut PgConnection,
+    ticket_id: i32,
+    author_id: i32,
+    as_staff: bool,
+    content: &str,
+) -> Result<TicketMessage, Error> {
+    check_content(content)?;
+
+    conn.transaction::<TicketMessage, Error, _>(|conn| {
+        let mut query = support_tickets::table.find(ticket_id).for_update().into_boxed();
+        if !as_staff {
+            query = query.filter(support_tickets::user_id.eq(author_id));
+        }
+        let ticket = query
+            .first::<SupportTicket>(conn)
+            .optional()?
+            .ok_or_else(Error::not_found)?;
+
+        let owner = users::table.find(ticket.user_id).first::<User>(conn)?;
+        let message = insert_message(conn, &ticket, &owner, author_id, content)?;
+
+        let status = if as_staff { statuses::AWAITING_USER } else { statuses::OPEN };
+        let ticket = diesel::update(&ticket)
+            .set((
+                support_tickets::status.eq(status),
+                support_tickets::resolved_at.eq(None::<DateTime<Utc>>),
+                support_tickets::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<SupportTicket>(conn)?;
+
+        if as_staff {
+            notification::notify(conn, ticket.user_id, ticket.event())?;
+        } else if let Some(staff_id) = ticket.assigned_to {
+            notification::notify(conn, staff_id, ticket.event())?;
+        }
+
+        Ok(message)
+    })
+}
+
+/// Give a ticket to a moderator or admin
+pub fn assign(conn: &mut PgConnection, ticket_id: i32, staff_id: i32) -> Result<SupportTicket, Error> {
+    conn.transaction::<SupportTicket, Error, _>(|conn| {
+        let staff = users::table.find(staff_id).first::<User>(conn)?;
+        if staff.role != roles::MODERATOR && staff.role != roles::ADMIN {
+            return Err(Error::validation_error("Tickets can only be assigned to moderators and admins"));
+        }
+
+        let ticket = support_tickets::table.find(ticket_id).for_update().first::<SupportTicket>(conn)?;
+        if ticket.status == statuses::RESOLVED {
+            return Err(Error::validation_error("This ticket is already resolved"));
+        }
+
+        let now = Utc::now();
+        let ticket = diesel::update(&ticket)
+            .set((
+                support_tickets::assigned_to.eq(staff_id),
+                support_tickets::assigned_at.eq(now),
+                support_tickets::updated_at.eq(now),
+            ))
+            .get_result::<SupportTicket>(conn)?;
+
+        notification::notify(conn, staff_id, ticket.event())?;
+
+        info!(ticket_id = ticket_id, staff_id = staff_id, "Support ticket assigned");
+        Ok(ticket)
+    })
+}
+
+/// Change a ticket's status or priority as staff; the user is told about status changes
+pub fn update(conn: &mut PgConnection, ticket_id: i32, changes: TicketUpdate) -> Result<SupportTicket, Error> {
+    if let Some(status) = &changes.status {
+        if !statuses::ALL.contains(&status.as_str()) {
+            return Err(Error::validation_error(format!("Invalid ticket status: {}", status)));
+        }
+    }
+    if let Some(priority) = &changes.priority {
+        if !priorities::ALL.contains(&priority.as_str()) {
+            return Err(Error::validation_error(format!("Invalid ticket priority: {}", priority)));
+        }
+    }
+
+    conn.transaction::<SupportTicket, Error, _>(|conn| {
+        let ticket = support_tickets::table.find(ticket_id).for_update().first::<SupportTicket>(conn)?;
+
+        let now = Utc::now();
+        let status = changes.status.unwrap_or_else(|| ticket.status.clone());
+        let resolved_at = if status == statuses::RESOLVED {
+            ticket.resolved_at.or(Some(now))
+        } else {
+            None
+        };
+
+        let updated = diesel::update(&ticket)
+            .set((
+                support_tickets::status.eq(&status),
+                support_tickets::priority.eq(changes.priority.unwrap_or_else(|| ticket.priority.clone())),
+                support_tickets::resolved_at.eq(resolved_at),
+                support_tickets::updated_at.eq(now),
+            ))
+            .get_result::<SupportTicket>(conn)?;
+
+        if updated.status != ticket.status {
+            notification::notify(conn, updated.user_id, updated.event())?;
+            info!(ticket_id = ticket_id, status = %updated.status, "Support ticket status changed");
+        }
+        Ok(updated)
+    })
+}
+
+/// Close a ticket as the user who opened it
+pub fn resolve_by_owner(conn: &mut PgConnection, ticket_id: i32, user_id: i32) -> Result<SupportTicket, Error> {
+    let now = Utc::now();
+    let ticket = diesel::update(
+        support_tickets::table
+            .find(ticket_id)
+            .filter(support_tickets::user_id.eq(user_id))
+            .filter(support_tickets::status.ne(statuses::RESOLVED)),
+    )
+    .set((
+        support_tickets::status.eq(statuses::RESOLVED),
+        support_tickets::resolved_at.eq(now),
+        support_tickets::updated_at.eq(now),
+    ))
+    .get_result::<SupportTicket>(conn)
+    .optional()?
+    .ok_or_else(Error::not_found)?;
+
+    if let Some(staff_id) = ticket.assigned_to {
+        notification::notify(conn, staff_id, ticket.event())?;
+    }
+    Ok(ticket)
+}
+
+fn check_content(content: &str) -> Result<(), Error> {
+    if content.trim().is_empty() {
+        return Err(Error::validation_error("The message cannot be empty"));
+    }
+    if content.len() > MAX_CONTENT_BYTES {
+        return Err(Error::validation_error(format!(
+            "Messages are limited to {} KiB",
+            MAX_CONTENT_BYTES / 1024
+        )));
+    }
+    Ok(())
+}
+
+fn insert_message(
+    conn: &mut PgConnection,
+    ticket: &SupportTicket,
+    owner: &User,
+    author_id: i32,
+    content: &str,
+) -> Result<TicketMessage, Error> {
+    let (content, encrypted) = seal(owner, content)?;
+
+    Ok(diesel::insert_into(support_ticket_messages::table)
+        .values(&NewTicketMessage {
+            ticket_id: ticket.id,
+            author_id,
+            content,
+            encrypted,
+        })
+        .get_result::<TicketMessage>(conn)?)
+}
+
+/// The content to store for a ticket message, and whether it is encrypted
+///
+/// Messages are encrypted to the ticket owner's key and the staff key (`support.pgp_public_key`),
+/// whichever are set. Already-encrypted messages must be readable by both; plaintext is
+/// encrypted here. Only when neither key exists is a message stored as sent.
+fn seal(owner: &User, content: &str) -> Result<(String, bool), Error> {
+    let keys: Vec<&str> = [owner.pgp_public_key.as_deref(), SETTINGS.support.pgp_public_key.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if keys.is_empty() {
+        return Ok((content.to_string(), false));
+    }
+
+    if !pgp::is_armored_message(content) {
+        return Ok((pgp::encrypt(content, &keys)?, true));
+    }
+
+    let recipients = pgp::recipients(content)?;
+    for key in keys {
+        if !pgp::encrypted_to(&recipients, key)? {
+            return Err(Error::validation_error(
+                "Ticket messages must be encrypted to both the user's key and the support key",
+            ));
+        }
+    }
+    Ok((content.to_string(), true))
+}
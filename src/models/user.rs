@@ -25,6 +25,8 @@ pub struct User {
     pub locked_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Let the server encrypt this user's messages to the recipient's key
+    pub server_side_encryption: bool,
 }
 
 #[derive(Debug, Insertable)]
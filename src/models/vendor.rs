@@ -1,10 +1,23 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use tracing::info;
 
-use crate::schema::{reviews, vendor_bonds};
-use bigdecimal::BigDecimal;
+use crate::errors::Error;
+use crate::models::availability::{self, Availability};
+use crate::models::escrow;
+use crate::models::order::OrderStatus;
+use crate::models::payment::{self, NewTransaction, Transaction, TransactionType, Wallet, WalletType};
+use crate::models::user::{roles, User};
+use crate::schema::{orders, products, reviews, transactions, users, vendor_bonds, wallets};
+use crate::settings::SETTINGS;
+use bigdecimal::{BigDecimal, ToPrimitive};
 
+/// A vendor application and the bond that backs it
+///
+/// `vendor_id` is the applicant, who stays a buyer until an admin approves the paid bond.
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
 #[diesel(table_name = vendor_bonds)]
 pub struct VendorBond {
@@ -12,10 +25,21 @@ pub struct VendorBond {
     pub vendor_id: i32,
     pub amount_btc: BigDecimal,
     pub status: String,
+    /// The `EscrowLock` posting recording the bond deposit
     pub transaction_id: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub approved_at: Option<DateTime<Utc>>,
+    /// Where the applicant sends the bond
+    pub payment_address: Option<String>,
+    pub tx_hash: Option<String>,
+    /// What the applicant told the admins about themselves
+    pub note: Option<String>,
+    pub paid_at: Option<DateTime<Utc>>,
+    /// The admin who approved, rejected or forfeited the bond
+    pub reviewed_by: Option<i32>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub close_reason: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -25,6 +49,7 @@ pub struct NewVendorBond {
     pub amount_btc: BigDecimal,
     pub status: String,
     pub transaction_id: Option<i32>,
+    pub note: Option<String>,
 }
 
 #[derive(Debug, AsChangeset)]
@@ -36,6 +61,412 @@ pub struct UpdateVendorBond {
     pub approved_at: Option<DateTime<Utc>>,
 }
 
+/// Valid vendor bond statuses
+pub mod bond_statuses {
+    /// Applied; waiting for the deposit
+    pub const PENDING: &str = "pending";
+    /// Deposit confirmed; waiting for an admin
+    pub const PAID: &str = "paid";
+    /// Approved; the user is a vendor
+    pub const ACTIVE: &str = "active";
+    /// Withdrawn by the applicant before paying
+    pub const CANCELLED: &str = "cancelled";
+    /// Turned down by an admin; a paid bond was refunded
+    pub const REJECTED: &str = "rejected";
+    /// The vendor left in good standing and got the bond back
+    pub const REFUNDED: &str = "refunded";
+    /// Kept by the marketplace after enforcement
+    pub const FORFEITED: &str = "forfeited";
+
+    pub const ALL: [&str; 7] = [PENDING, PAID, ACTIVE, CANCELLED, REJECTED, REFUNDED, FORFEITED];
+}
+
+/// Apply to become a vendor
+///
+/// Creates a pending bond for the configured amount with its own deposit address. The
+/// applicant needs a BTC wallet, since that is where the bond goes back if it is refunded.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `user` - The applicant; must be a buyer in good standing
+/// * `note` - Optional information for the admins reviewing the application
+///
+/// # Returns
+/// * `Result<VendorBond, Error>` - The pending bond with its payment address or an error
+pub fn apply(conn: &mut PgConnection, user: &User, note: Option<String>) -> Result<VendorBond, Error> {
+    if !user.is_buyer() {
+        return Err(Error::validation_error("Only buyers can apply to become vendors"));
+    }
+    if user.is_account_locked() {
+        return Err(Error::validation_error("Locked accounts cannot apply to become vendors"));
+    }
+
+    let amount_btc = BigDecimal::from_str(&SETTINGS.vendor_bonds.amount_btc).map_err(|err| {
+        Error::internal_error(
+            format!("Invalid bond amount {:?}: {}", SETTINGS.vendor_bonds.amount_btc, err),
+            None,
+            None,
+        )
+    })?;
+
+    conn.transaction::<VendorBond, Error, _>(|conn| {
+        let has_wallet = diesel::select(diesel::dsl::exists(
+            wallets::table
+                .filter(wallets::user_id.eq(user.id))
+                .filter(wallets::wallet_type.eq(WalletType::BTC)),
+        ))
+        .get_result::<bool>(conn)?;
+        if !has_wallet {
+            return Err(Error::validation_error("Create a BTC wallet before applying"));
+        }
+
+        let bond = diesel::insert_into(vendor_bonds::table)
+            .values(&NewVendorBond {
+                vendor_id: user.id,
+                amount_btc,
+                status: bond_statuses::PENDING.to_string(),
+                transaction_id: None,
+                note,
+            })
+            .on_conflict_do_nothing()
+            .get_result::<VendorBond>(conn)
+            .optional()?
+            .ok_or_else(|| Error::validation_error("You already have an open vendor application"))?;
+
+        let address = escrow::assign_bond_address(conn, bond.id)?;
+        let bond = diesel::update(&bond)
+            .set(vendor_bonds::payment_address.eq(address))
+            .get_result::<VendorBond>(conn)?;
+
+        info!(user_id = user.id, bond_id = bond.id, amount = %bond.amount_btc, "Vendor application opened");
+        Ok(bond)
+    })
+}
+
+/// The user's open bond, if any: pending, paid or active
+pub fn open_bond(conn: &mut PgConnection, user_id: i32) -> Result<Option<VendorBond>, Error> {
+    let bond = vendor_bonds::table
+        .filter(vendor_bonds::vendor_id.eq(user_id))
+        .filter(vendor_bonds::status.eq_any([bond_statuses::PENDING, bond_statuses::PAID, bond_statuses::ACTIVE]))
+        .first::<VendorBond>(conn)
+        .optional()?;
+
+    Ok(bond)
+}
+
+/// Withdraw an application that has not been paid yet
+pub fn cancel_application(conn: &mut PgConnection, user_id: i32) -> Result<VendorBond, Error> {
+    conn.transaction::<VendorBond, Error, _>(|conn| {
+        let bond = lock_open_bond(conn, user_id)?;
+        if bond.status != bond_statuses::PENDING {
+            return Err(Error::validation_error("Only unpaid applications can be cancelled"));
+        }
+
+        close(conn, &bond, bond_statuses::CANCELLED, None, None)
+    })
+}
+
+/// Record the confirmed deposit of a bond
+///
+/// Called once the payment to the bond's address has confirmed on chain. Watching bond
+/// addresses is deferred until there is a chain client (see `TODO`), so an admin confirms
+/// the deposit against the node and records it through `POST /admin/vendor-bonds/:id/payment`;
+/// a watcher would call this the same way.
+///
+/// The deposit is posted to the applicant's BTC wallet as an `EscrowLock` without changing
+/// its balance: the bond is held by the marketplace, not spendable by the vendor.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `bond_id` - The pending bond
+/// * `tx_hash` - The deposit transaction
+/// * `amount` - What arrived at the bond address; at least the bond amount
+///
+/// # Returns
+/// * `Result<VendorBond, Error>` - The paid bond, now awaiting review, or an error
+pub fn record_payment(
+    conn: &mut PgConnection,
+    bond_id: i32,
+    tx_hash: &str,
+    amount: BigDecimal,
+) -> Result<VendorBond, Error> {
+    let tx_hash = tx_hash.trim();
+    if tx_hash.is_empty() {
+        return Err(Error::validation_error("A transaction hash is required"));
+    }
+
+    conn.transaction::<VendorBond, Error, _>(|conn| {
+        let bond = vendor_bonds::table.find(bond_id).for_update().first::<VendorBond>(conn)?;
+
+        if bond.status != bond_statuses::PENDING {
+            return Err(Error::validation_error("This bond is not awaiting payment"));
+        }
+        if amount < bond.amount_btc {
+            return Err(Error::validation_error(format!(
+                "The bond is {} BTC but only {} BTC was paid",
+                bond.amount_btc, amount
+            )));
+        }
+
+        let wallet = bond_wallet(conn, bond.vendor_id)?;
+        let now = Utc::now();
+        let transaction = diesel::insert_into(transactions::table)
+            .values(&NewTransaction {
+                wallet_id: wallet.id,
+                transaction_type: TransactionType::EscrowLock,
+                amount: bond.amount_btc.clone(),
+                fee: BigDecimal::from(0),
+                tx_hash: Some(tx_hash.to_string()),
+                order_id: None,
+                status: "completed".to_string(),
+                completed_at: Some(now),
+            })
+            .get_result::<Transaction>(conn)?;
+
+        let bond = diesel::update(&bond)
+            .set((
+                vendor_bonds::status.eq(bond_statuses::PAID),
+                vendor_bonds::transaction_id.eq(transaction.id),
+                vendor_bonds::tx_hash.eq(tx_hash),
+                vendor_bonds::paid_at.eq(now),
+                vendor_bonds::updated_at.eq(now),
+            ))
+            .get_result::<VendorBond>(conn)?;
+
+        info!(bond_id = bond.id, user_id = bond.vendor_id, tx_hash = %tx_hash, "Vendor bond paid");
+        Ok(bond)
+    })
+}
+
+/// Approve a paid application and make the applicant a vendor
+///
+/// The new role is in the user's next token, so they have to sign in again to use it.
+pub fn approve(conn: &mut PgConnection, admin_id: i32, bond_id: i32) -> Result<VendorBond, Error> {
+    conn.transaction::<VendorBond, Error, _>(|conn| {
+        let bond = vendor_bonds::table.find(bond_id).for_update().first::<VendorBond>(conn)?;
+        if bond.status != bond_statuses::PAID {
+            return Err(Error::validation_error("Only paid applications can be approved"));
+        }
+
+        let user = users::table.find(bond.vendor_id).for_update().first::<User>(conn)?;
+        if !user.is_buyer() {
+            return Err(Error::validation_error(format!("The applicant is now a {}", user.role)));
+        }
+
+        let now = Utc::now();
+        diesel::update(&user)
+            .set((users::role.eq(roles::VENDOR), users::updated_at.eq(now)))
+            .execute(conn)?;
+
+        let bond = diesel::update(&bond)
+            .set((
+                vendor_bonds::status.eq(bond_statuses::ACTIVE),
+                vendor_bonds::reviewed_by.eq(admin_id),
+                vendor_bonds::approved_at.eq(now),
+                vendor_bonds::updated_at.eq(now),
+            ))
+            .get_result::<VendorBond>(conn)?;
+
+        info!(bond_id = bond.id, user_id = bond.vendor_id, admin_id = admin_id, "Vendor application approved");
+        Ok(bond)
+    })
+}
+
+/// Turn down an application, refunding the bond if it was paid
+pub fn reject(conn: &mut PgConnection, admin_id: i32, bond_id: i32, reason: String) -> Result<VendorBond, Error> {
+    conn.transaction::<VendorBond, Error, _>(|conn| {
+        let bond = vendor_bonds::table.find(bond_id).for_update().first::<VendorBond>(conn)?;
+        if bond.status != bond_statuses::PENDING && bond.status != bond_statuses::PAID {
+            return Err(Error::validation_error("Only open applications can be rejected"));
+        }
+
+        if bond.status == bond_statuses::PAID {
+            refund(conn, &bond)?;
+        }
+
+        close(conn, &bond, bond_statuses::REJECTED, Some(admin_id), Some(reason))
+    })
+}
+
+/// Stop selling and get the bond back
+///
+/// Only vendors in good standing can leave with their bond: the account must not be locked
+/// and every order they sold must be finished. Their listings are taken down and they
+/// become a buyer again.
+pub fn exit(conn: &mut PgConnection, user_id: i32) -> Result<VendorBond, Error> {
+    conn.transaction::<VendorBond, Error, _>(|conn| {
+        let bond = lock_open_bond(conn, user_id)?;
+        if bond.status != bond_statuses::ACTIVE {
+            return Err(Error::validation_error("You do not have an active vendor bond"));
+        }
+
+        let user = users::table.find(user_id).for_update().first::<User>(conn)?;
+        if user.is_account_locked() {
+            return Err(Error::validation_error("Locked accounts cannot reclaim their bond"));
+        }
+
+        let open_orders = orders::table
+            .filter(orders::vendor_id.eq(user_id))
+            .filter(orders::status.eq_any([
+                OrderStatus::Pending,
+                OrderStatus::Paid,
+                OrderStatus::Processing,
+                OrderStatus::Shipped,
+                OrderStatus::Delivered,
+                OrderStatus::Disputed,
+            ]))
+            .count()
+            .get_result::<i64>(conn)?;
+        if open_orders > 0 {
+            return Err(Error::validation_error(format!(
+                "Finish your {} open orders before closing your shop",
+                open_orders
+            )));
+        }
+
+        refund(conn, &bond)?;
+        demote(conn, user_id)?;
+
+        close(conn, &bond, bond_statuses::REFUNDED, None, Some("Voluntary exit".to_string()))
+    })
+}
+
+/// Keep a vendor's bond as a penalty and revoke their vendor status
+///
+/// Open orders are left alone; they finish or are disputed as usual.
+pub fn forfeit(conn: &mut PgConnection, admin_id: i32, bond_id: i32, reason: String) -> Result<VendorBond, Error> {
+    conn.transaction::<VendorBond, Error, _>(|conn| {
+        let bond = vendor_bonds::table.find(bond_id).for_update().first::<VendorBond>(conn)?;
+        if bond.status != bond_statuses::ACTIVE {
+            return Err(Error::validation_error("Only active bonds can be forfeited"));
+        }
+
+        demote(conn, bond.vendor_id)?;
+
+        close(conn, &bond, bond_statuses::FORFEITED, Some(admin_id), Some(reason))
+    })
+}
+
+/// One page of bonds, oldest first, optionally in one status
+pub fn list(
+    conn: &mut PgConnection,
+    status: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<VendorBond>, i64), Error> {
+    let mut query = vendor_bonds::table.into_boxed();
+    let mut count_query = vendor_bonds::table.into_boxed();
+
+    if let Some(status) = status {
+        query = query.filter(vendor_bonds::status.eq(status.to_string()));
+        count_query = count_query.filter(vendor_bonds::status.eq(status.to_string()));
+    }
+
+    let total = count_query.count().get_result::<i64>(conn)?;
+    let bonds = query
+        .order(vendor_bonds::created_at.asc())
+        .limit(limit)
+        .offset(offset)
+        .load::<VendorBond>(conn)?;
+
+    Ok((bonds, total))
+}
+
+fn lock_open_bond(conn: &mut PgConnection, user_id: i32) -> Result<VendorBond, Error> {
+    vendor_bonds::table
+        .filter(vendor_bonds::vendor_id.eq(user_id))
+        .filter(vendor_bonds::status.eq_any([bond_statuses::PENDING, bond_statuses::PAID, bond_statuses::ACTIVE]))
+        .for_update()
+        .first::<VendorBond>(conn)
+        .optional()?
+        .ok_or_else(Error::not_found)
+}
+
+/// The BTC wallet bonds are paid from and refunded to, locked
+fn bond_wallet(conn: &mut PgConnection, user_id: i32) -> Result<Wallet, Error> {
+    payment::lock_wallet(conn, user_id, WalletType::BTC)?
+        .ok_or_else(|| Error::CryptoError {
+            message: format!("User {} has no BTC wallet for their bond", user_id),
+            source: None,
+        })
+}
+
+/// Credit a paid bond back to the owner's BTC wallet
+fn refund(conn: &mut PgConnection, bond: &VendorBond) -> Result<Transaction, Error> {
+    let wallet = bond_wallet(conn, bond.vendor_id)?;
+    let now = Utc::now();
+
+    diesel::update(&wallet)
+        .set((
+            wallets::balance.eq(wallets::balance + &bond.amount_btc),
+            wallets::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    let transaction = diesel::insert_into(transactions::table)
+        .values(&NewTransaction {
+            wallet_id: wallet.id,
+            transaction_type: TransactionType::EscrowRefund,
+            amount: bond.amount_btc.clone(),
+            fee: BigDecimal::from(0),
+            tx_hash: None,
+            order_id: None,
+            status: "completed".to_string(),
+            completed_at: Some(now),
+        })
+        .get_result::<Transaction>(conn)?;
+
+    info!(bond_id = bond.id, user_id = bond.vendor_id, amount = %bond.amount_btc, "Vendor bond refunded");
+    Ok(transaction)
+}
+
+/// Make a vendor a buyer again and take their listings down
+fn demote(conn: &mut PgConnection, user_id: i32) -> Result<(), Error> {
+    let now = Utc::now();
+
+    diesel::update(users::table.find(user_id).filter(users::role.eq(roles::VENDOR)))
+        .set((users::role.eq(roles::BUYER), users::updated_at.eq(now)))
+        .execute(conn)?;
+
+    let delisted = diesel::update(
+        products::table
+            .filter(products::vendor_id.eq(user_id))
+            .filter(products::is_active.eq(true).or(products::stock_deactivated.eq(true))),
+    )
+    .set((
+        products::is_active.eq(false),
+        products::stock_deactivated.eq(false),
+        products::updated_at.eq(now),
+    ))
+    .execute(conn)?;
+
+    info!(user_id = user_id, delisted = delisted, "Vendor status revoked");
+    Ok(())
+}
+
+fn close(
+    conn: &mut PgConnection,
+    bond: &VendorBond,
+    status: &str,
+    admin_id: Option<i32>,
+    reason: Option<String>,
+) -> Result<VendorBond, Error> {
+    let now = Utc::now();
+
+    let bond = diesel::update(bond)
+        .set((
+            vendor_bonds::status.eq(status),
+            vendor_bonds::reviewed_by.eq(admin_id.or(bond.reviewed_by)),
+            vendor_bonds::close_reason.eq(reason),
+            vendor_bonds::closed_at.eq(now),
+            vendor_bonds::updated_at.eq(now),
+        ))
+        .get_result::<VendorBond>(conn)?;
+
+    info!(bond_id = bond.id, user_id = bond.vendor_id, status = %status, "Vendor bond closed");
+    Ok(bond)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
 #[diesel(table_name = reviews)]
 pub struct Review {
@@ -48,6 +479,21 @@ pub struct Review {
     pub comment: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Set when the buyer used their one edit
+    pub edited_at: Option<DateTime<Utc>>,
+    pub vendor_reply: Option<String>,
+    pub vendor_replied_at: Option<DateTime<Utc>>,
+    /// How much the review counts towards the vendor's reputation, from 0 to 1
+    #[serde(skip_serializing)]
+    pub weight: f64,
+    #[serde(skip_serializing)]
+    pub flag_status: Option<String>,
+    #[serde(skip_serializing)]
+    pub flag_reason: Option<String>,
+    #[serde(skip_serializing)]
+    pub moderated_by: Option<i32>,
+    #[serde(skip_serializing)]
+    pub moderated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Insertable)]
@@ -59,6 +505,9 @@ pub struct NewReview {
     pub product_id: i32,
     pub rating: i32,
     pub comment: Option<String>,
+    pub weight: f64,
+    pub flag_status: Option<String>,
+    pub flag_reason: Option<String>,
 }
 
 // For API responses
@@ -72,4 +521,68 @@ pub struct VendorWithStats {
     pub total_sales: i64,
     pub average_rating: Option<f64>,
     pub is_verified: bool,
+    /// Active, vacation or closed
+    pub status: String,
+    pub return_date: Option<NaiveDate>,
+    pub away_message: Option<String>,
+}
+
+impl VendorWithStats {
+    /// Add sales, ratings, bond status and availability to a page of vendors
+    ///
+    /// A vendor is verified while they have an active bond.
+    pub fn load(conn: &mut PgConnection, vendors: Vec<User>) -> Result<Vec<Self>, Error> {
+        let ids: Vec<i32> = vendors.iter().map(|vendor| vendor.id).collect();
+
+        let sales: HashMap<i32, i64> = orders::table
+            .filter(orders::vendor_id.eq_any(&ids))
+            .filter(orders::status.eq(OrderStatus::Completed))
+            .group_by(orders::vendor_id)
+            .select((orders::vendor_id, diesel::dsl::count_star()))
+            .load::<(i32, i64)>(conn)?
+            .into_iter()
+            .collect();
+
+        let ratings: HashMap<i32, f64> = reviews::table
+            .filter(reviews::vendor_id.eq_any(&ids))
+            .filter(reviews::weight.gt(0.0))
+            .group_by(reviews::vendor_id)
+            .select((reviews::vendor_id, diesel::dsl::avg(reviews::rating)))
+            .load::<(i32, Option<BigDecimal>)>(conn)?
+            .into_iter()
+            .filter_map(|(vendor_id, average)| Some((vendor_id, average?.to_f64()?)))
+            .collect();
+
+        let bonded: HashSet<i32> = vendor_bonds::table
+            .filter(vendor_bonds::vendor_id.eq_any(&ids))
+            .filter(vendor_bonds::status.eq(bond_statuses::ACTIVE))
+            .select(vendor_bonds::vendor_id)
+            .load::<i32>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut availabilities = availability::load(conn, &ids)?;
+
+        Ok(vendors
+            .into_iter()
+            .map(|vendor| {
+                let away = availabilities
+                    .remove(&vendor.id)
+                    .unwrap_or_else(|| Availability::active(vendor.id));
+                Self {
+                    total_sales: sales.get(&vendor.id).copied().unwrap_or(0),
+                    average_rating: ratings.get(&vendor.id).map(|average| (average * 100.0).round() / 100.0),
+                    is_verified: bonded.contains(&vendor.id),
+                    status: away.status,
+                    return_date: away.return_date,
+                    away_message: away.message,
+                    id: vendor.id,
+                    username: vendor.username,
+                    pgp_public_key: vendor.pgp_public_key,
+                    reputation: vendor.reputation,
+                    created_at: vendor.created_at,
+                }
+            })
+            .collect())
+    }
 }
@@ -0,0 +1,574 @@
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::constants::withdrawals::{MAX_ADDRESSES_PER_CURRENCY, MAX_LABEL_LENGTH};
+use crate::errors::Error;
+use crate::models::payment::{self, NewTransaction, PaymentCurrency, Transaction, TransactionType, Wallet};
+use crate::models::user::User;
+use crate::pgp;
+use crate::pricing;
+use crate::schema::{transactions, users, wallets, withdrawal_addresses, withdrawals};
+use crate::settings::SETTINGS;
+
+/// An address a user may withdraw to
+///
+/// A new address is unusable until the user signs its `challenge` with the PGP key on their
+/// account, and then until `usable_at`, so a stolen session cannot drain the account to an
+/// address the attacker just added.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = withdrawal_addresses)]
+pub struct WithdrawalAddress {
+    pub id: i32,
+    pub user_id: i32,
+    pub currency: PaymentCurrency,
+    pub address: String,
+    pub label: Option<String>,
+    /// The text the user has to clearsign to confirm the address
+    pub challenge: String,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub usable_at: Option<DateTime<Utc>>,
+    pub removed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = withdrawal_addresses)]
+pub struct NewWithdrawalAddress {
+    pub user_id: i32,
+    pub currency: PaymentCurrency,
+    pub address: String,
+    pub label: Option<String>,
+    pub challenge: String,
+}
+
+/// A request to send part of a wallet's balance to a saved address
+///
+/// The amount and network fee are taken from the wallet when the request is made and given
+/// back if it is cancelled or fails.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = withdrawals)]
+pub struct Withdrawal {
+    pub id: i32,
+    pub user_id: i32,
+    pub wallet_id: i32,
+    pub address_id: i32,
+    /// The ledger entry for the withdrawal
+    pub transaction_id: i32,
+    pub currency: PaymentCurrency,
+    /// What the address receives
+    pub amount: BigDecimal,
+    /// Charged on top of the amount
+    pub network_fee: BigDecimal,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub failure_reason: Option<String>,
+    /// The admin who processed the withdrawal
+    pub processed_by: Option<i32>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = withdrawals)]
+pub struct NewWithdrawal {
+    pub user_id: i32,
+    pub wallet_id: i32,
+    pub address_id: i32,
+    pub transaction_id: i32,
+    pub currency: PaymentCurrency,
+    pub amount: BigDecimal,
+    pub network_fee: BigDecimal,
+}
+
+/// Valid withdrawal statuses
+pub mod statuses {
+    /// Waiting for an admin to send it
+    pub const QUEUED: &str = "queued";
+    /// Picked up by an admin; can no longer be cancelled
+    pub const PROCESSING: &str = "processing";
+    /// Broadcast to the network
+    pub const SENT: &str = "sent";
+    /// Could not be sent; the funds were returned
+    pub const FAILED: &str = "failed";
+    /// Cancelled by the user; the funds were returned
+    pub const CANCELLED: &str = "cancelled";
+
+    pub const ALL: [&str; 5] = [QUEUED, PROCESSING, SENT, FAILED, CANCELLED];
+}
+
+/// Check that an address looks valid for its currency
+///
+/// This is a format check (prefix, alphabet and length), not a checksum check; the wallet
+/// software that sends the withdrawal verifies the checksum before broadcasting.
+pub fn validate_address(currency: PaymentCurrency, address: &str) -> Result<(), Error> {
+    const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    const BECH32: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+    let is_base58 = |s: &str| s.chars().all(|c| BASE58.contains(c));
+
+    let valid = match currency {
+        PaymentCurrency::BTC => {
+            let lower = address.to_ascii_lowercase();
+            if let Some(data) = lower.strip_prefix("bc1") {
+                // Mixed case is invalid in bech32
+                (address == lower || address == address.to_ascii_uppercase())
+                    && (11..=87).contains(&data.len())
+                    && data.chars().all(|c| BECH32.contains(c))
+            } else {
+                (address.starts_with('1') || address.starts_with('3'))
+                    && (26..=35).contains(&address.len())
+                    && is_base58(address)
+            }
+        }
+        PaymentCurrency::XMR => {
+            // Standard addresses and subaddresses are 95 characters, integrated addresses 106
+            (address.starts_with('4') || address.starts_with('8'))
+                && (address.len() == 95 || address.len() == 106)
+                && is_base58(address)
+        }
+    };
+
+    if !valid {
+        return Err(Error::validation_error(format!("Not a valid {:?} address", currency)));
+    }
+
+    Ok(())
+}
+
+/// The network fee charged on a withdrawal in a currency
+pub fn estimate_fee(currency: PaymentCurrency) -> Result<BigDecimal, Error> {
+    let configured = match currency {
+        PaymentCurrency::BTC => &SETTINGS.withdrawals.btc_network_fee,
+        PaymentCurrency::XMR => &SETTINGS.withdrawals.xmr_network_fee,
+    };
+
+    let fee = BigDecimal::from_str(configured).map_err(|err| {
+        Error::internal_error(format!("Invalid {:?} network fee {:?}: {}", currency, configured, err), None, None)
+    })?;
+
+    Ok(fee.with_scale(pricing::crypto_scale(currency)))
+}
+
+/// Save a new address and return it with the challenge the user has to sign
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `user` - The owner; must have a PGP key on their account
+/// * `currency` - The currency the address is for
+/// * `address` - The address
+/// * `label` - An optional name for the address
+///
+/// # Returns
+/// * `Result<WithdrawalAddress, Error>` - The unconfirmed address or an error
+pub fn add_address(
+    conn: &mut PgConnection,
+    user: &User,
+    currency: PaymentCurrency,
+    address: &str,
+    label: Option<String>,
+) -> Result<WithdrawalAddress, Error> {
+    let address = address.trim();
+    validate_address(currency, address)?;
+
+    let label = label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    if label.as_ref().is_some_and(|label| label.chars().count() > MAX_LABEL_LENGTH) {
+        return Err(Error::validation_error(format!(
+            "The label can be at most {} characters",
+            MAX_LABEL_LENGTH
+        )));
+    }
+
+    if user.pgp_public_key.is_none() {
+        return Err(Error::validation_error(
+            "Add a PGP key to your account before saving withdrawal addresses",
+        ));
+    }
+
+    let saved = withdrawal_addresses::table
+        .filter(withdrawal_addresses::user_id.eq(user.id))
+        .filter(withdrawal_addresses::currency.eq(currency))
+        .filter(withdrawal_addresses::removed_at.is_null())
+        .count()
+        .get_result::<i64>(conn)?;
+    if saved >= MAX_ADDRESSES_PER_CURRENCY {
+        return Err(Error::validation_error(format!(
+            "You can save at most {} {:?} addresses",
+            MAX_ADDRESSES_PER_CURRENCY, currency
+        )));
+    }
+
+    let challenge = format!(
+        "Add withdrawal address\nUser: {}\nCurrency: {:?}\nAddress: {}\nNonce: {}",
+        user.username,
+        currency,
+        address,
+        uuid::Uuid::new_v4()
+    );
+
+    let saved = diesel::insert_into(withdrawal_addresses::table)
+        .values(&NewWithdrawalAddress {
+            user_id: user.id,
+            currency,
+            address: address.to_string(),
+            label,
+            challenge,
+        })
+        .on_conflict_do_nothing()
+        .get_result::<WithdrawalAddress>(conn)
+        .optional()?
+        .ok_or_else(|| Error::validation_error("This address is already saved"))?;
+
+    info!(user_id = user.id, address_id = saved.id, currency = ?currency, "Withdrawal address added");
+    Ok(saved)
+}
+
+/// Confirm an address with the user's PGP signature over its challenge
+///
+/// Starts the cool-down: the address can be used `withdrawals.address_cooldown_hours` later.
+pub fn confirm_address(
+    conn: &mut PgConnection,
+    user: &User,
+    address_id: i32,
+    signed_challenge: &str,
+) -> Result<WithdrawalAddress, Error> {
+    let key = user
+        .pgp_public_key
+        .as_deref()
+        .ok_or_else(|| Error::validation_error("Your account has no PGP key"))?;
+
+    let address = find_address(conn, user.id, address_id)?;
+    if address.confirmed_at.is_some() {
+        return Err(Error::validation_error("This address is already confirmed"));
+    }
+
+    if let Err(err) = pgp::verify_challenge(key, signed_challenge, &address.challenge) {
+        warn!(user_id = user.id, address_id = address_id, "Withdrawal address confirmation failed");
+        return Err(err);
+    }
+
+    let now = Utc::now();
+    let address = diesel::update(&address)
+        .set((
+            withdrawal_addresses::confirmed_at.eq(now),
+            withdrawal_addresses::usable_at.eq(now + Duration::hours(SETTINGS.withdrawals.address_cooldown_hours)),
+            withdrawal_addresses::updated_at.eq(now),
+        ))
+        .get_result::<WithdrawalAddress>(conn)?;
+
+    info!(user_id = user.id, address_id = address_id, "Withdrawal address confirmed");
+    Ok(address)
+}
+
+/// Stop using an address; past withdrawals keep pointing at it
+pub fn remove_address(conn: &mut PgConnection, user_id: i32, address_id: i32) -> Result<(), Error> {
+    let address = find_address(conn, user_id, address_id)?;
+    let now = Utc::now();
+
+    diesel::update(&address)
+        .set((
+            withdrawal_addresses::removed_at.eq(now),
+            withdrawal_addresses::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn find_address(conn: &mut PgConnection, user_id: i32, address_id: i32) -> Result<WithdrawalAddress, Error> {
+    withdrawal_addresses::table
+        .find(address_id)
+        .filter(withdrawal_addresses::user_id.eq(user_id))
+        .filter(withdrawal_addresses::removed_at.is_null())
+        .first::<WithdrawalAddress>(conn)
+        .optional()?
+        .ok_or_else(Error::not_found)
+}
+
+/// Queue a withdrawal to a confirmed address
+///
+/// The amount plus the network fee is taken from the wallet right away and recorded as a
+/// pending `Withdrawal` transaction.
+///
+/// # Arguments
+/// * `conn` - A database connection
+/// * `user_id` - The user withdrawing
+/// * `address_id` - One of the user's confirmed addresses, past its cool-down
+/// * `amount` - What the address should receive
+///
+/// # Returns
+/// * `Result<Withdrawal, Error>` - The queued withdrawal or an error
+pub fn request(
+    conn: &mut PgConnection,
+    user_id: i32,
+    address_id: i32,
+    amount: BigDecimal,
+) -> Result<Withdrawal, Error> {
+    conn.transaction::<Withdrawal, Error, _>(|conn| {
+        let address = find_address(conn, user_id, address_id)?;
+        let now = Utc::now();
+
+        match address.usable_at {
+            None => return Err(Error::validation_error("Confirm this address with your PGP key first")),
+            Some(usable_at) if usable_at > now => {
+                return Err(Error::validation_error(format!(
+                    "This address can be used from {}",
+                    usable_at.to_rfc3339()
+                )))
+            }
+            Some(_) => {}
+        }
+
+        let scale = pricing::crypto_scale(address.currency);
+        if amount <= BigDecimal::zero() || amount.with_scale(scale) != amount {
+            return Err(Error::validation_error(format!(
+                "The amount must be positive with at most {} decimal places",
+                scale
+            )));
+        }
+
+        let network_fee = estimate_fee(address.currency)?;
+        let total = &amount + &network_fee;
+
+        let wallet = payment::lock_wallet(conn, user_id, payment::wallet_type(address.currency))?
+            .ok_or_else(|| Error::validation_error(format!("You have no {:?} wallet", address.currency)))?;
+
+        if wallet.balance < total {
+            return Err(Error::validation_error(format!(
+                "Insufficient balance: {} {:?} needed including the network fee, {} available",
+                total, address.currency, wallet.balance
+            )));
+        }
+
+        diesel::update(&wallet)
+            .set((
+                wallets::balance.eq(wallets::balance - &total),
+                wallets::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        let transaction = diesel::insert_into(transactions::table)
+            .values(&NewTransaction {
+                wallet_id: wallet.id,
+                transaction_type: TransactionType::Withdrawal,
+                amount: amount.clone(),
+                fee: network_fee.clone(),
+                tx_hash: None,
+                order_id: None,
+                status: "pending".to_string(),
+                completed_at: None,
+            })
+            .get_result::<Transaction>(conn)?;
+
+        let withdrawal = diesel::insert_into(withdrawals::table)
+            .values(&NewWithdrawal {
+                user_id,
+                wallet_id: wallet.id,
+                address_id,
+                transaction_id: transaction.id,
+                currency: address.currency,
+                amount,
+                network_fee,
+            })
+            .get_result::<Withdrawal>(conn)?;
+
+        info!(
+            withdrawal_id = withdrawal.id,
+            user_id = user_id,
+            currency = ?withdrawal.currency,
+            amount = %withdrawal.amount,
+            "Withdrawal queued"
+        );
+        Ok(withdrawal)
+    })
+}
+
+/// Cancel a queued withdrawal and return its funds
+pub fn cancel(conn: &mut PgConnection, user_id: i32, withdrawal_id: i32) -> Result<Withdrawal, Error> {
+    conn.transaction::<Withdrawal, Error, _>(|conn| {
+        let withdrawal = withdrawals::table
+            .find(withdrawal_id)
+            .filter(withdrawals::user_id.eq(user_id))
+            .for_update()
+            .first::<Withdrawal>(conn)?;
+
+        if withdrawal.status != statuses::QUEUED {
+            return Err(Error::validation_error("Only queued withdrawals can be cancelled"));
+        }
+
+        close(conn, &withdrawal, statuses::CANCELLED, None, None)
+    })
+}
+
+/// Take a queued withdrawal off the queue so it can be sent
+pub fn start_processing(conn: &mut PgConnection, admin_id: i32, withdrawal_id: i32) -> Result<Withdrawal, Error> {
+    conn.transaction::<Withdrawal, Error, _>(|conn| {
+        let withdrawal = withdrawals::table.find(withdrawal_id).for_update().first::<Withdrawal>(conn)?;
+
+        if withdrawal.status != statuses::QUEUED {
+            return Err(Error::validation_error("Only queued withdrawals can be processed"));
+        }
+
+        let withdrawal = diesel::update(&withdrawal)
+            .set((
+                withdrawals::status.eq(statuses::PROCESSING),
+                withdrawals::processed_by.eq(admin_id),
+                withdrawals::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<Withdrawal>(conn)?;
+
+        Ok(withdrawal)
+    })
+}
+
+/// Record that a withdrawal was broadcast
+pub fn mark_sent(
+    conn: &mut PgConnection,
+    admin_id: i32,
+    withdrawal_id: i32,
+    tx_hash: &str,
+) -> Result<Withdrawal, Error> {
+    let tx_hash = tx_hash.trim();
+    if tx_hash.is_empty() {
+        return Err(Error::validation_error("A transaction hash is required"));
+    }
+
+    conn.transaction::<Withdrawal, Error, _>(|conn| {
+        let withdrawal = withdrawals::table.find(withdrawal_id).for_update().first::<Withdrawal>(conn)?;
+
+        if withdrawal.status != statuses::QUEUED && withdrawal.status != statuses::PROCESSING {
+            return Err(Error::validation_error("This withdrawal is already closed"));
+        }
+
+        let now = Utc::now();
+        diesel::update(transactions::table.find(withdrawal.transaction_id))
+            .set((
+                transactions::status.eq("completed"),
+                transactions::tx_hash.eq(tx_hash),
+                transactions::completed_at.eq(now),
+                transactions::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        let withdrawal = diesel::update(&withdrawal)
+            .set((
+                withdrawals::status.eq(statuses::SENT),
+                withdrawals::tx_hash.eq(tx_hash),
+                withdrawals::processed_by.eq(admin_id),
+                withdrawals::processed_at.eq(now),
+                withdrawals::updated_at.eq(now),
+            ))
+            .get_result::<Withdrawal>(conn)?;
+
+        info!(withdrawal_id = withdrawal.id, admin_id = admin_id, tx_hash = %tx_hash, "Withdrawal sent");
+        Ok(withdrawal)
+    })
+}
+
+/// Record that a withdrawal could not be sent and return its funds
+pub fn mark_failed(
+    conn: &mut PgConnection,
+    admin_id: i32,
+    withdrawal_id: i32,
+    reason: String,
+) -> Result<Withdrawal, Error> {
+    conn.transaction::<Withdrawal, Error, _>(|conn| {
+        let withdrawal = withdrawals::table.find(withdrawal_id).for_update().first::<Withdrawal>(conn)?;
+
+        if withdrawal.status != statuses::QUEUED && withdrawal.status != statuses::PROCESSING {
+            return Err(Error::validation_error("This withdrawal is already closed"));
+        }
+
+        close(conn, &withdrawal, statuses::FAILED, Some(admin_id), Some(reason))
+    })
+}
+
+/// Return a withdrawal's funds to its wallet and close it
+fn close(
+    conn: &mut PgConnection,
+    withdrawal: &Withdrawal,
+    status: &str,
+    admin_id: Option<i32>,
+    reason: Option<String>,
+) -> Result<Withdrawal, Error> {
+    let now = Utc::now();
+
+    diesel::update(wallets::table.find(withdrawal.wallet_id))
+        .set((
+            wallets::balance.eq(wallets::balance + (&withdrawal.amount + &withdrawal.network_fee)),
+            wallets::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    diesel::update(transactions::table.find(withdrawal.transaction_id))
+        .set((
+            transactions::status.eq(status),
+            transactions::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    let withdrawal = diesel::update(withdrawal)
+        .set((
+            withdrawals::status.eq(status),
+            withdrawals::failure_reason.eq(reason),
+            withdrawals::processed_by.eq(admin_id.or(withdrawal.processed_by)),
+            withdrawals::processed_at.eq(now),
+            withdrawals::updated_at.eq(now),
+        ))
+        .get_result::<Withdrawal>(conn)?;
+
+    info!(withdrawal_id = withdrawal.id, status = %status, "Withdrawal closed, funds returned");
+    Ok(withdrawal)
+}
+
+/// A withdrawal with where it goes and who asked for it, for the admin queue
+#[derive(Debug, Serialize)]
+pub struct QueueEntry {
+    #[serde(flatten)]
+    pub withdrawal: Withdrawal,
+    pub address: String,
+    pub username: String,
+}
+
+/// One page of withdrawals, oldest first, optionally in one status
+pub fn queue(
+    conn: &mut PgConnection,
+    status: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<QueueEntry>, i64), Error> {
+    let mut query = withdrawals::table
+        .inner_join(withdrawal_addresses::table)
+        .inner_join(users::table.on(users::id.eq(withdrawals::user_id)))
+        .select((withdrawals::all_columns, withdrawal_addresses::address, users::username))
+        .into_boxed();
+    let mut count_query = withdrawals::table.into_boxed();
+
+    if let Some(status) = status {
+        query = query.filter(withdrawals::status.eq(status.to_string()));
+        count_query = count_query.filter(withdrawals::status.eq(status.to_string()));
+    }
+
+    let total = count_query.count().get_result::<i64>(conn)?;
+    let entries = query
+        .order(withdrawals::created_at.asc())
+        .limit(limit)
+        .offset(offset)
+        .load::<(Withdrawal, String, String)>(conn)?
+        .into_iter()
+        .map(|(withdrawal, address, username)| QueueEntry {
+            withdrawal,
+            address,
+            username,
+        })
+        .collect();
+
+    Ok((entries, total))
+}
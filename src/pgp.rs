@@ -0,0 +1,164 @@
+//! PGP signature and encryption checks
+//!
+//! Users prove they hold the key on their account by signing a challenge the server gave
+//! them (`gpg --clearsign`). Only the public key is ever stored, so the server can check who a
+//! message is encrypted to and encrypt to a key, but never decrypt.
+
+use pgp::composed::{Esk, Message};
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use pgp::types::{KeyId, KeyTrait, PublicKeyTrait};
+use pgp::{cleartext::CleartextSignedMessage, Deserializable, SignedPublicKey};
+use rand::rngs::OsRng;
+
+use crate::errors::Error;
+
+/// Parse an ASCII-armored public key
+pub fn parse_public_key(armored: &str) -> Result<SignedPublicKey, Error> {
+    let (key, _) = SignedPublicKey::from_string(armored)
+        .map_err(|err| Error::PGPError(format!("Invalid public key: {}", err)))?;
+
+    Ok(key)
+}
+
+/// Check a cleartext-signed message against a public key and return the signed text
+///
+/// The signature may come from the primary key or any of its subkeys.
+pub fn verify_cleartext(armored_key: &str, signed_message: &str) -> Result<String, Error> {
+    let key = parse_public_key(armored_key)?;
+
+    let (message, _) = CleartextSignedMessage::from_string(signed_message)
+        .map_err(|err| Error::validation_error(format!("Not a PGP signed message: {}", err)))?;
+
+    let verified = message.verify(&key).is_ok()
+        || key
+            .public_subkeys
+            .iter()
+            .any(|subkey| message.verify(subkey).is_ok());
+
+    if !verified {
+        return Err(Error::validation_error("The signature does not match the PGP key on your account"));
+    }
+
+    Ok(message.signed_text())
+}
+
+/// Check that `signed_message` is `expected` signed with the key
+///
+/// Line endings and trailing whitespace are ignored, since mail clients and terminals
+/// routinely change them.
+pub fn verify_challenge(armored_key: &str, signed_message: &str, expected: &str) -> Result<(), Error> {
+    let signed_text = verify_cleartext(armored_key, signed_message)?;
+
+    if normalize(&signed_text) != normalize(expected) {
+        return Err(Error::validation_error("The signed text does not match the challenge"));
+    }
+
+    Ok(())
+}
+
+fn normalize(text: &str) -> String {
+    text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim().to_string()
+}
+
+/// Whether `text` looks like an ASCII-armored OpenPGP message
+pub fn is_armored_message(text: &str) -> bool {
+    text.trim_start().starts_with("-----BEGIN PGP MESSAGE-----")
+}
+
+/// The key ids an ASCII-armored message is encrypted to
+///
+/// Fails for anything that is not an armored, public-key encrypted OpenPGP message, so
+/// plaintext, signed-only and passphrase-only messages are all rejected.
+pub fn recipients(armored_message: &str) -> Result<Vec<KeyId>, Error> {
+    if !is_armored_message(armored_message) {
+        return Err(Error::validation_error("Messages must be ASCII-armored PGP messages"));
+    }
+
+    let (message, _) = Message::from_string(armored_message)
+        .map_err(|err| Error::validation_error(format!("Not a PGP message: {}", err)))?;
+
+    encrypted_recipients(message)
+}
+
+/// The key ids an encrypted file is encrypted to, and whether it is ASCII-armored
+///
+/// Accepts binary or armored OpenPGP (what `gpg --encrypt` writes, with or without
+/// `--armor`) and fails for anything else, including files that are only signed, compressed
+/// or passphrase-encrypted.
+pub fn file_recipients(data: &[u8]) -> Result<(Vec<KeyId>, bool), Error> {
+    let armored = data.starts_with(b"-----BEGIN PGP MESSAGE-----");
+    // Binary OpenPGP starts with a packet header, which always has the top bit set
+    if !armored && data.first().map_or(true, |first| first & 0x80 == 0) {
+        return Err(Error::validation_error("Attachments must be PGP-encrypted files"));
+    }
+
+    let message = if armored {
+        Message::from_armor_single(data).map(|(message, _)| message)
+    } else {
+        Message::from_bytes(data)
+    }
+    .map_err(|err| Error::validation_error(format!("Not a PGP file: {}", err)))?;
+
+    Ok((encrypted_recipients(message)?, armored))
+}
+
+fn encrypted_recipients(message: Message) -> Result<Vec<KeyId>, Error> {
+    let Message::Encrypted { esk, .. } = message else {
+        return Err(Error::validation_error("The PGP message is not encrypted"));
+    };
+
+    let recipients: Vec<KeyId> = esk
+        .iter()
+        .filter_map(|esk| match esk {
+            Esk::PublicKeyEncryptedSessionKey(pkesk) => Some(pkesk.id().clone()),
+            _ => None,
+        })
+        .collect();
+
+    if recipients.is_empty() {
+        return Err(Error::validation_error("The PGP message is not encrypted to any public key"));
+    }
+
+    Ok(recipients)
+}
+
+/// Whether one of `recipients` is the key or one of its subkeys
+///
+/// Messages with hidden recipients (`--throw-keyids`) do not match, since nothing can be
+/// checked about them.
+pub fn encrypted_to(recipients: &[KeyId], armored_key: &str) -> Result<bool, Error> {
+    let key = parse_public_key(armored_key)?;
+
+    let mut key_ids = vec![key.key_id()];
+    key_ids.extend(key.public_subkeys.iter().map(|subkey| subkey.key_id()));
+
+    Ok(recipients.iter().any(|recipient| key_ids.contains(recipient)))
+}
+
+/// Encrypt `plaintext` to every key given and return the armored message
+///
+/// Each key is encrypted to through its first encryption subkey, or the primary key when it
+/// can encrypt itself.
+pub fn encrypt(plaintext: &str, armored_keys: &[&str]) -> Result<String, Error> {
+    let keys = armored_keys
+        .iter()
+        .map(|armored| parse_public_key(armored))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut encryption_keys: Vec<&dyn PublicKeyTrait> = Vec::with_capacity(keys.len());
+    for key in &keys {
+        match key.public_subkeys.iter().find(|subkey| subkey.is_encryption_key()) {
+            Some(subkey) => encryption_keys.push(subkey),
+            None if key.is_encryption_key() => encryption_keys.push(key),
+            None => return Err(Error::PGPError("The PGP key has no encryption key".to_string())),
+        }
+    }
+
+    let message = Message::new_literal("", plaintext)
+        .encrypt_to_keys(&mut OsRng, SymmetricKeyAlgorithm::AES256, &encryption_keys)
+        .map_err(|err| Error::PGPError(format!("Could not encrypt the message: {}", err)))?;
+
+    message
+        .to_armored_string(None)
+        .map_err(|err| Error::PGPError(format!("Could not armor the message: {}", err)))
+}
@@ -0,0 +1,341 @@
+//! Fiat reference pricing
+//!
+//! Vendors can price listings in a fiat currency. The BTC and XMR amounts are worked out at
+//! checkout from exchange rates supplied by an `ExchangeRateProvider`, cached in memory and
+//! refused once they are older than the configured staleness limit.
+
+pub mod providers;
+
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::constants::pricing::{BTC_SCALE, FIAT_SCALE, XMR_SCALE};
+use crate::errors::Error;
+use crate::models::cart::ShippingOption;
+use crate::models::payment::PaymentCurrency;
+use crate::models::product::{Product, ProductVariant};
+use crate::settings::{Pricing, SETTINGS};
+
+/// Price of one coin of each supported cryptocurrency in a fiat currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRates {
+    pub fiat_currency: String,
+    pub btc: BigDecimal,
+    pub xmr: BigDecimal,
+    /// When the provider says these rates were observed
+    pub as_of: DateTime<Utc>,
+    /// Name of the provider that supplied the rates
+    pub source: String,
+}
+
+impl ExchangeRates {
+    /// The price of one coin of `currency`
+    pub fn rate(&self, currency: PaymentCurrency) -> &BigDecimal {
+        match currency {
+            PaymentCurrency::BTC => &self.btc,
+            PaymentCurrency::XMR => &self.xmr,
+        }
+    }
+
+    /// Convert a fiat amount into `currency`, rounded up to the currency's precision
+    pub fn convert(&self, fiat_amount: &BigDecimal, currency: PaymentCurrency) -> Result<BigDecimal, Error> {
+        let rate = self.rate(currency);
+        if rate <= &BigDecimal::zero() {
+            return Err(Error::ExchangeRateUnavailable(format!(
+                "{:?}/{} rate from {} is not positive",
+                currency, self.fiat_currency, self.source
+            )));
+        }
+
+        Ok((fiat_amount / rate).with_scale_round(crypto_scale(currency), RoundingMode::Up))
+    }
+
+    fn age(&self) -> Duration {
+        Utc::now() - self.as_of
+    }
+}
+
+/// A source of exchange rates
+#[async_trait]
+pub trait ExchangeRateProvider: Send + Sync {
+    /// A short name recorded on every quote made with this provider's rates
+    fn name(&self) -> &str;
+
+    /// Fetch the current rates for a fiat currency
+    async fn fetch(&self, fiat_currency: &str) -> Result<ExchangeRates, Error>;
+}
+
+/// Caches the rates of a provider
+///
+/// Rates are reused for `cache_ttl` before the provider is asked again. If the provider fails,
+/// the last good rates are used for as long as they are younger than `max_staleness`.
+pub struct RateCache {
+    provider: Box<dyn ExchangeRateProvider>,
+    cache_ttl: Duration,
+    max_staleness: Duration,
+    entries: RwLock<HashMap<String, CachedRates>>,
+}
+
+struct CachedRates {
+    rates: ExchangeRates,
+    fetched_at: DateTime<Utc>,
+}
+
+impl RateCache {
+    /// Create a new cache around a provider
+    pub fn new(provider: Box<dyn ExchangeRateProvider>, cache_ttl: Duration, max_staleness: Duration) -> Self {
+        Self {
+            provider,
+            cache_ttl,
+            max_staleness,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Create a cache using the provider selected in the settings
+    pub fn from_settings(settings: &Pricing) -> Result<Self, Error> {
+        let provider = providers::from_settings(settings)?;
+
+        Ok(Self::new(
+            provider,
+            Duration::seconds(settings.cache_ttl_seconds as i64),
+            Duration::seconds(settings.max_staleness_seconds as i64),
+        ))
+    }
+
+    /// Get usable rates for a fiat currency
+    pub async fn rates(&self, fiat_currency: &str) -> Result<ExchangeRates, Error> {
+        let fiat_currency = normalize_fiat_currency(fiat_currency)?;
+
+        if let Some(cached) = self.entries.read().await.get(&fiat_currency) {
+            if Utc::now() - cached.fetched_at < self.cache_ttl && cached.rates.age() < self.max_staleness {
+                return Ok(cached.rates.clone());
+            }
+        }
+
+        match self.provider.fetch(&fiat_currency).await {
+            Ok(rates) if rates.age() < self.max_staleness => {
+                debug!(
+                    fiat_currency = %fiat_currency,
+                    source = %rates.source,
+                    "Exchange rates refreshed"
+                );
+                self.entries.write().await.insert(
+                    fiat_currency,
+                    CachedRates {
+                        rates: rates.clone(),
+                        fetched_at: Utc::now(),
+                    },
+                );
+                Ok(rates)
+            }
+            Ok(rates) => {
+                warn!(
+                    fiat_currency = %fiat_currency,
+                    source = %rates.source,
+                    as_of = %rates.as_of,
+                    "Provider returned stale exchange rates"
+                );
+                Err(Error::ExchangeRateUnavailable(format!(
+                    "{} rates from {} are stale",
+                    fiat_currency, rates.source
+                )))
+            }
+            Err(err) => {
+                warn!(
+                    error = %err,
+                    fiat_currency = %fiat_currency,
+                    provider = %self.provider.name(),
+                    "Failed to fetch exchange rates"
+                );
+
+                // Fall back to the last good rates while they are still fresh enough
+                match self.entries.read().await.get(&fiat_currency) {
+                    Some(cached) if cached.rates.age() < self.max_staleness => Ok(cached.rates.clone()),
+                    _ => Err(err),
+                }
+            }
+        }
+    }
+}
+
+static RATES: Lazy<RateCache> = Lazy::new(|| {
+    RateCache::from_settings(&SETTINGS.pricing).expect("Failed to set up exchange rate provider")
+});
+
+/// Get usable rates for a fiat currency from the configured provider
+pub async fn rates(fiat_currency: &str) -> Result<ExchangeRates, Error> {
+    RATES.rates(fiat_currency).await
+}
+
+/// Get usable rates for several fiat currencies at once, keyed by currency code
+pub async fn rates_for<I>(fiat_currencies: I) -> Result<HashMap<String, ExchangeRates>, Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut all = HashMap::new();
+    for fiat_currency in fiat_currencies {
+        let rates = rates(&fiat_currency).await?;
+        all.insert(rates.fiat_currency.clone(), rates);
+    }
+    Ok(all)
+}
+
+/// Get whatever rates are usable for several fiat currencies, for showing prices
+///
+/// Unlike `rates_for`, a currency without usable rates doesn't fail the call; it is returned
+/// in the second list so the caller can show its prices as unavailable.
+pub async fn usable_rates_for<I>(fiat_currencies: I) -> (HashMap<String, ExchangeRates>, Vec<String>)
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut usable = HashMap::new();
+    let mut missing = Vec::new();
+    for fiat_currency in fiat_currencies {
+        match rates(&fiat_currency).await {
+            Ok(rates) => {
+                usable.insert(rates.fiat_currency.clone(), rates);
+            }
+            Err(_) => missing.push(fiat_currency),
+        }
+    }
+    (usable, missing)
+}
+
+/// What one unit of an order line costs
+#[derive(Debug, Clone)]
+pub struct UnitPrice {
+    /// The price in the payment currency
+    pub amount: BigDecimal,
+    /// The fiat reference price the amount was converted from, if any
+    pub price_fiat: Option<BigDecimal>,
+    pub fiat_currency: Option<String>,
+}
+
+/// Work out the price of one unit of a product in a payment currency
+///
+/// A fiat reference price (the variant's, then the product's) wins over fixed crypto prices
+/// and is converted with `rates`, which must hold the product's fiat currency. Without one,
+/// the variant's crypto price is used, then the product's.
+pub fn unit_price(
+    product: &Product,
+    variant: Option<&ProductVariant>,
+    currency: PaymentCurrency,
+    rates: &HashMap<String, ExchangeRates>,
+) -> Result<UnitPrice, Error> {
+    let price_fiat = variant
+        .and_then(|variant| variant.price_fiat.clone())
+        .or_else(|| product.price_fiat.clone());
+
+    if let (Some(price_fiat), Some(fiat_currency)) = (price_fiat, product.fiat_currency.as_ref()) {
+        let rates = rates.get(fiat_currency).ok_or_else(|| {
+            Error::ExchangeRateUnavailable(format!("No {} rates to price product {}", fiat_currency, product.id))
+        })?;
+
+        return Ok(UnitPrice {
+            amount: rates.convert(&price_fiat, currency)?,
+            price_fiat: Some(price_fiat),
+            fiat_currency: Some(fiat_currency.clone()),
+        });
+    }
+
+    let fixed_price = match currency {
+        PaymentCurrency::BTC => variant
+            .and_then(|variant| variant.price_btc.clone())
+            .or_else(|| product.price_btc.clone()),
+        PaymentCurrency::XMR => variant
+            .and_then(|variant| variant.price_xmr.clone())
+            .or_else(|| product.price_xmr.clone()),
+    };
+
+    fixed_price
+        .map(|amount| UnitPrice {
+            amount,
+            price_fiat: None,
+            fiat_currency: None,
+        })
+        .ok_or_else(|| {
+            Error::validation_error(format!("Product {} is not sold in {:?}", product.id, currency))
+        })
+}
+
+/// Work out the cost of a shipping option in a payment currency
+///
+/// Like listings, a fiat price wins over fixed crypto prices and is converted with `rates`.
+pub fn shipping_price(
+    option: &ShippingOption,
+    currency: PaymentCurrency,
+    rates: &HashMap<String, ExchangeRates>,
+) -> Result<UnitPrice, Error> {
+    if let (Some(price_fiat), Some(fiat_currency)) = (&option.price_fiat, &option.fiat_currency) {
+        let rates = rates.get(fiat_currency).ok_or_else(|| {
+            Error::ExchangeRateUnavailable(format!(
+                "No {} rates to price shipping option {}",
+                fiat_currency, option.id
+            ))
+        })?;
+
+        return Ok(UnitPrice {
+            amount: rates.convert(price_fiat, currency)?,
+            price_fiat: Some(price_fiat.clone()),
+            fiat_currency: Some(fiat_currency.clone()),
+        });
+    }
+
+    let fixed_price = match currency {
+        PaymentCurrency::BTC => option.price_btc.clone(),
+        PaymentCurrency::XMR => option.price_xmr.clone(),
+    };
+
+    // An option without any price ships for free
+    let is_free = option.price_btc.is_none() && option.price_xmr.is_none() && option.price_fiat.is_none();
+    let amount = match fixed_price {
+        Some(amount) => amount,
+        None if is_free => BigDecimal::zero(),
+        None => {
+            return Err(Error::validation_error(format!(
+                "Shipping option {} is not available in {:?}",
+                option.id, currency
+            )))
+        }
+    };
+
+    Ok(UnitPrice {
+        amount,
+        price_fiat: None,
+        fiat_currency: None,
+    })
+}
+
+/// Check a fiat currency code and return it upper-cased
+pub fn normalize_fiat_currency(code: &str) -> Result<String, Error> {
+    let code = code.trim();
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(Error::validation_error(format!("Invalid fiat currency code: {}", code)));
+    }
+
+    Ok(code.to_ascii_uppercase())
+}
+
+/// Check a fiat price entered by a vendor and round it to cents
+pub fn normalize_fiat_price(price: &BigDecimal) -> Result<BigDecimal, Error> {
+    if price <= &BigDecimal::zero() {
+        return Err(Error::validation_error("Fiat price must be greater than zero"));
+    }
+
+    Ok(price.with_scale_round(FIAT_SCALE, RoundingMode::HalfUp))
+}
+
+/// Decimal places kept for amounts in a payment currency
+pub fn crypto_scale(currency: PaymentCurrency) -> i64 {
+    match currency {
+        PaymentCurrency::BTC => BTC_SCALE,
+        PaymentCurrency::XMR => XMR_SCALE,
+    }
+}
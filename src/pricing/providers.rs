@@ -0,0 +1,273 @@
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf, str::FromStr, time::Duration};
+use tracing::debug;
+
+use super::{ExchangeRateProvider, ExchangeRates};
+use crate::constants::{http::DEFAULT_TIMEOUT_SECONDS, tor::DEFAULT_SOCKS_PORT};
+use crate::errors::Error;
+use crate::settings::{Pricing, SETTINGS};
+
+/// Build the provider selected in the settings
+pub fn from_settings(settings: &Pricing) -> Result<Box<dyn ExchangeRateProvider>, Error> {
+    match settings.provider.as_str() {
+        "static" => {
+            let mut provider = StaticProvider::new();
+            if !matches!(SETTINGS.environment.as_str(), "development" | "test") {
+                // Hard-coded rates must never price real orders
+                provider = provider.fixed_at(Utc.timestamp_opt(0, 0).unwrap());
+            }
+            for (fiat_currency, rate) in &settings.static_rates {
+                provider = provider.with_rate(
+                    fiat_currency,
+                    parse_decimal(&rate.btc, "static BTC rate")?,
+                    parse_decimal(&rate.xmr, "static XMR rate")?,
+                );
+            }
+            Ok(Box::new(provider))
+        }
+        "file" => {
+            let path = settings
+                .rates_file
+                .as_ref()
+                .ok_or_else(|| Error::validation_error("pricing.rates_file is required for the file provider"))?;
+            Ok(Box::new(FileProvider::new(path)))
+        }
+        "http" => {
+            let url = settings
+                .rates_url
+                .as_ref()
+                .ok_or_else(|| Error::validation_error("pricing.rates_url is required for the http provider"))?;
+            Ok(Box::new(HttpProvider::new(url)?))
+        }
+        other => Err(Error::validation_error(format!(
+            "Unknown exchange rate provider: {}",
+            other
+        ))),
+    }
+}
+
+/// Fixed rates, for development and tests
+///
+/// The rates are reported as current unless `fixed_at` gives them a date, in which case the
+/// staleness check rejects them like any other old rates. Outside development and tests
+/// they are dated at the epoch, so they are never used.
+#[derive(Debug, Default)]
+pub struct StaticProvider {
+    rates: HashMap<String, (BigDecimal, BigDecimal)>,
+    as_of: Option<DateTime<Utc>>,
+}
+
+impl StaticProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the price of one BTC and one XMR in a fiat currency
+    pub fn with_rate(mut self, fiat_currency: &str, btc: BigDecimal, xmr: BigDecimal) -> Self {
+        self.rates.insert(fiat_currency.to_ascii_uppercase(), (btc, xmr));
+        self
+    }
+
+    /// Report the rates as of a fixed time instead of now
+    pub fn fixed_at(mut self, as_of: DateTime<Utc>) -> Self {
+        self.as_of = Some(as_of);
+        self
+    }
+}
+
+#[async_trait]
+impl ExchangeRateProvider for StaticProvider {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    async fn fetch(&self, fiat_currency: &str) -> Result<ExchangeRates, Error> {
+        let (btc, xmr) = self.rates.get(fiat_currency).ok_or_else(|| {
+            Error::ExchangeRateUnavailable(format!("No static rate for {}", fiat_currency))
+        })?;
+
+        Ok(ExchangeRates {
+            fiat_currency: fiat_currency.to_string(),
+            btc: btc.clone(),
+            xmr: xmr.clone(),
+            as_of: self.as_of.unwrap_or_else(Utc::now),
+            source: self.name().to_string(),
+        })
+    }
+}
+
+/// Rates read from a JSON file that an operator or an offline job keeps up to date
+///
+/// ```json
+/// { "as_of": "2023-05-09T12:00:00Z", "rates": { "USD": { "btc": "60000.00", "xmr": "150.00" } } }
+/// ```
+///
+/// When `as_of` is missing the file's modification time is used instead, so a file that is
+/// no longer refreshed goes stale.
+#[derive(Debug)]
+pub struct FileProvider {
+    path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct RatesFile {
+    as_of: Option<DateTime<Utc>>,
+    rates: HashMap<String, RatesFileEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RatesFileEntry {
+    btc: String,
+    xmr: String,
+}
+
+impl FileProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl ExchangeRateProvider for FileProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn fetch(&self, fiat_currency: &str) -> Result<ExchangeRates, Error> {
+        let unavailable = |err: &dyn std::fmt::Display| {
+            Error::ExchangeRateUnavailable(format!("Cannot read {}: {}", self.path.display(), err))
+        };
+
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|err| unavailable(&err))?;
+        let file: RatesFile = serde_json::from_str(&contents).map_err(|err| unavailable(&err))?;
+
+        let as_of = match file.as_of {
+            Some(as_of) => as_of,
+            None => {
+                let modified = tokio::fs::metadata(&self.path)
+                    .await
+                    .and_then(|metadata| metadata.modified())
+                    .map_err(|err| unavailable(&err))?;
+                DateTime::<Utc>::from(modified)
+            }
+        };
+
+        let entry = file
+            .rates
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(fiat_currency))
+            .map(|(_, entry)| entry)
+            .ok_or_else(|| {
+                Error::ExchangeRateUnavailable(format!(
+                    "{} has no rate for {}",
+                    self.path.display(),
+                    fiat_currency
+                ))
+            })?;
+
+        Ok(ExchangeRates {
+            fiat_currency: fiat_currency.to_string(),
+            btc: parse_decimal(&entry.btc, "BTC rate")?,
+            xmr: parse_decimal(&entry.xmr, "XMR rate")?,
+            as_of,
+            source: self.name().to_string(),
+        })
+    }
+}
+
+/// Rates from an HTTP price API using the CoinGecko `simple/price` response format
+///
+/// Requests go through the local Tor SOCKS proxy when Tor is enabled, so the marketplace
+/// never contacts the rate source from its real address.
+#[derive(Debug)]
+pub struct HttpProvider {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl HttpProvider {
+    pub fn new(base_url: impl Into<String>) -> Result<Self, Error> {
+        let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECONDS));
+
+        if SETTINGS.tor.enabled {
+            let proxy = reqwest::Proxy::all(format!("socks5h://127.0.0.1:{}", DEFAULT_SOCKS_PORT))
+                .map_err(|err| Error::ExchangeRateUnavailable(format!("Invalid Tor proxy: {}", err)))?;
+            builder = builder.proxy(proxy);
+        }
+
+        let client = builder
+            .build()
+            .map_err(|err| Error::ExchangeRateUnavailable(format!("Cannot build HTTP client: {}", err)))?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl ExchangeRateProvider for HttpProvider {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn fetch(&self, fiat_currency: &str) -> Result<ExchangeRates, Error> {
+        let vs_currency = fiat_currency.to_ascii_lowercase();
+        let url = format!(
+            "{}/simple/price?ids=bitcoin,monero&vs_currencies={}&include_last_updated_at=true",
+            self.base_url, vs_currency
+        );
+
+        debug!(url = %url, "Fetching exchange rates");
+
+        let body: HashMap<String, HashMap<String, serde_json::Value>> = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|err| Error::ExchangeRateUnavailable(format!("Rate request failed: {}", err)))?
+            .json()
+            .await
+            .map_err(|err| Error::ExchangeRateUnavailable(format!("Invalid rate response: {}", err)))?;
+
+        let coin = |id: &str| {
+            body.get(id).ok_or_else(|| {
+                Error::ExchangeRateUnavailable(format!("Rate response has no {} price", id))
+            })
+        };
+        let price = |id: &str| -> Result<BigDecimal, Error> {
+            let value = coin(id)?.get(&vs_currency).ok_or_else(|| {
+                Error::ExchangeRateUnavailable(format!("Rate response has no {} price in {}", id, fiat_currency))
+            })?;
+            parse_decimal(&value.to_string(), "rate")
+        };
+
+        // Use the older of the two timestamps so neither rate looks fresher than it is
+        let as_of = ["bitcoin", "monero"]
+            .iter()
+            .filter_map(|id| coin(id).ok()?.get("last_updated_at")?.as_i64())
+            .filter_map(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+            .min()
+            .unwrap_or_else(Utc::now);
+
+        Ok(ExchangeRates {
+            fiat_currency: fiat_currency.to_string(),
+            btc: price("bitcoin")?,
+            xmr: price("monero")?,
+            as_of,
+            source: self.name().to_string(),
+        })
+    }
+}
+
+fn parse_decimal(value: &str, what: &str) -> Result<BigDecimal, Error> {
+    BigDecimal::from_str(value.trim())
+        .map_err(|err| Error::ExchangeRateUnavailable(format!("Invalid {} {:?}: {}", what, value, err)))
+}
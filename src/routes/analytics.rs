@@ -0,0 +1,133 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+
+use crate::constants::analytics::DEFAULT_RANGE_DAYS;
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_vendor;
+use crate::models::analytics::{self, ActionItem, Bucket, Period, ProductStats, SeriesPoint, VendorSummary};
+use crate::templates::VendorStatsContext;
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+
+pub fn create_route() -> Router {
+    Router::new()
+        .route("/vendor/analytics/summary", get(get_summary))
+        .route("/vendor/analytics/dashboard", get(get_dashboard_stats))
+        .route("/vendor/analytics/products", get(get_product_stats))
+        .route("/vendor/analytics/products/:id/series", get(get_product_series))
+        .route("/vendor/orders/action-queue", get(get_action_queue))
+        .layer(middleware::from_fn(require_vendor))
+}
+
+#[derive(Debug, Deserialize)]
+struct RangeQuery {
+    #[serde(default = "default_days")]
+    days: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeriesQuery {
+    #[serde(default = "default_days")]
+    days: i64,
+    #[serde(default)]
+    bucket: Bucket,
+}
+
+fn default_days() -> i64 {
+    DEFAULT_RANGE_DAYS
+}
+
+/// The dashboard's headline numbers over the last `days` days
+///
+/// # Arguments
+/// * `token_user` - The vendor
+/// * `query` - How many days to cover
+///
+/// # Returns
+/// * `Result<CustomResponse<VendorSummary>, Error>` - The summary or an error
+async fn get_summary(
+    token_user: TokenUser,
+    Query(query): Query<RangeQuery>,
+) -> Result<CustomResponse<VendorSummary>, Error> {
+    let period = Period::last_days(query.days)?;
+
+    let mut conn = get_connection()?;
+    let summary = analytics::summary(&mut conn, token_user.id, period)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(summary)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// The stats block of the vendor dashboard, in the shape `vendor_dashboard.html` renders
+async fn get_dashboard_stats(
+    token_user: TokenUser,
+    Query(query): Query<RangeQuery>,
+) -> Result<CustomResponse<VendorStatsContext>, Error> {
+    let period = Period::last_days(query.days)?;
+
+    let mut conn = get_connection()?;
+    let stats = VendorStatsContext::from(analytics::summary(&mut conn, token_user.id, period)?);
+
+    let res = CustomResponseBuilder::new()
+        .body(stats)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Views, orders, conversion and sales for each of the vendor's products
+async fn get_product_stats(
+    token_user: TokenUser,
+    Query(query): Query<RangeQuery>,
+) -> Result<CustomResponse<Vec<ProductStats>>, Error> {
+    let period = Period::last_days(query.days)?;
+
+    let mut conn = get_connection()?;
+    let stats = analytics::product_stats(&mut conn, token_user.id, period)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(stats)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// One product's views and sales per day or week
+async fn get_product_series(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Query(query): Query<SeriesQuery>,
+) -> Result<CustomResponse<Vec<SeriesPoint>>, Error> {
+    let period = Period::last_days(query.days)?;
+
+    let mut conn = get_connection()?;
+    let series = analytics::product_series(&mut conn, token_user.id, id, period, query.bucket)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(series)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Orders waiting on the vendor, most urgent first
+async fn get_action_queue(token_user: TokenUser) -> Result<CustomResponse<Vec<ActionItem>>, Error> {
+    let mut conn = get_connection()?;
+    let queue = analytics::action_queue(&mut conn, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(queue)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
@@ -0,0 +1,176 @@
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Datelike, Utc};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::constants::pagination::MAX_PAGE_SIZE;
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_admin;
+use crate::models::announcement::{self, Announcement};
+use crate::settings::SETTINGS;
+use crate::templates::{AnnouncementContext, AnnouncementsTemplate};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
+
+pub fn create_route() -> Router {
+    let admin_routes = Router::new()
+        .route("/admin/announcements", post(publish_announcement))
+        .route("/admin/announcements/:id", delete(withdraw_announcement))
+        .layer(middleware::from_fn(require_admin));
+
+    Router::new()
+        .route("/announcements", get(get_announcements))
+        .route("/announcements/key", get(get_key))
+        .route("/announcements/archive", get(archive_page))
+        .merge(admin_routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    include_expired: bool,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Deserialize)]
+struct PublishBody {
+    severity: String,
+    /// Cleartext-signed with the marketplace key; the first line is the title
+    signed_message: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// The announcements for the banner in `layouts/base.html`
+///
+/// A page should still render when they can't be loaded, so failures only log.
+pub fn banner() -> Vec<AnnouncementContext> {
+    let announcements = get_connection()
+        .and_then(|mut conn| announcement::active(&mut conn, SETTINGS.announcements.banner_limit));
+
+    match announcements {
+        Ok(announcements) => announcements.iter().map(to_context).collect(),
+        Err(err) => {
+            warn!(error = %err, "Could not load announcements for the banner");
+            Vec::new()
+        }
+    }
+}
+
+fn to_context(announcement: &Announcement) -> AnnouncementContext {
+    AnnouncementContext {
+        id: announcement.id,
+        severity: announcement.severity.clone(),
+        title: announcement.title.clone(),
+        body: announcement.body.clone(),
+        signed_message: announcement.signed_message.clone(),
+        created_at: announcement.created_at.format("%Y-%m-%d %H:%M").to_string(),
+        expires_at: announcement
+            .expires_at
+            .map(|expires_at| expires_at.format("%Y-%m-%d %H:%M").to_string()),
+        is_expired: announcement.is_expired(),
+    }
+}
+
+/// Announcements newest first, without expired ones unless asked for
+async fn get_announcements(Query(query): Query<ListQuery>) -> Result<CustomResponse<Vec<Announcement>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (announcements, total) = announcement::list(
+        &mut conn,
+        query.include_expired,
+        limit as i64,
+        query.offset as i64,
+    )?;
+
+    Ok(response_formatter::format_paginated_success(
+        announcements,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// The marketplace's public key, to verify announcements against
+async fn get_key() -> Result<Response, Error> {
+    let key = SETTINGS
+        .announcements
+        .pgp_public_key
+        .clone()
+        .ok_or_else(Error::not_found)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pgp-keys".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"marketplace.asc\"".to_string(),
+            ),
+        ],
+        key,
+    )
+        .into_response())
+}
+
+/// Every announcement that was not withdrawn, with its signed text
+async fn archive_page(Query(query): Query<ListQuery>) -> Result<AnnouncementsTemplate, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (listed, _) = announcement::list(&mut conn, true, limit as i64, query.offset as i64)?;
+
+    Ok(AnnouncementsTemplate {
+        user: None,
+        current_year: Utc::now().year(),
+        announcements: banner(),
+        listed: listed.iter().map(to_context).collect(),
+    })
+}
+
+async fn publish_announcement(
+    token_user: TokenUser,
+    Json(body): Json<PublishBody>,
+) -> Result<CustomResponse<Announcement>, Error> {
+    let mut conn = get_connection()?;
+    let announcement = announcement::publish(
+        &mut conn,
+        token_user.id,
+        &body.severity,
+        &body.signed_message,
+        body.expires_at,
+    )?;
+
+    let res = CustomResponseBuilder::new()
+        .body(announcement)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+async fn withdraw_announcement(Path(id): Path<i32>) -> Result<CustomResponse<Announcement>, Error> {
+    let mut conn = get_connection()?;
+    let announcement = announcement::withdraw(&mut conn, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(announcement)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
@@ -0,0 +1,562 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::constants::cart::{MAX_CART_LINES, MAX_LINE_QUANTITY};
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::models::availability::{self, Availability};
+use crate::models::cart::{CartItem, NewCartItem, ShippingOption};
+use crate::models::checkout::{self, OrderLine, PlaceOrder};
+use crate::models::order::Order;
+use crate::models::payment::PaymentCurrency;
+use crate::models::product::{ListingState, Product, ProductVariant};
+use crate::pricing::{self, ExchangeRates};
+use crate::schema::{cart_items, product_variants, products, shipping_options};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+
+pub fn create_route() -> Router {
+    Router::new()
+        .route("/cart", get(get_cart).delete(clear_cart))
+        .route("/cart/items", post(add_item))
+        .route("/cart/items/:id", put(update_item).delete(remove_item))
+        .route("/cart/checkout", post(checkout_cart))
+}
+
+#[derive(Debug, Deserialize)]
+struct CartQuery {
+    currency: PaymentCurrency,
+}
+
+/// A buyer's cart priced in one currency, grouped by vendor
+#[derive(Debug, Serialize)]
+struct CartView {
+    currency: PaymentCurrency,
+    vendors: Vec<CartVendorGroup>,
+    /// Sum of the vendors' subtotals; shipping is added at checkout. `None` while a subtotal
+    /// is unavailable
+    total: Option<BigDecimal>,
+    /// Fiat currencies some prices are set in but that have no usable exchange rate right now
+    unavailable_rates: Vec<String>,
+}
+
+/// The part of a cart that becomes one order at checkout
+#[derive(Debug, Serialize)]
+struct CartVendorGroup {
+    vendor_id: i32,
+    /// Lines from a vendor who is away are all unavailable
+    availability: Availability,
+    items: Vec<CartLine>,
+    shipping_options: Vec<PricedShippingOption>,
+    /// `None` when an available line can't be priced, e.g. its fiat rate is unavailable
+    subtotal: Option<BigDecimal>,
+}
+
+#[derive(Debug, Serialize)]
+struct CartLine {
+    #[serde(flatten)]
+    item: CartItem,
+    title: String,
+    /// `false` when the listing or variant can no longer be bought; such lines block checkout
+    available: bool,
+    /// `None` for unavailable lines and for lines whose price can't be worked out right now
+    unit_price: Option<BigDecimal>,
+    line_total: Option<BigDecimal>,
+}
+
+#[derive(Debug, Serialize)]
+struct PricedShippingOption {
+    #[serde(flatten)]
+    option: ShippingOption,
+    price: Option<BigDecimal>,
+}
+
+/// Show the cart priced in a payment currency
+///
+/// Prices are worked out now from the current listings and exchange rates, the same way
+/// checkout will work them out.
+///
+/// # Arguments
+/// * `token_user` - The buyer
+/// * `query` - The currency to price the cart in
+///
+/// # Returns
+/// * `Result<CustomResponse<CartView>, Error>` - The priced cart or an error
+async fn get_cart(
+    token_user: TokenUser,
+    Query(query): Query<CartQuery>,
+) -> Result<CustomResponse<CartView>, Error> {
+    let mut conn = get_connection()?;
+
+    let lines = load_cart(&mut conn, token_user.id)?;
+    let vendor_ids: Vec<i32> = lines
+        .iter()
+        .map(|(_, product)| product.vendor_id)
+        .collect::<HashSet<i32>>()
+        .into_iter()
+        .collect();
+    let options = shipping_options::table
+        .filter(shipping_options::vendor_id.eq_any(&vendor_ids))
+        .filter(shipping_options::is_active.eq(true))
+        .order(shipping_options::id.asc())
+        .load::<ShippingOption>(&mut conn)?;
+
+    let product_ids: Vec<i32> = lines.iter().map(|(item, _)| item.product_id).collect();
+    let option_ids: Vec<i32> = options.iter().map(|option| option.id).collect();
+    let fiat_currencies = checkout::fiat_currencies(&mut conn, &product_ids, &option_ids)?;
+    let (rates, unavailable_rates) = pricing::usable_rates_for(fiat_currencies).await;
+
+    let variant_ids: Vec<i32> = lines.iter().filter_map(|(item, _)| item.variant_id).collect();
+    let variants = product_variants::table
+        .filter(product_variants::id.eq_any(variant_ids))
+        .load::<ProductVariant>(&mut conn)?;
+
+    let availability = availability::load(&mut conn, &vendor_ids)?;
+
+    let mut cart = price_cart(lines, variants, options, availability, query.currency, &rates);
+    cart.unavailable_rates = unavailable_rates;
+
+    let res = CustomResponseBuilder::new()
+        .body(cart)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Put a product in the cart, or add to the quantity already there
+///
+/// # Arguments
+/// * `token_user` - The buyer
+/// * `body` - The product, variant and quantity
+///
+/// # Returns
+/// * `Result<CustomResponse<CartItem>, Error>` - The cart line or an error
+async fn add_item(
+    token_user: TokenUser,
+    Json(body): Json<AddItemBody>,
+) -> Result<CustomResponse<CartItem>, Error> {
+    check_quantity(body.quantity)?;
+
+    let mut conn = get_connection()?;
+
+    let item = conn.transaction::<CartItem, Error, _>(|conn| {
+        let product = products::table
+            .find(body.product_id)
+            .filter(products::is_active.eq(true))
+            .filter(products::listing_state.eq(ListingState::Active))
+            .first::<Product>(conn)
+            .optional()?
+            .ok_or_else(|| Error::validation_error(format!("Product {} is not available", body.product_id)))?;
+
+        if product.vendor_id == token_user.id {
+            return Err(Error::validation_error("You cannot buy your own products"));
+        }
+
+        availability::ensure_open(conn, product.vendor_id)?;
+
+        if let Some(variant_id) = body.variant_id {
+            let belongs = diesel::select(diesel::dsl::exists(
+                product_variants::table
+                    .find(variant_id)
+                    .filter(product_variants::product_id.eq(product.id)),
+            ))
+            .get_result::<bool>(conn)?;
+            if !belongs {
+                return Err(Error::validation_error(format!("Variant {} is not available", variant_id)));
+            }
+        }
+
+        let existing = cart_items::table
+            .filter(cart_items::user_id.eq(token_user.id))
+            .filter(cart_items::product_id.eq(body.product_id))
+            .filter(cart_items::variant_id.is_not_distinct_from(body.variant_id))
+            .for_update()
+            .first::<CartItem>(conn)
+            .optional()?;
+
+        match existing {
+            Some(existing) => {
+                let quantity = existing.quantity + body.quantity;
+                check_quantity(quantity)?;
+
+                Ok(diesel::update(&existing)
+                    .set((
+                        cart_items::quantity.eq(quantity),
+                        cart_items::updated_at.eq(Utc::now()),
+                    ))
+                    .get_result::<CartItem>(conn)?)
+            }
+            None => {
+                let lines = cart_items::table
+                    .filter(cart_items::user_id.eq(token_user.id))
+                    .count()
+                    .get_result::<i64>(conn)?;
+                if lines >= MAX_CART_LINES {
+                    return Err(Error::validation_error(format!(
+                        "A cart can hold at most {} different items",
+                        MAX_CART_LINES
+                    )));
+                }
+
+                Ok(diesel::insert_into(cart_items::table)
+                    .values(&NewCartItem {
+                        user_id: token_user.id,
+                        product_id: body.product_id,
+                        variant_id: body.variant_id,
+                        quantity: body.quantity,
+                    })
+                    .get_result::<CartItem>(conn)?)
+            }
+        }
+    })?;
+
+    let res = CustomResponseBuilder::new()
+        .body(item)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Change the quantity of a cart line
+async fn update_item(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateItemBody>,
+) -> Result<CustomResponse<CartItem>, Error> {
+    check_quantity(body.quantity)?;
+
+    let mut conn = get_connection()?;
+
+    let item = diesel::update(
+        cart_items::table
+            .find(id)
+            .filter(cart_items::user_id.eq(token_user.id)),
+    )
+    .set((
+        cart_items::quantity.eq(body.quantity),
+        cart_items::updated_at.eq(Utc::now()),
+    ))
+    .get_result::<CartItem>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(item)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Take a line out of the cart
+async fn remove_item(token_user: TokenUser, Path(id): Path<i32>) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+
+    let deleted = diesel::delete(
+        cart_items::table
+            .find(id)
+            .filter(cart_items::user_id.eq(token_user.id)),
+    )
+    .execute(&mut conn)?;
+
+    if deleted == 0 {
+        return Err(Error::not_found());
+    }
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
+}
+
+/// Empty the cart
+async fn clear_cart(token_user: TokenUser) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+
+    diesel::delete(cart_items::table.filter(cart_items::user_id.eq(token_user.id))).execute(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
+}
+
+/// The orders created by one checkout
+#[derive(Debug, Serialize)]
+struct CheckoutResult {
+    checkout_id: String,
+    currency: PaymentCurrency,
+    orders: Vec<Order>,
+    /// Sum of the orders' totals, shipping included
+    total_amount: BigDecimal,
+}
+
+/// Turn the cart into one order per vendor
+///
+/// Each order gets its own escrow address and the shipping option and address chosen for
+/// that vendor. Either every order is placed and the cart emptied, or nothing is.
+///
+/// # Arguments
+/// * `token_user` - The buyer
+/// * `body` - The payment currency and, for each vendor in the cart, the shipping choice
+///
+/// # Returns
+/// * `Result<CustomResponse<CheckoutResult>, Error>` - The placed orders or an error
+async fn checkout_cart(
+    token_user: TokenUser,
+    Json(body): Json<CheckoutBody>,
+) -> Result<CustomResponse<CheckoutResult>, Error> {
+    let mut conn = get_connection()?;
+
+    // Rates are fetched before the transaction so no network call happens while rows are locked
+    let lines = load_cart(&mut conn, token_user.id)?;
+    if lines.is_empty() {
+        return Err(Error::validation_error("Your cart is empty"));
+    }
+
+    let product_ids: Vec<i32> = lines.iter().map(|(item, _)| item.product_id).collect();
+    let option_ids: Vec<i32> = body.vendors.iter().filter_map(|vendor| vendor.shipping_option_id).collect();
+    let fiat_currencies = checkout::fiat_currencies(&mut conn, &product_ids, &option_ids)?;
+    let rates = pricing::rates_for(fiat_currencies).await?;
+
+    let checkout_id = Uuid::new_v4().to_string();
+
+    let orders = conn.transaction::<Vec<Order>, Error, _>(|conn| {
+        let items = cart_items::table
+            .filter(cart_items::user_id.eq(token_user.id))
+            .order(cart_items::id.asc())
+            .for_update()
+            .load::<CartItem>(conn)?;
+
+        let item_product_ids: Vec<i32> = items.iter().map(|item| item.product_id).collect();
+        let vendor_of: HashMap<i32, i32> = products::table
+            .filter(products::id.eq_any(&item_product_ids))
+            .select((products::id, products::vendor_id))
+            .load::<(i32, i32)>(conn)?
+            .into_iter()
+            .collect();
+
+        // Ordered by vendor so orders are always placed (and rows locked) in the same order
+        let mut by_vendor: BTreeMap<i32, Vec<OrderLine>> = BTreeMap::new();
+        for item in &items {
+            let vendor_id = *vendor_of.get(&item.product_id).ok_or_else(|| {
+                Error::validation_error(format!(
+                    "Product {} is no longer available; remove it from your cart",
+                    item.product_id
+                ))
+            })?;
+            by_vendor.entry(vendor_id).or_default().push(OrderLine {
+                product_id: item.product_id,
+                variant_id: item.variant_id,
+                quantity: item.quantity,
+            });
+        }
+
+        if let Some(extra) = body.vendors.iter().find(|vendor| !by_vendor.contains_key(&vendor.vendor_id)) {
+            return Err(Error::validation_error(format!(
+                "Vendor {} has nothing in your cart",
+                extra.vendor_id
+            )));
+        }
+
+        let mut orders = Vec::with_capacity(by_vendor.len());
+        for (vendor_id, lines) in by_vendor {
+            let choice = body
+                .vendors
+                .iter()
+                .find(|vendor| vendor.vendor_id == vendor_id)
+                .ok_or_else(|| {
+                    Error::validation_error(format!("Choose shipping for the items from vendor {}", vendor_id))
+                })?;
+
+            let order = checkout::place_order(
+                conn,
+                &PlaceOrder {
+                    buyer_id: token_user.id,
+                    currency: body.currency,
+                    encrypted_shipping_address: choice.encrypted_shipping_address.clone(),
+                    shipping_option_id: choice.shipping_option_id,
+                    checkout_id: Some(checkout_id.clone()),
+                    lines,
+                },
+                &rates,
+            )?;
+            orders.push(order);
+        }
+
+        diesel::delete(cart_items::table.filter(cart_items::user_id.eq(token_user.id))).execute(conn)?;
+
+        Ok(orders)
+    })?;
+
+    let total_amount = orders
+        .iter()
+        .fold(BigDecimal::from(0), |total, order| total + &order.total_amount);
+
+    info!(
+        checkout_id = %checkout_id,
+        buyer_id = token_user.id,
+        orders = orders.len(),
+        "Cart checked out"
+    );
+
+    let res = CustomResponseBuilder::new()
+        .body(CheckoutResult {
+            checkout_id,
+            currency: body.currency,
+            orders,
+            total_amount,
+        })
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+struct AddItemBody {
+    product_id: i32,
+    variant_id: Option<i32>,
+    quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateItemBody {
+    quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckoutBody {
+    currency: PaymentCurrency,
+    vendors: Vec<VendorCheckout>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VendorCheckout {
+    vendor_id: i32,
+    shipping_option_id: Option<i32>,
+    encrypted_shipping_address: String,
+}
+
+fn check_quantity(quantity: i32) -> Result<(), Error> {
+    if quantity <= 0 || quantity > MAX_LINE_QUANTITY {
+        return Err(Error::validation_error(format!(
+            "Quantity must be between 1 and {}",
+            MAX_LINE_QUANTITY
+        )));
+    }
+    Ok(())
+}
+
+/// A buyer's cart lines with their products, oldest first
+fn load_cart(conn: &mut PgConnection, user_id: i32) -> Result<Vec<(CartItem, Product)>, Error> {
+    Ok(cart_items::table
+        .inner_join(products::table)
+        .filter(cart_items::user_id.eq(user_id))
+        .order(cart_items::id.asc())
+        .select((cart_items::all_columns, products::all_columns))
+        .load::<(CartItem, Product)>(conn)?)
+}
+
+/// Price every line of a cart and group the lines by vendor
+///
+/// Lines that can no longer be bought, including every line from a vendor who is away, are
+/// kept and marked unavailable instead of failing the whole cart. Lines that can be bought
+/// but not priced, because their fiat rate is missing, leave their vendor's subtotal and the
+/// total unavailable.
+fn price_cart(
+    lines: Vec<(CartItem, Product)>,
+    variants: Vec<ProductVariant>,
+    options: Vec<ShippingOption>,
+    availability: HashMap<i32, Availability>,
+    currency: PaymentCurrency,
+    rates: &HashMap<String, ExchangeRates>,
+) -> CartView {
+    let mut groups: BTreeMap<i32, CartVendorGroup> = BTreeMap::new();
+
+    for (item, product) in lines {
+        let variant = item
+            .variant_id
+            .and_then(|variant_id| variants.iter().find(|variant| variant.id == variant_id));
+        let vendor_availability = availability
+            .get(&product.vendor_id)
+            .cloned()
+            .unwrap_or_else(|| Availability::active(product.vendor_id));
+        let listed = product.is_active
+            && product.listing_state == ListingState::Active
+            && vendor_availability.is_open();
+        let variant_missing = item.variant_id.is_some() && variant.is_none();
+
+        // A missing rate leaves the price unknown for now, not the listing unavailable
+        let (available, unit_price) = if listed && !variant_missing {
+            match pricing::unit_price(&product, variant, currency, rates) {
+                Ok(price) => (true, Some(price.amount)),
+                Err(Error::ExchangeRateUnavailable(_)) => (true, None),
+                Err(_) => (false, None),
+            }
+        } else {
+            (false, None)
+        };
+        let line_total = unit_price
+            .as_ref()
+            .map(|price| price * BigDecimal::from(item.quantity));
+
+        let title = match variant {
+            Some(variant) => format!("{} ({})", product.title, variant.title),
+            None => product.title.clone(),
+        };
+
+        let group = groups.entry(product.vendor_id).or_insert_with(|| CartVendorGroup {
+            vendor_id: product.vendor_id,
+            availability: vendor_availability,
+            items: Vec::new(),
+            shipping_options: options
+                .iter()
+                .filter(|option| option.vendor_id == product.vendor_id)
+                .map(|option| PricedShippingOption {
+                    price: pricing::shipping_price(option, currency, rates)
+                        .ok()
+                        .map(|price| price.amount),
+                    option: option.clone(),
+                })
+                .collect(),
+            subtotal: Some(BigDecimal::from(0)),
+        });
+
+        if available {
+            group.subtotal = match (group.subtotal.take(), &line_total) {
+                (Some(subtotal), Some(line_total)) => Some(subtotal + line_total),
+                _ => None,
+            };
+        }
+
+        group.items.push(CartLine {
+            item,
+            title,
+            available,
+            unit_price,
+            line_total,
+        });
+    }
+
+    let vendors: Vec<CartVendorGroup> = groups.into_values().collect();
+    let total = vendors
+        .iter()
+        .try_fold(BigDecimal::from(0), |total, group| {
+            group.subtotal.as_ref().map(|subtotal| total + subtotal)
+        });
+
+    CartView {
+        currency,
+        vendors,
+        total,
+        unavailable_rates: Vec::new(),
+    }
+}
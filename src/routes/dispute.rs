@@ -0,0 +1,395 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+    Json, Router,
+};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::pagination::MAX_PAGE_SIZE;
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_moderator;
+use crate::models::dispute::{
+    self, evidence_kinds, statuses, Dispute, DisputeEvidence, DisputeMessage, NewDisputeEvidence,
+    NewDisputeMessage, QueueEntry, Resolution,
+};
+use crate::models::message::{self, Conversation, Message};
+use crate::models::notification;
+use crate::models::order::Order;
+use crate::models::payment::Transaction;
+use crate::models::user::roles;
+use crate::schema::{dispute_evidence, dispute_messages, disputes, orders};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
+
+pub fn create_route() -> Router {
+    let moderator_routes = Router::new()
+        .route("/admin/disputes", get(get_queue))
+        .route("/admin/disputes/:id", get(get_dispute_as_moderator))
+        .route("/admin/disputes/:id/conversation", get(get_conversation_as_moderator))
+        .route("/admin/disputes/:id/assign", post(assign_dispute))
+        .route("/admin/disputes/:id/resolve", post(resolve_dispute))
+        .layer(middleware::from_fn(require_moderator));
+
+    Router::new()
+        .route("/orders/:id/dispute", post(open_dispute))
+        .route("/disputes/:id", get(get_dispute))
+        .route("/disputes/:id/evidence", post(add_evidence))
+        .route("/disputes/:id/messages", post(add_message))
+        .merge(moderator_routes)
+}
+
+/// A dispute with everything both sides have submitted
+#[derive(Debug, Serialize)]
+struct DisputeDetails {
+    #[serde(flatten)]
+    dispute: Dispute,
+    order: Order,
+    evidence: Vec<DisputeEvidence>,
+    messages: Vec<DisputeMessage>,
+}
+
+/// An order's conversation as seen by a moderator
+#[derive(Debug, Serialize)]
+struct ModeratorConversation {
+    #[serde(flatten)]
+    conversation: Conversation,
+    messages: Vec<Message>,
+}
+
+/// Open a dispute on a paid order
+///
+/// # Arguments
+/// * `token_user` - The buyer of the order
+/// * `id` - The order ID
+/// * `body` - The reason and the buyer's account of the problem
+///
+/// # Returns
+/// * `Result<CustomResponse<Dispute>, Error>` - The new dispute or an error
+async fn open_dispute(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<OpenDisputeBody>,
+) -> Result<CustomResponse<Dispute>, Error> {
+    let mut conn = get_connection()?;
+    let dispute = dispute::open(&mut conn, id, token_user.id, &body.reason, body.description)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(dispute)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// A dispute as seen by the buyer or vendor of its order
+async fn get_dispute(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<DisputeDetails>, Error> {
+    let mut conn = get_connection()?;
+    let (dispute, order) = dispute::find_for_party(&mut conn, id, token_user.id)?;
+    let details = load_details(&mut conn, dispute, order)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(details)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Add a piece of evidence to an unresolved dispute
+///
+/// # Arguments
+/// * `token_user` - The buyer or vendor
+/// * `id` - The dispute ID
+/// * `body` - The kind of evidence, a description and optional encrypted content
+///
+/// # Returns
+/// * `Result<CustomResponse<DisputeEvidence>, Error>` - The stored evidence or an error
+async fn add_evidence(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<AddEvidenceBody>,
+) -> Result<CustomResponse<DisputeEvidence>, Error> {
+    if !evidence_kinds::ALL.contains(&body.kind.as_str()) {
+        return Err(Error::validation_error(format!("Invalid evidence kind: {}", body.kind)));
+    }
+
+    if body.description.trim().is_empty() {
+        return Err(Error::validation_error("Describe the evidence"));
+    }
+
+    let mut conn = get_connection()?;
+    let (dispute, _) = dispute::find_for_party(&mut conn, id, token_user.id)?;
+    ensure_unresolved(&dispute)?;
+
+    let evidence = diesel::insert_into(dispute_evidence::table)
+        .values(&NewDisputeEvidence {
+            dispute_id: dispute.id,
+            submitted_by: token_user.id,
+            kind: body.kind,
+            description: body.description,
+            encrypted_content: body.encrypted_content,
+        })
+        .get_result::<DisputeEvidence>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(evidence)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Post an encrypted message to a dispute's thread
+///
+/// Buyer, vendor and moderators share one thread per dispute.
+async fn add_message(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<AddMessageBody>,
+) -> Result<CustomResponse<DisputeMessage>, Error> {
+    if body.encrypted_content.trim().is_empty() {
+        return Err(Error::validation_error("Message cannot be empty"));
+    }
+
+    let mut conn = get_connection()?;
+
+    let (dispute, order) = if is_moderator(&token_user) {
+        disputes::table
+            .inner_join(orders::table)
+            .filter(disputes::id.eq(id))
+            .select((disputes::all_columns, orders::all_columns))
+            .first::<(Dispute, Order)>(&mut conn)?
+    } else {
+        dispute::find_for_party(&mut conn, id, token_user.id)?
+    };
+    ensure_unresolved(&dispute)?;
+
+    let message = diesel::insert_into(dispute_messages::table)
+        .values(&NewDisputeMessage {
+            dispute_id: dispute.id,
+            sender_id: token_user.id,
+            encrypted_content: body.encrypted_content,
+        })
+        .get_result::<DisputeMessage>(&mut conn)?;
+
+    notification::notify_parties(&mut conn, &order, dispute.event())?;
+
+    let res = CustomResponseBuilder::new()
+        .body(message)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueueQuery {
+    /// Only show disputes assigned to the current moderator
+    #[serde(default)]
+    mine: bool,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+/// Unresolved disputes, the most urgent first
+///
+/// Each entry says whether its SLA deadline (for assignment, or for a ruling once assigned)
+/// has passed.
+async fn get_queue(
+    token_user: TokenUser,
+    Query(query): Query<QueueQuery>,
+) -> Result<CustomResponse<Vec<QueueEntry>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let assigned_to = query.mine.then_some(token_user.id);
+    let (entries, total) = dispute::queue(&mut conn, assigned_to, limit as i64, query.offset as i64)?;
+
+    Ok(response_formatter::format_paginated_success(
+        entries,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// A dispute with everything both sides have submitted, for moderators
+async fn get_dispute_as_moderator(Path(id): Path<i32>) -> Result<CustomResponse<DisputeDetails>, Error> {
+    let mut conn = get_connection()?;
+
+    let (dispute, order) = disputes::table
+        .inner_join(orders::table)
+        .filter(disputes::id.eq(id))
+        .select((disputes::all_columns, orders::all_columns))
+        .first::<(Dispute, Order)>(&mut conn)?;
+    let details = load_details(&mut conn, dispute, order)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(details)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// The buyer and vendor's conversation about a disputed order, newest messages first
+///
+/// Opening a dispute is what lets moderators see the conversation; reading it marks nothing
+/// read. Messages stay encrypted to the parties, so a ruling that depends on their content
+/// needs the parties to submit it as evidence.
+async fn get_conversation_as_moderator(
+    Path(id): Path<i32>,
+    Query(query): Query<PageQuery>,
+) -> Result<CustomResponse<ModeratorConversation>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+
+    let dispute = disputes::table.find(id).first::<Dispute>(&mut conn)?;
+    let conversation = message::for_order(&mut conn, dispute.order_id)?.ok_or_else(Error::not_found)?;
+    let (messages, total) = message::page(
+        &mut conn,
+        &conversation,
+        None,
+        limit as i64,
+        query.offset as i64,
+    )?;
+
+    Ok(response_formatter::format_paginated_success(
+        ModeratorConversation { conversation, messages },
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// Assign a dispute to a moderator, by default the one making the request
+async fn assign_dispute(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<AssignBody>,
+) -> Result<CustomResponse<Dispute>, Error> {
+    let moderator_id = body.moderator_id.unwrap_or(token_user.id);
+
+    let mut conn = get_connection()?;
+    let dispute = dispute::assign(&mut conn, id, moderator_id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(dispute)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// The outcome of a ruling
+#[derive(Debug, Serialize)]
+struct ResolutionResult {
+    dispute: Dispute,
+    /// The escrow payouts posted to the parties' wallets
+    postings: Vec<Transaction>,
+}
+
+/// Rule on a dispute: a full refund, a full release or a percentage split
+///
+/// # Arguments
+/// * `token_user` - The assigned moderator, or an admin
+/// * `id` - The dispute ID
+/// * `body` - The ruling and the reasoning behind it
+///
+/// # Returns
+/// * `Result<CustomResponse<ResolutionResult>, Error>` - The resolved dispute and payouts
+async fn resolve_dispute(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<ResolveBody>,
+) -> Result<CustomResponse<ResolutionResult>, Error> {
+    let is_admin = token_user.role == roles::ADMIN;
+
+    let mut conn = get_connection()?;
+    let (dispute, postings) =
+        dispute::resolve(&mut conn, id, token_user.id, is_admin, body.resolution, body.notes)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(ResolutionResult { dispute, postings })
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenDisputeBody {
+    reason: String,
+    description: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddEvidenceBody {
+    kind: String,
+    description: String,
+    encrypted_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddMessageBody {
+    encrypted_content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssignBody {
+    moderator_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolveBody {
+    resolution: Resolution,
+    notes: String,
+}
+
+fn is_moderator(token_user: &TokenUser) -> bool {
+    token_user.role == roles::MODERATOR || token_user.role == roles::ADMIN
+}
+
+fn ensure_unresolved(dispute: &Dispute) -> Result<(), Error> {
+    if dispute.status == statuses::RESOLVED {
+        return Err(Error::validation_error("This dispute is already resolved"));
+    }
+    Ok(())
+}
+
+fn load_details(conn: &mut PgConnection, dispute: Dispute, order: Order) -> Result<DisputeDetails, Error> {
+    let evidence = dispute_evidence::table
+        .filter(dispute_evidence::dispute_id.eq(dispute.id))
+        .order(dispute_evidence::created_at.asc())
+        .load::<DisputeEvidence>(conn)?;
+
+    let messages = dispute_messages::table
+        .filter(dispute_messages::dispute_id.eq(dispute.id))
+        .order(dispute_messages::created_at.asc())
+        .load::<DisputeMessage>(conn)?;
+
+    Ok(DisputeDetails {
+        dispute,
+        order,
+        evidence,
+        messages,
+    })
+}
@@ -0,0 +1,210 @@
+use axum::{
+    extract::Query,
+    http::{header, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Redirect,
+    },
+    routing::{get, post},
+    Form, Router,
+};
+use axum_extra::{headers::Cookie, TypedHeader};
+use chrono::{DateTime, Datelike, Utc};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::StreamExt;
+
+use crate::constants::events::{FALLBACK_REFRESH_SECONDS, KEEP_ALIVE_SECONDS, SESSION_COOKIE, SESSION_COOKIE_PATH};
+use crate::constants::notifications::CENTER_PAGE_SIZE;
+use crate::database::get_connection;
+use crate::errors::{AuthenticateError, Error};
+use crate::events;
+use crate::models::notification;
+use crate::routes::announcement::banner;
+use crate::routes::frontend::user_context;
+use crate::settings::SETTINGS;
+use crate::templates::{NotificationContext, NotificationsTemplate};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::token;
+
+pub fn create_route() -> Router {
+    Router::new()
+        .route("/events", get(stream_events))
+        .route("/events/ticket", post(issue_ticket))
+        .route("/notifications/live", get(live_page))
+        .route("/notifications/live/read", post(mark_shown_read))
+        .route("/notifications/live/sign-out", post(sign_out))
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamQuery {
+    ticket: Option<String>,
+}
+
+/// The notification center's "mark as read" form
+#[derive(Debug, Deserialize)]
+struct MarkShownReadForm {
+    /// Comma-separated ids of the notifications the page showed
+    #[serde(default)]
+    ids: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Ticket {
+    ticket: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// The user behind a request, from its bearer token or a stream ticket
+fn stream_user(token_user: Result<TokenUser, Error>, ticket: Option<&str>) -> Result<i32, Error> {
+    match (token_user, ticket) {
+        (Ok(token_user), _) => Ok(token_user.id),
+        (Err(_), Some(ticket)) => {
+            events::redeem_ticket(ticket).ok_or(Error::Authenticate(AuthenticateError::InvalidToken))
+        }
+        (Err(err), None) => Err(err),
+    }
+}
+
+/// The user behind a notification center request, from its bearer token or session cookie
+///
+/// The cookie is set at sign-in and holds the same token, so it stops working when the token
+/// expires. It is `SameSite=Strict`, which keeps other sites from posting the center's forms.
+fn session_user(token_user: Result<TokenUser, Error>, cookies: Option<TypedHeader<Cookie>>) -> Result<i32, Error> {
+    if let Ok(token_user) = token_user {
+        return Ok(token_user.id);
+    }
+
+    let TypedHeader(cookies) = cookies.ok_or(Error::Authenticate(AuthenticateError::InvalidToken))?;
+    let session = cookies
+        .get(SESSION_COOKIE)
+        .ok_or(Error::Authenticate(AuthenticateError::InvalidToken))?;
+    let token_data = token::decode(session, SETTINGS.auth.secret.as_str())
+        .map_err(|_| Error::Authenticate(AuthenticateError::InvalidToken))?;
+
+    Ok(token_data.claims.user.id)
+}
+
+/// Trade a token for a ticket that opens event streams, for clients that cannot send headers
+async fn issue_ticket(token_user: TokenUser) -> Result<CustomResponse<Ticket>, Error> {
+    let (ticket, expires_at) = events::issue_ticket(token_user.id);
+
+    let res = CustomResponseBuilder::new()
+        .body(Ticket { ticket, expires_at })
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Stream the user's live notifications as Server-Sent Events
+///
+/// Each event is named after its type and carries the ids of what changed as JSON. A stream
+/// that falls too far behind gets a `resync` event and should refetch everything it shows.
+/// Authenticate with a bearer token, or with `?ticket=` from `POST /events/ticket` when using
+/// `EventSource`.
+async fn stream_events(
+    token_user: Result<TokenUser, Error>,
+    Query(query): Query<StreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, Error> {
+    let user_id = stream_user(token_user, query.ticket.as_deref())?;
+
+    let stream = BroadcastStream::new(events::subscribe()).filter_map(move |received| match received {
+        Ok(envelope) if envelope.user_id == user_id => SseEvent::default()
+            .event(envelope.event.name())
+            .json_data(&envelope.event)
+            .ok()
+            .map(Ok),
+        Ok(_) => None,
+        Err(BroadcastStreamRecvError::Lagged(missed)) => {
+            Some(Ok(SseEvent::default().event("resync").data(missed.to_string())))
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(KEEP_ALIVE_SECONDS))))
+}
+
+/// The notification center: the latest notifications
+///
+/// The page reloads itself, for browsers running without JavaScript, such as Tor Browser at
+/// its safest setting, where the event stream cannot be used. Such browsers are signed in by
+/// the session cookie set at sign-in. Showing notifications doesn't mark them read; the
+/// page's form does.
+async fn live_page(
+    token_user: Result<TokenUser, Error>,
+    cookies: Option<TypedHeader<Cookie>>,
+) -> Result<NotificationsTemplate, Error> {
+    let user_id = session_user(token_user, cookies)?;
+    let mut conn = get_connection()?;
+
+    let user = user_context(&mut conn, user_id)?;
+    let (shown, _) = notification::list(&mut conn, user_id, false, CENTER_PAGE_SIZE, 0)?;
+
+    let shown_ids = shown
+        .iter()
+        .filter(|notification| notification.read_at.is_none())
+        .map(|notification| notification.id.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let notifications = shown
+        .into_iter()
+        .map(|notification| NotificationContext {
+            is_read: notification.read_at.is_some(),
+            kind: notification.kind,
+            title: notification.title,
+            link: notification.link,
+            created_at: notification.created_at.format("%Y-%m-%d %H:%M").to_string(),
+        })
+        .collect();
+
+    Ok(NotificationsTemplate {
+        unread: user.unread_notifications,
+        user: Some(user),
+        current_year: Utc::now().year(),
+        announcements: banner(),
+        refresh_seconds: FALLBACK_REFRESH_SECONDS,
+        refresh_url: "/notifications/live".to_string(),
+        shown_ids,
+        notifications,
+    })
+}
+
+/// Mark the notifications the center showed as read, then go back to it
+///
+/// Only the ids the page listed are marked, so anything that arrived since stays unread.
+async fn mark_shown_read(
+    token_user: Result<TokenUser, Error>,
+    cookies: Option<TypedHeader<Cookie>>,
+    Form(form): Form<MarkShownReadForm>,
+) -> Result<Redirect, Error> {
+    let user_id = session_user(token_user, cookies)?;
+
+    let ids = form
+        .ids
+        .split(',')
+        .filter(|id| !id.is_empty())
+        .map(|id| id.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| Error::validation_error("Invalid notification id"))?;
+
+    if !ids.is_empty() {
+        let mut conn = get_connection()?;
+        notification::mark_read(&mut conn, user_id, Some(&ids))?;
+    }
+
+    Ok(Redirect::to("/notifications/live"))
+}
+
+/// Clear the notification center's session cookie, then go to the sign-in page
+async fn sign_out() -> impl IntoResponse {
+    let cookie = format!(
+        "{}=; HttpOnly; SameSite=Strict; Path={}; Max-Age=0",
+        SESSION_COOKIE, SESSION_COOKIE_PATH
+    );
+
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/login"))
+}
@@ -0,0 +1,160 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    middleware,
+    routing::{delete, get, put},
+    Json, Router,
+};
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_admin;
+use crate::models::fee::{self, FeeRule, NewFeeRule};
+use crate::models::payment::PaymentCurrency;
+use crate::schema::{escrow_settlements, fee_rules};
+use crate::settings::SETTINGS;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+
+pub fn create_route() -> Router {
+    Router::new()
+        .route("/admin/fees", get(list_rules))
+        .route("/admin/fees/categories/:id", put(set_category_fee))
+        .route("/admin/fees/vendors/:id", put(set_vendor_fee))
+        .route("/admin/fees/rules/:id", delete(delete_rule))
+        .route("/admin/fees/collected", get(collected_fees))
+        .layer(middleware::from_fn(require_admin))
+}
+
+#[derive(Debug, Serialize)]
+struct FeeSchedule {
+    /// Applies where no category or vendor rule does
+    default_bps: i32,
+    rules: Vec<FeeRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetFeeBody {
+    fee_bps: i32,
+}
+
+/// The default fee and every category and vendor rule
+async fn list_rules() -> Result<CustomResponse<FeeSchedule>, Error> {
+    let mut conn = get_connection()?;
+
+    let rules = fee_rules::table
+        .order(fee_rules::id.asc())
+        .load::<FeeRule>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(FeeSchedule {
+            default_bps: SETTINGS.fees.default_bps,
+            rules,
+        })
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Set the fee for a category and, unless they have their own rule, its subcategories
+async fn set_category_fee(
+    Path(id): Path<i32>,
+    Json(body): Json<SetFeeBody>,
+) -> Result<CustomResponse<FeeRule>, Error> {
+    let mut conn = get_connection()?;
+    let rule = fee::set_rule(
+        &mut conn,
+        NewFeeRule {
+            category_id: Some(id),
+            vendor_id: None,
+            fee_bps: body.fee_bps,
+        },
+    )?;
+
+    info!(category_id = id, fee_bps = rule.fee_bps, "Category fee set");
+
+    let res = CustomResponseBuilder::new()
+        .body(rule)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Set the fee for every order of one vendor, overriding category rules
+async fn set_vendor_fee(
+    Path(id): Path<i32>,
+    Json(body): Json<SetFeeBody>,
+) -> Result<CustomResponse<FeeRule>, Error> {
+    let mut conn = get_connection()?;
+    let rule = fee::set_rule(
+        &mut conn,
+        NewFeeRule {
+            category_id: None,
+            vendor_id: Some(id),
+            fee_bps: body.fee_bps,
+        },
+    )?;
+
+    info!(vendor_id = id, fee_bps = rule.fee_bps, "Vendor fee set");
+
+    let res = CustomResponseBuilder::new()
+        .body(rule)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Remove a rule; its category or vendor falls back to the next rule that applies
+async fn delete_rule(Path(id): Path<i32>) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+
+    let deleted = diesel::delete(fee_rules::table.find(id)).execute(&mut conn)?;
+    if deleted == 0 {
+        return Err(Error::not_found());
+    }
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Serialize)]
+struct CollectedFees {
+    currency: PaymentCurrency,
+    orders: i64,
+    released: BigDecimal,
+    fees: BigDecimal,
+}
+
+/// Fees the marketplace has kept, per currency
+async fn collected_fees() -> Result<CustomResponse<Vec<CollectedFees>>, Error> {
+    let mut conn = get_connection()?;
+
+    let totals = escrow_settlements::table
+        .group_by(escrow_settlements::currency)
+        .select((
+            escrow_settlements::currency,
+            diesel::dsl::count_star(),
+            diesel::dsl::sum(escrow_settlements::release),
+            diesel::dsl::sum(escrow_settlements::fee),
+        ))
+        .load::<(PaymentCurrency, i64, Option<BigDecimal>, Option<BigDecimal>)>(&mut conn)?
+        .into_iter()
+        .map(|(currency, orders, released, fees)| CollectedFees {
+            currency,
+            orders,
+            released: released.unwrap_or_default(),
+            fees: fees.unwrap_or_default(),
+        })
+        .collect();
+
+    let res = CustomResponseBuilder::new()
+        .body(totals)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
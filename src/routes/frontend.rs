@@ -4,8 +4,14 @@ use axum::{
     routing::get,
     Router,
 };
+use diesel::prelude::*;
 use serde::Deserialize;
 
+use crate::errors::Error;
+use crate::models::notification;
+use crate::models::user::User;
+use crate::routes::announcement::banner;
+use crate::schema::users;
 use crate::templates::{
     HomeTemplate, LoginTemplate, ProductDetailTemplate, ProductsTemplate, RegisterTemplate,
     UserContext,
@@ -25,6 +31,7 @@ async fn home() -> impl IntoResponse {
     let template = HomeTemplate {
         user: None,
         current_year: chrono::Utc::now().year(),
+        announcements: banner(),
     };
     template
 }
@@ -33,6 +40,7 @@ async fn login() -> impl IntoResponse {
     let template = LoginTemplate {
         user: None,
         current_year: chrono::Utc::now().year(),
+        announcements: banner(),
         error: None,
         pgp_challenge: None,
     };
@@ -43,6 +51,7 @@ async fn register() -> impl IntoResponse {
     let template = RegisterTemplate {
         user: None,
         current_year: chrono::Utc::now().year(),
+        announcements: banner(),
         error: None,
     };
     template
@@ -65,6 +74,7 @@ async fn products(Query(params): Query<ProductsQuery>) -> impl IntoResponse {
     let template = ProductsTemplate {
         user: None,
         current_year: chrono::Utc::now().year(),
+        announcements: banner(),
         products: vec![
             mock_product(1, "Product 1", 4.5, 10),
             mock_product(2, "Product 2", 5.0, 25),
@@ -93,6 +103,7 @@ async fn product_detail(Path(id): Path<i32>) -> impl IntoResponse {
     let template = ProductDetailTemplate {
         user: None,
         current_year: chrono::Utc::now().year(),
+        announcements: banner(),
         product: mock_product_detail(id),
         currency: "btc".to_string(),
     };
@@ -106,6 +117,27 @@ async fn product_image(Path((product_id, image_id)): Path<(i32, i32)>) -> impl I
 }
 
 // Mock data functions
+/// The signed-in user as the layout header shows them, with their unread notification count
+pub fn user_context(conn: &mut PgConnection, user_id: i32) -> Result<UserContext, Error> {
+    let user = users::table.find(user_id).first::<User>(conn)?;
+    let unread_notifications = notification::unread_count(conn, user_id)?;
+
+    Ok(UserContext {
+        is_vendor: user.is_vendor(),
+        is_admin: user.is_admin(),
+        is_moderator: user.is_moderator(),
+        id: user.id,
+        username: user.username,
+        role: user.role,
+        pgp_public_key: user.pgp_public_key,
+        pgp_added_date: None,
+        reputation: user.reputation,
+        review_count: None,
+        created_at: user.created_at.format("%Y-%m-%d").to_string(),
+        unread_notifications,
+    })
+}
+
 fn mock_product(id: i32, title: &str, rating: f64, review_count: i32) -> crate::templates::ProductContext {
     crate::templates::ProductContext {
         id,
@@ -144,7 +176,9 @@ fn mock_product_detail(id: i32) -> crate::templates::ProductDetailContext {
             total_sales: 100,
             response_time: "2 hours".to_string(),
             created_at: "2023-01-01".to_string(),
-            status: "verified".to_string(),
+            status: "active".to_string(),
+            return_date: None,
+            away_message: None,
         },
         category: mock_category(1, "Electronics"),
         price_btc: Some("0.001".to_string()),
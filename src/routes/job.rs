@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_admin;
+use crate::models::job::{self, statuses, JobRecord};
+use crate::schema::jobs;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
+
+pub fn create_route() -> Router {
+    Router::new()
+        .route("/admin/jobs", get(list_jobs))
+        .route("/admin/jobs/:id", get(get_job).delete(discard_job))
+        .route("/admin/jobs/:id/retry", post(retry_job))
+        .layer(middleware::from_fn(require_admin))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    status: Option<String>,
+    kind: Option<String>,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+/// Jobs in the queue, newest first
+///
+/// # Arguments
+/// * `query` - Optional status and kind filters, and pagination
+///
+/// # Returns
+/// * `Result<CustomResponse<Vec<JobRecord>>, Error>` - One page of jobs or an error
+async fn list_jobs(Query(query): Query<ListQuery>) -> Result<CustomResponse<Vec<JobRecord>>, Error> {
+    if let Some(status) = &query.status {
+        if !statuses::ALL.contains(&status.as_str()) {
+            return Err(Error::validation_error(format!("Invalid job status: {}", status)));
+        }
+    }
+
+    let mut conn = get_connection()?;
+    let (records, total) = job::list(
+        &mut conn,
+        query.status.as_deref(),
+        query.kind.as_deref(),
+        query.limit as i64,
+        query.offset as i64,
+    )?;
+
+    Ok(response_formatter::format_paginated_success(
+        records,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        query.limit,
+    ))
+}
+
+/// A single job with its payload and last error
+async fn get_job(Path(id): Path<i64>) -> Result<CustomResponse<JobRecord>, Error> {
+    let mut conn = get_connection()?;
+    let record = jobs::table.find(id).first::<JobRecord>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(record)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Queue a dead job again with a fresh set of attempts
+async fn retry_job(Path(id): Path<i64>) -> Result<CustomResponse<JobRecord>, Error> {
+    let mut conn = get_connection()?;
+    let record = job::retry(&mut conn, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(record)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Drop a dead job for good
+async fn discard_job(Path(id): Path<i64>) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+    job::discard(&mut conn, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
+}
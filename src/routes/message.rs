@@ -1,56 +1,354 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    routing::{get, post},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::database::{get_connection, DbPool};
+use crate::constants::messages::MAX_ATTACHMENT_BYTES;
+use crate::constants::pagination::MAX_PAGE_SIZE;
+use crate::database::get_connection;
 use crate::errors::Error;
-use crate::models::message::{Conversation, ConversationWithMessages, Message, NewMessage};
+use crate::models::attachment::{self, Attachment};
+use crate::models::block::{self, BlockedUser};
+use crate::models::message::{
+    self, Conversation, ConversationReport, ConversationSummary, ConversationWithMessages, Message, SharedMessage,
+};
+use crate::schema::users;
 use crate::utils::authenticate_request::TokenUser;
 use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
 
 pub fn create_route() -> Router {
     Router::new()
         .route("/conversations", get(list_conversations).post(create_conversation))
         .route("/conversations/:id", get(get_conversation))
         .route("/conversations/:id/messages", post(send_message))
+        .route("/conversations/:id/retention", put(set_retention))
+        .route("/conversations/:id/report", post(report_conversation))
+        .route(
+            "/messages/:id/attachments",
+            post(upload_attachment).layer(DefaultBodyLimit::max(MAX_ATTACHMENT_BYTES)),
+        )
+        .route("/attachments/:id", get(download_attachment))
+        .route("/blocks", get(list_blocks))
+        .route("/blocks/:user_id", put(block_user).delete(unblock_user))
+        .route("/orders/:id/conversation", get(get_order_conversation))
+        .route("/messages/settings", get(get_settings).put(update_settings))
 }
 
-// Placeholder implementations - these will be expanded with actual database operations
-async fn list_conversations(token_user: TokenUser) -> Result<CustomResponse<Vec<Conversation>>, Error> {
-    let conversations = Vec::new(); // Placeholder
-    let res = CustomResponseBuilder::new()
-        .body(conversations)
-        .status_code(StatusCode::OK)
-        .build();
-    Ok(res)
+#[derive(Debug, Deserialize)]
+struct CreateConversationBody {
+    user_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendMessageBody {
+    content: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct RetentionBody {
+    /// Days to keep messages, or `null` for the marketplace default
+    retention_days: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportBody {
+    reason: String,
+    details: Option<String>,
+    messages: Vec<SharedMessage>,
+    /// The reporter agrees to share the selected messages with moderators
+    #[serde(default)]
+    consent: bool,
+}
+
+/// How the user's outgoing messages are encrypted
+#[derive(Debug, Serialize, Deserialize)]
+struct MessageSettings {
+    /// The server encrypts plaintext messages to the recipient's key instead of rejecting them
+    server_side_encryption: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+/// The user's conversations, most recently active first, with unread counts
+async fn list_conversations(
+    token_user: TokenUser,
+    Query(query): Query<ListQuery>,
+) -> Result<CustomResponse<Vec<ConversationSummary>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (conversations, total) =
+        message::summaries(&mut conn, token_user.id, limit as i64, query.offset as i64)?;
+
+    Ok(response_formatter::format_paginated_success(
+        conversations,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// Start a conversation with another user, or get the one already open
 async fn create_conversation(
     token_user: TokenUser,
-    Json(body): Json<serde_json::Value>,
+    Json(body): Json<CreateConversationBody>,
 ) -> Result<CustomResponse<Conversation>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let mut conn = get_connection()?;
+    let conversation = message::open(&mut conn, token_user.id, body.user_id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(conversation)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
 }
 
+/// A page of a conversation's messages, newest first; the other side's messages on the page
+/// are marked read
+///
+/// # Arguments
+/// * `token_user` - The authenticated user; must take part in the conversation
+/// * `id` - The conversation
+/// * `query` - The page
+///
+/// # Returns
+/// * `Result<CustomResponse<ConversationWithMessages>, Error>` - The conversation and page, or
+///   not found for anyone outside it
 async fn get_conversation(
     token_user: TokenUser,
     Path(id): Path<i32>,
+    Query(query): Query<ListQuery>,
 ) -> Result<CustomResponse<ConversationWithMessages>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+
+    let conversation = message::find_for_participant(&mut conn, id, token_user.id)?;
+    let (messages, total) = message::page(
+        &mut conn,
+        &conversation,
+        Some(token_user.id),
+        limit as i64,
+        query.offset as i64,
+    )?;
+    let message_ids: Vec<i32> = messages.iter().map(|message| message.id).collect();
+    let attachments = attachment::for_messages(&mut conn, &message_ids)?;
+
+    Ok(response_formatter::format_paginated_success(
+        ConversationWithMessages {
+            other_user_id: conversation.other_user_id(token_user.id),
+            conversation,
+            messages,
+            attachments,
+        },
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
 }
 
+/// The conversation between the buyer and vendor about an order
+///
+/// It is opened at checkout and becomes read-only once the order is completed or cancelled.
+/// Its messages are paged with `GET /conversations/:id`.
+async fn get_order_conversation(
+    token_user: TokenUser,
+    Path(order_id): Path<i32>,
+) -> Result<CustomResponse<Conversation>, Error> {
+    let mut conn = get_connection()?;
+    let conversation = message::find_for_order_party(&mut conn, order_id, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(conversation)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Post a message to a conversation the user takes part in
+///
+/// The body must be PGP-encrypted to the recipient unless the user turned on server-side
+/// encryption.
 async fn send_message(
     token_user: TokenUser,
     Path(conversation_id): Path<i32>,
-    Json(body): Json<serde_json::Value>,
+    Json(body): Json<SendMessageBody>,
 ) -> Result<CustomResponse<Message>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let mut conn = get_connection()?;
+    let sent = message::send(&mut conn, token_user.id, conversation_id, &body.content)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(sent)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Change how long a conversation's messages are kept
+///
+/// Messages past the retention are deleted for both participants, except while the order the
+/// conversation is about is open or disputed.
+async fn set_retention(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<RetentionBody>,
+) -> Result<CustomResponse<Conversation>, Error> {
+    let mut conn = get_connection()?;
+    let conversation = message::set_retention(&mut conn, id, token_user.id, body.retention_days)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(conversation)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Attach a PGP-encrypted file to a message the user sent
+///
+/// The request body is the encrypted file, binary or ASCII-armored. Keep the file name inside
+/// the encrypted data; the server stores nothing about the file but its size.
+async fn upload_attachment(
+    token_user: TokenUser,
+    Path(message_id): Path<i32>,
+    body: Bytes,
+) -> Result<CustomResponse<Attachment>, Error> {
+    let mut conn = get_connection()?;
+    let attachment = attachment::attach(&mut conn, token_user.id, message_id, &body)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(attachment)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Download an attachment's encrypted contents; only the conversation's participants can
+async fn download_attachment(token_user: TokenUser, Path(id): Path<i32>) -> Result<Response, Error> {
+    let mut conn = get_connection()?;
+    let (attachment, data) = attachment::download(&mut conn, id, token_user.id)?;
+
+    let disposition = format!(
+        "attachment; filename=\"attachment-{}.{}\"",
+        attachment.id,
+        if attachment.armored { "asc" } else { "gpg" }
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pgp-encrypted".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+/// Report a conversation, sharing the selected messages with moderators
+///
+/// Moderators only see what the reporter shares, and only with `consent` set.
+async fn report_conversation(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<ReportBody>,
+) -> Result<CustomResponse<ConversationReport>, Error> {
+    let mut conn = get_connection()?;
+    let report = message::report(
+        &mut conn,
+        token_user.id,
+        id,
+        &body.reason,
+        body.details,
+        body.messages,
+        body.consent,
+    )?;
+
+    let res = CustomResponseBuilder::new()
+        .body(report)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// The users the current user has blocked
+async fn list_blocks(token_user: TokenUser) -> Result<CustomResponse<Vec<BlockedUser>>, Error> {
+    let mut conn = get_connection()?;
+    let blocked = block::list(&mut conn, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(blocked)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Stop a user from starting conversations with or messaging the current user
+async fn block_user(token_user: TokenUser, Path(user_id): Path<i32>) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+    block::block(&mut conn, token_user.id, user_id)?;
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
+}
+
+async fn unblock_user(token_user: TokenUser, Path(user_id): Path<i32>) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+    block::unblock(&mut conn, token_user.id, user_id)?;
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
+}
+
+async fn get_settings(token_user: TokenUser) -> Result<CustomResponse<MessageSettings>, Error> {
+    let mut conn = get_connection()?;
+    let server_side_encryption = users::table
+        .find(token_user.id)
+        .select(users::server_side_encryption)
+        .first::<bool>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(MessageSettings { server_side_encryption })
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Opt in or out of having the server encrypt outgoing messages
+///
+/// With it on, plaintext sent to the server is encrypted before it is stored; it is still
+/// seen by the server in transit, so encrypting locally remains the safer choice.
+async fn update_settings(
+    token_user: TokenUser,
+    Json(body): Json<MessageSettings>,
+) -> Result<CustomResponse<MessageSettings>, Error> {
+    let mut conn = get_connection()?;
+    message::set_server_side_encryption(&mut conn, token_user.id, body.server_side_encryption)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(body)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
 }
@@ -1,9 +1,20 @@
 pub mod admin; // Admin routes with middleware
+pub mod moderation;
+pub mod job;
+pub mod fee;
+pub mod withdrawal;
+pub mod analytics;
+pub mod events;
+pub mod notification;
+pub mod support;
+pub mod announcement;
 pub mod status;
 pub mod user;
 pub mod product;
 pub mod product_dry; // DRY implementation of product routes
 pub mod order;
+pub mod dispute;
+pub mod cart;
 pub mod message;
 pub mod payment;
 pub mod vendor;
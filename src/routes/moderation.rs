@@ -0,0 +1,297 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+    Json, Router,
+};
+use diesel::prelude::*;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::constants::pagination::MAX_PAGE_SIZE;
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_moderator;
+use crate::models::message::{self, report_statuses, ConversationReport, ReportDetails};
+use crate::models::moderation::{self, flag_statuses, ListingModerationEvent, QueueEntry};
+use crate::models::product::{ListingState, Product};
+use crate::models::review::{self, FlaggedReview};
+use crate::models::vendor::Review;
+use crate::schema::{listing_moderation_events, products};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
+
+pub fn create_route() -> Router {
+    Router::new()
+        .route("/admin/moderation/queue", get(get_queue))
+        .route("/admin/moderation/products/:id", get(get_history))
+        .route("/admin/moderation/products/:id/approve", post(approve_listing))
+        .route("/admin/moderation/products/:id/reject", post(reject_listing))
+        .route("/admin/moderation/products/:id/suspend", post(suspend_listing))
+        .route("/admin/moderation/reviews", get(get_flagged_reviews))
+        .route("/admin/moderation/reviews/:id/uphold", post(uphold_review_flag))
+        .route("/admin/moderation/reviews/:id/dismiss", post(dismiss_review_flag))
+        .route("/admin/moderation/conversations", get(get_conversation_reports))
+        .route("/admin/moderation/conversations/:id", get(get_conversation_report))
+        .route("/admin/moderation/conversations/:id/uphold", post(uphold_conversation_report))
+        .route("/admin/moderation/conversations/:id/dismiss", post(dismiss_conversation_report))
+        .layer(middleware::from_fn(require_moderator))
+}
+
+#[derive(Debug, Deserialize)]
+struct QueueQuery {
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Deserialize)]
+struct DecisionBody {
+    reason: Option<String>,
+}
+
+/// Listings waiting for a moderator, oldest first
+///
+/// The queue holds listings submitted for review and listings buyers have flagged.
+///
+/// # Arguments
+/// * `query` - Pagination
+///
+/// # Returns
+/// * `Result<CustomResponse<Vec<QueueEntry>>, Error>` - One page of the queue or an error
+async fn get_queue(Query(query): Query<QueueQuery>) -> Result<CustomResponse<Vec<QueueEntry>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (entries, total) = moderation::queue(&mut conn, limit as i64, query.offset as i64)?;
+
+    Ok(response_formatter::format_paginated_success(
+        entries,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// The moderation history of a listing, newest first
+async fn get_history(Path(id): Path<i32>) -> Result<CustomResponse<Vec<ListingModerationEvent>>, Error> {
+    let mut conn = get_connection()?;
+
+    let events = listing_moderation_events::table
+        .filter(listing_moderation_events::product_id.eq(id))
+        .order(listing_moderation_events::created_at.desc())
+        .load::<ListingModerationEvent>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(events)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Approve a listing
+///
+/// Publishes a listing waiting for review or reinstates a suspended one. For a listing that
+/// is already active this dismisses its open flags and leaves it up.
+///
+/// # Arguments
+/// * `token_user` - The moderator
+/// * `id` - The product ID
+/// * `body` - An optional note
+///
+/// # Returns
+/// * `Result<CustomResponse<Product>, Error>` - The product or an error
+async fn approve_listing(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<DecisionBody>,
+) -> Result<CustomResponse<Product>, Error> {
+    let product = decide(id, |conn, product| {
+        moderation::resolve_flags(conn, product.id, flag_statuses::DISMISSED, token_user.id)?;
+
+        if product.listing_state == ListingState::Active {
+            return Ok(product.clone());
+        }
+
+        moderation::transition(conn, product, ListingState::Active, Some(token_user.id), body.reason)
+    })?;
+
+    let res = CustomResponseBuilder::new()
+        .body(product)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Reject a listing waiting for review, or a suspended one, with a reason for the vendor
+///
+/// The vendor can fix a rejected listing and submit it again.
+async fn reject_listing(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<DecisionBody>,
+) -> Result<CustomResponse<Product>, Error> {
+    let product = decide(id, |conn, product| {
+        moderation::resolve_flags(conn, product.id, flag_statuses::UPHELD, token_user.id)?;
+        moderation::transition(conn, product, ListingState::Rejected, Some(token_user.id), body.reason)
+    })?;
+
+    let res = CustomResponseBuilder::new()
+        .body(product)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Take an active listing down with a reason for the vendor
+async fn suspend_listing(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<DecisionBody>,
+) -> Result<CustomResponse<Product>, Error> {
+    let product = decide(id, |conn, product| {
+        moderation::resolve_flags(conn, product.id, flag_statuses::UPHELD, token_user.id)?;
+        moderation::transition(conn, product, ListingState::Suspended, Some(token_user.id), body.reason)
+    })?;
+
+    let res = CustomResponseBuilder::new()
+        .body(product)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Lock a product and apply a moderator's decision to it in one transaction
+fn decide<F>(id: i32, decision: F) -> Result<Product, Error>
+where
+    F: FnOnce(&mut PgConnection, &Product) -> Result<Product, Error>,
+{
+    let mut conn = get_connection()?;
+
+    let product = conn.transaction::<Product, Error, _>(|conn| {
+        let product = products::table.find(id).for_update().first::<Product>(conn)?;
+        decision(conn, &product)
+    })?;
+
+    info!(
+        product_id = product.id,
+        listing_state = ?product.listing_state,
+        "Moderation decision applied"
+    );
+
+    Ok(product)
+}
+
+/// Reviews flagged by the manipulation checks, oldest first
+async fn get_flagged_reviews(Query(query): Query<QueueQuery>) -> Result<CustomResponse<Vec<FlaggedReview>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (entries, total) = review::flagged(&mut conn, limit as i64, query.offset as i64)?;
+
+    Ok(response_formatter::format_paginated_success(
+        entries,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// Agree with a review flag; the review stops counting and is hidden
+async fn uphold_review_flag(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<Review>, Error> {
+    let mut conn = get_connection()?;
+    let moderated = review::moderate(&mut conn, token_user.id, id, true)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(moderated)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Clear a review flag; the review counts in full again
+async fn dismiss_review_flag(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<Review>, Error> {
+    let mut conn = get_connection()?;
+    let moderated = review::moderate(&mut conn, token_user.id, id, false)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(moderated)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Open conversation reports, oldest first
+async fn get_conversation_reports(
+    Query(query): Query<QueueQuery>,
+) -> Result<CustomResponse<Vec<ConversationReport>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (reports, total) = message::open_reports(&mut conn, limit as i64, query.offset as i64)?;
+
+    Ok(response_formatter::format_paginated_success(
+        reports,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// A conversation report with the messages the reporter shared
+async fn get_conversation_report(Path(id): Path<i32>) -> Result<CustomResponse<ReportDetails>, Error> {
+    let mut conn = get_connection()?;
+    let details = message::report_details(&mut conn, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(details)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Agree that a reported conversation was abusive
+async fn uphold_conversation_report(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<ConversationReport>, Error> {
+    let mut conn = get_connection()?;
+    let report = message::resolve_report(&mut conn, id, token_user.id, report_statuses::UPHELD)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(report)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Close a conversation report without action
+async fn dismiss_conversation_report(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<ConversationReport>, Error> {
+    let mut conn = get_connection()?;
+    let report = message::resolve_report(&mut conn, id, token_user.id, report_statuses::DISMISSED)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(report)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
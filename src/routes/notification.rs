@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::pagination::MAX_PAGE_SIZE;
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::models::notification::{self, Notification, Preference};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
+
+pub fn create_route() -> Router {
+    Router::new()
+        .route("/notifications", get(list_notifications))
+        .route("/notifications/unread-count", get(get_unread_count))
+        .route("/notifications/read-all", post(mark_all_read))
+        .route("/notifications/:id/read", post(mark_read))
+        .route(
+            "/notifications/preferences",
+            get(get_preferences).put(update_preferences),
+        )
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    unread_only: bool,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Serialize)]
+struct UnreadCount {
+    unread: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct MarkedRead {
+    marked: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreferencesBody {
+    preferences: Vec<Preference>,
+}
+
+/// The user's notifications, newest first
+async fn list_notifications(
+    token_user: TokenUser,
+    Query(query): Query<ListQuery>,
+) -> Result<CustomResponse<Vec<Notification>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (notifications, total) = notification::list(
+        &mut conn,
+        token_user.id,
+        query.unread_only,
+        limit as i64,
+        query.offset as i64,
+    )?;
+
+    Ok(response_formatter::format_paginated_success(
+        notifications,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+async fn get_unread_count(token_user: TokenUser) -> Result<CustomResponse<UnreadCount>, Error> {
+    let mut conn = get_connection()?;
+    let unread = notification::unread_count(&mut conn, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(UnreadCount { unread })
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Mark one notification read; another user's notification is not found
+async fn mark_read(token_user: TokenUser, Path(id): Path<i32>) -> Result<CustomResponse<Notification>, Error> {
+    let mut conn = get_connection()?;
+    let notification = notification::mark_one_read(&mut conn, token_user.id, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(notification)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+async fn mark_all_read(token_user: TokenUser) -> Result<CustomResponse<MarkedRead>, Error> {
+    let mut conn = get_connection()?;
+    let marked = notification::mark_read(&mut conn, token_user.id, None)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(MarkedRead { marked })
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Every notification kind with whether the user gets it; all are on until turned off
+async fn get_preferences(token_user: TokenUser) -> Result<CustomResponse<Vec<Preference>>, Error> {
+    let mut conn = get_connection()?;
+    let preferences = notification::preferences(&mut conn, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(preferences)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Turn kinds of notification on or off; kinds left out keep their setting
+async fn update_preferences(
+    token_user: TokenUser,
+    Json(body): Json<PreferencesBody>,
+) -> Result<CustomResponse<Vec<Preference>>, Error> {
+    let mut conn = get_connection()?;
+    let preferences = notification::set_preferences(&mut conn, token_user.id, &body.preferences)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(preferences)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
@@ -4,12 +4,24 @@ use axum::{
     routing::{get, post},
     Json, Router,
 };
+use bigdecimal::BigDecimal;
+use chrono::Utc;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
 
 use crate::database::{get_connection, DbPool};
 use crate::errors::Error;
-use crate::models::order::{NewOrder, Order, OrderWithItems};
+use crate::models::checkout::{self, OrderLine, PlaceOrder};
+use crate::models::escrow::{self, Settlement};
+use crate::models::inventory;
+use crate::models::order::{
+    self, NewOrderStatusHistory, Order, OrderItem, OrderQuote, OrderStatus, OrderWithItems,
+};
+use crate::models::order_timer::{self, OrderTimer};
+use crate::models::payment::PaymentCurrency;
+use crate::pricing;
+use crate::schema::{order_items, order_quotes, orders};
 use crate::utils::authenticate_request::TokenUser;
 use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
 
@@ -17,6 +29,8 @@ pub fn create_route() -> Router {
     Router::new()
         .route("/orders", get(list_orders).post(create_order))
         .route("/orders/:id", get(get_order).patch(update_order))
+        .route("/orders/:id/quote", post(requote_order))
+        .route("/orders/:id/finalize/extend", post(extend_finalize))
 }
 
 // Placeholder implementations - these will be expanded with actual database operations
@@ -29,12 +43,64 @@ async fn list_orders(token_user: TokenUser) -> Result<CustomResponse<Vec<Order>>
     Ok(res)
 }
 
+/// Place an order and reserve its stock
+///
+/// The order, its items and the stock reservations are written in one transaction, so an
+/// order is never created for stock that is not there. All items must come from one vendor;
+/// use the cart to buy from several at once. The reservation expires after
+/// `inventory.reservation_ttl_minutes` if the order is not paid. Items priced in fiat are
+/// converted at the current exchange rate, which is locked for `pricing.quote_lock_minutes`.
+///
+/// # Arguments
+/// * `token_user` - The buyer placing the order
+/// * `body` - The request body containing the order lines
+///
+/// # Returns
+/// * `Result<CustomResponse<Order>, Error>` - The created order or an error
 async fn create_order(
     token_user: TokenUser,
-    Json(body): Json<serde_json::Value>,
+    Json(body): Json<CreateOrderBody>,
 ) -> Result<CustomResponse<Order>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let mut conn = get_connection()?;
+
+    // Rates are fetched up front: the provider may have to go over the network, which must
+    // not happen while the transaction holds row locks
+    let product_ids: Vec<i32> = body.items.iter().map(|item| item.product_id).collect();
+    let shipping_option_ids: Vec<i32> = body.shipping_option_id.into_iter().collect();
+    let fiat_currencies = checkout::fiat_currencies(&mut conn, &product_ids, &shipping_option_ids)?;
+    let rates = pricing::rates_for(fiat_currencies).await?;
+
+    let request = PlaceOrder {
+        buyer_id: token_user.id,
+        currency: body.currency,
+        encrypted_shipping_address: body.encrypted_shipping_address,
+        shipping_option_id: body.shipping_option_id,
+        checkout_id: None,
+        lines: body
+            .items
+            .iter()
+            .map(|item| OrderLine {
+                product_id: item.product_id,
+                variant_id: item.variant_id,
+                quantity: item.quantity,
+            })
+            .collect(),
+    };
+
+    let order = conn.transaction::<Order, Error, _>(|conn| checkout::place_order(conn, &request, &rates))?;
+
+    info!(
+        order_id = order.id,
+        buyer_id = order.buyer_id,
+        vendor_id = order.vendor_id,
+        "Order placed"
+    );
+
+    let res = CustomResponseBuilder::new()
+        .body(order)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
 }
 
 async fn get_order(
@@ -45,11 +111,241 @@ async fn get_order(
     Err(Error::not_found())
 }
 
+/// Change the status of an order
+///
+/// Cancelling a pending order puts its reserved stock back; marking it paid commits the
+/// reservation. The vendor accepts a paid order by moving it to `Processing` and then ships
+/// it; the buyer finalizes a shipped order by completing it, which releases the escrow.
+/// Each change starts or stops the order's timers.
+///
+/// # Arguments
+/// * `token_user` - The user changing the order
+/// * `id` - The order ID
+/// * `body` - The request body containing the new status
+///
+/// # Returns
+/// * `Result<CustomResponse<Order>, Error>` - The updated order or an error
 async fn update_order(
     token_user: TokenUser,
     Path(id): Path<i32>,
-    Json(body): Json<serde_json::Value>,
+    Json(body): Json<UpdateOrderBody>,
 ) -> Result<CustomResponse<Order>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let mut conn = get_connection()?;
+
+    let order = conn.transaction::<Order, Error, _>(|conn| {
+        let order = orders::table
+            .find(id)
+            .for_update()
+            .first::<Order>(conn)?;
+
+        let is_buyer = order.buyer_id == token_user.id;
+        let is_vendor = order.vendor_id == token_user.id;
+        let is_admin = token_user.role == crate::models::user::roles::ADMIN;
+        if !is_buyer && !is_vendor && !is_admin {
+            return Err(Error::not_found());
+        }
+
+        match (order.status, body.status) {
+            (OrderStatus::Pending, OrderStatus::Cancelled) => {
+                inventory::release_reservations(conn, order.id)?;
+            }
+            // Until payments are confirmed on-chain, an admin confirms them by hand
+            (OrderStatus::Pending, OrderStatus::Paid) if is_admin => {
+                let quote_expired = diesel::select(diesel::dsl::exists(
+                    order_quotes::table
+                        .filter(order_quotes::order_id.eq(order.id))
+                        .filter(order_quotes::expires_at.lt(Utc::now())),
+                ))
+                .get_result::<bool>(conn)?;
+
+                if quote_expired {
+                    return Err(Error::validation_error(
+                        "The order's price quote has expired, it must be re-quoted before payment",
+                    ));
+                }
+
+                inventory::commit_reservations(conn, order.id)?;
+            }
+            (OrderStatus::Paid, OrderStatus::Processing) if is_vendor => {}
+            (OrderStatus::Processing, OrderStatus::Shipped) if is_vendor => {}
+            (OrderStatus::Shipped | OrderStatus::Delivered, OrderStatus::Completed) if is_buyer => {
+                escrow::settle(conn, &order, &Settlement::release(&order))?;
+            }
+            (from, to) => {
+                debug!(order_id = order.id, from = ?from, to = ?to, "Rejected order status change");
+                return Err(Error::validation_error(format!(
+                    "Cannot change order status from {:?} to {:?}",
+                    from, to
+                )));
+            }
+        }
+
+        let order = order::set_status(
+            conn,
+            &order,
+            NewOrderStatusHistory::by_user(order.id, body.status, token_user.id, body.notes.clone()),
+        )?;
+        order_timer::on_status_change(conn, order.id, order.status)?;
+
+        Ok(order)
+    })?;
+
+    info!(order_id = order.id, status = ?order.status, "Order status changed");
+
+    let res = CustomResponseBuilder::new()
+        .body(order)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Give the buyer more time before a shipped order is finalized automatically
+///
+/// Can be used once per order; adds `order_timers.extension_days` to the deadline.
+///
+/// # Arguments
+/// * `token_user` - The buyer of the order
+/// * `id` - The order ID
+///
+/// # Returns
+/// * `Result<CustomResponse<OrderTimer>, Error>` - The extended finalize timer or an error
+async fn extend_finalize(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<OrderTimer>, Error> {
+    let mut conn = get_connection()?;
+    let timer = order_timer::extend_finalize(&mut conn, id, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(timer)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Re-price an unpaid order whose quote has expired
+///
+/// Fiat-priced items are converted again at the current rates and the new quote is locked
+/// for another `pricing.quote_lock_minutes`. Items with fixed crypto prices are unchanged.
+///
+/// # Arguments
+/// * `token_user` - The buyer of the order
+/// * `id` - The order ID
+///
+/// # Returns
+/// * `Result<CustomResponse<Order>, Error>` - The re-priced order or an error
+async fn requote_order(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<Order>, Error> {
+    let mut conn = get_connection()?;
+
+    let quotes = order_quotes::table
+        .inner_join(orders::table)
+        .filter(orders::id.eq(id))
+        .filter(orders::buyer_id.eq(token_user.id))
+        .select(order_quotes::all_columns)
+        .load::<OrderQuote>(&mut conn)?;
+
+    if quotes.is_empty() {
+        return Err(Error::validation_error("This order has no fiat-priced items to re-quote"));
+    }
+
+    if let Some(locked) = quotes.iter().find(|quote| quote.expires_at > Utc::now()) {
+        return Err(Error::validation_error(format!(
+            "The current quote is locked until {}",
+            locked.expires_at.to_rfc3339()
+        )));
+    }
+
+    let rates = pricing::rates_for(quotes.iter().map(|quote| quote.fiat_currency.clone())).await?;
+
+    let order = conn.transaction::<Order, Error, _>(|conn| {
+        let order = orders::table
+            .find(id)
+            .filter(orders::buyer_id.eq(token_user.id))
+            .for_update()
+            .first::<Order>(conn)?;
+
+        if order.status != OrderStatus::Pending {
+            return Err(Error::validation_error("Only unpaid orders can be re-quoted"));
+        }
+
+        let items = order_items::table
+            .filter(order_items::order_id.eq(order.id))
+            .load::<OrderItem>(conn)?;
+
+        let mut total_amount = BigDecimal::from(0);
+        for item in &items {
+            let price_per_unit = match (&item.price_fiat, &item.fiat_currency) {
+                (Some(price_fiat), Some(fiat_currency)) => {
+                    let rates = rates.get(fiat_currency).ok_or_else(|| {
+                        Error::ExchangeRateUnavailable(format!("No {} rates to re-quote", fiat_currency))
+                    })?;
+                    let price_per_unit = rates.convert(price_fiat, order.currency)?;
+
+                    diesel::update(item)
+                        .set(order_items::price_per_unit.eq(&price_per_unit))
+                        .execute(conn)?;
+
+                    price_per_unit
+                }
+                _ => item.price_per_unit.clone(),
+            };
+
+            total_amount += price_per_unit * BigDecimal::from(item.quantity);
+        }
+
+        let shipping_amount = match (&order.shipping_fiat, &order.shipping_fiat_currency) {
+            (Some(shipping_fiat), Some(fiat_currency)) => {
+                let rates = rates.get(fiat_currency).ok_or_else(|| {
+                    Error::ExchangeRateUnavailable(format!("No {} rates to re-quote", fiat_currency))
+                })?;
+                rates.convert(shipping_fiat, order.currency)?
+            }
+            _ => order.shipping_amount.clone(),
+        };
+        total_amount += &shipping_amount;
+
+        checkout::save_quotes(conn, order.id, order.currency, rates.keys().cloned(), &rates)?;
+
+        let order = diesel::update(&order)
+            .set((
+                orders::total_amount.eq(total_amount),
+                orders::shipping_amount.eq(shipping_amount),
+                orders::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<Order>(conn)?;
+
+        Ok(order)
+    })?;
+
+    info!(order_id = order.id, total_amount = %order.total_amount, "Order re-quoted");
+
+    let res = CustomResponseBuilder::new()
+        .body(order)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateOrderBody {
+    currency: PaymentCurrency,
+    encrypted_shipping_address: String,
+    shipping_option_id: Option<i32>,
+    items: Vec<CreateOrderItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateOrderItem {
+    product_id: i32,
+    variant_id: Option<i32>,
+    quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateOrderBody {
+    status: OrderStatus,
+    notes: Option<String>,
 }
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    routing::{get, post},
+    routing::get,
     Json, Router,
 };
 use diesel::prelude::*;
@@ -17,7 +17,7 @@ pub fn create_route() -> Router {
     Router::new()
         .route("/wallets", get(list_wallets).post(create_wallet))
         .route("/wallets/:id", get(get_wallet))
-        .route("/transactions", get(list_transactions).post(create_transaction))
+        .route("/transactions", get(list_transactions))
         .route("/transactions/:id", get(get_transaction))
 }
 
@@ -56,14 +56,6 @@ async fn list_transactions(token_user: TokenUser) -> Result<CustomResponse<Vec<T
     Ok(res)
 }
 
-async fn create_transaction(
-    token_user: TokenUser,
-    Json(body): Json<serde_json::Value>,
-) -> Result<CustomResponse<Transaction>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
-}
-
 async fn get_transaction(
     token_user: TokenUser,
     Path(id): Path<i32>,
@@ -1,15 +1,36 @@
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
-    http::StatusCode,
-    routing::{get, post},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
+use bigdecimal::BigDecimal;
+use chrono::Utc;
 use diesel::prelude::*;
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
+use tokio_util::io::StreamReader;
+use tracing::{info, warn};
 
+use crate::catalog::{self, Format, ImportReport};
+use crate::constants::catalog::MAX_IMPORT_BYTES;
 use crate::database::{get_connection, DbPool};
 use crate::errors::Error;
-use crate::models::product::{Category, NewCategory, NewProduct, Product, ProductWithDetails};
+use crate::middleware::auth::require_vendor;
+use crate::models::analytics;
+use crate::models::availability;
+use crate::models::inventory;
+use crate::models::moderation::{self, ListingFlag};
+use crate::models::product::{
+    Category, ListingState, NewCategory, NewProduct, Product, ProductVariant, ProductWithDetails,
+    UpdateProduct,
+};
+use crate::models::user::roles;
+use crate::pricing::{self, ExchangeRates};
+use crate::schema::{categories, product_images, product_variants, products};
 use crate::utils::authenticate_request::TokenUser;
 use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
 
@@ -17,7 +38,17 @@ pub fn create_route() -> Router {
     Router::new()
         .route("/products", get(list_products).post(create_product))
         .route("/products/:id", get(get_product).patch(update_product))
+        .route("/products/:id/inventory", put(update_inventory))
+        .route("/products/:id/submit", post(submit_product))
+        .route("/products/:id/flags", post(flag_product))
         .route("/categories", get(list_categories).post(create_category))
+        .route("/exchange-rates/:fiat_currency", get(get_exchange_rates))
+        .merge(
+            Router::new()
+                .route("/products/import", post(import_products))
+                .route("/products/export", get(export_products))
+                .route_layer(middleware::from_fn(require_vendor)),
+        )
 }
 
 // Placeholder implementations - these will be expanded with actual database operations
@@ -34,6 +65,21 @@ async fn create_product(
     token_user: TokenUser,
     Json(body): Json<NewProduct>,
 ) -> Result<CustomResponse<Product>, Error> {
+    let fiat_currency = body
+        .fiat_currency
+        .as_deref()
+        .map(pricing::normalize_fiat_currency)
+        .transpose()?;
+    let price_fiat = body
+        .price_fiat
+        .as_ref()
+        .map(pricing::normalize_fiat_price)
+        .transpose()?;
+
+    if price_fiat.is_some() && fiat_currency.is_none() {
+        return Err(Error::validation_error("A fiat price needs a fiat currency"));
+    }
+
     let product = Product {
         id: 1,
         vendor_id: token_user.id,
@@ -46,6 +92,17 @@ async fn create_product(
         is_active: body.is_active,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        low_stock_threshold: body.low_stock_threshold,
+        deactivate_when_out_of_stock: body.deactivate_when_out_of_stock,
+        stock_deactivated: false,
+        price_fiat,
+        fiat_currency,
+        sku: body.sku,
+        listing_state: ListingState::Draft,
+        moderation_reason: None,
+        submitted_at: None,
+        reviewed_by: None,
+        reviewed_at: None,
     }; // Placeholder
     
     let res = CustomResponseBuilder::new()
@@ -55,18 +112,227 @@ async fn create_product(
     Ok(res)
 }
 
+/// A listing as buyers see it; counts as a view in the vendor's analytics
+///
+/// Listings of a closed vendor, or of a vendor on vacation who chose to hide them, are not
+/// found.
 async fn get_product(Path(id): Path<i32>) -> Result<CustomResponse<ProductWithDetails>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let mut conn = get_connection()?;
+
+    let product = products::table
+        .find(id)
+        .filter(products::listing_state.eq(ListingState::Active))
+        .filter(products::is_active.eq(true))
+        .first::<Product>(&mut conn)
+        .optional()?
+        .ok_or_else(Error::not_found)?;
+
+    if availability::get(&mut conn, product.vendor_id)?.hides_listings() {
+        return Err(Error::not_found());
+    }
+
+    let category = match product.category_id {
+        Some(category_id) => categories::table
+            .find(category_id)
+            .first::<Category>(&mut conn)
+            .optional()?,
+        None => None,
+    };
+
+    let variants = product_variants::table
+        .filter(product_variants::product_id.eq(product.id))
+        .order(product_variants::id.asc())
+        .load::<ProductVariant>(&mut conn)?;
+
+    let primary_image_id = product_images::table
+        .filter(product_images::product_id.eq(product.id))
+        .order((product_images::is_primary.desc(), product_images::id.asc()))
+        .select(product_images::id)
+        .first::<i32>(&mut conn)
+        .optional()?;
+
+    // A lost view count must not cost the buyer the page
+    if let Err(err) = analytics::record_view(&mut conn, product.id) {
+        warn!(error = %err, product_id = product.id, "Could not record a product view");
+    }
+
+    let res = CustomResponseBuilder::new()
+        .body(ProductWithDetails {
+            product,
+            category,
+            variants,
+            primary_image_id,
+        })
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
 }
 
+/// Edit a product's listing
+///
+/// Changing the title, description or category of an active listing sends it back to the
+/// moderation queue; it is hidden from buyers until a moderator approves it again.
+///
+/// # Arguments
+/// * `token_user` - The vendor that owns the product
+/// * `id` - The product ID
+/// * `body` - The fields to change
+///
+/// # Returns
+/// * `Result<CustomResponse<Product>, Error>` - The updated product or an error
 async fn update_product(
     token_user: TokenUser,
     Path(id): Path<i32>,
-    Json(body): Json<serde_json::Value>,
+    Json(body): Json<UpdateProductBody>,
 ) -> Result<CustomResponse<Product>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    if body.title.as_ref().map_or(false, |title| title.trim().is_empty()) {
+        return Err(Error::validation_error("Title cannot be empty"));
+    }
+
+    if body.description.as_ref().map_or(false, |text| text.trim().is_empty()) {
+        return Err(Error::validation_error("Description cannot be empty"));
+    }
+
+    let price_fiat = body
+        .price_fiat
+        .as_ref()
+        .map(pricing::normalize_fiat_price)
+        .transpose()?;
+    let fiat_currency = body
+        .fiat_currency
+        .as_deref()
+        .map(pricing::normalize_fiat_currency)
+        .transpose()?;
+
+    let mut conn = get_connection()?;
+
+    let product = conn.transaction::<Product, Error, _>(|conn| {
+        let before = products::table
+            .find(id)
+            .filter(products::vendor_id.eq(token_user.id))
+            .for_update()
+            .first::<Product>(conn)?;
+
+        if price_fiat.is_some() && fiat_currency.is_none() && before.fiat_currency.is_none() {
+            return Err(Error::validation_error("A fiat price needs a fiat currency"));
+        }
+
+        diesel::update(&before)
+            .set(&UpdateProduct {
+                title: body.title.map(|title| title.trim().to_string()),
+                description: body.description,
+                category_id: body.category_id,
+                price_btc: body.price_btc,
+                price_xmr: body.price_xmr,
+                stock: None,
+                is_active: body.is_active,
+                stock_deactivated: body.is_active.map(|_| false),
+                low_stock_threshold: None,
+                deactivate_when_out_of_stock: None,
+                price_fiat: price_fiat.map(Some),
+                fiat_currency: fiat_currency.map(Some),
+                sku: body.sku.map(Some),
+                updated_at: Some(Utc::now()),
+            })
+            .execute(conn)?;
+
+        moderation::review_after_edit(conn, &before, token_user.id)
+    })?;
+
+    info!(
+        product_id = product.id,
+        vendor_id = token_user.id,
+        listing_state = ?product.listing_state,
+        "Product updated"
+    );
+
+    let res = CustomResponseBuilder::new()
+        .body(product)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateProductBody {
+    title: Option<String>,
+    description: Option<String>,
+    category_id: Option<i32>,
+    price_btc: Option<BigDecimal>,
+    price_xmr: Option<BigDecimal>,
+    is_active: Option<bool>,
+    price_fiat: Option<BigDecimal>,
+    fiat_currency: Option<String>,
+    sku: Option<String>,
+}
+
+/// Submit a draft or rejected listing for review
+///
+/// # Arguments
+/// * `token_user` - The vendor that owns the product
+/// * `id` - The product ID
+///
+/// # Returns
+/// * `Result<CustomResponse<Product>, Error>` - The submitted product or an error
+async fn submit_product(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<Product>, Error> {
+    let mut conn = get_connection()?;
+
+    let product = conn.transaction::<Product, Error, _>(|conn| {
+        let product = products::table
+            .find(id)
+            .filter(products::vendor_id.eq(token_user.id))
+            .for_update()
+            .first::<Product>(conn)?;
+
+        if !matches!(product.listing_state, ListingState::Draft | ListingState::Rejected) {
+            return Err(Error::validation_error("Only draft or rejected listings can be submitted"));
+        }
+
+        moderation::transition(conn, &product, ListingState::PendingReview, Some(token_user.id), None)
+    })?;
+
+    let res = CustomResponseBuilder::new()
+        .body(product)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Flag a listing for moderators to look at
+///
+/// # Arguments
+/// * `token_user` - The buyer flagging the listing
+/// * `id` - The product ID
+/// * `body` - The reason and optional details
+///
+/// # Returns
+/// * `Result<CustomResponse<ListingFlag>, Error>` - The new flag or an error
+async fn flag_product(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<FlagProductBody>,
+) -> Result<CustomResponse<ListingFlag>, Error> {
+    if token_user.role != roles::BUYER {
+        return Err(Error::validation_error("Only buyers can flag listings"));
+    }
+
+    let mut conn = get_connection()?;
+    let flag = moderation::flag_listing(&mut conn, id, token_user.id, &body.reason, body.details)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(flag)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+struct FlagProductBody {
+    reason: String,
+    details: Option<String>,
 }
 
 async fn list_categories() -> Result<CustomResponse<Vec<Category>>, Error> {
@@ -85,3 +351,207 @@ async fn create_category(
     // Placeholder implementation
     Err(Error::not_found())
 }
+
+/// Current exchange rates for a fiat currency, as used to price fiat-denominated listings
+async fn get_exchange_rates(
+    Path(fiat_currency): Path<String>,
+) -> Result<CustomResponse<ExchangeRates>, Error> {
+    let rates = pricing::rates(&fiat_currency).await?;
+
+    let res = CustomResponseBuilder::new()
+        .body(rates)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Set a product's stock and the rules that deactivate it when stock runs low
+///
+/// The rules are applied straight away, so restocking a listing that was switched off for
+/// being out of stock puts it back on sale.
+///
+/// # Arguments
+/// * `token_user` - The vendor that owns the product
+/// * `id` - The product ID
+/// * `body` - The request body containing the stock and rules
+///
+/// # Returns
+/// * `Result<CustomResponse<Product>, Error>` - The updated product or an error
+async fn update_inventory(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateInventoryBody>,
+) -> Result<CustomResponse<Product>, Error> {
+    if body.stock.map_or(false, |stock| stock < 0) {
+        return Err(Error::validation_error("Stock cannot be negative"));
+    }
+
+    if body.low_stock_threshold.map_or(false, |threshold| threshold < 0) {
+        return Err(Error::validation_error("Low stock threshold cannot be negative"));
+    }
+
+    let mut conn = get_connection()?;
+
+    let product = conn.transaction::<Product, Error, _>(|conn| {
+        let product = products::table
+            .find(id)
+            .filter(products::vendor_id.eq(token_user.id))
+            .for_update()
+            .first::<Product>(conn)?;
+
+        let low_stock_threshold = if body.clear_low_stock_threshold {
+            None
+        } else {
+            body.low_stock_threshold.or(product.low_stock_threshold)
+        };
+
+        diesel::update(&product)
+            .set((
+                products::stock.eq(body.stock.unwrap_or(product.stock)),
+                products::low_stock_threshold.eq(low_stock_threshold),
+                products::deactivate_when_out_of_stock.eq(body
+                    .deactivate_when_out_of_stock
+                    .unwrap_or(product.deactivate_when_out_of_stock)),
+                products::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+
+        inventory::apply_stock_rules(conn, product.id)?;
+
+        Ok(products::table.find(product.id).first::<Product>(conn)?)
+    })?;
+
+    info!(
+        product_id = product.id,
+        vendor_id = token_user.id,
+        stock = product.stock,
+        is_active = product.is_active,
+        "Product inventory updated"
+    );
+
+    let res = CustomResponseBuilder::new()
+        .body(product)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Fields left out keep their current values
+#[derive(Debug, Deserialize)]
+struct UpdateInventoryBody {
+    stock: Option<i32>,
+    low_stock_threshold: Option<i32>,
+    /// Remove the low stock threshold; takes precedence over `low_stock_threshold`
+    #[serde(default)]
+    clear_low_stock_threshold: bool,
+    deactivate_when_out_of_stock: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    format: Format,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    format: Format,
+}
+
+/// Create and update products in bulk from a CSV or JSON Lines file
+///
+/// The request body is the file itself and is read as a stream, up to `MAX_IMPORT_BYTES`. The import is
+/// all-or-nothing: if any line has an error nothing is written, and the report lists every
+/// error by line. With `dry_run` the report shows what would change without writing anything.
+///
+/// # Arguments
+/// * `token_user` - The vendor whose catalog is imported into
+/// * `query` - The file format and whether this is a dry run
+/// * `body` - The import file
+///
+/// # Returns
+/// * `Result<CustomResponse<ImportReport>, Error>` - What the import did, or would do
+async fn import_products(
+    token_user: TokenUser,
+    Query(query): Query<ImportQuery>,
+    body: Body,
+) -> Result<CustomResponse<ImportReport>, Error> {
+    let mut received = 0;
+    let stream = body
+        .into_data_stream()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+        .and_then(move |chunk| {
+            received += chunk.len();
+            let result = if received > MAX_IMPORT_BYTES {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("import files are limited to {} MiB", MAX_IMPORT_BYTES / (1024 * 1024)),
+                ))
+            } else {
+                Ok(chunk)
+            };
+            futures::future::ready(result)
+        });
+    let (records, parse_errors) = catalog::read(query.format, StreamReader::new(stream)).await?;
+
+    let vendor_id = token_user.id;
+    let report = tokio::task::spawn_blocking(move || {
+        let mut conn = get_connection()?;
+        catalog::import(&mut conn, vendor_id, records, parse_errors, query.dry_run)
+    })
+    .await??;
+
+    let status_code = if report.errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+
+    let res = CustomResponseBuilder::new()
+        .body(report)
+        .status_code(status_code)
+        .build();
+    Ok(res)
+}
+
+/// Download the vendor's whole catalog as CSV or JSON Lines
+///
+/// The file has the same shape the importer reads, so it can be edited and imported back.
+/// It is streamed a page of products at a time.
+///
+/// # Arguments
+/// * `token_user` - The vendor whose catalog is exported
+/// * `query` - The file format
+///
+/// # Returns
+/// * `Result<Response, Error>` - The catalog file or an error
+async fn export_products(
+    token_user: TokenUser,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, Error> {
+    let vendor_id = token_user.id;
+    let stream = catalog::export_stream(vendor_id, query.format)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()));
+
+    info!(
+        vendor_id = vendor_id,
+        format = query.format.extension(),
+        "Catalog export started"
+    );
+
+    let disposition = format!(
+        "attachment; filename=\"catalog-{}.{}\"",
+        Utc::now().format("%Y%m%d"),
+        query.format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
@@ -0,0 +1,242 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+
+use crate::constants::pagination::MAX_PAGE_SIZE;
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_moderator;
+use crate::models::support::{self, SupportTicket, TicketMessage, TicketUpdate, TicketWithMessages};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
+
+pub fn create_route() -> Router {
+    let staff_routes = Router::new()
+        .route("/admin/support/tickets", get(get_queue))
+        .route("/admin/support/tickets/:id", get(get_ticket_as_staff).put(update_ticket))
+        .route("/admin/support/tickets/:id/messages", post(reply_as_staff))
+        .route("/admin/support/tickets/:id/assign", post(assign_ticket))
+        .layer(middleware::from_fn(require_moderator));
+
+    Router::new()
+        .route("/support/tickets", get(get_tickets).post(open_ticket))
+        .route("/support/tickets/:id", get(get_ticket))
+        .route("/support/tickets/:id/messages", post(reply))
+        .route("/support/tickets/:id/resolve", post(resolve_ticket))
+        .merge(staff_routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenTicketBody {
+    category: String,
+    priority: Option<String>,
+    subject: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplyBody {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueueQuery {
+    status: Option<String>,
+    category: Option<String>,
+    /// Only show tickets assigned to the current staff member
+    #[serde(default)]
+    mine: bool,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Deserialize)]
+struct AssignBody {
+    assignee_id: Option<i32>,
+}
+
+/// Open a ticket; the first message is encrypted like any other reply
+async fn open_ticket(
+    token_user: TokenUser,
+    Json(body): Json<OpenTicketBody>,
+) -> Result<CustomResponse<TicketWithMessages>, Error> {
+    let mut conn = get_connection()?;
+    let ticket = support::open(
+        &mut conn,
+        token_user.id,
+        &body.category,
+        body.priority.as_deref(),
+        &body.subject,
+        &body.content,
+    )?;
+
+    let res = CustomResponseBuilder::new()
+        .body(ticket)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// The current user's tickets, most recently updated first
+async fn get_tickets(
+    token_user: TokenUser,
+    Query(query): Query<ListQuery>,
+) -> Result<CustomResponse<Vec<SupportTicket>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (tickets, total) =
+        support::list_for_user(&mut conn, token_user.id, limit as i64, query.offset as i64)?;
+
+    Ok(response_formatter::format_paginated_success(
+        tickets,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+async fn get_ticket(token_user: TokenUser, Path(id): Path<i32>) -> Result<CustomResponse<TicketWithMessages>, Error> {
+    let mut conn = get_connection()?;
+    let ticket = support::find_for_owner(&mut conn, id, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(ticket)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Reply as the user who opened the ticket, reopening it if it was resolved
+async fn reply(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<ReplyBody>,
+) -> Result<CustomResponse<TicketMessage>, Error> {
+    let mut conn = get_connection()?;
+    let message = support::reply(&mut conn, id, token_user.id, false, &body.content)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(message)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+async fn resolve_ticket(token_user: TokenUser, Path(id): Path<i32>) -> Result<CustomResponse<SupportTicket>, Error> {
+    let mut conn = get_connection()?;
+    let ticket = support::resolve_by_owner(&mut conn, id, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(ticket)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// The staff queue: unresolved tickets unless a status is given, most urgent first
+async fn get_queue(
+    token_user: TokenUser,
+    Query(query): Query<QueueQuery>,
+) -> Result<CustomResponse<Vec<SupportTicket>>, Error> {
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let assigned_to = query.mine.then_some(token_user.id);
+    let (tickets, total) = support::queue(
+        &mut conn,
+        query.status.as_deref(),
+        query.category.as_deref(),
+        assigned_to,
+        limit as i64,
+        query.offset as i64,
+    )?;
+
+    Ok(response_formatter::format_paginated_success(
+        tickets,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+async fn get_ticket_as_staff(Path(id): Path<i32>) -> Result<CustomResponse<TicketWithMessages>, Error> {
+    let mut conn = get_connection()?;
+    let ticket = support::find(&mut conn, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(ticket)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Reply as staff; the ticket then waits for the user
+async fn reply_as_staff(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<ReplyBody>,
+) -> Result<CustomResponse<TicketMessage>, Error> {
+    let mut conn = get_connection()?;
+    let message = support::reply(&mut conn, id, token_user.id, true, &body.content)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(message)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Assign a ticket to a moderator or admin, the current one by default
+async fn assign_ticket(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<AssignBody>,
+) -> Result<CustomResponse<SupportTicket>, Error> {
+    let assignee_id = body.assignee_id.unwrap_or(token_user.id);
+
+    let mut conn = get_connection()?;
+    let ticket = support::assign(&mut conn, id, assignee_id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(ticket)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+async fn update_ticket(
+    Path(id): Path<i32>,
+    Json(body): Json<TicketUpdate>,
+) -> Result<CustomResponse<SupportTicket>, Error> {
+    let mut conn = get_connection()?;
+    let ticket = support::update(&mut conn, id, body)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(ticket)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
@@ -1,9 +1,12 @@
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
 use axum::{extract::Path, routing::{get, post}, Json, Router};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
+use crate::constants::auth::DEFAULT_TOKEN_EXPIRATION_DAYS;
+use crate::constants::events::{SESSION_COOKIE, SESSION_COOKIE_PATH};
 use crate::database::get_connection;
 use crate::errors::{AuthenticateError, Error};
 use crate::models::user;
@@ -149,10 +152,13 @@ async fn get_user(Path(user_id): Path<i32>) -> Result<CustomResponse<PublicUser>
 /// * `body` - The request body containing the username and password
 ///
 /// # Returns
-/// * `Result<Json<AuthenticateResponse>, Error>` - The authentication response or an error
+/// * `Result<impl IntoResponse, Error>` - The authentication response or an error
+///
+/// The token is also set as an HTTP-only cookie scoped to the notification center, which
+/// browsers without JavaScript load directly. The cookie expires with the token.
 async fn authenticate_user(
     Json(body): Json<AuthorizeBody>,
-) -> Result<Json<AuthenticateResponse>, Error> {
+) -> Result<impl IntoResponse, Error> {
     use tracing::{debug, error, info, warn};
     use crate::constants::auth::{ACCOUNT_LOCKOUT_MINUTES, MAX_FAILED_LOGIN_ATTEMPTS};
 
@@ -322,13 +328,21 @@ async fn authenticate_user(
         "User authenticated successfully"
     );
 
+    let cookie = format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path={}; Max-Age={}",
+        SESSION_COOKIE,
+        token,
+        SESSION_COOKIE_PATH,
+        chrono::Duration::days(DEFAULT_TOKEN_EXPIRATION_DAYS).num_seconds()
+    );
+
     // Return the authentication response
     let res = AuthenticateResponse {
         access_token: token,
         user: PublicUser::from(user_result),
     };
 
-    Ok(Json(res))
+    Ok(([(header::SET_COOKIE, cookie)], Json(res)))
 }
 
 #[derive(Debug, Deserialize)]
@@ -1,70 +1,672 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post},
     Json, Router,
 };
+use bigdecimal::{BigDecimal, Zero};
+use chrono::Utc;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use tracing::info;
 
 use crate::database::{get_connection, DbPool};
 use crate::errors::Error;
-use crate::models::vendor::{Review, VendorBond, VendorWithStats};
+use crate::jobs;
+use crate::middleware::auth::{require_admin, require_vendor};
+use crate::models::availability::{self, Availability, AvailabilityInput};
+use crate::models::cart::{NewShippingOption, ShippingOption};
+use crate::models::reputation;
+use crate::models::review::{self, ReviewInput};
+use crate::models::user::{roles, User};
+use crate::models::vendor::{self, bond_statuses, Review, VendorBond, VendorWithStats};
+use crate::pricing;
+use crate::schema::{reviews, shipping_options, users};
 use crate::utils::authenticate_request::TokenUser;
 use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
 
 pub fn create_route() -> Router {
+    let admin_routes = Router::new()
+        .route("/admin/vendor-bonds", get(list_bonds))
+        .route("/admin/vendor-bonds/:id/payment", post(record_bond_payment))
+        .route("/admin/vendor-bonds/:id/approve", post(approve_bond))
+        .route("/admin/vendor-bonds/:id/reject", post(reject_bond))
+        .route("/admin/vendor-bonds/:id/forfeit", post(forfeit_bond))
+        .route("/admin/reputation/recompute", post(recompute_reputation))
+        .layer(middleware::from_fn(require_admin));
+
+    let vendor_routes = Router::new()
+        .route("/vendors/availability", get(get_availability).put(set_availability))
+        .route_layer(middleware::from_fn(require_vendor));
+
     Router::new()
         .route("/vendors", get(list_vendors))
         .route("/vendors/:id", get(get_vendor))
-        .route("/vendors/bond", post(create_vendor_bond))
+        .route("/vendors/:id/reputation", get(get_reputation))
+        .route(
+            "/vendors/bond",
+            get(get_vendor_bond).post(create_vendor_bond).delete(cancel_vendor_bond),
+        )
+        .route("/vendors/bond/exit", post(exit_vendor_bond))
         .route("/reviews", get(list_reviews).post(create_review))
-        .route("/reviews/:id", get(get_review))
+        .route("/reviews/:id", get(get_review).put(edit_review))
+        .route("/reviews/:id/reply", post(reply_to_review))
+        .route("/vendors/:id/shipping-options", get(list_shipping_options))
+        .route("/shipping-options", post(create_shipping_option))
+        .route("/shipping-options/:id", delete(delete_shipping_option))
+        .merge(vendor_routes)
+        .merge(admin_routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct VendorListQuery {
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+/// Vendors, best reputation first
+async fn list_vendors(Query(query): Query<VendorListQuery>) -> Result<CustomResponse<Vec<VendorWithStats>>, Error> {
+    let mut conn = get_connection()?;
+
+    let total = users::table
+        .filter(users::role.eq(roles::VENDOR))
+        .count()
+        .get_result::<i64>(&mut conn)?;
+
+    let page = users::table
+        .filter(users::role.eq(roles::VENDOR))
+        .order((users::reputation.desc().nulls_last(), users::id.asc()))
+        .limit(query.limit as i64)
+        .offset(query.offset as i64)
+        .load::<User>(&mut conn)?;
+    let vendors = VendorWithStats::load(&mut conn, page)?;
+
+    Ok(response_formatter::format_paginated_success(
+        vendors,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        query.limit,
+    ))
+}
+
+async fn get_vendor(Path(id): Path<i32>) -> Result<CustomResponse<VendorWithStats>, Error> {
+    let mut conn = get_connection()?;
+
+    let vendor = users::table
+        .find(id)
+        .filter(users::role.eq(roles::VENDOR))
+        .first::<User>(&mut conn)
+        .optional()?
+        .ok_or_else(Error::not_found)?;
+    let vendor = VendorWithStats::load(&mut conn, vec![vendor])?
+        .pop()
+        .ok_or_else(Error::not_found)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(vendor)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
 }
 
-// Placeholder implementations - these will be expanded with actual database operations
-async fn list_vendors() -> Result<CustomResponse<Vec<VendorWithStats>>, Error> {
-    let vendors = Vec::new(); // Placeholder
+/// The vendor's own availability settings
+async fn get_availability(token_user: TokenUser) -> Result<CustomResponse<Availability>, Error> {
+    let mut conn = get_connection()?;
+    let current = availability::get(&mut conn, token_user.id)?;
+
     let res = CustomResponseBuilder::new()
-        .body(vendors)
+        .body(current)
         .status_code(StatusCode::OK)
         .build();
     Ok(res)
 }
 
-async fn get_vendor(Path(id): Path<i32>) -> Result<CustomResponse<VendorWithStats>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+/// Go on vacation, close the shop or come back
+///
+/// While away, none of the vendor's listings can be ordered; closing the shop or choosing to
+/// hide them on vacation also takes the listings out of view. Listings keep their own state
+/// and come back as they were.
+///
+/// # Arguments
+/// * `token_user` - The vendor
+/// * `body` - The status, an optional return date and public message, and whether to hide
+///   listings while on vacation
+///
+/// # Returns
+/// * `Result<CustomResponse<Availability>, Error>` - The saved availability or an error
+async fn set_availability(
+    token_user: TokenUser,
+    Json(body): Json<AvailabilityInput>,
+) -> Result<CustomResponse<Availability>, Error> {
+    let mut conn = get_connection()?;
+    let updated = availability::set(&mut conn, token_user.id, body)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(updated)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// How a vendor's reputation score is made up
+///
+/// Computed fresh, so it can be a little ahead of the stored score while a recompute is
+/// queued.
+async fn get_reputation(Path(id): Path<i32>) -> Result<CustomResponse<reputation::Breakdown>, Error> {
+    let mut conn = get_connection()?;
+
+    let is_vendor = diesel::select(diesel::dsl::exists(
+        users::table.find(id).filter(users::role.eq(roles::VENDOR)),
+    ))
+    .get_result::<bool>(&mut conn)?;
+    if !is_vendor {
+        return Err(Error::not_found());
+    }
+
+    let breakdown = reputation::compute(&mut conn, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(breakdown)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Serialize)]
+struct RecomputeSummary {
+    vendors: usize,
+    /// Updates actually queued; vendors with one already waiting are not queued again
+    queued: usize,
+}
+
+/// Queue a reputation update for every vendor, e.g. after changing the formula
+async fn recompute_reputation() -> Result<CustomResponse<RecomputeSummary>, Error> {
+    let mut conn = get_connection()?;
+
+    let vendor_ids = users::table
+        .filter(users::role.eq(roles::VENDOR))
+        .select(users::id)
+        .load::<i32>(&mut conn)?;
+
+    let mut queued = 0;
+    for vendor_id in &vendor_ids {
+        if jobs::reputation::schedule(&mut conn, *vendor_id)? {
+            queued += 1;
+        }
+    }
+
+    info!(vendors = vendor_ids.len(), queued = queued, "Reputation recompute queued");
+
+    let res = CustomResponseBuilder::new()
+        .body(RecomputeSummary {
+            vendors: vendor_ids.len(),
+            queued,
+        })
+        .status_code(StatusCode::ACCEPTED)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+struct ApplyBody {
+    note: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct BondListQuery {
+    status: Option<String>,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Deserialize)]
+struct BondPaymentBody {
+    tx_hash: String,
+    amount: BigDecimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct BondReasonBody {
+    reason: String,
+}
+
+/// Apply to become a vendor
+///
+/// # Arguments
+/// * `token_user` - The applicant
+/// * `body` - An optional note for the admins
+///
+/// # Returns
+/// * `Result<CustomResponse<VendorBond>, Error>` - The pending bond with the address to pay
+///   it to, or an error
 async fn create_vendor_bond(
     token_user: TokenUser,
-    Json(body): Json<serde_json::Value>,
+    Json(body): Json<ApplyBody>,
+) -> Result<CustomResponse<VendorBond>, Error> {
+    let mut conn = get_connection()?;
+    let user = users::table.find(token_user.id).first::<User>(&mut conn)?;
+
+    let note = body.note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+    let bond = vendor::apply(&mut conn, &user, note)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(bond)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// The user's open application or active bond
+async fn get_vendor_bond(token_user: TokenUser) -> Result<CustomResponse<VendorBond>, Error> {
+    let mut conn = get_connection()?;
+    let bond = vendor::open_bond(&mut conn, token_user.id)?.ok_or_else(Error::not_found)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(bond)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Withdraw an unpaid application
+async fn cancel_vendor_bond(token_user: TokenUser) -> Result<CustomResponse<VendorBond>, Error> {
+    let mut conn = get_connection()?;
+    let bond = vendor::cancel_application(&mut conn, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(bond)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Close the shop and get the bond back
+async fn exit_vendor_bond(token_user: TokenUser) -> Result<CustomResponse<VendorBond>, Error> {
+    let mut conn = get_connection()?;
+    let bond = vendor::exit(&mut conn, token_user.id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(bond)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Vendor applications and bonds, oldest first
+async fn list_bonds(Query(query): Query<BondListQuery>) -> Result<CustomResponse<Vec<VendorBond>>, Error> {
+    if let Some(status) = &query.status {
+        if !bond_statuses::ALL.contains(&status.as_str()) {
+            return Err(Error::validation_error(format!("Invalid bond status: {}", status)));
+        }
+    }
+
+    let mut conn = get_connection()?;
+    let (bonds, total) = vendor::list(
+        &mut conn,
+        query.status.as_deref(),
+        query.limit as i64,
+        query.offset as i64,
+    )?;
+
+    Ok(response_formatter::format_paginated_success(
+        bonds,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        query.limit,
+    ))
+}
+
+/// Record a confirmed bond deposit
+///
+/// Bond payments are confirmed by hand until bond addresses are watched on chain.
+async fn record_bond_payment(
+    Path(id): Path<i32>,
+    Json(body): Json<BondPaymentBody>,
+) -> Result<CustomResponse<VendorBond>, Error> {
+    let mut conn = get_connection()?;
+    let bond = vendor::record_payment(&mut conn, id, &body.tx_hash, body.amount)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(bond)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Approve a paid application; the applicant becomes a vendor
+async fn approve_bond(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<VendorBond>, Error> {
+    let mut conn = get_connection()?;
+    let bond = vendor::approve(&mut conn, token_user.id, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(bond)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Turn down an application, refunding a paid bond
+async fn reject_bond(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<BondReasonBody>,
 ) -> Result<CustomResponse<VendorBond>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(Error::validation_error("A reason is required"));
+    }
+
+    let mut conn = get_connection()?;
+    let bond = vendor::reject(&mut conn, token_user.id, id, reason.to_string())?;
+
+    let res = CustomResponseBuilder::new()
+        .body(bond)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
 }
 
-async fn list_reviews(
-    Query(params): Query<serde_json::Value>,
-) -> Result<CustomResponse<Vec<Review>>, Error> {
-    let reviews = Vec::new(); // Placeholder
+/// Keep a vendor's bond and revoke their vendor status
+async fn forfeit_bond(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<BondReasonBody>,
+) -> Result<CustomResponse<VendorBond>, Error> {
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(Error::validation_error("A reason is required"));
+    }
+
+    let mut conn = get_connection()?;
+    let bond = vendor::forfeit(&mut conn, token_user.id, id, reason.to_string())?;
+
     let res = CustomResponseBuilder::new()
-        .body(reviews)
+        .body(bond)
         .status_code(StatusCode::OK)
         .build();
     Ok(res)
 }
 
+#[derive(Debug, Deserialize)]
+struct ReviewListQuery {
+    vendor_id: Option<i32>,
+    product_id: Option<i32>,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateReviewBody {
+    order_id: i32,
+    product_id: Option<i32>,
+    rating: i32,
+    comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EditReviewBody {
+    rating: i32,
+    comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplyBody {
+    reply: String,
+}
+
+/// Reviews of a vendor or product, newest first
+///
+/// Reviews a moderator removed are left out.
+async fn list_reviews(Query(query): Query<ReviewListQuery>) -> Result<CustomResponse<Vec<Review>>, Error> {
+    if query.vendor_id.is_none() && query.product_id.is_none() {
+        return Err(Error::validation_error("Filter by vendor_id or product_id"));
+    }
+
+    let mut conn = get_connection()?;
+
+    let mut list_query = reviews::table
+        .filter(reviews::flag_status.is_distinct_from(review::flag_statuses::UPHELD))
+        .into_boxed();
+    let mut count_query = reviews::table
+        .filter(reviews::flag_status.is_distinct_from(review::flag_statuses::UPHELD))
+        .into_boxed();
+    if let Some(vendor_id) = query.vendor_id {
+        list_query = list_query.filter(reviews::vendor_id.eq(vendor_id));
+        count_query = count_query.filter(reviews::vendor_id.eq(vendor_id));
+    }
+    if let Some(product_id) = query.product_id {
+        list_query = list_query.filter(reviews::product_id.eq(product_id));
+        count_query = count_query.filter(reviews::product_id.eq(product_id));
+    }
+
+    let total = count_query.count().get_result::<i64>(&mut conn)?;
+    let page = list_query
+        .order(reviews::created_at.desc())
+        .limit(query.limit as i64)
+        .offset(query.offset as i64)
+        .load::<Review>(&mut conn)?;
+
+    Ok(response_formatter::format_paginated_success(
+        page,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        query.limit,
+    ))
+}
+
+/// Review a completed order
+///
+/// # Arguments
+/// * `token_user` - The buyer of the order
+/// * `body` - The order, optionally the product, the rating and a comment
+///
+/// # Returns
+/// * `Result<CustomResponse<Review>, Error>` - The new review or an error
 async fn create_review(
     token_user: TokenUser,
-    Json(body): Json<serde_json::Value>,
+    Json(body): Json<CreateReviewBody>,
 ) -> Result<CustomResponse<Review>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let mut conn = get_connection()?;
+    let created = review::create(
+        &mut conn,
+        token_user.id,
+        body.order_id,
+        body.product_id,
+        ReviewInput {
+            rating: body.rating,
+            comment: body.comment,
+        },
+    )?;
+
+    let res = CustomResponseBuilder::new()
+        .body(created)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
 }
 
 async fn get_review(Path(id): Path<i32>) -> Result<CustomResponse<Review>, Error> {
-    // Placeholder implementation
-    Err(Error::not_found())
+    let mut conn = get_connection()?;
+
+    let found = reviews::table
+        .find(id)
+        .filter(reviews::flag_status.is_distinct_from(review::flag_statuses::UPHELD))
+        .first::<Review>(&mut conn)
+        .optional()?
+        .ok_or_else(Error::not_found)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(found)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Use the one edit a review gets
+async fn edit_review(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<EditReviewBody>,
+) -> Result<CustomResponse<Review>, Error> {
+    let mut conn = get_connection()?;
+    let edited = review::edit(
+        &mut conn,
+        token_user.id,
+        id,
+        ReviewInput {
+            rating: body.rating,
+            comment: body.comment,
+        },
+    )?;
+
+    let res = CustomResponseBuilder::new()
+        .body(edited)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Answer a review publicly; vendors get one reply per review
+async fn reply_to_review(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<ReplyBody>,
+) -> Result<CustomResponse<Review>, Error> {
+    if token_user.role != roles::VENDOR {
+        return Err(Error::validation_error("Only vendors can reply to reviews"));
+    }
+
+    let mut conn = get_connection()?;
+    let replied = review::reply(&mut conn, token_user.id, id, &body.reply)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(replied)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// The shipping options a vendor currently offers
+async fn list_shipping_options(Path(id): Path<i32>) -> Result<CustomResponse<Vec<ShippingOption>>, Error> {
+    let mut conn = get_connection()?;
+
+    let options = shipping_options::table
+        .filter(shipping_options::vendor_id.eq(id))
+        .filter(shipping_options::is_active.eq(true))
+        .order(shipping_options::id.asc())
+        .load::<ShippingOption>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(options)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Offer a new shipping option
+///
+/// Like listings, an option can be priced in BTC, XMR or a fiat currency. An option with no
+/// price at all ships for free.
+///
+/// # Arguments
+/// * `token_user` - The vendor
+/// * `body` - The option's name, description and prices
+///
+/// # Returns
+/// * `Result<CustomResponse<ShippingOption>, Error>` - The new option or an error
+async fn create_shipping_option(
+    token_user: TokenUser,
+    Json(mut body): Json<NewShippingOption>,
+) -> Result<CustomResponse<ShippingOption>, Error> {
+    if token_user.role != roles::VENDOR {
+        return Err(Error::validation_error("Only vendors can offer shipping options"));
+    }
+
+    body.name = body.name.trim().to_string();
+    if body.name.is_empty() {
+        return Err(Error::validation_error("Name is required"));
+    }
+
+    for price in [&body.price_btc, &body.price_xmr].into_iter().flatten() {
+        if price < &BigDecimal::zero() {
+            return Err(Error::validation_error("Shipping prices cannot be negative"));
+        }
+    }
+
+    body.fiat_currency = body
+        .fiat_currency
+        .as_deref()
+        .map(pricing::normalize_fiat_currency)
+        .transpose()?;
+    body.price_fiat = body
+        .price_fiat
+        .as_ref()
+        .map(pricing::normalize_fiat_price)
+        .transpose()?;
+    if body.price_fiat.is_some() && body.fiat_currency.is_none() {
+        return Err(Error::validation_error("A fiat price needs a fiat currency"));
+    }
+
+    body.vendor_id = token_user.id;
+
+    let mut conn = get_connection()?;
+    let option = diesel::insert_into(shipping_options::table)
+        .values(&body)
+        .get_result::<ShippingOption>(&mut conn)?;
+
+    info!(vendor_id = token_user.id, shipping_option_id = option.id, "Shipping option created");
+
+    let res = CustomResponseBuilder::new()
+        .body(option)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Stop offering a shipping option
+///
+/// The option is deactivated rather than deleted, since existing orders refer to it.
+async fn delete_shipping_option(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+
+    let updated = diesel::update(
+        shipping_options::table
+            .find(id)
+            .filter(shipping_options::vendor_id.eq(token_user.id)),
+    )
+    .set((
+        shipping_options::is_active.eq(false),
+        shipping_options::updated_at.eq(Utc::now()),
+    ))
+    .execute(&mut conn)?;
+
+    if updated == 0 {
+        return Err(Error::not_found());
+    }
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
 }
@@ -0,0 +1,312 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::pagination::MAX_PAGE_SIZE;
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::middleware::auth::require_admin;
+use crate::models::payment::PaymentCurrency;
+use crate::models::user::User;
+use crate::models::withdrawal::{self, statuses, QueueEntry, Withdrawal, WithdrawalAddress};
+use crate::schema::{users, withdrawal_addresses, withdrawals};
+use crate::utils::authenticate_request::TokenUser;
+use crate::utils::custom_response::{CustomResponse, CustomResponseBuilder};
+use crate::utils::response_formatter;
+
+pub fn create_route() -> Router {
+    let admin_routes = Router::new()
+        .route("/admin/withdrawals", get(get_queue))
+        .route("/admin/withdrawals/:id/processing", post(start_processing))
+        .route("/admin/withdrawals/:id/sent", post(mark_sent))
+        .route("/admin/withdrawals/:id/failed", post(mark_failed))
+        .layer(middleware::from_fn(require_admin));
+
+    Router::new()
+        .route("/withdrawal-addresses", get(list_addresses).post(add_address))
+        .route("/withdrawal-addresses/:id", delete(remove_address))
+        .route("/withdrawal-addresses/:id/confirm", post(confirm_address))
+        .route("/withdrawals", get(list_withdrawals).post(request_withdrawal))
+        .route("/withdrawals/fee-estimate", get(fee_estimate))
+        .route("/withdrawals/:id/cancel", post(cancel_withdrawal))
+        .merge(admin_routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct AddAddressBody {
+    currency: PaymentCurrency,
+    address: String,
+    label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfirmAddressBody {
+    /// The address's challenge, clearsigned with the key on the account
+    signed_message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WithdrawalBody {
+    address_id: i32,
+    amount: BigDecimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeeEstimateQuery {
+    currency: PaymentCurrency,
+}
+
+#[derive(Debug, Serialize)]
+struct FeeEstimate {
+    currency: PaymentCurrency,
+    network_fee: BigDecimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    status: Option<String>,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Deserialize)]
+struct SentBody {
+    tx_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FailedBody {
+    reason: String,
+}
+
+/// The user's saved addresses, confirmed or not
+async fn list_addresses(token_user: TokenUser) -> Result<CustomResponse<Vec<WithdrawalAddress>>, Error> {
+    let mut conn = get_connection()?;
+
+    let addresses = withdrawal_addresses::table
+        .filter(withdrawal_addresses::user_id.eq(token_user.id))
+        .filter(withdrawal_addresses::removed_at.is_null())
+        .order(withdrawal_addresses::created_at.desc())
+        .load::<WithdrawalAddress>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(addresses)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Save an address; the response carries the challenge to sign
+///
+/// # Arguments
+/// * `token_user` - The authenticated user; must have a PGP key
+/// * `body` - The currency, address and an optional label
+///
+/// # Returns
+/// * `Result<CustomResponse<WithdrawalAddress>, Error>` - The unconfirmed address or an error
+async fn add_address(
+    token_user: TokenUser,
+    Json(body): Json<AddAddressBody>,
+) -> Result<CustomResponse<WithdrawalAddress>, Error> {
+    let mut conn = get_connection()?;
+    let user = users::table.find(token_user.id).first::<User>(&mut conn)?;
+
+    let address = withdrawal::add_address(&mut conn, &user, body.currency, &body.address, body.label)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(address)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Confirm an address with a signature over its challenge, starting its cool-down
+async fn confirm_address(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<ConfirmAddressBody>,
+) -> Result<CustomResponse<WithdrawalAddress>, Error> {
+    let mut conn = get_connection()?;
+    let user = users::table.find(token_user.id).first::<User>(&mut conn)?;
+
+    let address = withdrawal::confirm_address(&mut conn, &user, id, &body.signed_message)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(address)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Remove a saved address
+async fn remove_address(token_user: TokenUser, Path(id): Path<i32>) -> Result<CustomResponse<()>, Error> {
+    let mut conn = get_connection()?;
+    withdrawal::remove_address(&mut conn, token_user.id, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .status_code(StatusCode::NO_CONTENT)
+        .build();
+    Ok(res)
+}
+
+/// The network fee that would be charged on a withdrawal
+async fn fee_estimate(
+    _token_user: TokenUser,
+    Query(query): Query<FeeEstimateQuery>,
+) -> Result<CustomResponse<FeeEstimate>, Error> {
+    let network_fee = withdrawal::estimate_fee(query.currency)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(FeeEstimate {
+            currency: query.currency,
+            network_fee,
+        })
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// The user's withdrawals, newest first
+async fn list_withdrawals(token_user: TokenUser) -> Result<CustomResponse<Vec<Withdrawal>>, Error> {
+    let mut conn = get_connection()?;
+
+    let list = withdrawals::table
+        .filter(withdrawals::user_id.eq(token_user.id))
+        .order(withdrawals::created_at.desc())
+        .load::<Withdrawal>(&mut conn)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(list)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Queue a withdrawal to a confirmed address
+///
+/// # Arguments
+/// * `token_user` - The authenticated user
+/// * `body` - The address and the amount it should receive
+///
+/// # Returns
+/// * `Result<CustomResponse<Withdrawal>, Error>` - The queued withdrawal or an error
+async fn request_withdrawal(
+    token_user: TokenUser,
+    Json(body): Json<WithdrawalBody>,
+) -> Result<CustomResponse<Withdrawal>, Error> {
+    let mut conn = get_connection()?;
+    let queued = withdrawal::request(&mut conn, token_user.id, body.address_id, body.amount)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(queued)
+        .status_code(StatusCode::CREATED)
+        .build();
+    Ok(res)
+}
+
+/// Cancel a withdrawal that has not been picked up yet
+async fn cancel_withdrawal(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<Withdrawal>, Error> {
+    let mut conn = get_connection()?;
+    let cancelled = withdrawal::cancel(&mut conn, token_user.id, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(cancelled)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Withdrawals waiting to be sent or already handled, oldest first
+async fn get_queue(Query(query): Query<ListQuery>) -> Result<CustomResponse<Vec<QueueEntry>>, Error> {
+    if let Some(status) = &query.status {
+        if !statuses::ALL.contains(&status.as_str()) {
+            return Err(Error::validation_error(format!("Invalid withdrawal status: {}", status)));
+        }
+    }
+
+    let limit = query.limit.min(MAX_PAGE_SIZE);
+
+    let mut conn = get_connection()?;
+    let (entries, total) = withdrawal::queue(
+        &mut conn,
+        query.status.as_deref(),
+        limit as i64,
+        query.offset as i64,
+    )?;
+
+    Ok(response_formatter::format_paginated_success(
+        entries,
+        StatusCode::OK,
+        total as u64,
+        query.offset,
+        limit,
+    ))
+}
+
+/// Take a withdrawal off the queue before sending it, so the user can no longer cancel it
+async fn start_processing(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+) -> Result<CustomResponse<Withdrawal>, Error> {
+    let mut conn = get_connection()?;
+    let processing = withdrawal::start_processing(&mut conn, token_user.id, id)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(processing)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Record the transaction hash of a sent withdrawal
+async fn mark_sent(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<SentBody>,
+) -> Result<CustomResponse<Withdrawal>, Error> {
+    let mut conn = get_connection()?;
+    let sent = withdrawal::mark_sent(&mut conn, token_user.id, id, &body.tx_hash)?;
+
+    let res = CustomResponseBuilder::new()
+        .body(sent)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
+
+/// Give up on a withdrawal and return the funds to the user
+async fn mark_failed(
+    token_user: TokenUser,
+    Path(id): Path<i32>,
+    Json(body): Json<FailedBody>,
+) -> Result<CustomResponse<Withdrawal>, Error> {
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(Error::validation_error("A reason is required"));
+    }
+
+    let mut conn = get_connection()?;
+    let failed = withdrawal::mark_failed(&mut conn, token_user.id, id, reason.to_string())?;
+
+    let res = CustomResponseBuilder::new()
+        .body(failed)
+        .status_code(StatusCode::OK)
+        .build();
+    Ok(res)
+}
@@ -1,5 +1,31 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    announcements (id) {
+        id -> Int4,
+        author_id -> Nullable<Int4>,
+        severity -> Varchar,
+        title -> Varchar,
+        body -> Text,
+        signed_message -> Text,
+        expires_at -> Nullable<Timestamp>,
+        withdrawn_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    cart_items (id) {
+        id -> Int4,
+        user_id -> Int4,
+        product_id -> Int4,
+        variant_id -> Nullable<Int4>,
+        quantity -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     categories (id) {
         id -> Int4,
@@ -18,6 +44,173 @@ diesel::table! {
         user2_id -> Int4,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        order_id -> Nullable<Int4>,
+        locked_at -> Nullable<Timestamp>,
+        retention_days -> Nullable<Int4>,
+        started_by -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    conversation_report_messages (id) {
+        id -> Int4,
+        report_id -> Int4,
+        message_id -> Nullable<Int4>,
+        sender_id -> Int4,
+        encrypted_content -> Text,
+        shared_content -> Nullable<Text>,
+        sent_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    conversation_reports (id) {
+        id -> Int4,
+        conversation_id -> Int4,
+        reporter_id -> Int4,
+        reported_user_id -> Int4,
+        reason -> Varchar,
+        details -> Nullable<Text>,
+        status -> Varchar,
+        resolved_by -> Nullable<Int4>,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    dispute_evidence (id) {
+        id -> Int4,
+        dispute_id -> Int4,
+        submitted_by -> Int4,
+        kind -> Varchar,
+        description -> Text,
+        encrypted_content -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    dispute_messages (id) {
+        id -> Int4,
+        dispute_id -> Int4,
+        sender_id -> Int4,
+        encrypted_content -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    disputes (id) {
+        id -> Int4,
+        order_id -> Int4,
+        opened_by -> Int4,
+        reason -> Varchar,
+        description -> Text,
+        status -> Varchar,
+        assigned_to -> Nullable<Int4>,
+        assigned_at -> Nullable<Timestamp>,
+        sla_due_at -> Timestamp,
+        resolution -> Nullable<Varchar>,
+        buyer_percent -> Nullable<Int4>,
+        resolution_notes -> Nullable<Text>,
+        resolved_by -> Nullable<Int4>,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    escrow_addresses (id) {
+        id -> Int4,
+        currency -> crate::models::payment::PaymentCurrencyMapping,
+        address -> Varchar,
+        order_id -> Nullable<Int4>,
+        assigned_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        vendor_bond_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    escrow_settlements (id) {
+        id -> Int4,
+        order_id -> Int4,
+        currency -> crate::models::payment::PaymentCurrencyMapping,
+        refund -> Numeric,
+        release -> Numeric,
+        fee -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    fee_rules (id) {
+        id -> Int4,
+        category_id -> Nullable<Int4>,
+        vendor_id -> Nullable<Int4>,
+        fee_bps -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    jobs (id) {
+        id -> Int8,
+        kind -> Varchar,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamp,
+        unique_key -> Nullable<Varchar>,
+        locked_by -> Nullable<Varchar>,
+        locked_at -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        finished_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    listing_flags (id) {
+        id -> Int4,
+        product_id -> Int4,
+        reporter_id -> Int4,
+        reason -> Varchar,
+        details -> Nullable<Text>,
+        status -> Varchar,
+        resolved_by -> Nullable<Int4>,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    listing_moderation_events (id) {
+        id -> Int4,
+        product_id -> Int4,
+        actor_id -> Nullable<Int4>,
+        from_state -> crate::models::product::ListingStateMapping,
+        to_state -> crate::models::product::ListingStateMapping,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    message_attachments (id) {
+        id -> Int4,
+        message_id -> Int4,
+        encrypted_data -> Bytea,
+        size_bytes -> Int4,
+        armored -> Bool,
+        created_at -> Timestamp,
     }
 }
 
@@ -32,6 +225,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    notification_preferences (user_id, kind) {
+        user_id -> Int4,
+        kind -> Varchar,
+        enabled -> Bool,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Int4,
+        user_id -> Int4,
+        kind -> Varchar,
+        title -> Varchar,
+        link -> Nullable<Varchar>,
+        read_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     order_items (id) {
         id -> Int4,
@@ -41,6 +255,23 @@ diesel::table! {
         quantity -> Int4,
         price_per_unit -> Numeric,
         created_at -> Timestamp,
+        price_fiat -> Nullable<Numeric>,
+        fiat_currency -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    order_quotes (id) {
+        id -> Int4,
+        order_id -> Int4,
+        fiat_currency -> Varchar,
+        currency -> crate::models::payment::PaymentCurrencyMapping,
+        rate -> Numeric,
+        source -> Varchar,
+        quoted_at -> Timestamp,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -51,6 +282,22 @@ diesel::table! {
         status -> crate::models::order::OrderStatusMapping,
         notes -> Nullable<Text>,
         created_at -> Timestamp,
+        actor -> Varchar,
+        changed_by -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    order_timers (id) {
+        id -> Int4,
+        order_id -> Int4,
+        kind -> Varchar,
+        due_at -> Timestamp,
+        extended -> Bool,
+        fired_at -> Nullable<Timestamp>,
+        cancelled_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -67,6 +314,11 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         completed_at -> Nullable<Timestamp>,
+        checkout_id -> Nullable<Varchar>,
+        shipping_option_id -> Nullable<Int4>,
+        shipping_amount -> Numeric,
+        shipping_fiat -> Nullable<Numeric>,
+        shipping_fiat_currency -> Nullable<Varchar>,
     }
 }
 
@@ -91,6 +343,15 @@ diesel::table! {
         stock -> Int4,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        price_fiat -> Nullable<Numeric>,
+    }
+}
+
+diesel::table! {
+    product_view_counts (product_id, day) {
+        product_id -> Int4,
+        day -> Date,
+        views -> Int4,
     }
 }
 
@@ -107,6 +368,17 @@ diesel::table! {
         is_active -> Bool,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        low_stock_threshold -> Nullable<Int4>,
+        deactivate_when_out_of_stock -> Bool,
+        stock_deactivated -> Bool,
+        price_fiat -> Nullable<Numeric>,
+        fiat_currency -> Nullable<Varchar>,
+        sku -> Nullable<Varchar>,
+        listing_state -> crate::models::product::ListingStateMapping,
+        moderation_reason -> Nullable<Text>,
+        submitted_at -> Nullable<Timestamp>,
+        reviewed_by -> Nullable<Int4>,
+        reviewed_at -> Nullable<Timestamp>,
     }
 }
 
@@ -121,6 +393,71 @@ diesel::table! {
         comment -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        edited_at -> Nullable<Timestamp>,
+        vendor_reply -> Nullable<Text>,
+        vendor_replied_at -> Nullable<Timestamp>,
+        weight -> Float8,
+        flag_status -> Nullable<Varchar>,
+        flag_reason -> Nullable<Text>,
+        moderated_by -> Nullable<Int4>,
+        moderated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    shipping_options (id) {
+        id -> Int4,
+        vendor_id -> Int4,
+        name -> Varchar,
+        description -> Nullable<Text>,
+        price_btc -> Nullable<Numeric>,
+        price_xmr -> Nullable<Numeric>,
+        price_fiat -> Nullable<Numeric>,
+        fiat_currency -> Nullable<Varchar>,
+        is_active -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    stock_reservations (id) {
+        id -> Int4,
+        order_id -> Int4,
+        product_id -> Int4,
+        variant_id -> Nullable<Int4>,
+        quantity -> Int4,
+        status -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    support_ticket_messages (id) {
+        id -> Int4,
+        ticket_id -> Int4,
+        author_id -> Int4,
+        content -> Text,
+        encrypted -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    support_tickets (id) {
+        id -> Int4,
+        user_id -> Int4,
+        category -> Varchar,
+        priority -> Varchar,
+        status -> Varchar,
+        subject -> Varchar,
+        assigned_to -> Nullable<Int4>,
+        assigned_at -> Nullable<Timestamp>,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -152,6 +489,26 @@ diesel::table! {
         locked_at -> Nullable<Timestamp>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        server_side_encryption -> Bool,
+    }
+}
+
+diesel::table! {
+    user_blocks (blocker_id, blocked_id) {
+        blocker_id -> Int4,
+        blocked_id -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    vendor_availability (vendor_id) {
+        vendor_id -> Int4,
+        status -> Varchar,
+        return_date -> Nullable<Date>,
+        message -> Nullable<Text>,
+        hide_listings -> Bool,
+        updated_at -> Timestamp,
     }
 }
 
@@ -165,6 +522,13 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         approved_at -> Nullable<Timestamp>,
+        payment_address -> Nullable<Varchar>,
+        tx_hash -> Nullable<Varchar>,
+        note -> Nullable<Text>,
+        paid_at -> Nullable<Timestamp>,
+        reviewed_by -> Nullable<Int4>,
+        closed_at -> Nullable<Timestamp>,
+        close_reason -> Nullable<Text>,
     }
 }
 
@@ -181,34 +545,134 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    withdrawal_addresses (id) {
+        id -> Int4,
+        user_id -> Int4,
+        currency -> crate::models::payment::PaymentCurrencyMapping,
+        address -> Varchar,
+        label -> Nullable<Varchar>,
+        challenge -> Text,
+        confirmed_at -> Nullable<Timestamp>,
+        usable_at -> Nullable<Timestamp>,
+        removed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    withdrawals (id) {
+        id -> Int4,
+        user_id -> Int4,
+        wallet_id -> Int4,
+        address_id -> Int4,
+        transaction_id -> Int4,
+        currency -> crate::models::payment::PaymentCurrencyMapping,
+        amount -> Numeric,
+        network_fee -> Numeric,
+        status -> Varchar,
+        tx_hash -> Nullable<Varchar>,
+        failure_reason -> Nullable<Text>,
+        processed_by -> Nullable<Int4>,
+        processed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(announcements -> users (author_id));
+diesel::joinable!(cart_items -> product_variants (variant_id));
+diesel::joinable!(cart_items -> products (product_id));
+diesel::joinable!(cart_items -> users (user_id));
+diesel::joinable!(conversation_report_messages -> conversation_reports (report_id));
+diesel::joinable!(conversation_report_messages -> messages (message_id));
+diesel::joinable!(conversation_reports -> conversations (conversation_id));
+diesel::joinable!(conversations -> orders (order_id));
+diesel::joinable!(dispute_evidence -> disputes (dispute_id));
+diesel::joinable!(dispute_messages -> disputes (dispute_id));
+diesel::joinable!(disputes -> orders (order_id));
+diesel::joinable!(escrow_addresses -> orders (order_id));
+diesel::joinable!(escrow_addresses -> vendor_bonds (vendor_bond_id));
+diesel::joinable!(escrow_settlements -> orders (order_id));
+diesel::joinable!(fee_rules -> categories (category_id));
+diesel::joinable!(fee_rules -> users (vendor_id));
+diesel::joinable!(listing_flags -> products (product_id));
+diesel::joinable!(listing_moderation_events -> products (product_id));
+diesel::joinable!(notification_preferences -> users (user_id));
+diesel::joinable!(message_attachments -> messages (message_id));
+diesel::joinable!(messages -> conversations (conversation_id));
+diesel::joinable!(notifications -> users (user_id));
 diesel::joinable!(order_items -> orders (order_id));
 diesel::joinable!(order_items -> product_variants (variant_id));
 diesel::joinable!(order_items -> products (product_id));
+diesel::joinable!(order_quotes -> orders (order_id));
 diesel::joinable!(order_status_history -> orders (order_id));
+diesel::joinable!(order_status_history -> users (changed_by));
+diesel::joinable!(order_timers -> orders (order_id));
+diesel::joinable!(orders -> shipping_options (shipping_option_id));
 diesel::joinable!(product_images -> products (product_id));
 diesel::joinable!(product_variants -> products (product_id));
+diesel::joinable!(product_view_counts -> products (product_id));
 diesel::joinable!(products -> categories (category_id));
 diesel::joinable!(reviews -> orders (order_id));
 diesel::joinable!(reviews -> products (product_id));
+diesel::joinable!(shipping_options -> users (vendor_id));
+diesel::joinable!(stock_reservations -> orders (order_id));
+diesel::joinable!(stock_reservations -> product_variants (variant_id));
+diesel::joinable!(stock_reservations -> products (product_id));
+diesel::joinable!(support_ticket_messages -> support_tickets (ticket_id));
 diesel::joinable!(transactions -> orders (order_id));
 diesel::joinable!(transactions -> wallets (wallet_id));
+diesel::joinable!(vendor_availability -> users (vendor_id));
 diesel::joinable!(vendor_bonds -> transactions (transaction_id));
 diesel::joinable!(vendor_bonds -> users (vendor_id));
 diesel::joinable!(wallets -> users (user_id));
+diesel::joinable!(withdrawal_addresses -> users (user_id));
+diesel::joinable!(withdrawals -> transactions (transaction_id));
+diesel::joinable!(withdrawals -> wallets (wallet_id));
+diesel::joinable!(withdrawals -> withdrawal_addresses (address_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    announcements,
+    cart_items,
     categories,
+    conversation_report_messages,
+    conversation_reports,
     conversations,
+    dispute_evidence,
+    dispute_messages,
+    disputes,
+    escrow_addresses,
+    escrow_settlements,
+    fee_rules,
+    jobs,
+    listing_flags,
+    listing_moderation_events,
+    message_attachments,
     messages,
+    notification_preferences,
+    notifications,
     order_items,
+    order_quotes,
     order_status_history,
+    order_timers,
     orders,
     product_images,
     product_variants,
+    product_view_counts,
     products,
     reviews,
+    shipping_options,
+    stock_reservations,
+    support_ticket_messages,
+    support_tickets,
     transactions,
+    user_blocks,
     users,
+    vendor_availability,
     vendor_bonds,
     wallets,
+    withdrawal_addresses,
+    withdrawals,
 );
@@ -1,7 +1,7 @@
 use config::{Config, ConfigError, Environment, File};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
-use std::{env, fmt};
+use std::{collections::HashMap, env, fmt};
 
 pub static SETTINGS: Lazy<Settings> =
     Lazy::new(|| Settings::new().expect("Failed to setup settings"));
@@ -47,6 +47,284 @@ fn default_tor_service_dir() -> String {
     "./tor_service".to_string()
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct Inventory {
+    #[serde(default = "default_reservation_ttl_minutes")]
+    pub reservation_ttl_minutes: i64,
+}
+
+fn default_reservation_ttl_minutes() -> i64 {
+    crate::constants::inventory::DEFAULT_RESERVATION_TTL_MINUTES
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Pricing {
+    /// Where exchange rates come from: "static", "file" or "http"
+    #[serde(default = "default_rate_provider")]
+    pub provider: String,
+    /// JSON file read by the "file" provider
+    pub rates_file: Option<String>,
+    /// Base URL queried by the "http" provider
+    pub rates_url: Option<String>,
+    /// Rates used by the "static" provider, keyed by fiat currency code
+    #[serde(default)]
+    pub static_rates: HashMap<String, StaticRate>,
+    #[serde(default = "default_rate_cache_ttl_seconds")]
+    pub cache_ttl_seconds: u64,
+    #[serde(default = "default_rate_max_staleness_seconds")]
+    pub max_staleness_seconds: u64,
+    #[serde(default = "default_quote_lock_minutes")]
+    pub quote_lock_minutes: i64,
+}
+
+/// Price of one coin in a fiat currency, as decimal strings
+#[derive(Debug, Clone, Deserialize)]
+pub struct StaticRate {
+    pub btc: String,
+    pub xmr: String,
+}
+
+fn default_rate_provider() -> String {
+    "file".to_string()
+}
+
+fn default_rate_cache_ttl_seconds() -> u64 {
+    crate::constants::pricing::DEFAULT_CACHE_TTL_SECONDS
+}
+
+fn default_rate_max_staleness_seconds() -> u64 {
+    crate::constants::pricing::DEFAULT_MAX_STALENESS_SECONDS
+}
+
+fn default_quote_lock_minutes() -> i64 {
+    crate::constants::pricing::DEFAULT_QUOTE_LOCK_MINUTES
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Disputes {
+    /// Hours a moderator has to pick up a new dispute
+    #[serde(default = "default_assignment_sla_hours")]
+    pub assignment_sla_hours: i64,
+    /// Hours the assigned moderator has to rule on it
+    #[serde(default = "default_resolution_sla_hours")]
+    pub resolution_sla_hours: i64,
+}
+
+fn default_assignment_sla_hours() -> i64 {
+    crate::constants::disputes::DEFAULT_ASSIGNMENT_SLA_HOURS
+}
+
+fn default_resolution_sla_hours() -> i64 {
+    crate::constants::disputes::DEFAULT_RESOLUTION_SLA_HOURS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fees {
+    /// Fee for items whose category and vendor have no rule, in basis points
+    #[serde(default = "default_fee_bps")]
+    pub default_bps: i32,
+}
+
+fn default_fee_bps() -> i32 {
+    crate::constants::fees::DEFAULT_FEE_BPS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VendorBonds {
+    /// Bond required to become a vendor, in BTC, as a decimal string
+    #[serde(default = "default_bond_amount_btc")]
+    pub amount_btc: String,
+}
+
+fn default_bond_amount_btc() -> String {
+    crate::constants::vendor_bonds::DEFAULT_AMOUNT_BTC.to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Notifications {
+    /// Days notifications are kept, read or not
+    #[serde(default = "default_notification_retention_days")]
+    pub retention_days: i64,
+    /// Most notifications kept per user
+    #[serde(default = "default_notification_max_per_user")]
+    pub max_per_user: i64,
+}
+
+fn default_notification_retention_days() -> i64 {
+    crate::constants::notifications::DEFAULT_RETENTION_DAYS
+}
+
+fn default_notification_max_per_user() -> i64 {
+    crate::constants::notifications::DEFAULT_MAX_PER_USER
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Housekeeping {
+    /// Seconds between runs of the pruning jobs
+    #[serde(default = "default_housekeeping_interval_seconds")]
+    pub interval_seconds: u64,
+}
+
+fn default_housekeeping_interval_seconds() -> u64 {
+    crate::constants::housekeeping::DEFAULT_INTERVAL_SECONDS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Messaging {
+    /// Reject messages the sender could not read back themselves
+    #[serde(default = "default_require_sender_copy")]
+    pub require_sender_copy: bool,
+    /// Days messages are kept in conversations without their own retention
+    #[serde(default = "default_message_retention_days")]
+    pub retention_days: i32,
+    /// Direct conversations an account younger than `NEW_ACCOUNT_DAYS` can start per day
+    #[serde(default = "default_new_account_daily_conversations")]
+    pub new_account_daily_conversations: i64,
+    /// Direct conversations an established account can start per day
+    #[serde(default = "default_daily_conversations")]
+    pub daily_conversations: i64,
+    /// Direct conversations an account with `TRUSTED_REPUTATION` can start per day
+    #[serde(default = "default_trusted_daily_conversations")]
+    pub trusted_daily_conversations: i64,
+}
+
+fn default_require_sender_copy() -> bool {
+    crate::constants::messages::DEFAULT_REQUIRE_SENDER_COPY
+}
+
+fn default_message_retention_days() -> i32 {
+    crate::constants::messages::DEFAULT_RETENTION_DAYS
+}
+
+fn default_new_account_daily_conversations() -> i64 {
+    crate::constants::messages::DEFAULT_NEW_ACCOUNT_DAILY_CONVERSATIONS
+}
+
+fn default_daily_conversations() -> i64 {
+    crate::constants::messages::DEFAULT_DAILY_CONVERSATIONS
+}
+
+fn default_trusted_daily_conversations() -> i64 {
+    crate::constants::messages::DEFAULT_TRUSTED_DAILY_CONVERSATIONS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Announcements {
+    /// The marketplace's public key; announcements must be cleartext-signed with it
+    pub pgp_public_key: Option<String>,
+    /// How many announcements the banner shows at once
+    #[serde(default = "default_banner_limit")]
+    pub banner_limit: i64,
+}
+
+fn default_banner_limit() -> i64 {
+    crate::constants::announcements::DEFAULT_BANNER_LIMIT
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Support {
+    /// The staff's shared public key; ticket messages are encrypted to it when it is set
+    pub pgp_public_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Withdrawals {
+    /// Hours after confirming a new address before it can be used
+    #[serde(default = "default_address_cooldown_hours")]
+    pub address_cooldown_hours: i64,
+    /// Network fee charged on a BTC withdrawal, as a decimal string
+    #[serde(default = "default_btc_network_fee")]
+    pub btc_network_fee: String,
+    /// Network fee charged on an XMR withdrawal, as a decimal string
+    #[serde(default = "default_xmr_network_fee")]
+    pub xmr_network_fee: String,
+}
+
+fn default_address_cooldown_hours() -> i64 {
+    crate::constants::withdrawals::DEFAULT_ADDRESS_COOLDOWN_HOURS
+}
+
+fn default_btc_network_fee() -> String {
+    crate::constants::withdrawals::DEFAULT_BTC_NETWORK_FEE.to_string()
+}
+
+fn default_xmr_network_fee() -> String {
+    crate::constants::withdrawals::DEFAULT_XMR_NETWORK_FEE.to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jobs {
+    /// Run a worker inside the server process; turn off when running `worker` separately
+    #[serde(default = "default_in_process")]
+    pub in_process: bool,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: i64,
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    #[serde(default = "default_stale_after_seconds")]
+    pub stale_after_seconds: i64,
+    #[serde(default = "default_backoff_base_seconds")]
+    pub backoff_base_seconds: i64,
+    #[serde(default = "default_backoff_max_seconds")]
+    pub backoff_max_seconds: i64,
+}
+
+fn default_in_process() -> bool {
+    true
+}
+
+fn default_concurrency() -> i64 {
+    crate::constants::jobs::DEFAULT_CONCURRENCY
+}
+
+fn default_poll_interval_ms() -> u64 {
+    crate::constants::jobs::DEFAULT_POLL_INTERVAL_MS
+}
+
+fn default_stale_after_seconds() -> i64 {
+    crate::constants::jobs::DEFAULT_STALE_AFTER_SECONDS
+}
+
+fn default_backoff_base_seconds() -> i64 {
+    crate::constants::jobs::DEFAULT_BACKOFF_BASE_SECONDS
+}
+
+fn default_backoff_max_seconds() -> i64 {
+    crate::constants::jobs::DEFAULT_BACKOFF_MAX_SECONDS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OrderTimers {
+    /// How often the scheduler looks for due timers, in seconds
+    #[serde(default = "default_timer_interval_seconds")]
+    pub interval_seconds: u64,
+    /// Hours a vendor has to accept a paid order
+    #[serde(default = "default_acceptance_timeout_hours")]
+    pub acceptance_timeout_hours: i64,
+    /// Days after shipping before an order is finalized automatically
+    #[serde(default = "default_finalize_after_days")]
+    pub finalize_after_days: i64,
+    /// Days a buyer can add, once, to the finalize window
+    #[serde(default = "default_extension_days")]
+    pub extension_days: i64,
+}
+
+fn default_timer_interval_seconds() -> u64 {
+    crate::constants::order_timers::DEFAULT_INTERVAL_SECONDS
+}
+
+fn default_acceptance_timeout_hours() -> i64 {
+    crate::constants::order_timers::DEFAULT_ACCEPTANCE_TIMEOUT_HOURS
+}
+
+fn default_finalize_after_days() -> i64 {
+    crate::constants::order_timers::DEFAULT_FINALIZE_AFTER_DAYS
+}
+
+fn default_extension_days() -> i64 {
+    crate::constants::order_timers::DEFAULT_EXTENSION_DAYS
+}
+
 // Remove the #[allow(dead_code)] attribute from the Settings struct when all the fields are being
 // used.
 #[allow(dead_code)]
@@ -58,6 +336,19 @@ pub struct Settings {
     pub database: Database,
     pub auth: Auth,
     pub tor: Tor,
+    pub inventory: Inventory,
+    pub pricing: Pricing,
+    pub disputes: Disputes,
+    pub order_timers: OrderTimers,
+    pub jobs: Jobs,
+    pub fees: Fees,
+    pub withdrawals: Withdrawals,
+    pub vendor_bonds: VendorBonds,
+    pub messaging: Messaging,
+    pub notifications: Notifications,
+    pub housekeeping: Housekeeping,
+    pub support: Support,
+    pub announcements: Announcements,
 }
 
 impl Settings {
@@ -0,0 +1,48 @@
+use diesel::{pg::PgConnection, prelude::*};
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::{
+    constants::events::{LISTEN_POLL_MS, LISTEN_RETRY_SECONDS, NOTIFY_CHANNEL},
+    errors::Error,
+    events::{self, Envelope},
+    settings::SETTINGS,
+};
+
+/// Forward committed events from Postgres to the in-process bus
+///
+/// Runs on its own thread with a dedicated connection, since `LISTEN` ties up the
+/// connection for as long as it listens. Events sent while the listener is reconnecting are
+/// lost; streams recover on the client's next refetch.
+pub fn spawn_listener() {
+    info!(channel = NOTIFY_CHANNEL, "Starting event listener");
+
+    let spawned = thread::Builder::new().name("event-listener".to_string()).spawn(|| loop {
+        if let Err(err) = listen() {
+            error!(error = %err, "Event listener lost its connection");
+        }
+        thread::sleep(Duration::from_secs(LISTEN_RETRY_SECONDS));
+    });
+
+    if let Err(err) = spawned {
+        error!(error = %err, "Failed to start the event listener");
+    }
+}
+
+fn listen() -> Result<(), Error> {
+    let mut conn = PgConnection::establish(&SETTINGS.database.url)
+        .map_err(|err| Error::internal_error(format!("Cannot connect: {}", err), None, None))?;
+    conn.batch_execute(&format!("LISTEN {}", NOTIFY_CHANNEL))?;
+
+    loop {
+        for notification in conn.notifications_iter() {
+            let notification = notification?;
+            match serde_json::from_str::<Envelope>(&notification.payload) {
+                Ok(envelope) => events::publish(envelope),
+                Err(err) => warn!(error = %err, "Ignoring a malformed event"),
+            }
+        }
+        thread::sleep(Duration::from_millis(LISTEN_POLL_MS));
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info};
+
+use crate::{
+    database::get_connection,
+    errors::Error,
+    jobs::{
+        self,
+        housekeeping::{PruneNotifications, PurgeExpiredMessages},
+    },
+    settings::SETTINGS,
+};
+
+/// Periodically queue the jobs that delete data past its retention limits
+///
+/// The jobs are unique, so a run that is still queued when the next tick comes is not doubled.
+pub fn spawn_scheduler() {
+    let period = Duration::from_secs(SETTINGS.housekeeping.interval_seconds);
+
+    info!(interval_seconds = period.as_secs(), "Starting housekeeping scheduler");
+
+    tokio::spawn(async move {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            match tokio::task::spawn_blocking(queue_jobs).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => error!(error = %err, "Failed to queue housekeeping jobs"),
+                Err(err) => error!(error = %err, "Housekeeping scheduler task panicked"),
+            }
+        }
+    });
+}
+
+fn queue_jobs() -> Result<(), Error> {
+    let mut conn = get_connection()?;
+    jobs::enqueue(&mut conn, &PruneNotifications)?;
+    jobs::enqueue(&mut conn, &PurgeExpiredMessages)?;
+    Ok(())
+}
@@ -0,0 +1,18 @@
+//! Background tasks that run alongside the HTTP server
+
+pub mod events;
+pub mod housekeeping;
+pub mod order_timers;
+
+use crate::{jobs, settings::SETTINGS};
+
+/// Spawn every background task
+pub fn spawn_all() {
+    events::spawn_listener();
+    order_timers::spawn_scheduler();
+    housekeeping::spawn_scheduler();
+
+    if SETTINGS.jobs.in_process {
+        jobs::worker::Worker::new(jobs::registry()).spawn();
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, error, info};
+
+use crate::{
+    database::get_connection,
+    errors::Error,
+    jobs::{self, order_timers::FireOrderTimer},
+    models::order_timer,
+    settings::SETTINGS,
+};
+
+/// Periodically queue a job for every order timer that is due
+///
+/// The jobs cancel unpaid and unaccepted orders and finalize shipped ones, with the queue's
+/// retries when one fails. Timers live in the database, so nothing is lost across restarts:
+/// anything that came due while the server was down is queued on the first run.
+pub fn spawn_scheduler() {
+    let period = Duration::from_secs(SETTINGS.order_timers.interval_seconds);
+
+    info!(interval_seconds = period.as_secs(), "Starting order timer scheduler");
+
+    tokio::spawn(async move {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            match tokio::task::spawn_blocking(queue_due).await {
+                Ok(Ok(0)) => debug!("No order timers due"),
+                Ok(Ok(count)) => info!(count = count, "Due order timers queued"),
+                Ok(Err(err)) => error!(error = %err, "Failed to queue due order timers"),
+                Err(err) => error!(error = %err, "Order timer scheduler task panicked"),
+            }
+        }
+    });
+}
+
+fn queue_due() -> Result<usize, Error> {
+    let mut conn = get_connection()?;
+    let mut queued = 0;
+
+    for timer_id in order_timer::due(&mut conn)? {
+        // Timers with a queued, running or dead job are not due; the unique key only guards
+        // against another scheduler queueing the same timer in the meantime
+        if jobs::enqueue(&mut conn, &FireOrderTimer { timer_id })?.is_some() {
+            queued += 1;
+        }
+    }
+
+    Ok(queued)
+}
@@ -11,6 +11,7 @@ use serde::Serialize;
 pub struct HomeTemplate {
     pub user: Option<UserContext>,
     pub current_year: i32,
+    pub announcements: Vec<AnnouncementContext>,
 }
 
 // Login page template
@@ -19,6 +20,7 @@ pub struct HomeTemplate {
 pub struct LoginTemplate {
     pub user: Option<UserContext>,
     pub current_year: i32,
+    pub announcements: Vec<AnnouncementContext>,
     pub error: Option<String>,
     pub pgp_challenge: Option<String>,
 }
@@ -29,6 +31,7 @@ pub struct LoginTemplate {
 pub struct RegisterTemplate {
     pub user: Option<UserContext>,
     pub current_year: i32,
+    pub announcements: Vec<AnnouncementContext>,
     pub error: Option<String>,
 }
 
@@ -38,6 +41,7 @@ pub struct RegisterTemplate {
 pub struct ProductsTemplate {
     pub user: Option<UserContext>,
     pub current_year: i32,
+    pub announcements: Vec<AnnouncementContext>,
     pub products: Vec<ProductContext>,
     pub categories: Vec<CategoryContext>,
     pub currency: String,
@@ -51,10 +55,36 @@ pub struct ProductsTemplate {
 pub struct ProductDetailTemplate {
     pub user: Option<UserContext>,
     pub current_year: i32,
+    pub announcements: Vec<AnnouncementContext>,
     pub product: ProductDetailContext,
     pub currency: String,
 }
 
+// Notification center, refreshing itself for browsers without JavaScript
+#[derive(Template)]
+#[template(path = "pages/notifications.html")]
+pub struct NotificationsTemplate {
+    pub user: Option<UserContext>,
+    pub current_year: i32,
+    pub announcements: Vec<AnnouncementContext>,
+    pub refresh_seconds: u32,
+    pub refresh_url: String,
+    pub unread: i64,
+    /// The unread notifications shown, for the "mark as read" form
+    pub shown_ids: String,
+    pub notifications: Vec<NotificationContext>,
+}
+
+// Announcements page, with each announcement's signed text for verification
+#[derive(Template)]
+#[template(path = "pages/announcements.html")]
+pub struct AnnouncementsTemplate {
+    pub user: Option<UserContext>,
+    pub current_year: i32,
+    pub announcements: Vec<AnnouncementContext>,
+    pub listed: Vec<AnnouncementContext>,
+}
+
 // Context structs for templates
 
 #[derive(Serialize, Clone)]
@@ -70,6 +100,7 @@ pub struct UserContext {
     pub is_vendor: bool,
     pub is_admin: bool,
     pub is_moderator: bool,
+    pub unread_notifications: i64,
 }
 
 #[derive(Serialize, Clone)]
@@ -147,7 +178,10 @@ pub struct VendorContext {
     pub total_sales: i32,
     pub response_time: String,
     pub created_at: String,
+    /// Active, vacation or closed, as in the vendor profile
     pub status: String,
+    pub return_date: Option<String>,
+    pub away_message: Option<String>,
 }
 
 #[derive(Serialize, Clone)]
@@ -201,6 +235,11 @@ pub struct ConversationContext {
     pub last_message: String,
     pub last_message_time: String,
     pub unread_count: i32,
+    pub order_id: Option<i32>,
+    pub retention_days: i32,
+    pub on_legal_hold: bool,
+    /// When the oldest message will be deleted
+    pub expires_at: Option<String>,
 }
 
 #[derive(Serialize, Clone)]
@@ -211,6 +250,8 @@ pub struct ConversationDetailContext {
     pub other_user_pgp_key: Option<String>,
     pub messages: Vec<MessageContext>,
     pub related_order: Option<RelatedOrderContext>,
+    /// The order is closed and nothing more can be sent
+    pub is_locked: bool,
 }
 
 #[derive(Serialize, Clone)]
@@ -219,6 +260,7 @@ pub struct MessageContext {
     pub content: String,
     pub is_from_me: bool,
     pub created_at: String,
+    pub attachment_ids: Vec<i32>,
 }
 
 #[derive(Serialize, Clone)]
@@ -295,6 +337,27 @@ pub struct ReviewContext {
     pub created_at: String,
 }
 
+#[derive(Serialize, Clone)]
+pub struct NotificationContext {
+    pub kind: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub is_read: bool,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AnnouncementContext {
+    pub id: i32,
+    pub severity: String,
+    pub title: String,
+    pub body: String,
+    pub signed_message: String,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub is_expired: bool,
+}
+
 #[derive(Serialize, Clone)]
 pub struct LoginHistoryContext {
     pub timestamp: String,
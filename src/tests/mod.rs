@@ -1,3 +1,4 @@
+mod models;
 mod routes;
 mod setup;
 mod utils;
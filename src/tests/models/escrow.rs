@@ -0,0 +1,75 @@
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::models::escrow::{self, Settlement};
+use crate::models::order::OrderStatus;
+use crate::models::payment::{PaymentCurrency, TransactionType, Wallet, WalletType};
+use crate::models::user::roles;
+use crate::schema::wallets;
+use crate::tests::models::{insert_order, insert_user, order};
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+fn amount(value: &str) -> BigDecimal {
+    value.parse().unwrap()
+}
+
+#[test]
+fn refund_and_release_take_everything() {
+    let order = order(PaymentCurrency::BTC, "0.015", "0");
+
+    let refund = Settlement::refund(&order);
+    assert_eq!(refund.refund, amount("0.015"));
+    assert_eq!(refund.release, amount("0"));
+
+    let release = Settlement::release(&order);
+    assert_eq!(release.refund, amount("0"));
+    assert_eq!(release.release, amount("0.015"));
+}
+
+#[test]
+fn split_rounds_the_refund_down_and_loses_nothing() {
+    let order = order(PaymentCurrency::BTC, "0.00000010", "0");
+
+    let settlement = Settlement::split(&order, 33);
+
+    // 33% of 10 satoshi is 3.3, so the buyer gets 3 and the vendor the other 7
+    assert_eq!(settlement.refund, amount("0.00000003"));
+    assert_eq!(settlement.release, amount("0.00000007"));
+    assert_eq!(&settlement.refund + &settlement.release, order.total_amount);
+}
+
+#[test]
+fn split_caps_the_buyer_share() {
+    let order = order(PaymentCurrency::XMR, "2.5", "0");
+
+    let settlement = Settlement::split(&order, 150);
+
+    assert_eq!(settlement.refund, amount("2.5"));
+    assert_eq!(settlement.release, amount("0"));
+}
+
+#[test]
+fn settling_opens_a_wallet_for_a_party_without_one() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let buyer = insert_user(conn, roles::BUYER);
+        let vendor = insert_user(conn, roles::VENDOR);
+        let order = insert_order(conn, &buyer, &vendor, OrderStatus::Disputed);
+
+        let posted = escrow::settle(conn, &order, &Settlement::refund(&order))?;
+
+        let wallet = wallets::table
+            .filter(wallets::user_id.eq(buyer.id))
+            .first::<Wallet>(conn)?;
+        assert_eq!(wallet.wallet_type, WalletType::BTC);
+        assert_eq!(wallet.balance, order.total_amount);
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].wallet_id, wallet.id);
+        assert_eq!(posted[0].transaction_type, TransactionType::EscrowRefund);
+        Ok(())
+    });
+}
@@ -0,0 +1,77 @@
+use bigdecimal::BigDecimal;
+use std::collections::HashMap;
+
+use crate::models::fee::{apply_bps, share_of_fee, CategoryRates};
+use crate::models::payment::PaymentCurrency;
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+fn amount(value: &str) -> BigDecimal {
+    value.parse().unwrap()
+}
+
+#[test]
+fn basis_points() {
+    assert_eq!(apply_bps(&amount("1"), 250), amount("0.025"));
+    assert_eq!(apply_bps(&amount("0.5"), 0), amount("0"));
+    assert_eq!(apply_bps(&amount("0.5"), 10000), amount("0.5"));
+}
+
+#[test]
+fn the_fee_scales_with_the_share_released() {
+    let full = amount("0.0003");
+    let total = amount("0.01");
+
+    let actual = share_of_fee(&full, &total, &amount("0.006"), PaymentCurrency::BTC);
+    let expected = amount("0.00018");
+    assert_eq!(actual, expected);
+
+    let actual = share_of_fee(&full, &total, &total, PaymentCurrency::BTC);
+    assert_eq!(actual, full);
+}
+
+#[test]
+fn the_fee_share_is_rounded_down() {
+    // A third of 10 satoshi is 3.33..., so the vendor pays 3
+    let actual = share_of_fee(&amount("0.0000001"), &amount("0.03"), &amount("0.01"), PaymentCurrency::BTC);
+    let expected = amount("0.00000003");
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn nothing_released_means_no_fee() {
+    let actual = share_of_fee(&amount("0.0003"), &amount("0.01"), &amount("0"), PaymentCurrency::BTC);
+    assert_eq!(actual, amount("0"));
+
+    let actual = share_of_fee(&amount("0.0003"), &amount("0"), &amount("0.01"), PaymentCurrency::BTC);
+    assert_eq!(actual, amount("0"));
+}
+
+#[test]
+fn the_fee_never_exceeds_the_release() {
+    let actual = share_of_fee(&amount("2"), &amount("1"), &amount("0.5"), PaymentCurrency::XMR);
+    assert_eq!(actual, amount("0.5"));
+}
+
+#[test]
+fn category_rates_come_from_the_nearest_ancestor() {
+    // 1 <- 2 <- 3, and 4 on its own
+    let parents = HashMap::from([(1, None), (2, Some(1)), (3, Some(2)), (4, None)]);
+    let rates = HashMap::from([(1, 300), (2, 150)]);
+    let rates = CategoryRates::new(parents, rates);
+
+    assert_eq!(rates.resolve(1), Some(300));
+    assert_eq!(rates.resolve(2), Some(150));
+    assert_eq!(rates.resolve(3), Some(150));
+    assert_eq!(rates.resolve(4), None);
+    assert_eq!(rates.resolve(99), None);
+}
+
+#[test]
+fn category_rates_survive_a_cycle() {
+    let parents = HashMap::from([(1, Some(2)), (2, Some(1))]);
+    let rates = CategoryRates::new(parents, HashMap::new());
+
+    assert_eq!(rates.resolve(1), None);
+}
@@ -0,0 +1,184 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::jobs::{enqueue, order_timers::FireOrderTimer};
+use crate::models::job::{self, backoff, statuses, JobRecord, NewJobRecord};
+use crate::models::order::OrderStatus;
+use crate::models::order_timer::{self, NewOrderTimer};
+use crate::models::user::roles;
+use crate::schema::{jobs, order_timers};
+use crate::settings::SETTINGS;
+use crate::tests::models::{insert_order, insert_user};
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+const KIND: &str = "test_job";
+
+fn new_job(unique_key: Option<&str>, max_attempts: i32) -> NewJobRecord {
+    NewJobRecord {
+        kind: KIND.to_string(),
+        payload: json!({}),
+        max_attempts,
+        run_at: Utc::now() - Duration::seconds(1),
+        unique_key: unique_key.map(str::to_string),
+    }
+}
+
+fn unique_key() -> String {
+    format!("test:{}", Uuid::new_v4())
+}
+
+fn claim_one(conn: &mut PgConnection) -> JobRecord {
+    let mut claimed = job::claim(conn, "test-worker", &[KIND], 1).unwrap();
+    assert_eq!(claimed.len(), 1);
+    claimed.remove(0)
+}
+
+/// A payment timer overdue by years, so it comes first among the due timers
+fn insert_overdue_timer(conn: &mut PgConnection) -> i32 {
+    let buyer = insert_user(conn, roles::BUYER);
+    let vendor = insert_user(conn, roles::VENDOR);
+    let order = insert_order(conn, &buyer, &vendor, OrderStatus::Pending);
+
+    diesel::insert_into(order_timers::table)
+        .values(&NewOrderTimer {
+            order_id: order.id,
+            kind: order_timer::kinds::PAYMENT.to_string(),
+            due_at: Utc::now() - Duration::days(3650),
+        })
+        .returning(order_timers::id)
+        .get_result::<i32>(conn)
+        .unwrap()
+}
+
+fn set_status(conn: &mut PgConnection, job: &JobRecord, status: &str) {
+    diesel::update(jobs::table.find(job.id))
+        .set(jobs::status.eq(status))
+        .execute(conn)
+        .unwrap();
+}
+
+fn jobs_with_key(conn: &mut PgConnection, key: &str) -> Vec<JobRecord> {
+    jobs::table
+        .filter(jobs::unique_key.eq(key))
+        .order(jobs::id.asc())
+        .load::<JobRecord>(conn)
+        .unwrap()
+}
+
+#[test]
+fn backoff_doubles_up_to_the_ceiling() {
+    let base = SETTINGS.jobs.backoff_base_seconds;
+
+    assert_eq!(backoff(1), Duration::seconds(base));
+    assert_eq!(backoff(2), Duration::seconds(base * 2));
+    assert_eq!(backoff(3), Duration::seconds(base * 4));
+    assert_eq!(backoff(100), Duration::seconds(SETTINGS.jobs.backoff_max_seconds));
+}
+
+#[test]
+fn a_unique_job_is_not_queued_twice() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let key = unique_key();
+
+        assert!(job::insert(conn, &new_job(Some(&key), 3))?.is_some());
+        assert!(job::insert(conn, &new_job(Some(&key), 3))?.is_none());
+
+        // Jobs without a key are never duplicates
+        assert!(job::insert(conn, &new_job(None, 3))?.is_some());
+        assert!(job::insert(conn, &new_job(None, 3))?.is_some());
+        Ok(())
+    });
+}
+
+#[test]
+fn a_failed_job_gives_way_to_its_queued_twin() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let key = unique_key();
+        job::insert(conn, &new_job(Some(&key), 3))?;
+        let running = claim_one(conn);
+        let twin = job::insert(conn, &new_job(Some(&key), 3))?.unwrap();
+
+        job::fail(conn, &running, "boom")?;
+
+        let remaining: Vec<i64> = jobs_with_key(conn, &key).iter().map(|job| job.id).collect();
+        assert_eq!(remaining, vec![twin.id]);
+        Ok(())
+    });
+}
+
+#[test]
+fn a_failed_job_without_a_twin_is_queued_again() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let key = unique_key();
+        job::insert(conn, &new_job(Some(&key), 3))?;
+        let running = claim_one(conn);
+
+        job::fail(conn, &running, "boom")?;
+
+        let jobs = jobs_with_key(conn, &key);
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].status, statuses::QUEUED);
+        assert_eq!(jobs[0].last_error.as_deref(), Some("boom"));
+        assert!(jobs[0].run_at > Utc::now());
+        Ok(())
+    });
+}
+
+#[test]
+fn a_timer_with_an_unfinished_job_is_not_due() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let timer_id = insert_overdue_timer(conn);
+        assert!(order_timer::due(conn)?.contains(&timer_id));
+
+        let queued = enqueue(conn, &FireOrderTimer { timer_id })?.unwrap();
+        assert!(!order_timer::due(conn)?.contains(&timer_id));
+
+        set_status(conn, &queued, statuses::RUNNING);
+        assert!(!order_timer::due(conn)?.contains(&timer_id));
+        Ok(())
+    });
+}
+
+#[test]
+fn a_timer_whose_job_died_waits_for_an_admin() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let timer_id = insert_overdue_timer(conn);
+        let dead = enqueue(conn, &FireOrderTimer { timer_id })?.unwrap();
+        set_status(conn, &dead, statuses::DEAD);
+
+        assert!(!order_timer::due(conn)?.contains(&timer_id));
+
+        // Discarding the dead job hands the timer back to the scheduler
+        job::discard(conn, dead.id)?;
+        assert!(order_timer::due(conn)?.contains(&timer_id));
+        Ok(())
+    });
+}
+
+#[test]
+fn a_dead_job_can_be_retried_alone() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let key = unique_key();
+        job::insert(conn, &new_job(Some(&key), 1))?;
+        let running = claim_one(conn);
+        job::fail(conn, &running, "boom")?;
+
+        let retried = job::retry(conn, running.id)?;
+
+        assert_eq!(retried.status, statuses::QUEUED);
+        assert_eq!(retried.attempts, 0);
+        Ok(())
+    });
+}
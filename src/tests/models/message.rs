@@ -0,0 +1,159 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+
+use crate::constants::messages::{MIN_RETENTION_DAYS, ORDER_RETENTION_MARGIN_DAYS};
+use crate::database::get_connection;
+use crate::errors::Error;
+use crate::models::dispute::{reasons, NewDispute};
+use crate::models::message::{self, Conversation, NewConversation, NewMessage};
+use crate::models::order::OrderStatus;
+use crate::models::user::roles;
+use crate::schema::{conversations, disputes, messages};
+use crate::settings::SETTINGS;
+use crate::tests::models::{insert_order, insert_user};
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+fn conversation(order_id: Option<i32>, retention_days: Option<i32>) -> Conversation {
+    let now = Utc::now();
+    Conversation {
+        id: 1,
+        user1_id: 1,
+        user2_id: 2,
+        created_at: now,
+        updated_at: now,
+        order_id,
+        locked_at: None,
+        retention_days,
+        started_by: None,
+    }
+}
+
+/// Add a message sent `age_days` ago
+fn insert_message(conn: &mut PgConnection, conversation: &Conversation, age_days: i64) -> i32 {
+    let id = diesel::insert_into(messages::table)
+        .values(&NewMessage {
+            conversation_id: conversation.id,
+            sender_id: conversation.user1_id,
+            encrypted_content: "-----BEGIN PGP MESSAGE-----".to_string(),
+            is_read: false,
+        })
+        .returning(messages::id)
+        .get_result::<i32>(conn)
+        .unwrap();
+
+    diesel::update(messages::table.find(id))
+        .set(messages::created_at.eq(Utc::now() - Duration::days(age_days)))
+        .execute(conn)
+        .unwrap();
+    id
+}
+
+fn message_exists(conn: &mut PgConnection, id: i32) -> bool {
+    diesel::select(diesel::dsl::exists(messages::table.find(id)))
+        .get_result::<bool>(conn)
+        .unwrap()
+}
+
+#[test]
+fn retention_falls_back_to_the_setting() {
+    assert_eq!(
+        conversation(None, None).effective_retention_days(),
+        SETTINGS.messaging.retention_days
+    );
+    assert_eq!(conversation(None, Some(30)).effective_retention_days(), 30);
+}
+
+#[test]
+fn order_conversations_outlive_the_dispute_window() {
+    let timers = &SETTINGS.order_timers;
+    let dispute_window = (timers.finalize_after_days + timers.extension_days) as i32;
+
+    assert_eq!(conversation(None, None).min_retention_days(), MIN_RETENTION_DAYS);
+    assert_eq!(
+        conversation(Some(1), None).min_retention_days(),
+        dispute_window + ORDER_RETENTION_MARGIN_DAYS
+    );
+}
+
+#[test]
+fn purge_deletes_only_expired_direct_messages() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let alice = insert_user(conn, roles::BUYER);
+        let bob = insert_user(conn, roles::BUYER);
+        let conversation = diesel::insert_into(conversations::table)
+            .values(&NewConversation {
+                user1_id: alice.id.min(bob.id),
+                user2_id: alice.id.max(bob.id),
+                order_id: None,
+                started_by: Some(alice.id),
+            })
+            .get_result::<Conversation>(conn)?;
+        let conversation = diesel::update(&conversation)
+            .set(conversations::retention_days.eq(Some(30)))
+            .get_result::<Conversation>(conn)?;
+
+        let expired = insert_message(conn, &conversation, 31);
+        let kept = insert_message(conn, &conversation, 29);
+
+        message::purge_expired(conn)?;
+
+        assert!(!message_exists(conn, expired));
+        assert!(message_exists(conn, kept));
+        Ok(())
+    });
+}
+
+#[test]
+fn open_orders_keep_their_messages() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let buyer = insert_user(conn, roles::BUYER);
+        let vendor = insert_user(conn, roles::VENDOR);
+        let order = insert_order(conn, &buyer, &vendor, OrderStatus::Shipped);
+        let conversation = message::open_for_order(conn, &order)?;
+
+        let old = insert_message(conn, &conversation, SETTINGS.messaging.retention_days as i64 + 1);
+
+        message::purge_expired(conn)?;
+
+        assert!(message_exists(conn, old));
+        Ok(())
+    });
+}
+
+#[test]
+fn closed_orders_keep_their_messages_while_disputed() {
+    let mut conn = get_connection().unwrap();
+    conn.test_transaction::<_, Error, _>(|conn| {
+        let buyer = insert_user(conn, roles::BUYER);
+        let vendor = insert_user(conn, roles::VENDOR);
+        let order = insert_order(conn, &buyer, &vendor, OrderStatus::Completed);
+        let conversation = message::open_for_order(conn, &order)?;
+        message::lock_for_order(conn, order.id)?;
+
+        let old = insert_message(conn, &conversation, SETTINGS.messaging.retention_days as i64 + 1);
+
+        diesel::insert_into(disputes::table)
+            .values(&NewDispute {
+                order_id: order.id,
+                opened_by: buyer.id,
+                reason: reasons::NOT_RECEIVED.to_string(),
+                description: "Never arrived".to_string(),
+                sla_due_at: Utc::now() + Duration::days(1),
+            })
+            .execute(conn)?;
+
+        message::purge_expired(conn)?;
+        assert!(message_exists(conn, old));
+
+        // Once the dispute is gone the hold is lifted
+        diesel::delete(disputes::table.filter(disputes::order_id.eq(order.id))).execute(conn)?;
+
+        message::purge_expired(conn)?;
+        assert!(!message_exists(conn, old));
+        Ok(())
+    });
+}
@@ -0,0 +1,71 @@
+mod escrow;
+mod fee;
+mod job;
+mod message;
+mod moderation;
+mod pricing;
+
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::models::order::{NewOrder, Order, OrderStatus};
+use crate::models::payment::PaymentCurrency;
+use crate::models::user::{NewUser, User};
+use crate::schema::{orders, users};
+
+/// An order that is never saved, for the pure calculations
+pub fn order(currency: PaymentCurrency, total_amount: &str, shipping_amount: &str) -> Order {
+    let now = Utc::now();
+    Order {
+        id: 1,
+        buyer_id: 1,
+        vendor_id: 2,
+        status: OrderStatus::Paid,
+        currency,
+        total_amount: total_amount.parse().unwrap(),
+        escrow_address: None,
+        encrypted_shipping_address: String::new(),
+        created_at: now,
+        updated_at: now,
+        completed_at: None,
+        checkout_id: None,
+        shipping_option_id: None,
+        shipping_amount: shipping_amount.parse().unwrap(),
+        shipping_fiat: None,
+        shipping_fiat_currency: None,
+    }
+}
+
+pub fn insert_user(conn: &mut PgConnection, role: &str) -> User {
+    diesel::insert_into(users::table)
+        .values(&NewUser {
+            username: format!("test-{}", Uuid::new_v4().simple()),
+            password_hash: "not a hash".to_string(),
+            pgp_public_key: None,
+            role: role.to_string(),
+        })
+        .get_result::<User>(conn)
+        .unwrap()
+}
+
+pub fn insert_order(conn: &mut PgConnection, buyer: &User, vendor: &User, status: OrderStatus) -> Order {
+    diesel::insert_into(orders::table)
+        .values(&NewOrder {
+            buyer_id: buyer.id,
+            vendor_id: vendor.id,
+            status,
+            currency: PaymentCurrency::BTC,
+            total_amount: BigDecimal::from(1),
+            escrow_address: None,
+            encrypted_shipping_address: String::new(),
+            checkout_id: None,
+            shipping_option_id: None,
+            shipping_amount: BigDecimal::from(0),
+            shipping_fiat: None,
+            shipping_fiat_currency: None,
+        })
+        .get_result::<Order>(conn)
+        .unwrap()
+}
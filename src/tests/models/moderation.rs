@@ -0,0 +1,44 @@
+use crate::models::moderation::can_transition;
+use crate::models::product::ListingState::{self, *};
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+const ALL: [ListingState; 5] = [Draft, PendingReview, Active, Rejected, Suspended];
+
+#[test]
+fn allowed_listing_transitions() {
+    let allowed = [
+        (Draft, PendingReview),
+        (Rejected, PendingReview),
+        (PendingReview, Draft),
+        (PendingReview, Active),
+        (PendingReview, Rejected),
+        (Active, PendingReview),
+        (Active, Suspended),
+        (Active, Rejected),
+        (Suspended, Active),
+        (Suspended, Rejected),
+    ];
+
+    for from in ALL {
+        for to in ALL {
+            let actual = can_transition(from, to);
+            let expected = allowed.contains(&(from, to));
+            assert_eq!(actual, expected, "{:?} -> {:?}", from, to);
+        }
+    }
+}
+
+#[test]
+fn a_listing_cannot_stay_in_its_state() {
+    for state in ALL {
+        assert!(!can_transition(state, state), "{:?} -> {:?}", state, state);
+    }
+}
+
+#[test]
+fn drafts_cannot_skip_review() {
+    assert!(!can_transition(Draft, Active));
+    assert!(!can_transition(Rejected, Active));
+}
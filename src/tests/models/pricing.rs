@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Duration, Utc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::errors::Error;
+use crate::models::payment::PaymentCurrency;
+use crate::pricing::providers::StaticProvider;
+use crate::pricing::{ExchangeRateProvider, ExchangeRates, RateCache};
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+fn amount(value: &str) -> BigDecimal {
+    value.parse().unwrap()
+}
+
+fn static_rates(as_of: DateTime<Utc>) -> Box<StaticProvider> {
+    Box::new(
+        StaticProvider::new()
+            .with_rate("USD", amount("60000"), amount("150"))
+            .fixed_at(as_of),
+    )
+}
+
+/// Answers once with rates observed at `as_of`, then fails
+struct FlakyProvider {
+    as_of: DateTime<Utc>,
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl ExchangeRateProvider for FlakyProvider {
+    fn name(&self) -> &str {
+        "flaky"
+    }
+
+    async fn fetch(&self, fiat_currency: &str) -> Result<ExchangeRates, Error> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) > 0 {
+            return Err(Error::ExchangeRateUnavailable("Provider is down".to_string()));
+        }
+
+        Ok(ExchangeRates {
+            fiat_currency: fiat_currency.to_string(),
+            btc: amount("60000"),
+            xmr: amount("150"),
+            as_of: self.as_of,
+            source: self.name().to_string(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn fresh_rates_are_used() {
+    let cache = RateCache::new(static_rates(Utc::now()), Duration::minutes(1), Duration::hours(1));
+
+    let rates = cache.rates("usd").await.unwrap();
+
+    assert_eq!(rates.fiat_currency, "USD");
+    assert_eq!(rates.btc, amount("60000"));
+}
+
+#[tokio::test]
+async fn stale_rates_from_the_provider_are_refused() {
+    let as_of = Utc::now() - Duration::hours(2);
+    let cache = RateCache::new(static_rates(as_of), Duration::minutes(1), Duration::hours(1));
+
+    let result = cache.rates("USD").await;
+
+    assert!(matches!(result, Err(Error::ExchangeRateUnavailable(_))));
+}
+
+#[tokio::test]
+async fn a_failing_provider_falls_back_to_fresh_enough_rates() {
+    let provider = FlakyProvider {
+        as_of: Utc::now() - Duration::minutes(10),
+        calls: AtomicUsize::new(0),
+    };
+    // No caching, so the second call goes to the provider again
+    let cache = RateCache::new(Box::new(provider), Duration::zero(), Duration::hours(1));
+
+    let first = cache.rates("USD").await.unwrap();
+    let second = cache.rates("USD").await.unwrap();
+
+    assert_eq!(second.as_of, first.as_of);
+    assert_eq!(second.source, "flaky");
+}
+
+#[tokio::test]
+async fn a_failing_provider_without_fresh_rates_is_an_error() {
+    let provider = FlakyProvider {
+        as_of: Utc::now() - Duration::minutes(10),
+        calls: AtomicUsize::new(1),
+    };
+    let cache = RateCache::new(Box::new(provider), Duration::zero(), Duration::hours(1));
+
+    let result = cache.rates("USD").await;
+
+    assert!(matches!(result, Err(Error::ExchangeRateUnavailable(_))));
+}
+
+#[test]
+fn conversion_rounds_up() {
+    let rates = ExchangeRates {
+        fiat_currency: "USD".to_string(),
+        btc: amount("60000"),
+        xmr: amount("150"),
+        as_of: Utc::now(),
+        source: "test".to_string(),
+    };
+
+    // 10 USD is 0.000166666... BTC
+    let actual = rates.convert(&amount("10"), PaymentCurrency::BTC).unwrap();
+    assert_eq!(actual, amount("0.00016667"));
+}
+
+#[test]
+fn a_zero_rate_cannot_convert() {
+    let rates = ExchangeRates {
+        fiat_currency: "USD".to_string(),
+        btc: amount("0"),
+        xmr: amount("150"),
+        as_of: Utc::now(),
+        source: "test".to_string(),
+    };
+
+    assert!(rates.convert(&amount("10"), PaymentCurrency::BTC).is_err());
+}
@@ -2,6 +2,7 @@ use jsonwebtoken::{errors::Error, DecodingKey, EncodingKey, Header, TokenData, V
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
+use crate::constants::auth::DEFAULT_TOKEN_EXPIRATION_DAYS;
 use crate::models::user::User;
 
 type TokenResult = Result<TokenData<Claims>, Error>;
@@ -36,7 +37,8 @@ pub struct Claims {
 impl Claims {
     pub fn new(user: User) -> Self {
         Self {
-            exp: (chrono::Utc::now() + chrono::Duration::days(30)).timestamp() as usize,
+            exp: (chrono::Utc::now() + chrono::Duration::days(DEFAULT_TOKEN_EXPIRATION_DAYS))
+                .timestamp() as usize,
             iat: chrono::Utc::now().timestamp() as usize,
             user: TokenUser::from(user),
         }
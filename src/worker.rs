@@ -0,0 +1,37 @@
+//! The job worker
+//!
+//! Runs the job queue without the HTTP server until interrupted; see `cli::worker`. Build it
+//! with `cargo build --features worker --bin worker` and start as many as needed.
+
+mod catalog;
+mod cli;
+mod constants;
+mod database;
+mod errors;
+mod events;
+mod jobs;
+mod logger;
+mod middleware;
+mod models;
+mod pgp;
+mod pricing;
+mod routes;
+mod schema;
+mod settings;
+mod tasks;
+mod templates;
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    logger::setup();
+
+    let args: Vec<String> = std::iter::once("worker".to_string())
+        .chain(std::env::args().skip(1))
+        .collect();
+
+    if let Some(Err(err)) = cli::run(&args).await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}